@@ -4,6 +4,11 @@ fn main() -> Result<()> {
     tonic_prost_build::configure()
         .build_server(true)
         .build_client(true)
+        // Lets the messages travel with rpcmoq_lite's JsonCodec
+        .type_attribute(
+            ".",
+            "#[cfg_attr(feature = \"json\", derive(serde::Serialize, serde::Deserialize))]",
+        )
         .compile_protos(&["proto/drone.proto", "proto/telemetry.proto"], &["proto/"])?;
     Ok(())
 }
@@ -1,7 +1,14 @@
-use std::time::Duration;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::time::{Duration, SystemTime};
 
 use bon::Builder;
 
+use crate::compression::Compression;
+use crate::connection::DEFAULT_MIN_FRAME_LEN;
+use crate::error::{CodeSpace, RpcClientError};
+use crate::path::strip_version;
+
 /// Configuration for the RPC client.
 #[derive(Debug, Clone, Builder)]
 pub struct RpcClientConfig {
@@ -22,12 +29,157 @@ pub struct RpcClientConfig {
     #[builder(default = "primary".to_string())]
     pub track_name: String,
 
+    /// Track names used instead of `track_name` by individual routes, keyed by gRPC path.
+    /// The server must be configured with the same overrides.
+    #[builder(default)]
+    pub route_track_names: HashMap<String, String>,
+
     /// Timeout for waiting for server response broadcast.
     #[builder(default = Duration::from_secs(30))]
     pub timeout: Duration,
+
+    /// Timeout for the first response frame once connected.
+    /// If set, a connection that receives nothing in time yields
+    /// `RpcWireError::Deadline` and ends. Later frames may take as long as they like.
+    pub first_response_timeout: Option<Duration>,
+
+    /// Send a keepalive frame on the request track whenever no request has been sent for this
+    /// long, so a server with a keepalive timeout doesn't mistake a quiet client for a dead one.
+    pub keepalive_interval: Option<Duration>,
+
+    /// Fail connections that receive no frame, responses and keepalives alike, within this
+    /// window with `RpcWireError::KeepaliveTimeout`. The server should send keepalives at a
+    /// fraction of it.
+    pub keepalive_timeout: Option<Duration>,
+
+    /// Response frames with a payload shorter than this are skipped rather than
+    /// decoded. The default of 1 skips empty frames, which protobuf would
+    /// otherwise decode into an all-default message. Set this to 0 if a route
+    /// legitimately exchanges messages whose fields are all default.
+    #[builder(default = DEFAULT_MIN_FRAME_LEN)]
+    pub min_frame_len: usize,
+
+    /// Compression applied to every frame in both directions.
+    /// The server must be configured with the same codec.
+    #[builder(default)]
+    pub compression: Compression,
+
+    /// Where the RPC layer's error codes sit among MoQ app error codes.
+    /// The server must be configured with the same code space.
+    #[builder(default)]
+    pub code_space: CodeSpace,
+
+    /// Send the current W3C trace context to the server ahead of the first request.
+    /// Requires a propagator installed with `set_trace_propagator`.
+    #[builder(default)]
+    pub trace_propagation: bool,
+
+    /// End a connection cleanly on the trailer frame a server with status trailers sends after
+    /// its last response. Must match the server's setting.
+    #[builder(default)]
+    pub status_trailers: bool,
+
+    /// Track the sizes of every connection's request and response frames, read with
+    /// `RpcSender::size_stats` and `RpcReceiver::size_stats`.
+    #[builder(default)]
+    pub size_stats: bool,
 }
 
 impl RpcClientConfig {
+    /// Check the settings a connection can't be opened with.
+    pub(crate) fn validate(&self) -> Result<(), RpcClientError> {
+        for (name, duration) in [
+            ("keepalive_interval", self.keepalive_interval),
+            ("keepalive_timeout", self.keepalive_timeout),
+        ] {
+            if duration.is_some_and(|duration| duration.is_zero()) {
+                return Err(RpcClientError::Config(format!("{name} must not be zero")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Exchange the messages of `grpc_path` on a track named `track_name` instead of the
+    /// default one.
+    pub fn with_route_track_name(
+        mut self,
+        grpc_path: impl Into<String>,
+        track_name: impl Into<String>,
+    ) -> Self {
+        self.route_track_names
+            .insert(grpc_path.into(), track_name.into());
+        self
+    }
+
+    /// The name of the track the messages of `grpc_path` are exchanged on.
+    ///
+    /// A versioned path without an override of its own takes the override of its unversioned
+    /// path, like its requests fall back to the unversioned handler.
+    pub fn track_name_for(&self, grpc_path: &str) -> &str {
+        self.route_track_names
+            .get(grpc_path)
+            .or_else(|| self.route_track_names.get(strip_version(grpc_path)))
+            .unwrap_or(&self.track_name)
+    }
+
+    /// Fail connections that receive no response within `timeout` of connecting.
+    pub fn with_first_response_timeout(mut self, timeout: Duration) -> Self {
+        self.first_response_timeout = Some(timeout);
+        self
+    }
+
+    /// Send a keepalive frame after `interval` without a request.
+    pub fn with_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_interval = Some(interval);
+        self
+    }
+
+    /// Fail connections that hear nothing from the server within `timeout`.
+    pub fn with_keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive_timeout = Some(timeout);
+        self
+    }
+
+    /// Compress every frame in both directions with `compression`.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Expect error codes offset into `space`.
+    pub fn with_code_space(mut self, space: CodeSpace) -> Self {
+        self.code_space = space;
+        self
+    }
+
+    /// Send the current trace context to the server when connecting.
+    pub fn with_trace_propagation(mut self, enabled: bool) -> Self {
+        self.trace_propagation = enabled;
+        self
+    }
+
+    /// Expect the server to end each response stream with a status trailer.
+    pub fn with_status_trailers(mut self, enabled: bool) -> Self {
+        self.status_trailers = enabled;
+        self
+    }
+
+    /// Track the sizes of every connection's request and response frames.
+    pub fn with_size_stats(mut self, enabled: bool) -> Self {
+        self.size_stats = enabled;
+        self
+    }
+
+    /// Append a random suffix to the client ID, e.g. `drone-1-3f9a0c12`.
+    ///
+    /// Lets several processes share a configured ID without colliding on their broadcast
+    /// paths. The server sees the suffixed ID.
+    pub fn with_random_client_suffix(mut self) -> Self {
+        let suffix = RandomState::new().hash_one(SystemTime::now()) as u32;
+        self.client_id = format!("{}-{suffix:08x}", self.client_id);
+        self
+    }
+
     /// Build the client broadcast path for a given gRPC path.
     pub(crate) fn client_path(&self, grpc_path: &str) -> String {
         match &self.client_prefix {
@@ -1,13 +1,56 @@
+use bytes::Bytes;
 use futures::{Sink, Stream};
 use moq_lite::BroadcastProducer;
-use prost::Message;
+use std::collections::HashMap;
+use std::future::Future;
 use std::marker::PhantomData;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, Sleep};
+
+use crate::codec::{MessageCodec, ProstCodec, codec_id};
 use crate::connection::{RpcInbound, RpcOutbound};
-use crate::error::{RpcSendError, RpcWireError};
+use crate::error::{RejectReason, RpcClientError, RpcSendError, RpcWireError};
+use crate::stats::SizeStats;
+
+/// Resolves once the server's response broadcast is no longer announced.
+pub(crate) type WithdrawnFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// The paths a connection was established on, from [`RpcConnection::info`] or either half's.
+///
+/// Handy for logging, or for telling why a client and server that look configured alike never
+/// meet: the server only sees a client whose `client_path` is under its client prefix, and
+/// answers at the `server_path` the client waits on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectInfo {
+    /// Where the client published its request broadcast.
+    pub client_path: String,
+    /// Where the client found the server's response broadcast.
+    pub server_path: String,
+    /// The gRPC path that was connected to.
+    pub grpc_path: String,
+}
+
+/// What the halves of a connection share: the request broadcast, which stays up as long as
+/// either half is alive, and where the connection was established.
+pub(crate) struct Established {
+    _broadcast: BroadcastProducer,
+    info: ConnectInfo,
+}
+
+impl Established {
+    pub(crate) fn new(broadcast: BroadcastProducer, info: ConnectInfo) -> Self {
+        Self {
+            _broadcast: broadcast,
+            info,
+        }
+    }
+}
 
 /// A bidirectional RPC connection.
 ///
@@ -32,47 +75,84 @@ use crate::error::{RpcSendError, RpcWireError};
 ///     println!("Got: {:?}", response?);
 /// }
 /// ```
-pub struct RpcConnection<Req, Resp> {
-    sender: RpcSender<Req>,
-    receiver: RpcReceiver<Resp>,
+///
+/// Messages are encoded with the codec `C`, protobuf by default.
+pub struct RpcConnection<Req, Resp, C = ProstCodec> {
+    sender: RpcSender<Req, C>,
+    receiver: RpcReceiver<Resp, C>,
 }
 
-impl<Req, Resp> RpcConnection<Req, Resp> {
+impl<Req, Resp, C> RpcConnection<Req, Resp, C>
+where
+    C: MessageCodec<Req>,
+{
     /// Create a new RPC connection from its parts.
     pub(crate) fn new(
         outbound: RpcOutbound,
-        inbound: RpcInbound,
-        broadcast: Arc<BroadcastProducer>,
+        receiver: RpcReceiver<Resp, C>,
+        established: Arc<Established>,
+        server_gone: WithdrawnFuture,
     ) -> Self {
         Self {
-            sender: RpcSender::new(outbound, Arc::clone(&broadcast)),
-            receiver: RpcReceiver::new(inbound, broadcast),
+            sender: RpcSender::new(outbound, established, server_gone),
+            receiver,
         }
     }
 
+    /// Fail the receiver if no response arrives within `timeout`.
+    pub(crate) fn with_first_response_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.receiver = self
+            .receiver
+            .with_first_response_deadline(timeout.map(|timeout| Instant::now() + timeout));
+        self
+    }
+}
+
+impl<Req, Resp, C> RpcConnection<Req, Resp, C> {
+    /// The paths the connection was established on. Both halves keep them once split.
+    pub fn info(&self) -> &ConnectInfo {
+        self.sender.info()
+    }
+
+    /// Signal that no more requests will be sent, keeping the receive half.
+    ///
+    /// See [`RpcSender::finish`]. Responses keep arriving on the returned receiver until the
+    /// server ends the response stream.
+    pub fn finish(self) -> RpcReceiver<Resp, C> {
+        self.sender.finish();
+        self.receiver
+    }
+
     /// Split the connection into separate send and receive halves.
     ///
     /// Both halves share ownership of the underlying broadcast, so the connection
     /// stays alive as long as either half is alive.
-    pub fn split(self) -> (RpcSender<Req>, RpcReceiver<Resp>) {
+    pub fn split(self) -> (RpcSender<Req, C>, RpcReceiver<Resp, C>) {
         (self.sender, self.receiver)
     }
+
+    /// The sizes of the request frames sent so far, see [`RpcSender::size_stats`].
+    pub fn request_size_stats(&self) -> Option<SizeStats> {
+        self.sender.size_stats()
+    }
+
+    /// The sizes of the response frames read so far, see [`RpcReceiver::size_stats`].
+    pub fn response_size_stats(&self) -> Option<SizeStats> {
+        self.receiver.size_stats()
+    }
 }
 
-impl<Req, Resp> Stream for RpcConnection<Req, Resp>
-where
-    Resp: Message + Default,
-{
-    type Item = Result<Resp, RpcWireError>;
+impl<Req, Resp, C> Stream for RpcConnection<Req, Resp, C> {
+    type Item = Result<Resp, RpcClientError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         Pin::new(&mut self.receiver).poll_next(cx)
     }
 }
 
-impl<Req, Resp> Sink<Req> for RpcConnection<Req, Resp>
+impl<Req, Resp, C> Sink<Req> for RpcConnection<Req, Resp, C>
 where
-    Req: Message,
+    C: MessageCodec<Req>,
 {
     type Error = RpcSendError;
 
@@ -93,50 +173,248 @@ where
     }
 }
 
+/// An RPC connection whose responses arrive on several named tracks.
+///
+/// Returned by [`RpcClient::connect_multi`](crate::RpcClient::connect_multi) for services that
+/// send logically separate streams, say a high-rate position feed and a low-rate event feed,
+/// on tracks of their own rather than interleaved on one. Each track is read by its own
+/// [`RpcReceiver`], which may decode a different response type.
+///
+/// The sender and every receiver share ownership of the request broadcast, so the connection
+/// stays alive as long as any of them is.
+pub struct MultiTrackConnection<Req, C = ProstCodec> {
+    sender: RpcSender<Req, C>,
+    tracks: HashMap<String, (RpcInbound, Option<WithdrawnFuture>)>,
+    established: Arc<Established>,
+    min_frame_len: usize,
+    first_response_deadline: Option<Instant>,
+}
+
+impl<Req, C> MultiTrackConnection<Req, C> {
+    /// Create a multi-track connection from its parts, with one inbound per track name.
+    pub(crate) fn new(
+        outbound: RpcOutbound,
+        tracks: HashMap<String, (RpcInbound, Option<WithdrawnFuture>)>,
+        established: Arc<Established>,
+        min_frame_len: usize,
+        server_gone: WithdrawnFuture,
+    ) -> Self {
+        Self {
+            sender: RpcSender::new(outbound, Arc::clone(&established), server_gone),
+            tracks,
+            established,
+            min_frame_len,
+            first_response_deadline: None,
+        }
+    }
+
+    /// Fail each receiver if no response arrives on its track within `timeout` of connecting.
+    pub(crate) fn with_first_response_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.first_response_deadline = timeout.map(|timeout| Instant::now() + timeout);
+        self
+    }
+
+    /// The paths the connection was established on. The sender and receivers keep them too.
+    pub fn info(&self) -> &ConnectInfo {
+        &self.established.info
+    }
+
+    /// The sender for requests.
+    pub fn sender(&mut self) -> &mut RpcSender<Req, C> {
+        &mut self.sender
+    }
+
+    /// The names of the tracks whose receivers have not been taken yet.
+    pub fn track_names(&self) -> impl Iterator<Item = &str> {
+        self.tracks.keys().map(String::as_str)
+    }
+
+    /// Take the receiver for the track `name`, decoding responses as `Resp`.
+    ///
+    /// Returns `None` if `name` was not requested, or its receiver was already taken.
+    pub fn take_receiver<Resp>(&mut self, name: &str) -> Option<RpcReceiver<Resp, C>>
+    where
+        Resp: 'static,
+        C: MessageCodec<Resp>,
+    {
+        let (inbound, withdrawn) = self.tracks.remove(name)?;
+        Some(
+            RpcReceiver::new(
+                inbound,
+                Arc::clone(&self.established),
+                self.min_frame_len,
+                withdrawn,
+            )
+            .with_first_response_deadline(self.first_response_deadline),
+        )
+    }
+
+    /// Split into the sender and a receiver for every remaining track, keyed by track name.
+    ///
+    /// Every receiver decodes the same `Resp`. Use
+    /// [`take_receiver`](Self::take_receiver) first for tracks carrying other types.
+    pub fn split<Resp>(mut self) -> (RpcSender<Req, C>, HashMap<String, RpcReceiver<Resp, C>>)
+    where
+        Resp: 'static,
+        C: MessageCodec<Resp>,
+    {
+        let names: Vec<_> = self.tracks.keys().cloned().collect();
+        let receivers = names
+            .into_iter()
+            .filter_map(|name| {
+                let receiver = self.take_receiver(&name)?;
+                Some((name, receiver))
+            })
+            .collect();
+        (self.sender, receivers)
+    }
+}
+
 /// The send half of an `RpcConnection`.
 ///
 /// Implements `Sink` for sending request messages to the server.
 /// Shares ownership of the underlying broadcast with `RpcReceiver`.
-pub struct RpcSender<Req> {
+///
+/// Once the server's response broadcast is closed or withdrawn, or its response track ends,
+/// `poll_ready` fails with [`RpcSendError::Closed`], so `SinkExt::send` notices a server that
+/// has gone away without waiting for the receiver to.
+pub struct RpcSender<Req, C = ProstCodec> {
     outbound: RpcOutbound,
     // Keeps the broadcast alive; shared with RpcReceiver when split
-    _broadcast: Arc<BroadcastProducer>,
-    _marker: PhantomData<fn(Req)>,
+    established: Arc<Established>,
+    // Dropped once it resolves, after which the sender stays closed
+    server_gone: Option<WithdrawnFuture>,
+    _marker: PhantomData<fn(Req, C)>,
 }
 
-impl<Req> RpcSender<Req> {
-    fn new(outbound: RpcOutbound, broadcast: Arc<BroadcastProducer>) -> Self {
+impl<Req, C> RpcSender<Req, C> {
+    fn new(
+        outbound: RpcOutbound,
+        established: Arc<Established>,
+        server_gone: WithdrawnFuture,
+    ) -> Self {
         Self {
             outbound,
-            _broadcast: broadcast,
+            established,
+            server_gone: Some(server_gone),
             _marker: PhantomData,
         }
     }
+
+    /// The paths the connection was established on.
+    pub fn info(&self) -> &ConnectInfo {
+        &self.established.info
+    }
+
+    /// The sizes of the request frames sent so far, or `None` unless the client tracks them.
+    ///
+    /// See [`RpcOutbound::size_stats`] and `RpcClientConfig::size_stats`.
+    pub fn size_stats(&self) -> Option<SizeStats> {
+        self.outbound.size_stats()
+    }
+
+    /// Whether the server's response broadcast or track has been closed or withdrawn.
+    fn poll_server_gone(&mut self, cx: &mut Context<'_>) -> bool {
+        let Some(server_gone) = self.server_gone.as_mut() else {
+            return true;
+        };
+        if server_gone.as_mut().poll(cx).is_pending() {
+            return false;
+        }
+        self.server_gone = None;
+        true
+    }
+
+    /// Signal that no more requests will be sent.
+    ///
+    /// Buffered requests are written and the request track is closed cleanly, so the server's
+    /// request stream ends promptly instead of when the broadcast is eventually torn down.
+    /// This completes the client-streaming side of the gRPC call without an error.
+    pub fn finish(self) {
+        self.outbound.finish();
+    }
+
+    /// Move the sender onto a task of its own, fed by a cloneable channel of `capacity`
+    /// requests.
+    ///
+    /// Every clone of the returned `mpsc::Sender` feeds the same connection, so several tasks
+    /// can send without sharing this non-`Clone` sender. Requests are written in the order the
+    /// channel receives them.
+    ///
+    /// Dropping every clone of the sender [`finish`](Self::finish)es the request track and
+    /// ends the task. So does the server going away, after which sends on the channel fail.
+    /// A request that fails to encode is logged and skipped.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn into_channel(mut self, capacity: usize) -> (mpsc::Sender<Req>, JoinHandle<()>)
+    where
+        Req: Send + 'static,
+        C: MessageCodec<Req> + 'static,
+    {
+        let (sender, mut requests) = mpsc::channel(capacity);
+        let task = tokio::spawn(async move {
+            loop {
+                let server_gone = std::future::poll_fn(|cx| {
+                    if self.poll_server_gone(cx) {
+                        Poll::Ready(())
+                    } else {
+                        Poll::Pending
+                    }
+                });
+                let request = tokio::select! {
+                    request = requests.recv() => request,
+                    () = server_gone => {
+                        tracing::debug!("Server is gone, closing the request channel");
+                        return;
+                    }
+                };
+                let Some(request) = request else {
+                    break;
+                };
+                if let Err(err) = self.outbound.send_with::<C, Req>(&request) {
+                    tracing::warn!(%err, "Failed to send request from channel");
+                }
+            }
+            self.finish();
+        });
+        (sender, task)
+    }
 }
 
-impl<Req> Sink<Req> for RpcSender<Req>
+impl<Req, C> Sink<Req> for RpcSender<Req, C>
 where
-    Req: Message,
+    C: MessageCodec<Req>,
 {
     type Error = RpcSendError;
 
-    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        // MoQ tracks are always ready to accept writes
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // MoQ tracks always accept writes, the only reason to refuse is that nobody will read them
+        if self.poll_server_gone(cx) {
+            return Poll::Ready(Err(RpcSendError::Closed));
+        }
         Poll::Ready(Ok(()))
     }
 
     fn start_send(mut self: Pin<&mut Self>, item: Req) -> Result<(), Self::Error> {
-        self.outbound.send(&item)?;
+        self.outbound.send_with::<C, Req>(&item)?;
         Ok(())
     }
 
-    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        // MoQ writes are immediately flushed
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        // MoQ writes are immediately flushed; only auto-flush batches need writing out
+        self.outbound.flush();
         Poll::Ready(Ok(()))
     }
 
-    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
         // Closing is handled by dropping the broadcast
+        self.outbound.flush();
         Poll::Ready(Ok(()))
     }
 }
@@ -145,38 +423,205 @@ where
 ///
 /// Implements `Stream` for receiving response messages from the server.
 /// Shares ownership of the underlying broadcast with `RpcSender`.
-pub struct RpcReceiver<Resp> {
+///
+/// The stream ends with `None` when the server closes the response track, and
+/// yields `RpcClientError::ServerDisconnected` before ending if the server's
+/// response broadcast is withdrawn instead.
+///
+/// With a first response timeout configured, it yields `RpcWireError::Deadline` and ends if
+/// nothing arrives before the deadline. With a keepalive timeout configured, it yields
+/// `RpcWireError::KeepaliveTimeout` and ends if the server, keepalives included, goes quiet
+/// for longer than the timeout.
+pub struct RpcReceiver<Resp, C = ProstCodec> {
     inbound: RpcInbound,
+    decoder: ResponseDecoder<Resp>,
     // Keeps the broadcast alive; shared with RpcSender when split
-    _broadcast: Arc<BroadcastProducer>,
-    _marker: PhantomData<fn() -> Resp>,
+    established: Arc<Established>,
+    min_frame_len: usize,
+    withdrawn: Option<WithdrawnFuture>,
+    // Cleared once the first response arrives.
+    first_response: Option<Pin<Box<Sleep>>>,
+    disconnected: bool,
+    _marker: PhantomData<fn() -> (Resp, C)>,
+}
+
+/// Turns a response frame payload into a response.
+type ResponseDecoder<Resp> = Box<dyn Fn(Bytes) -> Result<Resp, RpcWireError> + Send + Sync>;
+
+/// Wrap `decode` so a rejected response yields `RpcWireError::Decode`, as with the codecs.
+pub(crate) fn decode_response_with<Resp, E: std::fmt::Display>(
+    decode: impl Fn(Bytes) -> Result<Resp, E> + Send + Sync + 'static,
+) -> ResponseDecoder<Resp> {
+    Box::new(move |payload| {
+        decode(payload).map_err(|err| {
+            tracing::warn!(%err, "Failed to decode response");
+            RpcWireError::Decode
+        })
+    })
+}
+
+impl<Resp, C> RpcReceiver<Resp, C>
+where
+    Resp: 'static,
+    C: MessageCodec<Resp>,
+{
+    /// A receiver decoding responses with the codec `C`.
+    pub(crate) fn new(
+        inbound: RpcInbound,
+        established: Arc<Established>,
+        min_frame_len: usize,
+        withdrawn: Option<WithdrawnFuture>,
+    ) -> Self {
+        Self::from_decoder(
+            inbound.with_codec_id(codec_id::<C, Resp>()),
+            Box::new(C::decode),
+            established,
+            min_frame_len,
+            withdrawn,
+        )
+    }
 }
 
-impl<Resp> RpcReceiver<Resp> {
-    fn new(inbound: RpcInbound, broadcast: Arc<BroadcastProducer>) -> Self {
+impl<Resp, C> RpcReceiver<Resp, C> {
+    /// A receiver decoding responses with `decoder`, whatever their codec tag.
+    pub(crate) fn from_decoder(
+        inbound: RpcInbound,
+        decoder: ResponseDecoder<Resp>,
+        established: Arc<Established>,
+        min_frame_len: usize,
+        withdrawn: Option<WithdrawnFuture>,
+    ) -> Self {
         Self {
             inbound,
-            _broadcast: broadcast,
+            decoder,
+            established,
+            min_frame_len,
+            withdrawn,
+            first_response: None,
+            disconnected: false,
             _marker: PhantomData,
         }
     }
+
+    fn with_first_response_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.first_response = deadline.map(|deadline| Box::pin(tokio::time::sleep_until(deadline)));
+        self
+    }
+
+    /// When the last frame from the server, keepalives included, arrived. `None` until the
+    /// first.
+    pub fn last_seen(&self) -> Option<Instant> {
+        self.inbound.last_seen()
+    }
+
+    /// The paths the connection was established on.
+    pub fn info(&self) -> &ConnectInfo {
+        &self.established.info
+    }
+
+    /// Decode response frames with `decoder` instead of the codec.
+    ///
+    /// The client side of `DecodedInbound::with_decoder`: the decoder gets the frame payload,
+    /// decompressed and without the codec tag, and may reject it for any reason, for example a
+    /// response that decodes fine but is not valid. A rejected frame is yielded as
+    /// `RpcWireError::Decode`, and the decoder's error is logged.
+    pub fn with_decoder<F, E>(mut self, decoder: F) -> Self
+    where
+        F: Fn(Bytes) -> Result<Resp, E> + Send + Sync + 'static,
+        E: std::fmt::Display,
+    {
+        self.decoder = decode_response_with(decoder);
+        self
+    }
+
+    /// The sizes of the response frames read so far, or `None` unless the client tracks them.
+    ///
+    /// See [`RpcInbound::size_stats`] and `RpcClientConfig::size_stats`.
+    pub fn size_stats(&self) -> Option<SizeStats> {
+        self.inbound.size_stats()
+    }
+
+    /// Whether the first response deadline has passed without a response.
+    fn poll_first_response_expired(&mut self, cx: &mut Context<'_>) -> bool {
+        self.first_response
+            .as_mut()
+            .is_some_and(|deadline| deadline.as_mut().poll(cx).is_ready())
+    }
+
+    /// Yield `Deadline` once, after which the stream ends.
+    fn expire(&mut self) -> Poll<Option<Result<Resp, RpcClientError>>> {
+        self.first_response = None;
+        self.disconnected = true;
+        Poll::Ready(Some(Err(RpcWireError::Deadline.into())))
+    }
+
+    /// Whether the server's response broadcast has been withdrawn.
+    ///
+    /// Once the withdrawal has been observed the future is dropped, so this only
+    /// needs to poll it until it first resolves.
+    fn poll_withdrawn(&mut self, cx: &mut Context<'_>) -> bool {
+        let Some(withdrawn) = self.withdrawn.as_mut() else {
+            return false;
+        };
+        if withdrawn.as_mut().poll(cx).is_pending() {
+            return false;
+        }
+        self.withdrawn = None;
+        true
+    }
+
+    /// Yield `ServerDisconnected` once, after which the stream ends.
+    fn disconnect(&mut self) -> Poll<Option<Result<Resp, RpcClientError>>> {
+        self.disconnected = true;
+        Poll::Ready(Some(Err(RpcClientError::ServerDisconnected)))
+    }
 }
 
-impl<Resp> Stream for RpcReceiver<Resp>
-where
-    Resp: Message + Default,
-{
-    type Item = Result<Resp, RpcWireError>;
+impl<Resp, C> Stream for RpcReceiver<Resp, C> {
+    type Item = Result<Resp, RpcClientError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match Pin::new(&mut self.inbound).poll_next(cx) {
-            Poll::Ready(Some(Ok(bytes))) => match Resp::decode(bytes) {
-                Ok(msg) => Poll::Ready(Some(Ok(msg))),
-                Err(_) => Poll::Ready(Some(Err(RpcWireError::Decode))),
-            },
-            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(RpcWireError::from(err)))),
-            Poll::Ready(None) => Poll::Ready(None),
-            Poll::Pending => Poll::Pending,
+        let this = self.as_mut().get_mut();
+        if this.disconnected {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            return match Pin::new(&mut this.inbound).poll_next(cx) {
+                Poll::Ready(Some(Ok(bytes))) if bytes.len() < this.min_frame_len => {
+                    tracing::debug!(len = bytes.len(), "Skipping short response frame");
+                    continue;
+                }
+                Poll::Ready(Some(Ok(bytes))) => {
+                    this.first_response = None;
+                    Poll::Ready(Some((this.decoder)(bytes).map_err(RpcClientError::from)))
+                }
+                Poll::Ready(Some(Err(err))) => {
+                    let space = this.inbound.code_space();
+                    let err = match this.inbound.take_server_error() {
+                        Some(err) => err,
+                        // A transport error after the broadcast went away means the
+                        // server is gone rather than that it rejected us.
+                        None if !matches!(err, moq_lite::Error::App(_))
+                            && this.poll_withdrawn(cx) =>
+                        {
+                            return this.disconnect();
+                        }
+                        None => RpcWireError::transport_in(err, space),
+                    };
+                    let err = match RejectReason::from_wire(&err, space) {
+                        Some(reason) => RpcClientError::Rejected { reason },
+                        None => err.into(),
+                    };
+                    Poll::Ready(Some(Err(err)))
+                }
+                Poll::Ready(None) => Poll::Ready(None),
+                // MoQ does not close a broadcast's tracks when it is withdrawn,
+                // so watch the announcement alongside the track.
+                Poll::Pending if this.poll_withdrawn(cx) => this.disconnect(),
+                Poll::Pending if this.poll_first_response_expired(cx) => this.expire(),
+                Poll::Pending => Poll::Pending,
+            };
         }
     }
 }
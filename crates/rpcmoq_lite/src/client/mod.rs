@@ -35,5 +35,5 @@ mod connection;
 mod rpc_client;
 
 pub use config::RpcClientConfig;
-pub use connection::{RpcConnection, RpcReceiver, RpcSender};
+pub use connection::{ConnectInfo, MultiTrackConnection, RpcConnection, RpcReceiver, RpcSender};
 pub use rpc_client::RpcClient;
@@ -1,12 +1,25 @@
-use moq_lite::{BroadcastConsumer, OriginConsumer, OriginProducer, Track};
+use bytes::Bytes;
+use futures::future::Shared;
+use futures::{FutureExt, SinkExt, StreamExt};
+use moq_lite::{BroadcastConsumer, OriginConsumer, OriginProducer, Path, Track, TrackConsumer};
 use prost::Message;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{debug, info};
 
 use crate::client::config::RpcClientConfig;
-use crate::client::connection::RpcConnection;
+use crate::client::connection::{
+    ConnectInfo, Established, MultiTrackConnection, RpcConnection, RpcReceiver, WithdrawnFuture,
+    decode_response_with,
+};
+use crate::codec::{MessageCodec, PROST_CODEC_ID, ProstCodec};
 use crate::connection::{RpcInbound, RpcOutbound};
 use crate::error::RpcClientError;
+use crate::metadata::RpcMetadata;
+use crate::path::GrpcPath;
+use crate::reflection::{ListMethodsRequest, ListMethodsResponse, REFLECTION_PATH};
+use crate::trace::trace_propagator;
 
 /// An RPC client that connects to a server over MoQ.
 ///
@@ -68,7 +81,8 @@ impl RpcClient {
     ///
     /// This method:
     /// 1. Creates a broadcast at `{client_prefix}/{client_id}/{grpc_path}`
-    /// 2. Waits for the server to announce its response broadcast (with timeout)
+    /// 2. Waits for the server to announce its response broadcast, returning as soon
+    ///    as it appears (the configured timeout is only an upper bound)
     /// 3. Returns an `RpcConnection` that implements `Sink` and `Stream`
     ///
     /// # Type Parameters
@@ -79,6 +93,7 @@ impl RpcClient {
     /// # Errors
     ///
     /// Returns an error if:
+    /// * The config is invalid, like a zero keepalive interval
     /// * Failed to create the client broadcast
     /// * Timeout waiting for server response broadcast
     /// * Server broadcast was not found
@@ -89,10 +104,161 @@ impl RpcClient {
     where
         Req: Message + Default + Send + 'static,
         Resp: Message + Default + Send + 'static,
+    {
+        self.connect_with_codec::<Req, Resp, ProstCodec>(grpc_path)
+            .await
+    }
+
+    /// Connect to an RPC endpoint, encoding messages with codec `C`.
+    ///
+    /// Behaves like [`connect`](Self::connect). The server must register the
+    /// path with the same codec, otherwise both sides fail with
+    /// `RpcWireError::CodecMismatch` on the first frame.
+    pub async fn connect_with_codec<Req, Resp, C>(
+        &mut self,
+        grpc_path: impl Into<String>,
+    ) -> Result<RpcConnection<Req, Resp, C>, RpcClientError>
+    where
+        Req: Send + 'static,
+        Resp: Send + 'static,
+        C: MessageCodec<Req> + MessageCodec<Resp>,
+    {
+        self.connect_receiving(grpc_path.into(), RpcReceiver::new)
+            .await
+    }
+
+    /// Connect to an RPC endpoint whose protobuf responses are decoded with `decoder` instead
+    /// of `Resp::decode`.
+    ///
+    /// Behaves like [`connect`](Self::connect), but `Resp` need not be a prost message, for
+    /// servers registered with `RpcRouter::register_with_decoder`. The decoder may also reject
+    /// responses, see [`RpcReceiver::with_decoder`].
+    pub async fn connect_with_decoder<Req, Resp, D, E>(
+        &mut self,
+        grpc_path: impl Into<String>,
+        decoder: D,
+    ) -> Result<RpcConnection<Req, Resp>, RpcClientError>
+    where
+        Req: Message + Default + Send + 'static,
+        Resp: Send + 'static,
+        D: Fn(Bytes) -> Result<Resp, E> + Send + Sync + 'static,
+        E: std::fmt::Display,
+    {
+        let decoder = decode_response_with(decoder);
+        self.connect_receiving(
+            grpc_path.into(),
+            |inbound: RpcInbound, established, min_frame_len, withdrawn| {
+                let inbound = inbound.with_codec_id(PROST_CODEC_ID);
+                RpcReceiver::from_decoder(inbound, decoder, established, min_frame_len, withdrawn)
+            },
+        )
+        .await
+    }
+
+    /// Connect to `grpc_path`, reading responses with the receiver `receiver` builds from the
+    /// response track, what the halves of the connection share, the minimum frame length and
+    /// the withdrawal.
+    async fn connect_receiving<Req, Resp, C>(
+        &mut self,
+        grpc_path: String,
+        receiver: impl FnOnce(
+            RpcInbound,
+            Arc<Established>,
+            usize,
+            Option<WithdrawnFuture>,
+        ) -> RpcReceiver<Resp, C>,
+    ) -> Result<RpcConnection<Req, Resp, C>, RpcClientError>
+    where
+        C: MessageCodec<Req>,
+    {
+        let Opened {
+            outbound,
+            server_broadcast,
+            established,
+        } = self.open(&grpc_path).await?;
+
+        // Subscribe to the server's response track
+        let inbound = self.inbound(&server_broadcast, self.config.track_name_for(&grpc_path));
+        let withdrawn = self.withdrawal(&grpc_path);
+        let server_gone = server_gone(&server_broadcast, inbound.track(), withdrawn.clone());
+        let withdrawn = withdrawn.map(|withdrawn| Box::pin(withdrawn) as WithdrawnFuture);
+
+        info!(
+            client_id = %self.config.client_id,
+            grpc_path = %grpc_path,
+            "RPC connection established"
+        );
+
+        let receiver = receiver(
+            inbound,
+            Arc::clone(&established),
+            self.config.min_frame_len,
+            withdrawn,
+        );
+        Ok(
+            RpcConnection::new(outbound, receiver, established, server_gone)
+                .with_first_response_timeout(self.config.first_response_timeout),
+        )
+    }
+
+    /// Connect to an RPC endpoint whose responses are sent on several named tracks.
+    ///
+    /// Behaves like [`connect`](Self::connect), but subscribes to each of `track_names` on
+    /// the server's response broadcast instead of the configured track. Take a receiver per
+    /// track from the returned [`MultiTrackConnection`]. The server opens the extra tracks
+    /// with `SessionContext::response_track`.
+    pub async fn connect_multi<Req>(
+        &mut self,
+        grpc_path: impl Into<String>,
+        track_names: &[&str],
+    ) -> Result<MultiTrackConnection<Req>, RpcClientError>
+    where
+        Req: Message + Default + Send + 'static,
     {
         let grpc_path = grpc_path.into();
-        let client_path = self.config.client_path(&grpc_path);
-        let server_path = self.config.server_path(&grpc_path);
+        let Opened {
+            outbound,
+            server_broadcast,
+            established,
+        } = self.open(&grpc_path).await?;
+        let withdrawn = self.withdrawal(&grpc_path);
+
+        let tracks = track_names
+            .iter()
+            .map(|&name| {
+                let inbound = self.inbound(&server_broadcast, name);
+                let withdrawn = withdrawn
+                    .clone()
+                    .map(|withdrawn| Box::pin(withdrawn) as WithdrawnFuture);
+                (name.to_string(), (inbound, withdrawn))
+            })
+            .collect::<HashMap<_, _>>();
+        let response_tracks = tracks.values().filter_map(|(inbound, _)| inbound.track());
+        let server_gone = server_gone(&server_broadcast, response_tracks, withdrawn);
+
+        info!(
+            client_id = %self.config.client_id,
+            grpc_path = %grpc_path,
+            tracks = ?track_names,
+            "Multi-track RPC connection established"
+        );
+
+        Ok(MultiTrackConnection::new(
+            outbound,
+            tracks,
+            established,
+            self.config.min_frame_len,
+            server_gone,
+        )
+        .with_first_response_timeout(self.config.first_response_timeout))
+    }
+
+    /// Announce the request broadcast for `grpc_path` and wait for the server's response
+    /// broadcast.
+    async fn open(&mut self, grpc_path: &str) -> Result<Opened, RpcClientError> {
+        self.config.validate()?;
+        let client_path = self.config.client_path(grpc_path);
+        let server_path = self.config.server_path(grpc_path);
 
         info!(
             client_id = %self.config.client_id,
@@ -101,6 +267,13 @@ impl RpcClient {
             "Connecting to RPC endpoint"
         );
 
+        if self.client_path_in_use(&client_path) {
+            return Err(RpcClientError::ClientIdInUse {
+                client_id: self.config.client_id.clone(),
+                client_path,
+            });
+        }
+
         let mut broadcast = self
             .producer
             .create_broadcast(&client_path)
@@ -111,61 +284,91 @@ impl RpcClient {
             })?;
 
         // Create the outbound track for sending requests
-        let outbound_track = broadcast.create_track(Track::new(&self.config.track_name));
-        let outbound = RpcOutbound::new(outbound_track);
-
-        let server_broadcast = self.wait_for_server(&server_path).await?;
+        let outbound_track =
+            broadcast.create_track(Track::new(self.config.track_name_for(grpc_path)));
+        let mut outbound = RpcOutbound::new(outbound_track)
+            .with_compression(self.config.compression)
+            .with_code_space(self.config.code_space);
+        if self.config.trace_propagation
+            && let Some(ctx) = trace_propagator().and_then(|propagator| propagator.current())
+        {
+            let mut metadata = RpcMetadata::default();
+            ctx.inject(&mut metadata);
+            outbound = outbound.with_metadata(&metadata);
+        }
+        if let Some(interval) = self.config.keepalive_interval {
+            outbound = outbound.with_keepalive(interval);
+        }
+        if self.config.size_stats {
+            outbound = outbound.with_size_stats();
+        }
 
-        // Subscribe to the server's response track
-        let inbound = RpcInbound::new(&server_broadcast, &self.config.track_name);
+        let server_broadcast =
+            await_broadcast(&self.consumer, &server_path, self.config.timeout).await?;
 
-        info!(
-            client_id = %self.config.client_id,
-            grpc_path = %grpc_path,
-            "RPC connection established"
-        );
-
-        // Wrap the broadcast in Arc for shared ownership when split
-        let broadcast = Arc::new(broadcast);
+        let info = ConnectInfo {
+            client_path,
+            server_path,
+            grpc_path: grpc_path.to_string(),
+        };
+        Ok(Opened {
+            outbound,
+            server_broadcast,
+            // Shared by the halves of the connection when split
+            established: Arc::new(Established::new(broadcast, info)),
+        })
+    }
 
-        Ok(RpcConnection::new(outbound, inbound, broadcast))
+    /// Subscribe to the response track `track_name` of the server's broadcast.
+    fn inbound(&self, server_broadcast: &BroadcastConsumer, track_name: &str) -> RpcInbound {
+        let mut inbound = RpcInbound::new(server_broadcast, track_name)
+            .with_compression(self.config.compression)
+            .with_status_trailers(self.config.status_trailers)
+            .with_code_space(self.config.code_space);
+        if self.config.size_stats {
+            inbound = inbound.with_size_stats();
+        }
+        match self.config.keepalive_timeout {
+            Some(timeout) => inbound.with_keepalive_timeout(timeout),
+            None => inbound,
+        }
     }
 
-    /// Wait for the server to announce its response broadcast.
-    async fn wait_for_server(
-        &mut self,
-        server_path: &str,
-    ) -> Result<BroadcastConsumer, RpcClientError> {
-        let timeout = self.config.timeout;
+    /// Whether a broadcast is already announced at `client_path`, by this process or another.
+    fn client_path_in_use(&self, client_path: &str) -> bool {
+        self.producer
+            .consume()
+            .consume_broadcast(client_path)
+            .is_some()
+            || self.consumer.consume_broadcast(client_path).is_some()
+    }
 
-        debug!(
-            server_path = %server_path,
-            timeout_secs = %timeout.as_secs(),
-            "Waiting for server response broadcast"
-        );
+    /// A future resolving once the server's response broadcast for `grpc_path` is withdrawn.
+    ///
+    /// Shared, so the sender and receivers of a connection watch a single announcement stream.
+    fn withdrawal(&self, grpc_path: &str) -> Option<Shared<WithdrawnFuture>> {
+        let server_path = self.config.server_path(grpc_path);
+        self.consumer
+            .consume_only(&[Path::new(&server_path)])
+            .map(|announcements| {
+                (Box::pin(await_withdrawal(announcements, server_path)) as WithdrawnFuture).shared()
+            })
+    }
 
-        let wait_fut = async {
-            loop {
-                match self.consumer.announced().await {
-                    Some((path, Some(broadcast))) if path.as_str() == server_path => {
-                        debug!(path = %server_path, "Found server response broadcast");
-                        return Ok(broadcast);
-                    }
-                    Some((path, None)) if path.as_str() == server_path => {
-                        return Err(RpcClientError::ServerNotFound(server_path.to_string()));
-                    }
-                    Some(_) => {
-                        // Not our path, keep waiting
-                        continue;
-                    }
-                    None => {
-                        return Err(RpcClientError::ConnectionClosed);
-                    }
-                }
-            }
-        };
+    /// List the gRPC paths the server has handlers for.
+    ///
+    /// The server must have enabled reflection with `RpcRouter::enable_reflection`.
+    /// Waits at most the configured timeout for the response.
+    pub async fn list_methods(&mut self) -> Result<Vec<GrpcPath>, RpcClientError> {
+        let mut conn = self
+            .connect::<ListMethodsRequest, ListMethodsResponse>(REFLECTION_PATH)
+            .await?;
+        conn.send(ListMethodsRequest {}).await?;
 
-        tokio::time::timeout(timeout, wait_fut).await?
+        let response = tokio::time::timeout(self.config.timeout, conn.next())
+            .await?
+            .ok_or(RpcClientError::ConnectionClosed)??;
+        Ok(response.methods.into_iter().map(GrpcPath::from).collect())
     }
 
     /// Get the client ID.
@@ -178,3 +381,633 @@ impl RpcClient {
         &self.config
     }
 }
+
+/// A request broadcast announced by [`RpcClient::open`], and the server's response broadcast
+/// for it.
+struct Opened {
+    outbound: RpcOutbound,
+    server_broadcast: BroadcastConsumer,
+    established: Arc<Established>,
+}
+
+/// A future resolving once the server is done with a connection: its response broadcast is
+/// closed or `withdrawn`, or every one of its `response_tracks` has ended.
+fn server_gone(
+    server_broadcast: &BroadcastConsumer,
+    response_tracks: impl IntoIterator<Item = TrackConsumer>,
+    withdrawn: Option<Shared<WithdrawnFuture>>,
+) -> WithdrawnFuture {
+    let server_broadcast = server_broadcast.clone();
+    let response_tracks: Vec<_> = response_tracks.into_iter().collect();
+    Box::pin(async move {
+        let closed = std::pin::pin!(server_broadcast.closed());
+        let withdrawn = std::pin::pin!(async move {
+            match withdrawn {
+                Some(withdrawn) => withdrawn.await,
+                None => std::future::pending().await,
+            }
+        });
+        // A connection without a response track to watch relies on the broadcast alone
+        let tracks_ended = std::pin::pin!(async move {
+            if response_tracks.is_empty() {
+                return std::future::pending().await;
+            }
+            futures::future::join_all(response_tracks.iter().map(TrackConsumer::closed)).await;
+        });
+        futures::future::select(closed, futures::future::select(withdrawn, tracks_ended)).await;
+    })
+}
+
+/// Wait for the broadcast at exactly `path` to be announced on `consumer`.
+///
+/// Returns immediately if the broadcast is already active. Otherwise watches the
+/// announcement stream, scoped to `path`, until the broadcast appears, fails fast
+/// if it is unannounced or the origin closes, and gives up after `timeout`.
+pub(crate) async fn await_broadcast(
+    consumer: &OriginConsumer,
+    path: &str,
+    timeout: Duration,
+) -> Result<BroadcastConsumer, RpcClientError> {
+    if let Some(broadcast) = consumer.consume_broadcast(path) {
+        debug!(path = %path, "Broadcast already announced");
+        return Ok(broadcast);
+    }
+
+    // A fresh consumer scoped to the path replays anything announced since the check above.
+    let mut announcements = consumer
+        .consume_only(&[Path::new(path)])
+        .ok_or_else(|| RpcClientError::ServerNotFound(path.to_string()))?;
+
+    debug!(
+        path = %path,
+        timeout_secs = %timeout.as_secs(),
+        "Waiting for broadcast announcement"
+    );
+
+    let wait_fut = async {
+        loop {
+            match announcements.announced().await {
+                Some((announced, Some(broadcast))) if announced.as_str() == path => {
+                    debug!(path = %path, "Found broadcast");
+                    return Ok(broadcast);
+                }
+                Some((announced, None)) if announced.as_str() == path => {
+                    return Err(RpcClientError::ServerNotFound(path.to_string()));
+                }
+                Some(_) => {
+                    // Not our path, keep waiting
+                    continue;
+                }
+                None => {
+                    return Err(RpcClientError::ConnectionClosed);
+                }
+            }
+        }
+    };
+
+    tokio::time::timeout(timeout, wait_fut).await?
+}
+
+/// Resolve once the broadcast at `path` is unannounced on `announcements`, or
+/// the origin itself closes.
+async fn await_withdrawal(mut announcements: OriginConsumer, path: String) {
+    while let Some((announced, broadcast)) = announcements.announced().await {
+        if announced.as_str() == path && broadcast.is_none() {
+            debug!(path = %path, "Broadcast withdrawn");
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{CodeSpace, RejectReason, RpcWireError};
+    use futures::FutureExt;
+    use moq_lite::Origin;
+
+    const SERVER_PATH: &str = "server/drone-1/drone.EchoService/Echo";
+
+    #[tokio::test]
+    async fn test_await_broadcast_already_announced() {
+        let origin = Origin::produce();
+        let _broadcast = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+
+        let result = await_broadcast(&origin.consumer, SERVER_PATH, Duration::from_secs(30))
+            .now_or_never()
+            .expect("should not wait for an active broadcast");
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_await_broadcast_announced_later() {
+        let origin = Origin::produce();
+        let producer = origin.producer;
+
+        let announce = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let _other = producer.create_broadcast("server/drone-2/drone.EchoService/Echo");
+            let broadcast = producer.create_broadcast(SERVER_PATH);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            drop(broadcast);
+        });
+
+        let result = await_broadcast(&origin.consumer, SERVER_PATH, Duration::from_secs(30)).await;
+        assert!(result.is_ok());
+        announce.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_await_broadcast_times_out() {
+        let origin = Origin::produce();
+
+        let result =
+            await_broadcast(&origin.consumer, SERVER_PATH, Duration::from_millis(20)).await;
+        assert!(matches!(result, Err(RpcClientError::Timeout(_))));
+    }
+
+    fn client(origin: &moq_lite::Produce<OriginProducer, OriginConsumer>) -> RpcClient {
+        let config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .client_prefix("drone".to_string())
+            .server_prefix("server".to_string())
+            .timeout(Duration::from_secs(1))
+            .build();
+        RpcClient::new(
+            Arc::new(origin.producer.clone()),
+            origin.consumer.clone(),
+            config,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_receiver_reports_server_disconnected() {
+        let origin = Origin::produce();
+        let mut client = client(&origin);
+        let mut server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+        let track = server.create_track(Track::new(&client.config().track_name));
+
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        let mut outbound = RpcOutbound::new(track);
+        outbound.send(&"pong".to_string()).unwrap();
+        let response = tokio::time::timeout(Duration::from_secs(1), conn.next()).await;
+        assert_eq!(response.unwrap().unwrap().unwrap(), "pong");
+
+        // Withdraw the broadcast while the track itself stays open
+        drop(server);
+
+        let next = tokio::time::timeout(Duration::from_secs(1), conn.next()).await;
+        assert!(matches!(
+            next.unwrap(),
+            Some(Err(RpcClientError::ServerDisconnected))
+        ));
+        assert!(conn.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_connection_info_reports_resolved_paths() {
+        let origin = Origin::produce();
+        let mut client = client(&origin);
+        let _server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+
+        let grpc_path = "drone.EchoService/Echo";
+        let conn = client.connect::<String, String>(grpc_path).await.unwrap();
+        let expected = ConnectInfo {
+            client_path: client.config().client_path(grpc_path),
+            server_path: client.config().server_path(grpc_path),
+            grpc_path: grpc_path.to_string(),
+        };
+        assert_eq!(conn.info(), &expected);
+        assert_eq!(expected.client_path, "drone/drone-1/drone.EchoService/Echo");
+        assert_eq!(expected.server_path, SERVER_PATH);
+
+        // Either half can still tell where it was connected once split
+        let (sender, receiver) = conn.split();
+        assert_eq!(sender.info(), &expected);
+        assert_eq!(receiver.info(), &expected);
+    }
+
+    #[tokio::test]
+    async fn test_channel_sender_fans_in_from_several_tasks() {
+        const TASKS: u64 = 4;
+        const PER_TASK: u64 = 25;
+
+        let origin = Origin::produce();
+        let mut client = client(&origin);
+        let _server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+        let conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        let request_broadcast = origin
+            .consumer
+            .consume_broadcast("drone/drone-1/drone.EchoService/Echo")
+            .unwrap();
+        let mut requests = RpcInbound::new(&request_broadcast, &client.config().track_name);
+
+        let (sender, _receiver) = conn.split();
+        let (requests_tx, forwarder) = sender.into_channel(8);
+        let producers: Vec<_> = (0..TASKS)
+            .map(|task| {
+                let requests_tx = requests_tx.clone();
+                tokio::spawn(async move {
+                    for i in 0..PER_TASK {
+                        requests_tx.send(format!("{task}-{i}")).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        // Each request is its own group, and a reader that falls behind skips to the latest,
+        // so wait for the last one rather than counting
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while let Some(next) = requests.next_with_group().await {
+                if next.unwrap().0 == TASKS * PER_TASK - 1 {
+                    break;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        for producer in producers {
+            producer.await.unwrap();
+        }
+
+        // Dropping the last sender finishes the request track
+        drop(requests_tx);
+        tokio::time::timeout(Duration::from_secs(1), forwarder)
+            .await
+            .unwrap()
+            .unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(1), requests.next()).await;
+        assert!(next.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejects_client_id_in_use() {
+        let origin = Origin::produce();
+        let _server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+
+        let mut first = client(&origin);
+        let _conn = first
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+
+        let mut second = client(&origin);
+        let result = second
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await;
+        assert!(matches!(
+            result,
+            Err(RpcClientError::ClientIdInUse { client_id, .. }) if client_id == "drone-1"
+        ));
+    }
+
+    #[test]
+    fn test_random_client_suffix_disambiguates() {
+        let config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .build();
+        let first = config.clone().with_random_client_suffix();
+        let second = config.with_random_client_suffix();
+
+        assert!(first.client_id.starts_with("drone-1-"));
+        assert_ne!(first.client_id, second.client_id);
+    }
+
+    #[tokio::test]
+    async fn test_receiver_decodes_codes_in_code_space() {
+        let origin = Origin::produce();
+        let space = CodeSpace::new(1000);
+        let mut client = RpcClient::new(
+            Arc::new(origin.producer.clone()),
+            origin.consumer.clone(),
+            client(&origin).config().clone().with_code_space(space),
+        );
+        let mut server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+        let track = server.create_track(Track::new(&client.config().track_name));
+
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        let outbound = RpcOutbound::new(track).with_code_space(space);
+        outbound.abort_app(RpcWireError::Grpc.to_code_in(outbound.code_space()));
+
+        let next = tokio::time::timeout(Duration::from_secs(1), conn.next()).await;
+        assert!(matches!(
+            next.unwrap(),
+            Some(Err(RpcClientError::Wire(RpcWireError::Grpc)))
+        ));
+    }
+
+    /// Connect, have the server abort the response track with `err`, and return what the
+    /// client reads. The error frame is sent first if `error_frames` is set.
+    async fn rejected_with(err: RpcWireError, error_frames: bool) -> Option<RpcClientError> {
+        let origin = Origin::produce();
+        let mut client = client(&origin);
+        let mut server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+        let track = server.create_track(Track::new(&client.config().track_name));
+
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        RpcOutbound::new(track)
+            .with_error_frames(error_frames)
+            .abort_with_error(err.to_code(), err.to_string());
+
+        let next = tokio::time::timeout(Duration::from_secs(1), conn.next()).await;
+        next.unwrap().unwrap().err()
+    }
+
+    #[tokio::test]
+    async fn test_rejected_duplicate() {
+        let err = rejected_with(RpcWireError::SessionAlreadyActive, false).await;
+        assert!(matches!(
+            err,
+            Some(RpcClientError::Rejected {
+                reason: RejectReason::Duplicate
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_overloaded() {
+        let err = rejected_with(RpcWireError::Overloaded, false).await;
+        assert!(matches!(
+            err,
+            Some(RpcClientError::Rejected {
+                reason: RejectReason::Overloaded
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_unauthorized() {
+        let err = rejected_with(RpcWireError::Unauthorized, false).await;
+        assert!(matches!(
+            err,
+            Some(RpcClientError::Rejected {
+                reason: RejectReason::Unauthorized
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_no_handler() {
+        let err = rejected_with(RpcWireError::NoHandler, false).await;
+        assert!(matches!(
+            err,
+            Some(RpcClientError::Rejected {
+                reason: RejectReason::NoHandler
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_with_error_frame() {
+        let err = rejected_with(RpcWireError::Overloaded, true).await;
+        assert!(matches!(
+            err,
+            Some(RpcClientError::Rejected {
+                reason: RejectReason::Overloaded
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_first_response_timeout_when_server_never_responds() {
+        let origin = Origin::produce();
+        let mut client = RpcClient::new(
+            Arc::new(origin.producer.clone()),
+            origin.consumer.clone(),
+            client(&origin)
+                .config()
+                .clone()
+                .with_first_response_timeout(Duration::from_millis(50)),
+        );
+        // The server accepts the connection but never writes a response
+        let mut server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+        let _track = server.create_track(Track::new(&client.config().track_name));
+
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(1), conn.next()).await;
+        assert!(matches!(
+            next.unwrap(),
+            Some(Err(RpcClientError::Wire(RpcWireError::Deadline)))
+        ));
+        assert!(conn.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_first_response_timeout_only_covers_first_response() {
+        let origin = Origin::produce();
+        let mut client = RpcClient::new(
+            Arc::new(origin.producer.clone()),
+            origin.consumer.clone(),
+            client(&origin)
+                .config()
+                .clone()
+                .with_first_response_timeout(Duration::from_millis(50)),
+        );
+        let mut server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+        let track = server.create_track(Track::new(&client.config().track_name));
+
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        let mut outbound = RpcOutbound::new(track);
+        outbound.send(&"pong".to_string()).unwrap();
+        let response = tokio::time::timeout(Duration::from_secs(1), conn.next()).await;
+        assert_eq!(response.unwrap().unwrap().unwrap(), "pong");
+
+        // Well past the deadline, the connection is still waiting rather than failed
+        let next = tokio::time::timeout(Duration::from_millis(150), conn.next()).await;
+        assert!(next.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_timeout_detects_stalled_server() {
+        let origin = Origin::produce();
+        let mut client = RpcClient::new(
+            Arc::new(origin.producer.clone()),
+            origin.consumer.clone(),
+            client(&origin)
+                .config()
+                .clone()
+                .with_keepalive_timeout(Duration::from_millis(100)),
+        );
+        let mut server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+        let track = server.create_track(Track::new(&client.config().track_name));
+
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        let mut outbound = RpcOutbound::new(track).with_keepalive(Duration::from_millis(20));
+
+        // Quiet but alive: nothing is yielded, and nothing fails either
+        let next = tokio::time::timeout(Duration::from_millis(300), conn.next()).await;
+        assert!(next.is_err());
+        outbound.send(&"pong".to_string()).unwrap();
+        let response = tokio::time::timeout(Duration::from_secs(1), conn.next()).await;
+        assert_eq!(response.unwrap().unwrap().unwrap(), "pong");
+
+        // Stall the server: keepalives stop while the track stays open
+        let _stalled = outbound.track_mut().clone();
+        drop(outbound);
+
+        let next = tokio::time::timeout(Duration::from_secs(1), conn.next()).await;
+        assert!(matches!(
+            next.unwrap(),
+            Some(Err(RpcClientError::Wire(RpcWireError::KeepaliveTimeout)))
+        ));
+        assert!(conn.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejects_zero_keepalives() {
+        let origin = Origin::produce();
+        for config in [
+            client(&origin)
+                .config()
+                .clone()
+                .with_keepalive_interval(Duration::ZERO),
+            client(&origin)
+                .config()
+                .clone()
+                .with_keepalive_timeout(Duration::ZERO),
+        ] {
+            let mut client = RpcClient::new(
+                Arc::new(origin.producer.clone()),
+                origin.consumer.clone(),
+                config,
+            );
+            let result = client
+                .connect::<String, String>("drone.EchoService/Echo")
+                .await;
+            assert!(matches!(result, Err(RpcClientError::Config(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_multi_reads_tracks_separately() {
+        let origin = Origin::produce();
+        let mut client = client(&origin);
+        let mut server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+        let mut positions = RpcOutbound::new(server.create_track(Track::new("positions")));
+        let mut events = RpcOutbound::new(server.create_track(Track::new("events")));
+
+        let mut conn = client
+            .connect_multi::<String>("drone.EchoService/Echo", &["positions", "events"])
+            .await
+            .unwrap();
+        let mut event_receiver = conn.take_receiver::<String>("events").unwrap();
+        assert!(conn.take_receiver::<String>("events").is_none());
+        let (_sender, mut receivers) = conn.split::<String>();
+        assert_eq!(receivers.keys().collect::<Vec<_>>(), ["positions"]);
+        let position_receiver = receivers.get_mut("positions").unwrap();
+
+        positions.send(&"position".to_string()).unwrap();
+        events.send(&"event".to_string()).unwrap();
+
+        let position = tokio::time::timeout(Duration::from_secs(1), position_receiver.next());
+        assert_eq!(position.await.unwrap().unwrap().unwrap(), "position");
+        let event = tokio::time::timeout(Duration::from_secs(1), event_receiver.next());
+        assert_eq!(event.await.unwrap().unwrap().unwrap(), "event");
+
+        // Every receiver notices the server going away
+        drop(server);
+        let next = tokio::time::timeout(Duration::from_secs(1), event_receiver.next()).await;
+        assert!(matches!(
+            next.unwrap(),
+            Some(Err(RpcClientError::ServerDisconnected))
+        ));
+        let next = tokio::time::timeout(Duration::from_secs(1), position_receiver.next()).await;
+        assert!(matches!(
+            next.unwrap(),
+            Some(Err(RpcClientError::ServerDisconnected))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_finish_ends_server_inbound() {
+        let origin = Origin::produce();
+        let mut client = client(&origin);
+        let _server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        let request_broadcast = origin
+            .consumer
+            .consume_broadcast("drone/drone-1/drone.EchoService/Echo")
+            .unwrap();
+        let mut inbound = RpcInbound::new(&request_broadcast, &client.config().track_name);
+
+        conn.send("last".to_string()).await.unwrap();
+        let frame = tokio::time::timeout(Duration::from_secs(1), inbound.next()).await;
+        assert_eq!(
+            String::decode(frame.unwrap().unwrap().unwrap()).unwrap(),
+            "last"
+        );
+
+        let _receiver = conn.finish();
+        let next = tokio::time::timeout(Duration::from_secs(1), inbound.next()).await;
+        assert!(next.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_send_confirmed_reaches_client() {
+        let origin = Origin::produce();
+        let mut client = client(&origin);
+        let mut server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+        let track = server.create_track(Track::new(&client.config().track_name));
+        let outbound = RpcOutbound::new(track).with_broadcast(server);
+
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        let reader = tokio::spawn(async move {
+            let response = conn.next().await.unwrap().unwrap();
+            assert!(conn.next().await.is_none());
+            response
+        });
+
+        let start = std::time::Instant::now();
+        outbound
+            .send_confirmed(&"pong".to_string(), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(reader.await.unwrap(), "pong");
+    }
+
+    #[tokio::test]
+    async fn test_receiver_ends_when_track_closes() {
+        let origin = Origin::produce();
+        let mut client = client(&origin);
+        let mut server = origin.producer.create_broadcast(SERVER_PATH).unwrap();
+        let track = server.create_track(Track::new(&client.config().track_name));
+
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        track.close();
+
+        let next = tokio::time::timeout(Duration::from_secs(1), conn.next()).await;
+        assert!(next.unwrap().is_none());
+    }
+}
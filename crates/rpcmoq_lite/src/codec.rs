@@ -0,0 +1,107 @@
+use bytes::Bytes;
+
+use crate::error::{RpcSendError, RpcWireError};
+
+/// Serializes messages to and from the payload of a MoQ frame.
+///
+/// The codec's [`ID`](MessageCodec::ID) is carried in the frame's tag byte, so a peer
+/// decoding with a different codec fails with `RpcWireError::CodecMismatch` instead of
+/// misinterpreting the payload. See `Compression` for the layout of the tag.
+pub trait MessageCodec<M>: Send + Sync + 'static {
+    /// Identifies the codec on the wire. Must be unique and in `0..16`.
+    const ID: u8;
+
+    /// Encode `msg` into a frame payload.
+    fn encode(msg: &M) -> Result<Vec<u8>, RpcSendError>;
+
+    /// Decode a frame payload into a message.
+    fn decode(payload: Bytes) -> Result<M, RpcWireError>;
+}
+
+/// The wire ID of codec `C`, checked at compile time to fit the tag byte.
+pub(crate) const fn codec_id<C: MessageCodec<M>, M>() -> u8 {
+    const { assert!(C::ID < 16, "MessageCodec::ID must be in 0..16") };
+    C::ID
+}
+
+/// The wire ID of [`ProstCodec`], used for frames sent without an explicit codec.
+pub(crate) const PROST_CODEC_ID: u8 = 0;
+
+/// Encode `msg` as protobuf, as [`ProstCodec`] does.
+pub(crate) fn encode_prost<M: prost::Message>(msg: &M) -> Result<Vec<u8>, RpcSendError> {
+    let mut buf = Vec::with_capacity(msg.encoded_len());
+    msg.encode(&mut buf)?;
+    Ok(buf)
+}
+
+/// The default codec, encoding messages as protobuf with `prost`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProstCodec;
+
+impl<M> MessageCodec<M> for ProstCodec
+where
+    M: prost::Message + Default,
+{
+    const ID: u8 = PROST_CODEC_ID;
+
+    fn encode(msg: &M) -> Result<Vec<u8>, RpcSendError> {
+        encode_prost(msg)
+    }
+
+    fn decode(payload: Bytes) -> Result<M, RpcWireError> {
+        M::decode(payload).map_err(|_| RpcWireError::Decode)
+    }
+}
+
+/// Encodes messages as JSON with `serde_json`, for debugging and web client interop.
+///
+/// Messages must implement serde's traits, which prost-generated types only do when their
+/// build adds the derives, for instance with `type_attribute`.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl<M> MessageCodec<M> for JsonCodec
+where
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    const ID: u8 = 1;
+
+    fn encode(msg: &M) -> Result<Vec<u8>, RpcSendError> {
+        serde_json::to_vec(msg).map_err(|e| RpcSendError::Codec(e.to_string()))
+    }
+
+    fn decode(payload: Bytes) -> Result<M, RpcWireError> {
+        serde_json::from_slice(&payload).map_err(|_| RpcWireError::Decode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prost_round_trip() {
+        let payload = <ProstCodec as MessageCodec<String>>::encode(&"drone-123".to_string());
+        let decoded: String = ProstCodec::decode(payload.unwrap().into()).unwrap();
+        assert_eq!(decoded, "drone-123");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_round_trip() {
+        let payload = JsonCodec::encode(&vec![1u32, 2, 3]).unwrap();
+        assert_eq!(payload, b"[1,2,3]");
+
+        let decoded: Vec<u32> = JsonCodec::decode(payload.into()).unwrap();
+        assert_eq!(decoded, vec![1, 2, 3]);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_decode_error() {
+        let result: Result<Vec<u32>, _> = JsonCodec::decode(Bytes::from_static(b"{"));
+        assert!(matches!(result, Err(RpcWireError::Decode)));
+    }
+}
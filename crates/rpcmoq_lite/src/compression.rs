@@ -0,0 +1,209 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use std::io::{Read, Write};
+
+use crate::codec::PROST_CODEC_ID;
+use crate::error::RpcWireError;
+
+/// Per-frame compression applied by `RpcOutbound` and reversed by `RpcInbound`.
+///
+/// Every message frame is prefixed with a one-byte tag naming the compression in its low four
+/// bits and the `MessageCodec` in its high four bits, uncompressed prost frames included. A
+/// peer configured differently fails with `RpcWireError::CompressionMismatch` or
+/// `RpcWireError::CodecMismatch` instead of handing garbage to the decoder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Frames are sent as-is.
+    #[default]
+    None,
+
+    /// Frames are gzip compressed.
+    Gzip,
+
+    /// Frames are zstd compressed.
+    Zstd,
+}
+
+impl Compression {
+    const TAG_NONE: u8 = 0;
+    const TAG_GZIP: u8 = 1;
+    const TAG_ZSTD: u8 = 2;
+
+    const TAG_MASK: u8 = 0x0f;
+    const CODEC_SHIFT: u8 = 4;
+
+    /// The one-byte tag that prefixes frames encoded with this codec.
+    pub fn tag(self) -> u8 {
+        match self {
+            Compression::None => Self::TAG_NONE,
+            Compression::Gzip => Self::TAG_GZIP,
+            Compression::Zstd => Self::TAG_ZSTD,
+        }
+    }
+
+    /// Compress `payload` and prefix it with the tag for the default codec.
+    pub(crate) fn encode(self, payload: &[u8]) -> Bytes {
+        self.encode_tagged(PROST_CODEC_ID, payload)
+    }
+
+    /// Compress `payload` and prefix it with a tag naming this compression and `codec_id`.
+    pub(crate) fn encode_tagged(self, codec_id: u8, payload: &[u8]) -> Bytes {
+        let mut buf = BytesMut::with_capacity(payload.len() + 1);
+        buf.put_u8(self.tag() | (codec_id << Self::CODEC_SHIFT));
+
+        let mut writer = buf.writer();
+        match self {
+            Compression::None => writer.write_all(payload),
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(&mut writer, flate2::Compression::default());
+                encoder
+                    .write_all(payload)
+                    .and_then(|_| encoder.finish().map(|_| ()))
+            }
+            Compression::Zstd => zstd::stream::copy_encode(payload, &mut writer, 0),
+        }
+        .expect("writing to an in-memory buffer cannot fail");
+
+        writer.into_inner().freeze()
+    }
+
+    /// Strip the tag from a frame encoded with the default codec and decompress the payload.
+    #[cfg(test)]
+    pub(crate) fn decode(self, frame: Bytes) -> Result<Bytes, RpcWireError> {
+        self.decode_tagged(PROST_CODEC_ID, frame, None)
+    }
+
+    /// Strip the tag from `frame` and decompress the payload to at most `max_len` bytes.
+    ///
+    /// Fails with `CodecMismatch` if the frame was encoded with a codec other than
+    /// `codec_id`, `CompressionMismatch` if it was compressed differently, `FrameTooLarge` if
+    /// it decompresses to more than `max_len` bytes, or `Decode` if the payload is missing or
+    /// corrupt. Decompression stops as soon as the limit is passed, so a small frame can't
+    /// expand into an arbitrarily large buffer.
+    pub(crate) fn decode_tagged(
+        self,
+        codec_id: u8,
+        mut frame: Bytes,
+        max_len: Option<usize>,
+    ) -> Result<Bytes, RpcWireError> {
+        let Some(&tag) = frame.first() else {
+            return Err(RpcWireError::Decode);
+        };
+        if tag >> Self::CODEC_SHIFT != codec_id {
+            return Err(RpcWireError::CodecMismatch);
+        }
+        if tag & Self::TAG_MASK != self.tag() {
+            return Err(RpcWireError::CompressionMismatch);
+        }
+        let payload = frame.split_off(1);
+
+        // One byte over the limit is enough to tell the frame is too large
+        let limit = max_len.map_or(u64::MAX, |max| max as u64 + 1);
+        let mut out = Vec::new();
+        match self {
+            Compression::None => return Ok(payload),
+            Compression::Gzip => flate2::read::GzDecoder::new(payload.as_ref())
+                .take(limit)
+                .read_to_end(&mut out),
+            Compression::Zstd => zstd::stream::read::Decoder::new(payload.as_ref())
+                .and_then(|decoder| decoder.take(limit).read_to_end(&mut out)),
+        }
+        .map_err(|_| RpcWireError::Decode)?;
+
+        if max_len.is_some_and(|max| out.len() > max) {
+            return Err(RpcWireError::FrameTooLarge);
+        }
+        Ok(out.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &[u8] = b"drone-123 drone-123 drone-123 drone-123 drone-123";
+
+    #[test]
+    fn test_round_trip() {
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            let frame = compression.encode(PAYLOAD);
+            assert_eq!(frame[0], compression.tag());
+            assert_eq!(compression.decode(frame).unwrap(), PAYLOAD);
+        }
+    }
+
+    #[test]
+    fn test_uncompressed_frames_are_tagged() {
+        let frame = Compression::None.encode(PAYLOAD);
+        assert_eq!(frame[0], Compression::TAG_NONE);
+        assert_eq!(frame[1..], *PAYLOAD);
+        let frame = Compression::None.encode_tagged(1, PAYLOAD);
+        assert_eq!(frame[0], 1 << Compression::CODEC_SHIFT);
+        assert_eq!(
+            Compression::None.decode_tagged(1, frame, None).unwrap(),
+            PAYLOAD
+        );
+    }
+
+    #[test]
+    fn test_decompression_stops_at_the_limit() {
+        let payload = vec![0u8; 1 << 20];
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let frame = compression.encode(&payload);
+            assert!(frame.len() < 4096);
+            assert!(matches!(
+                compression.decode_tagged(0, frame.clone(), Some(1 << 16)),
+                Err(RpcWireError::FrameTooLarge)
+            ));
+            let decoded = compression.decode_tagged(0, frame, Some(1 << 20)).unwrap();
+            assert_eq!(decoded.len(), 1 << 20);
+        }
+    }
+
+    #[test]
+    fn test_mismatched_codec_rejected() {
+        let frame = Compression::Zstd.encode(PAYLOAD);
+        assert!(matches!(
+            Compression::Gzip.decode(frame),
+            Err(RpcWireError::CompressionMismatch)
+        ));
+        // Uncompressed and compressed ends are told apart either way round
+        let frame = Compression::None.encode(PAYLOAD);
+        assert!(matches!(
+            Compression::Zstd.decode(frame),
+            Err(RpcWireError::CompressionMismatch)
+        ));
+        let frame = Compression::Gzip.encode(PAYLOAD);
+        assert!(matches!(
+            Compression::None.decode(frame),
+            Err(RpcWireError::CompressionMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_mismatched_message_codec_rejected() {
+        let frame = Compression::Gzip.encode_tagged(1, PAYLOAD);
+        assert!(matches!(
+            Compression::Gzip.decode(frame.clone()),
+            Err(RpcWireError::CodecMismatch)
+        ));
+        assert_eq!(
+            Compression::Gzip.decode_tagged(1, frame, None).unwrap(),
+            PAYLOAD
+        );
+    }
+
+    #[test]
+    fn test_corrupt_payload_rejected() {
+        let frame = Bytes::from_static(&[Compression::TAG_ZSTD, 0xde, 0xad, 0xbe, 0xef]);
+        assert!(matches!(
+            Compression::Zstd.decode(frame),
+            Err(RpcWireError::Decode)
+        ));
+        assert!(matches!(
+            Compression::Gzip.decode(Bytes::new()),
+            Err(RpcWireError::Decode)
+        ));
+    }
+}
@@ -1,17 +1,94 @@
 use async_stream::stream;
 use bytes::Bytes;
-use futures::Stream;
-use moq_lite::{BroadcastConsumer, Error as MoqError, Track, TrackConsumer, TrackProducer};
+use futures::{FutureExt, Stream, StreamExt};
+use moq_lite::{
+    BroadcastConsumer, BroadcastProducer, Error as MoqError, GroupProducer, Track, TrackConsumer,
+    TrackProducer,
+};
 use prost::Message;
+use std::collections::{HashMap, VecDeque};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::{Instant, Sleep};
 
-use crate::error::RpcSendError;
+use crate::codec::{MessageCodec, PROST_CODEC_ID, codec_id, encode_prost};
+use crate::compression::Compression;
+use crate::error::{CodeSpace, RpcSendError, RpcTrackError, RpcWireError};
+use crate::error_frame::{self, RpcError};
+use crate::metadata::RpcMetadata;
+use crate::retry::RetryPolicy;
+use crate::stats::{RouteCounters, SizeCounters, SizeStats};
+
+/// The default minimum payload length for a frame to be decoded as a message.
+pub const DEFAULT_MIN_FRAME_LEN: usize = 1;
+
+/// What a buffered [`RpcInbound`] does with a frame that arrives while its buffer is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InboundBufferPolicy {
+    /// Stop reading the track until the consumer catches up.
+    ///
+    /// moq-lite keeps only the latest group for a subscriber that falls behind, so groups
+    /// published in the meantime may still be skipped.
+    #[default]
+    Backpressure,
+
+    /// Drop the oldest buffered message to make room, counting it in
+    /// [`RpcInbound::lagged_frames`]. Control frames are never dropped.
+    DropOldest,
+}
+
+/// What an [`RpcInbound`] with a maximum rate does with a frame that arrives over the limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InboundRatePolicy {
+    /// Hold the frame back until the rate allows it, which stops reading the track meanwhile.
+    ///
+    /// moq-lite skips a reader that falls behind to the latest group, so groups sent while a
+    /// frame is held back may never be read. They are counted in
+    /// [`RpcInbound::throttled_groups`] rather than lost silently. Keep each group small, or
+    /// use [`Drop`](Self::Drop), if every message counts.
+    #[default]
+    Backpressure,
+
+    /// Drop the frame, counting it in [`RpcInbound::throttled_frames`].
+    Drop,
+}
 
 /// A stream of raw bytes from a MoQ track.
 ///
-/// This wraps a `TrackConsumer` and yields frames as `Bytes`.
+/// This wraps a `TrackConsumer` and yields frames as `Bytes`, with the codec
+/// tag stripped and the payload decompressed. Use
+/// [`next_with_group`](Self::next_with_group) to also learn which group each
+/// frame arrived in.
 pub struct RpcInbound {
-    inner: Pin<Box<dyn Stream<Item = Result<Bytes, moq_lite::Error>> + Send>>,
+    inner: Pin<Box<dyn Stream<Item = Result<SequencedFrame, moq_lite::Error>> + Send>>,
+    on_frame: Option<Arc<dyn Fn() + Send + Sync>>,
+    // Taken and run the first time the stream ends.
+    on_end: Option<Box<dyn FnOnce() + Send>>,
+    max_frame_size: Option<usize>,
+    compression: Compression,
+    codec_id: u8,
+    code_space: CodeSpace,
+    // A frame read ahead by `read_metadata`, yielded before polling `inner` again.
+    pending: Option<Result<SequencedFrame, moq_lite::Error>>,
+    metadata: Option<RpcMetadata>,
+    server_error: Option<RpcError>,
+    // Whether a trailer frame ends the stream, rather than being read as a message.
+    status_trailers: bool,
+    terminated: bool,
+    // Frames dropped by a `DropOldest` buffer.
+    lagged: Arc<AtomicU64>,
+    // What a rate limit has dropped, and whether it is holding a frame back.
+    throttle: Arc<ThrottleState>,
+    sizes: Option<Arc<SizeCounters>>,
+    // The track read by `inner`, unless it re-subscribes
+    track: Option<TrackConsumer>,
+    keepalive_timeout: Option<Duration>,
+    // Started on the first poll, reset by every frame
+    keepalive_deadline: Option<Pin<Box<Sleep>>>,
+    last_seen: Option<Instant>,
 }
 
 impl RpcInbound {
@@ -23,12 +100,14 @@ impl RpcInbound {
 
     /// Create from an existing track consumer.
     pub fn from_track(mut track: TrackConsumer) -> Self {
+        let raw_track = track.clone();
         let inner = stream! {
             loop {
                 match track.next_group().await {
                     Ok(Some(mut group)) => {
+                        let sequence = group.info.sequence;
                         while let Ok(Some(frame)) = group.read_frame().await {
-                            yield Ok(frame);
+                            yield Ok((sequence, frame));
                         }
                     }
                     Ok(None) => {
@@ -43,8 +122,654 @@ impl RpcInbound {
             }
         };
 
+        Self {
+            track: Some(raw_track),
+            ..Self::from_stream(inner)
+        }
+    }
+
+    /// Create an inbound stream that re-subscribes after transient track errors.
+    ///
+    /// When the track errors, the stream waits according to `retry_policy` and
+    /// subscribes to `track_name` on `broadcast` again. The error is only yielded
+    /// once the retry budget is exhausted or the broadcast itself has closed.
+    /// A clean track closure still ends the stream.
+    pub fn new_resilient(
+        broadcast: BroadcastConsumer,
+        track_name: &str,
+        retry_policy: RetryPolicy,
+    ) -> Self {
+        let info = Track::new(track_name);
+        let inner = stream! {
+            let mut attempt = 0;
+            'subscribe: loop {
+                let mut track = broadcast.subscribe_track(&info);
+                loop {
+                    match track.next_group().await {
+                        Ok(Some(mut group)) => {
+                            attempt = 0;
+                            let sequence = group.info.sequence;
+                            while let Ok(Some(frame)) = group.read_frame().await {
+                                yield Ok((sequence, frame));
+                            }
+                        }
+                        Ok(None) => {
+                            // Track closed
+                            break 'subscribe;
+                        }
+                        Err(e) => {
+                            let broadcast_closed = broadcast.closed().now_or_never().is_some();
+                            let delay = match retry_policy.backoff(attempt) {
+                                Some(delay) if !broadcast_closed => delay,
+                                _ => {
+                                    yield Err(e);
+                                    break 'subscribe;
+                                }
+                            };
+
+                            tracing::debug!(
+                                track = %info.name,
+                                error = %e,
+                                attempt,
+                                delay_ms = %delay.as_millis(),
+                                "Inbound track error, re-subscribing"
+                            );
+                            // Release the failed subscription so it isn't handed back to us.
+                            drop(track);
+                            tokio::time::sleep(delay).await;
+                            attempt += 1;
+                            continue 'subscribe;
+                        }
+                    }
+                }
+            }
+        };
+
+        Self::from_stream(inner)
+    }
+
+    fn from_stream(
+        inner: impl Stream<Item = Result<SequencedFrame, moq_lite::Error>> + Send + 'static,
+    ) -> Self {
         Self {
             inner: Box::pin(inner),
+            on_frame: None,
+            on_end: None,
+            max_frame_size: None,
+            compression: Compression::None,
+            codec_id: PROST_CODEC_ID,
+            code_space: CodeSpace::default(),
+            pending: None,
+            metadata: None,
+            server_error: None,
+            status_trailers: false,
+            terminated: false,
+            lagged: Arc::default(),
+            throttle: Arc::default(),
+            sizes: None,
+            track: None,
+            keepalive_timeout: None,
+            keepalive_deadline: None,
+            last_seen: None,
+        }
+    }
+
+    /// Read the track ahead into a buffer of up to `capacity` frames, applying `policy` when it
+    /// fills.
+    ///
+    /// Without a buffer frames are only read from the track as fast as the stream is polled. A
+    /// buffer lets a consumer that is briefly slow, say while a backend call is in flight,
+    /// catch up without the track reader waiting on it. Under
+    /// [`DropOldest`](InboundBufferPolicy::DropOldest) only messages are dropped, metadata,
+    /// keepalive, trailer and error frames are always kept.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn with_buffer(mut self, capacity: usize, policy: InboundBufferPolicy) -> Self {
+        let placeholder: Pin<Box<dyn Stream<Item = _> + Send>> = Box::pin(futures::stream::empty());
+        let inner = std::mem::replace(&mut self.inner, placeholder);
+        self.inner = Box::pin(buffered(
+            inner,
+            capacity.max(1),
+            policy,
+            Arc::clone(&self.lagged),
+        ));
+        self
+    }
+
+    /// The number of frames a [`DropOldest`](InboundBufferPolicy::DropOldest) buffer has
+    /// dropped so far. Always zero for an unbuffered stream.
+    pub fn lagged_frames(&self) -> u64 {
+        self.lagged.load(Ordering::Relaxed)
+    }
+
+    /// Let through at most `per_second` frames per second on average, applying `policy` to
+    /// frames over the limit.
+    ///
+    /// The limit is a token bucket holding up to one second's worth of frames, so a client
+    /// that was quiet can send a short burst at full speed. Keepalive, metadata, trailer and
+    /// error frames are never throttled. When combined with [`with_buffer`](Self::with_buffer),
+    /// call that first so the buffer absorbs frames held back under
+    /// [`Backpressure`](InboundRatePolicy::Backpressure). The
+    /// [keepalive timeout](Self::with_keepalive_timeout) does not run while a frame is held
+    /// back, as the peer has plainly not gone quiet.
+    ///
+    /// Must be called from within a Tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `per_second` is not a positive, finite number.
+    pub fn with_max_rate(mut self, per_second: f64, policy: InboundRatePolicy) -> Self {
+        assert!(
+            per_second.is_finite() && per_second > 0.0,
+            "max rate must be a positive number of frames per second, got {per_second}"
+        );
+        let placeholder: Pin<Box<dyn Stream<Item = _> + Send>> = Box::pin(futures::stream::empty());
+        let inner = std::mem::replace(&mut self.inner, placeholder);
+        self.inner = Box::pin(throttled(
+            inner,
+            per_second,
+            policy,
+            Arc::clone(&self.throttle),
+        ));
+        self
+    }
+
+    /// The number of frames dropped for exceeding the
+    /// [`with_max_rate`](Self::with_max_rate) limit so far. Always zero without a limit or
+    /// under [`Backpressure`](InboundRatePolicy::Backpressure).
+    pub fn throttled_frames(&self) -> u64 {
+        self.throttle.dropped.load(Ordering::Relaxed)
+    }
+
+    /// The number of groups the track skipped past while the
+    /// [`with_max_rate`](Self::with_max_rate) limit held a frame back, so far. Their frames
+    /// were never read. Always zero without a limit or under
+    /// [`Drop`](InboundRatePolicy::Drop).
+    pub fn throttled_groups(&self) -> u64 {
+        self.throttle.skipped_groups.load(Ordering::Relaxed)
+    }
+
+    /// The state behind [`throttled_frames`](Self::throttled_frames) and
+    /// [`throttled_groups`](Self::throttled_groups), readable after the stream has been handed
+    /// off.
+    pub(crate) fn throttle_state(&self) -> Arc<ThrottleState> {
+        Arc::clone(&self.throttle)
+    }
+
+    /// **Advanced:** give up the RPC framing and read the track directly.
+    ///
+    /// For MoQ features this stream hides, such as inspecting groups yourself. The consumer is
+    /// a fresh reader of the same track: it starts at the latest group rather than where this
+    /// stream left off, and frames this stream had read ahead are lost. Frames still carry the
+    /// codec tag and compression, and metadata, keepalive and error frames are mixed in with
+    /// messages, so reading them as plain payloads will not decode.
+    ///
+    /// Returns `None` for a stream created with [`new_resilient`](Self::new_resilient), which
+    /// has no single track.
+    pub fn into_track(self) -> Option<TrackConsumer> {
+        self.track
+    }
+
+    /// Another reader of the track this stream reads, for watching it close.
+    pub(crate) fn track(&self) -> Option<TrackConsumer> {
+        self.track.clone()
+    }
+
+    /// Track the sizes of the message frames read, for [`size_stats`](Self::size_stats).
+    ///
+    /// Off by default. Oversized frames rejected by
+    /// [`with_max_frame_size`](Self::with_max_frame_size) are counted too, so a limit can be
+    /// tuned against the sizes actually seen.
+    pub fn with_size_stats(mut self) -> Self {
+        self.sizes = Some(Arc::default());
+        self
+    }
+
+    /// The sizes of the message frames read so far, or `None` unless
+    /// [`with_size_stats`](Self::with_size_stats) was called.
+    pub fn size_stats(&self) -> Option<SizeStats> {
+        self.sizes.as_ref().map(|sizes| sizes.snapshot())
+    }
+
+    /// End the stream with `Err(moq_lite::Error::App(RpcWireError::CODE_KEEPALIVE_TIMEOUT))`
+    /// if no frame arrives within `timeout`.
+    ///
+    /// Keepalive frames, sent by a peer's [`RpcOutbound::with_keepalive`], count as arrivals
+    /// but are not yielded, so a quiet peer that is still alive is told apart from a dead one.
+    /// The timeout should be a few times the peer's keepalive interval. The clock starts when
+    /// the stream is first polled.
+    ///
+    /// Must be polled from within a Tokio runtime.
+    pub fn with_keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive_timeout = Some(timeout);
+        self
+    }
+
+    /// When the last frame of any kind, keepalives included, arrived. `None` until the first.
+    pub fn last_seen(&self) -> Option<Instant> {
+        self.last_seen
+    }
+
+    /// Expect frames compressed with `compression`.
+    ///
+    /// A frame tagged with a different codec yields
+    /// `Err(moq_lite::Error::App(RpcWireError::CODE_COMPRESSION_MISMATCH))` and ends the stream.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Expect frames encoded with the message codec identified by `codec_id`.
+    pub(crate) fn with_codec_id(mut self, codec_id: u8) -> Self {
+        self.codec_id = codec_id;
+        self
+    }
+
+    /// Report errors with app codes in `space`, which must match the sender's.
+    pub fn with_code_space(mut self, space: CodeSpace) -> Self {
+        self.code_space = space;
+        self
+    }
+
+    pub fn code_space(&self) -> CodeSpace {
+        self.code_space
+    }
+
+    /// Treat a trailer frame, sent by a peer's [`RpcOutbound::with_status_trailers`], as the end
+    /// of the stream. Only enable it when the peer sends trailers, as a message could
+    /// otherwise be mistaken for one.
+    pub fn with_status_trailers(mut self, enabled: bool) -> Self {
+        self.status_trailers = enabled;
+        self
+    }
+
+    /// Reject frames larger than `max` bytes.
+    ///
+    /// An oversized frame yields `Err(moq_lite::Error::App(RpcWireError::CODE_FRAME_TOO_LARGE))`
+    /// and ends the stream instead of handing the bytes on to be decoded.
+    pub fn with_max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = Some(max);
+        self
+    }
+
+    /// Attach a callback that runs every time a frame is received.
+    pub fn with_frame_handler<F>(mut self, f: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_frame = Some(Arc::new(f));
+        self
+    }
+
+    /// Attach a callback that runs once the stream has ended.
+    pub(crate) fn with_end_handler<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.on_end = Some(Box::new(f));
+        self
+    }
+
+    /// Wait for the first frame and return the metadata the client sent ahead of
+    /// its first message, if any.
+    ///
+    /// A message frame read while waiting is yielded by the stream as usual.
+    pub(crate) async fn read_metadata(&mut self) -> Option<&RpcMetadata> {
+        if self.metadata.is_none() && self.pending.is_none() && !self.terminated {
+            match self.inner.next().await {
+                // An oversized one is left for `poll_next` to report
+                Some(Ok((sequence, frame)))
+                    if RpcMetadata::is_metadata_frame(&frame) && !self.is_oversized(&frame) =>
+                {
+                    match RpcMetadata::from_frame(frame.clone()) {
+                        Ok(metadata) => self.metadata = Some(metadata),
+                        // Leave it for `poll_next` to report.
+                        Err(_) => self.pending = Some(Ok((sequence, frame))),
+                    }
+                }
+                Some(next) => self.pending = Some(next),
+                None => self.terminated = true,
+            }
+        }
+        self.metadata.as_ref()
+    }
+
+    /// Take the error the server explained in an error frame, if the stream
+    /// ended because of one.
+    pub(crate) fn take_server_error(&mut self) -> Option<RpcWireError> {
+        self.server_error.take().map(RpcWireError::from)
+    }
+
+    /// Get the next frame along with the sequence number of the group it arrived in.
+    ///
+    /// `TrackConsumer::next_group` skips to the latest group when the reader
+    /// falls behind, so a jump of more than one between consecutive frames
+    /// means groups were dropped. Frames of the same group share a sequence.
+    pub async fn next_with_group(&mut self) -> Option<Result<(u64, Bytes), moq_lite::Error>> {
+        std::future::poll_fn(|cx| self.poll_next_with_group(cx)).await
+    }
+
+    fn poll_next_with_group(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<SequencedFrame, moq_lite::Error>>> {
+        let next = self.poll_next_frame(cx);
+        if let std::task::Poll::Ready(None) = next
+            && let Some(handler) = self.on_end.take()
+        {
+            handler();
+        }
+        next
+    }
+
+    fn poll_next_frame(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<SequencedFrame, moq_lite::Error>>> {
+        loop {
+            if self.terminated {
+                return std::task::Poll::Ready(None);
+            }
+
+            let next = match self.pending.take() {
+                Some(next) => Some(next),
+                None => match self.inner.as_mut().poll_next(cx) {
+                    std::task::Poll::Ready(next) => next,
+                    std::task::Poll::Pending if self.poll_keepalive_expired(cx) => {
+                        tracing::debug!(
+                            last_seen = ?self.last_seen,
+                            "No inbound frame within the keepalive timeout"
+                        );
+                        self.terminate();
+                        return std::task::Poll::Ready(Some(Err(MoqError::App(
+                            RpcWireError::KeepaliveTimeout.to_code_in(self.code_space),
+                        ))));
+                    }
+                    std::task::Poll::Pending => return std::task::Poll::Pending,
+                },
+            };
+            if matches!(next, Some(Ok(_))) {
+                self.saw_frame();
+            }
+
+            let result = match next {
+                // Checked ahead of the tag, so control frames are held to the limit too
+                Some(Ok((_, frame))) if self.is_oversized(&frame) => {
+                    if let Some(sizes) = &self.sizes {
+                        sizes.record(frame.len());
+                    }
+                    tracing::warn!(size = frame.len(), "Inbound frame exceeds maximum size");
+                    Err(RpcWireError::FrameTooLarge)
+                }
+                Some(Ok((_, frame))) if error_frame::is_keepalive_frame(&frame) => continue,
+                Some(Ok((_, frame))) if RpcMetadata::is_metadata_frame(&frame) => {
+                    match RpcMetadata::from_frame(frame) {
+                        Ok(metadata) => {
+                            self.metadata = Some(metadata);
+                            continue;
+                        }
+                        Err(err) => Err(err),
+                    }
+                }
+                // The server ended the stream with an OK status
+                Some(Ok((_, frame)))
+                    if self.status_trailers && error_frame::is_end_frame(&frame) =>
+                {
+                    self.terminate();
+                    return std::task::Poll::Ready(None);
+                }
+                Some(Ok((_, frame))) if RpcError::is_error_frame(&frame) => {
+                    RpcError::from_frame(frame).and_then(|err| {
+                        let code = err.code;
+                        self.server_error = Some(err);
+                        Err(RpcWireError::from_code_in(code, self.code_space))
+                    })
+                }
+                Some(Ok((sequence, frame))) => {
+                    self.accept_frame(frame).map(|payload| (sequence, payload))
+                }
+                other => return std::task::Poll::Ready(other),
+            };
+
+            return match result {
+                Ok(frame) => {
+                    if let Some(handler) = &self.on_frame {
+                        handler();
+                    }
+                    std::task::Poll::Ready(Some(Ok(frame)))
+                }
+                // stop the stream, the remaining frames can't be trusted either
+                Err(err) => {
+                    self.terminate();
+                    std::task::Poll::Ready(Some(Err(MoqError::App(
+                        err.to_code_in(self.code_space),
+                    ))))
+                }
+            };
+        }
+    }
+}
+
+impl RpcInbound {
+    /// Whether the keepalive timeout has passed since the last frame, or the first poll.
+    ///
+    /// The clock is held while a rate limit holds a frame back, as that frame has arrived.
+    fn poll_keepalive_expired(&mut self, cx: &mut std::task::Context<'_>) -> bool {
+        let Some(timeout) = self.keepalive_timeout else {
+            return false;
+        };
+        if self.throttle.holding.load(Ordering::Relaxed) {
+            // The limit wakes the stream once it lets the frame through
+            if let Some(deadline) = self.keepalive_deadline.as_mut() {
+                deadline.as_mut().reset(Instant::now() + timeout);
+            }
+            return false;
+        }
+        self.keepalive_deadline
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)))
+            .as_mut()
+            .poll(cx)
+            .is_ready()
+    }
+
+    /// End the stream and let go of the track, so a server draining it isn't kept waiting
+    /// until this stream is dropped.
+    fn terminate(&mut self) {
+        self.terminated = true;
+        self.inner = Box::pin(futures::stream::empty());
+        self.pending = None;
+        self.track = None;
+    }
+
+    fn saw_frame(&mut self) {
+        let now = Instant::now();
+        self.last_seen = Some(now);
+        if let (Some(timeout), Some(deadline)) =
+            (self.keepalive_timeout, self.keepalive_deadline.as_mut())
+        {
+            deadline.as_mut().reset(now + timeout);
+        }
+    }
+}
+
+/// A frame payload and the sequence number of the group it arrived in.
+type SequencedFrame = (u64, Bytes);
+
+/// Frames read ahead by a buffered `RpcInbound`, shared between the track reader and the stream.
+struct FrameBuffer {
+    state: Mutex<FrameBufferState>,
+    // Signalled when a frame is queued or the reader finishes.
+    readable: Notify,
+    // Signalled when a frame is taken off a full queue.
+    writable: Notify,
+}
+
+impl FrameBuffer {
+    /// Queue `next`, or return it if the buffer is full and `policy` is to wait.
+    fn push(
+        &self,
+        next: Result<SequencedFrame, moq_lite::Error>,
+        capacity: usize,
+        policy: InboundBufferPolicy,
+        lagged: &AtomicU64,
+    ) -> Option<Result<SequencedFrame, moq_lite::Error>> {
+        let mut state = self.state.lock().expect("inbound buffer lock poisoned");
+        if state.frames.len() >= capacity {
+            if policy == InboundBufferPolicy::Backpressure {
+                return Some(next);
+            }
+            // Control frames and errors are kept, a buffer holding nothing else briefly overfills
+            let oldest_message = state
+                .frames
+                .iter()
+                .position(|frame| matches!(frame, Ok((_, frame)) if !is_control_frame(frame)));
+            if let Some(oldest_message) = oldest_message {
+                state.frames.remove(oldest_message);
+                lagged.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        state.frames.push_back(next);
+        None
+    }
+}
+
+struct FrameBufferState {
+    frames: VecDeque<Result<SequencedFrame, moq_lite::Error>>,
+    finished: bool,
+}
+
+/// Aborts the track reader task when the buffered stream is dropped.
+struct AbortOnDrop(tokio::task::AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Read `inner` on a task of its own into a buffer of `capacity` frames.
+fn buffered(
+    mut inner: Pin<Box<dyn Stream<Item = Result<SequencedFrame, moq_lite::Error>> + Send>>,
+    capacity: usize,
+    policy: InboundBufferPolicy,
+    lagged: Arc<AtomicU64>,
+) -> impl Stream<Item = Result<SequencedFrame, moq_lite::Error>> + Send {
+    let buffer = Arc::new(FrameBuffer {
+        state: Mutex::new(FrameBufferState {
+            frames: VecDeque::with_capacity(capacity),
+            finished: false,
+        }),
+        readable: Notify::new(),
+        writable: Notify::new(),
+    });
+
+    let writer = Arc::clone(&buffer);
+    let reader = tokio::spawn(async move {
+        while let Some(mut next) = inner.next().await {
+            // Hand the frame back while the buffer is full under `Backpressure`
+            while let Some(rejected) = writer.push(next, capacity, policy, &lagged) {
+                next = rejected;
+                writer.writable.notified().await;
+            }
+            writer.readable.notify_one();
+        }
+        writer
+            .state
+            .lock()
+            .expect("inbound buffer lock poisoned")
+            .finished = true;
+        writer.readable.notify_one();
+    });
+    let reader = AbortOnDrop(reader.abort_handle());
+
+    stream! {
+        let _reader = reader;
+        loop {
+            let (next, finished) = {
+                let mut state = buffer.state.lock().expect("inbound buffer lock poisoned");
+                (state.frames.pop_front(), state.finished)
+            };
+            match next {
+                Some(next) => {
+                    buffer.writable.notify_one();
+                    yield next;
+                }
+                None if finished => break,
+                None => buffer.readable.notified().await,
+            }
+        }
+    }
+}
+
+/// Whether a frame is protocol control rather than a message, and so exempt from throttling
+/// and from being dropped by a full buffer.
+fn is_control_frame(frame: &[u8]) -> bool {
+    error_frame::is_keepalive_frame(frame)
+        || error_frame::is_end_frame(frame)
+        || RpcMetadata::is_metadata_frame(frame)
+        || RpcError::is_error_frame(frame)
+}
+
+/// What a rate-limited `RpcInbound` has lost to its limit, shared with the throttled stream.
+#[derive(Debug, Default)]
+pub(crate) struct ThrottleState {
+    // Frames dropped under `InboundRatePolicy::Drop`
+    pub(crate) dropped: AtomicU64,
+    // Groups skipped by the track while `InboundRatePolicy::Backpressure` held a frame back
+    pub(crate) skipped_groups: AtomicU64,
+    // Whether a frame is being held back right now
+    holding: AtomicBool,
+}
+
+/// Pass on at most `per_second` message frames per second from `inner`, see
+/// `RpcInbound::with_max_rate`.
+fn throttled(
+    mut inner: Pin<Box<dyn Stream<Item = Result<SequencedFrame, moq_lite::Error>> + Send>>,
+    per_second: f64,
+    policy: InboundRatePolicy,
+    state: Arc<ThrottleState>,
+) -> impl Stream<Item = Result<SequencedFrame, moq_lite::Error>> + Send {
+    let burst = per_second.ceil().max(1.0);
+    stream! {
+        let mut tokens = burst;
+        let mut refilled = Instant::now();
+        // The group of the last frame let through, and whether a frame was held back since
+        let mut last_group = None;
+        let mut held = false;
+        while let Some(next) = inner.next().await {
+            if let Ok((sequence, _)) = &next {
+                if held && let Some(last) = last_group {
+                    let skipped = sequence.saturating_sub(last + 1);
+                    state.skipped_groups.fetch_add(skipped, Ordering::Relaxed);
+                }
+                last_group = Some(*sequence);
+                held = false;
+            }
+            if matches!(&next, Ok((_, frame)) if !is_control_frame(frame)) {
+                let now = Instant::now();
+                tokens = (tokens + (now - refilled).as_secs_f64() * per_second).min(burst);
+                refilled = now;
+                if tokens < 1.0 {
+                    match policy {
+                        InboundRatePolicy::Drop => {
+                            state.dropped.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+                        InboundRatePolicy::Backpressure => {
+                            let wait = Duration::try_from_secs_f64((1.0 - tokens) / per_second)
+                                .unwrap_or(Duration::MAX);
+                            state.holding.store(true, Ordering::Relaxed);
+                            tokio::time::sleep(wait).await;
+                            state.holding.store(false, Ordering::Relaxed);
+                            held = true;
+                            tokens = 1.0;
+                            refilled = Instant::now();
+                        }
+                    }
+                }
+                tokens -= 1.0;
+            }
+            yield next;
         }
     }
 }
@@ -53,40 +778,1828 @@ impl Stream for RpcInbound {
     type Item = Result<Bytes, moq_lite::Error>;
 
     fn poll_next(
-        mut self: Pin<&mut Self>,
+        self: Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        self.inner.as_mut().poll_next(cx)
+        self.get_mut()
+            .poll_next_with_group(cx)
+            .map(|next| next.map(|result| result.map(|(_, payload)| payload)))
+    }
+}
+
+impl RpcInbound {
+    /// Whether `frame` is over the size limit.
+    fn is_oversized(&self, frame: &Bytes) -> bool {
+        self.max_frame_size.is_some_and(|max| frame.len() > max)
+    }
+
+    /// Record the size of a message frame and strip its compression.
+    fn accept_frame(&self, frame: Bytes) -> Result<Bytes, RpcWireError> {
+        if let Some(sizes) = &self.sizes {
+            sizes.record(frame.len());
+        }
+
+        self.compression
+            .decode_tagged(self.codec_id, frame, self.max_frame_size)
+            .inspect_err(|err| {
+                tracing::warn!(
+                    %err,
+                    compression = ?self.compression,
+                    "Failed to decompress inbound frame"
+                );
+            })
     }
 }
 
 /// A sink for sending responses back to a MoQ track.
+///
+/// By default every message is written as its own MoQ group. Use
+/// [`send_batch`](Self::send_batch) or [`with_auto_flush`](Self::with_auto_flush)
+/// to write several messages as frames of a single group instead, or
+/// [`begin_group`](Self::begin_group) to control group boundaries explicitly.
+///
+/// An outbound attached to its broadcast with [`with_broadcast`](Self::with_broadcast) can open
+/// further named tracks on it with [`track`](Self::track).
 #[derive(Clone)]
 pub struct RpcOutbound {
     track: TrackProducer,
+    // The broadcast `track` belongs to, for creating sibling tracks. Boxed behind a mutex as
+    // the producer is not `Unpin`, and this sink must be. Only the outbound attached with
+    // `with_broadcast` and its clones own it; siblings hold it weakly so they don't keep
+    // the broadcast up after the owner is gone.
+    broadcast: Option<Weak<Mutex<SharedBroadcast>>>,
+    broadcast_owner: Option<Arc<Mutex<SharedBroadcast>>>,
+    compression: Compression,
+    batch: Option<Arc<Mutex<PendingBatch>>>,
+    // The group opened by `begin_group`, shared with clones and the guard.
+    open_group: Arc<Mutex<Option<GroupProducer>>>,
+    // A metadata frame to prepend to the next group written, shared with clones.
+    metadata: Arc<Mutex<Option<Bytes>>>,
+    counters: Option<Arc<RouteCounters>>,
+    sizes: Option<Arc<SizeCounters>>,
+    error_frames: bool,
+    status_trailers: bool,
+    code_space: CodeSpace,
+    keepalive: Option<Arc<KeepaliveTimer>>,
+}
+
+/// The broadcast an outbound's track belongs to, and the tracks open on it.
+struct SharedBroadcast {
+    producer: BroadcastProducer,
+    // The track the broadcast was attached with, whose drain also drains the siblings.
+    primary: String,
+    // The tracks opened with `track`, by name.
+    siblings: HashMap<String, RpcOutbound>,
+}
+
+/// How long `abort_with_error` keeps the track open after sending an error frame,
+/// so subscribers read the frame before the abort overtakes it. `send_confirmed` waits
+/// as long before closing the track after its message.
+pub(crate) const ERROR_FRAME_GRACE: Duration = Duration::from_millis(250);
+
+/// Frames buffered by an auto-flushing `RpcOutbound`.
+struct PendingBatch {
+    frames: Vec<Bytes>,
+    max_batch: usize,
+    max_delay: Duration,
+    // Bumped on every flush so a stale delay timer doesn't flush a newer batch early.
+    generation: u64,
+}
+
+impl PendingBatch {
+    fn take(&mut self) -> Vec<Bytes> {
+        self.generation += 1;
+        std::mem::take(&mut self.frames)
+    }
+}
+
+/// Sends a keepalive frame on a track nothing has been written to for a while.
+///
+/// Shared by the clones of an `RpcOutbound`; the last one dropped stops the task.
+struct KeepaliveTimer {
+    interval: Duration,
+    state: Arc<Mutex<KeepaliveState>>,
+    task: tokio::task::AbortHandle,
+}
+
+struct KeepaliveState {
+    last_write: Instant,
+    // Set before the track is closed or aborted, after which nothing may be written
+    stopped: bool,
+    // The last group written, kept open so keepalives don't start one that supersedes it
+    tail: Option<GroupProducer>,
+}
+
+impl KeepaliveState {
+    fn close_tail(&mut self) {
+        if let Some(tail) = self.tail.take() {
+            tail.close();
+        }
+    }
+
+    /// Keep `group` open for keepalives in place of the previous tail, or close it once
+    /// keepalives have stopped.
+    fn keep_open(&mut self, group: GroupProducer) {
+        self.close_tail();
+        if self.stopped {
+            group.close();
+        } else {
+            self.tail = Some(group);
+        }
+    }
+}
+
+impl KeepaliveTimer {
+    fn spawn(
+        interval: Duration,
+        mut track: TrackProducer,
+        open_group: Arc<Mutex<Option<GroupProducer>>>,
+        metadata: Arc<Mutex<Option<Bytes>>>,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(KeepaliveState {
+            last_write: Instant::now(),
+            stopped: false,
+            tail: None,
+        }));
+
+        let timer = Arc::clone(&state);
+        let task = tokio::spawn(async move {
+            loop {
+                let due = timer.lock().expect("keepalive lock poisoned").last_write + interval;
+                tokio::time::sleep_until(due).await;
+
+                // Locked before the state, like a group guard being dropped does
+                let mut open = open_group.lock().expect("outbound group lock poisoned");
+                // Held while writing, so the track can't be closed under us
+                let mut state = timer.lock().expect("keepalive lock poisoned");
+                if state.stopped {
+                    return;
+                }
+                if state.last_write + interval > Instant::now() {
+                    continue;
+                }
+                // Keep the open or last group going rather than starting one that would
+                // supersede it
+                let keepalive = error_frame::keepalive_frame();
+                match (open.as_mut(), state.tail.as_mut()) {
+                    (Some(group), _) | (None, Some(group)) => group.write_frame(keepalive),
+                    (None, None) => {
+                        let mut group = append_group(&mut track, &metadata);
+                        group.write_frame(keepalive);
+                        state.tail = Some(group);
+                    }
+                }
+                state.last_write = Instant::now();
+            }
+        });
+
+        Self {
+            interval,
+            state,
+            task: task.abort_handle(),
+        }
+    }
+
+    fn touch(&self) {
+        self.state
+            .lock()
+            .expect("keepalive lock poisoned")
+            .last_write = Instant::now();
+    }
+
+    fn stop(&self) {
+        let mut state = self.state.lock().expect("keepalive lock poisoned");
+        state.stopped = true;
+        state.close_tail();
+        self.task.abort();
+    }
+}
+
+impl Drop for KeepaliveTimer {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.state
+            .lock()
+            .expect("keepalive lock poisoned")
+            .close_tail();
+    }
+}
+
+/// Append a group to `track`, starting with the pending metadata frame if there is one.
+fn append_group(track: &mut TrackProducer, metadata: &Mutex<Option<Bytes>>) -> GroupProducer {
+    let mut group = track.append_group();
+    if let Some(frame) = metadata
+        .lock()
+        .expect("outbound metadata lock poisoned")
+        .take()
+    {
+        group.write_frame(frame);
+    }
+    group
+}
+
+/// Write `frames` as a single group, skipping empty batches.
+///
+/// A pending metadata frame is written first and then cleared. While keepalives run, the
+/// group stays open for them until the next one is appended.
+fn write_group(
+    track: &mut TrackProducer,
+    metadata: &Mutex<Option<Bytes>>,
+    keepalive: Option<&Mutex<KeepaliveState>>,
+    frames: Vec<Bytes>,
+) {
+    if frames.is_empty() {
+        return;
+    }
+
+    // Held until the group is kept, so a keepalive can't start a group in between
+    let mut state = keepalive.map(|state| state.lock().expect("keepalive lock poisoned"));
+    if let Some(state) = &mut state {
+        state.close_tail();
+    }
+    let mut group = append_group(track, metadata);
+    for frame in frames {
+        group.write_frame(frame);
+    }
+    match state {
+        Some(mut state) => state.keep_open(group),
+        None => group.close(),
+    }
 }
 
 impl RpcOutbound {
     /// Create a new outbound sink from a track producer.
     pub fn new(track: TrackProducer) -> Self {
-        Self { track }
+        Self {
+            track,
+            broadcast: None,
+            broadcast_owner: None,
+            compression: Compression::None,
+            batch: None,
+            open_group: Arc::new(Mutex::new(None)),
+            metadata: Arc::new(Mutex::new(None)),
+            counters: None,
+            sizes: None,
+            error_frames: false,
+            status_trailers: false,
+            code_space: CodeSpace::default(),
+            keepalive: None,
+        }
+    }
+
+    /// Remember the broadcast this outbound's track was created on, so
+    /// [`track`](Self::track) can create sibling tracks on it.
+    ///
+    /// This outbound and its clones keep the broadcast open; the sibling tracks don't.
+    pub fn with_broadcast(mut self, broadcast: BroadcastProducer) -> Self {
+        let shared = Arc::new(Mutex::new(SharedBroadcast {
+            producer: broadcast,
+            primary: self.track.info.name.clone(),
+            siblings: HashMap::new(),
+        }));
+        self.broadcast = Some(Arc::downgrade(&shared));
+        self.broadcast_owner = Some(shared);
+        self
+    }
+
+    /// A clone that can still open sibling tracks but does not keep the broadcast open, for
+    /// handing out beyond the session's own lifetime.
+    pub(crate) fn without_broadcast_owner(&self) -> Self {
+        Self {
+            broadcast_owner: None,
+            ..self.clone()
+        }
+    }
+
+    /// Create another outbound on a new track named `name` of the same broadcast.
+    ///
+    /// Use this to send logically separate streams, say positions and events, without
+    /// multiplexing them onto one track. Clients subscribe to each by name on the response
+    /// broadcast, for example with `RpcInbound::new(&broadcast, name)`, and need not subscribe
+    /// to the tracks they don't want.
+    ///
+    /// The new outbound shares this one's compression, error frame, code space, keepalive and
+    /// stats settings but has its own groups, batching, metadata and size stats. It does not
+    /// keep the broadcast open. Fails if this outbound has no broadcast, the broadcast has
+    /// closed, or a track named `name` is already open on it; a drained track's name is free
+    /// again.
+    pub fn track(&self, name: &str) -> Result<RpcOutbound, RpcTrackError> {
+        let shared = self
+            .broadcast
+            .as_ref()
+            .ok_or(RpcTrackError::NoBroadcast)?
+            .upgrade()
+            .ok_or(RpcTrackError::BroadcastClosed)?;
+
+        let mut shared_lock = shared.lock().expect("outbound broadcast lock poisoned");
+        if name == shared_lock.primary || shared_lock.siblings.contains_key(name) {
+            return Err(RpcTrackError::DuplicateTrack(name.to_string()));
+        }
+        let track = shared_lock.producer.create_track(Track::new(name));
+        let sibling = Self {
+            broadcast: Some(Arc::downgrade(&shared)),
+            compression: self.compression,
+            counters: self.counters.clone(),
+            sizes: self.sizes.as_ref().map(|_| Arc::default()),
+            error_frames: self.error_frames,
+            status_trailers: self.status_trailers,
+            code_space: self.code_space,
+            ..Self::new(track)
+        };
+        let sibling = match &self.keepalive {
+            Some(keepalive) => sibling.with_keepalive(keepalive.interval),
+            None => sibling,
+        };
+        shared_lock
+            .siblings
+            .insert(name.to_string(), sibling.clone());
+        Ok(sibling)
+    }
+
+    /// **Advanced:** the track this outbound writes to, for MoQ features it hides, such as
+    /// creating groups with explicit sequence numbers.
+    ///
+    /// Writing through the track bypasses batching, the open group and pending metadata, so
+    /// [`flush`](Self::flush) first and don't hold a [`begin_group`](Self::begin_group) guard
+    /// meanwhile. Frames written here reach the peer as they are: without the codec tag and
+    /// compression an `RpcInbound` will reject or misread them.
+    pub fn track_mut(&mut self) -> &mut TrackProducer {
+        &mut self.track
+    }
+
+    /// Make [`abort_with_error`](Self::abort_with_error) send an error frame
+    /// before aborting.
+    pub fn with_error_frames(mut self, enabled: bool) -> Self {
+        self.error_frames = enabled;
+        self
+    }
+
+    /// End the stream like a gRPC call, with a trailer frame after the last message of a stream
+    /// that ended with an OK status and an error frame carrying the status of one that did not.
+    /// The reader must opt in with [`RpcInbound::with_status_trailers`].
+    pub fn with_status_trailers(mut self, enabled: bool) -> Self {
+        self.status_trailers = enabled;
+        self
+    }
+
+    pub fn status_trailers(&self) -> bool {
+        self.status_trailers
+    }
+
+    /// Whether aborting may send an error frame first.
+    pub(crate) fn sends_error_frames(&self) -> bool {
+        self.error_frames || self.status_trailers
+    }
+
+    /// Track the sizes of the message frames sent, for [`size_stats`](Self::size_stats).
+    ///
+    /// Off by default. Clones share the same stats. Error, status and metadata frames are
+    /// not counted.
+    pub fn with_size_stats(mut self) -> Self {
+        self.sizes = Some(Arc::default());
+        self
+    }
+
+    /// The sizes of the message frames sent so far, or `None` unless
+    /// [`with_size_stats`](Self::with_size_stats) was called.
+    pub fn size_stats(&self) -> Option<SizeStats> {
+        self.sizes.as_ref().map(|sizes| sizes.snapshot())
+    }
+
+    /// Count every message sent towards `counters`.
+    pub(crate) fn with_counters(mut self, counters: Arc<RouteCounters>) -> Self {
+        self.counters = Some(counters);
+        self
+    }
+
+    /// Abort with [`RpcWireError`] codes in `space`, which must match the receiver's.
+    pub fn with_code_space(mut self, space: CodeSpace) -> Self {
+        self.code_space = space;
+        self
+    }
+
+    pub fn code_space(&self) -> CodeSpace {
+        self.code_space
+    }
+
+    /// Send `metadata` as the first frame of the next group written.
+    pub(crate) fn with_metadata(self, metadata: &RpcMetadata) -> Self {
+        *self
+            .metadata
+            .lock()
+            .expect("outbound metadata lock poisoned") = Some(metadata.to_frame());
+        self
+    }
+
+    /// Send a keepalive frame whenever nothing has been written for `interval`, so a peer
+    /// reading with [`RpcInbound::with_keepalive_timeout`] can tell a quiet track from a dead
+    /// one.
+    ///
+    /// The frame goes into the open group if there is one, and otherwise into the last group
+    /// written, which is kept open for it until the next, so a reader that has fallen behind
+    /// doesn't skip messages for a keepalive. Keepalives stop once the track is finished,
+    /// aborted or ended with a trailer or error frame, and when the last clone is dropped.
+    ///
+    /// Must be called from within a Tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// If `interval` is zero.
+    pub fn with_keepalive(mut self, interval: Duration) -> Self {
+        assert!(!interval.is_zero(), "keepalive interval must not be zero");
+        if let Some(previous) = self.keepalive.take() {
+            previous.stop();
+        }
+        self.keepalive = Some(Arc::new(KeepaliveTimer::spawn(
+            interval,
+            self.track.clone(),
+            Arc::clone(&self.open_group),
+            Arc::clone(&self.metadata),
+        )));
+        self
+    }
+
+    /// Buffer outgoing messages and write them as a single group.
+    ///
+    /// The buffer is flushed once it holds `max_batch` messages, or `max_delay`
+    /// after the first message was buffered, whichever comes first. Call
+    /// [`flush`](Self::flush) to write buffered messages immediately.
+    ///
+    /// The `max_delay` timer is a Tokio task, spawned by the send that starts a new batch, so
+    /// sends must then happen within a Tokio runtime. Setting this up does not need one, and
+    /// neither do sends with a `max_batch` of 1, which are written straight away.
+    pub fn with_auto_flush(mut self, max_batch: usize, max_delay: Duration) -> Self {
+        self.batch = Some(Arc::new(Mutex::new(PendingBatch {
+            frames: Vec::with_capacity(max_batch),
+            max_batch: max_batch.max(1),
+            max_delay,
+            generation: 0,
+        })));
+        self
+    }
+
+    /// Compress every frame with `compression` before writing it.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
     }
 
     /// Send a protobuf message.
     pub fn send<M: Message>(&mut self, msg: &M) -> Result<(), RpcSendError> {
-        let mut buf = Vec::with_capacity(msg.encoded_len());
-        msg.encode(&mut buf)?;
-        self.send_raw(buf);
+        self.send_payload(PROST_CODEC_ID, encode_prost(msg)?);
         Ok(())
     }
 
-    /// Send raw bytes.
+    /// Send a message encoded with codec `C`.
+    pub fn send_with<C: MessageCodec<M>, M>(&mut self, msg: &M) -> Result<(), RpcSendError> {
+        self.send_payload(codec_id::<C, M>(), C::encode(msg)?);
+        Ok(())
+    }
+
+    fn send_payload(&mut self, codec_id: u8, payload: Vec<u8>) {
+        if let Some(counters) = &self.counters {
+            counters.record_outbound(payload.len());
+        }
+        let frame = self.compression.encode_tagged(codec_id, &payload);
+        self.record_size(&frame);
+        self.write_frame(frame);
+    }
+
+    /// Send several protobuf messages as frames of a single group.
+    ///
+    /// Nothing is written if any message fails to encode. Messages buffered by
+    /// auto-flush are written first so ordering is preserved.
+    pub fn send_batch<M: Message>(&mut self, msgs: &[M]) -> Result<(), RpcSendError> {
+        let payloads = msgs.iter().map(encode_prost).collect::<Result<_, _>>()?;
+        self.send_batch_payloads(PROST_CODEC_ID, payloads);
+        Ok(())
+    }
+
+    /// Send several messages encoded with codec `C` as frames of a single group.
+    ///
+    /// See [`send_batch`](Self::send_batch).
+    pub fn send_batch_with<C: MessageCodec<M>, M>(
+        &mut self,
+        msgs: &[M],
+    ) -> Result<(), RpcSendError> {
+        let payloads = msgs.iter().map(C::encode).collect::<Result<_, _>>()?;
+        self.send_batch_payloads(codec_id::<C, M>(), payloads);
+        Ok(())
+    }
+
+    fn send_batch_payloads(&mut self, codec_id: u8, payloads: Vec<Vec<u8>>) {
+        let sizes: Vec<_> = payloads.iter().map(Vec::len).collect();
+        let frames: Vec<_> = payloads
+            .iter()
+            .map(|payload| self.compression.encode_tagged(codec_id, payload))
+            .collect();
+        frames.iter().for_each(|frame| self.record_size(frame));
+        self.touch_keepalive();
+        if let Some(counters) = &self.counters {
+            sizes
+                .into_iter()
+                .for_each(|size| counters.record_outbound(size));
+        }
+
+        match &self.batch {
+            Some(batch) => {
+                let mut batch = batch.lock().expect("outbound batch lock poisoned");
+                let pending = batch.take();
+                let keepalive = self.keepalive.as_deref().map(|keepalive| &*keepalive.state);
+                write_group(&mut self.track, &self.metadata, keepalive, pending);
+                write_group(&mut self.track, &self.metadata, keepalive, frames);
+            }
+            None => {
+                let keepalive = self.keepalive.as_deref().map(|keepalive| &*keepalive.state);
+                write_group(&mut self.track, &self.metadata, keepalive, frames);
+            }
+        }
+    }
+
+    /// Start a new group; messages sent while the returned guard is alive are
+    /// written as frames of that group, and dropping the guard ends it.
+    ///
+    /// Any messages buffered by auto-flush are written first. Beginning a group
+    /// while another is open ends the previous one.
+    ///
+    /// On the consuming side, `TrackConsumer::next_group()` always returns the
+    /// latest group, so a subscriber that joins (or falls behind) starts reading
+    /// at the beginning of the most recent group rather than mid-way through it.
+    /// Frames of an open group are delivered as they are written; the group's
+    /// `read_frame()` returns `None` once the guard is dropped.
+    pub fn begin_group(&mut self) -> OutboundGroup {
+        self.flush();
+        self.touch_keepalive();
+
+        let mut open_group = self
+            .open_group
+            .lock()
+            .expect("outbound group lock poisoned");
+        // Held until the group is open, so a keepalive can't start a group in between
+        let mut state = self
+            .keepalive
+            .as_deref()
+            .map(|keepalive| keepalive.state.lock().expect("keepalive lock poisoned"));
+        if let Some(state) = &mut state {
+            state.close_tail();
+        }
+        let group = append_group(&mut self.track, &self.metadata);
+        let sequence = group.info.sequence;
+        if let Some(previous) = open_group.replace(group) {
+            previous.close();
+        }
+
+        OutboundGroup {
+            open_group: Arc::clone(&self.open_group),
+            keepalive: self
+                .keepalive
+                .as_ref()
+                .map(|keepalive| Arc::clone(&keepalive.state)),
+            sequence,
+        }
+    }
+
+    /// Send raw bytes.
+    ///
+    /// The bytes are compressed and tagged like any other frame, see [`Compression`].
     pub fn send_raw(&mut self, bytes: impl Into<Bytes>) {
-        self.track.write_frame(bytes.into());
+        let frame = self.compression.encode(&bytes.into());
+        self.record_size(&frame);
+        self.write_frame(frame);
+    }
+
+    fn record_size(&self, frame: &Bytes) {
+        if let Some(sizes) = &self.sizes {
+            sizes.record(frame.len());
+        }
+    }
+
+    fn touch_keepalive(&self) {
+        if let Some(keepalive) = &self.keepalive {
+            keepalive.touch();
+        }
+    }
+
+    fn stop_keepalive(&self) {
+        if let Some(keepalive) = &self.keepalive {
+            keepalive.stop();
+        }
+    }
+
+    /// Write an encoded frame to the open group, the auto-flush batch, or its own group.
+    fn write_frame(&mut self, frame: Bytes) {
+        self.touch_keepalive();
+        if let Some(group) = self
+            .open_group
+            .lock()
+            .expect("outbound group lock poisoned")
+            .as_mut()
+        {
+            group.write_frame(frame);
+            return;
+        }
+
+        let keepalive = self.keepalive.as_deref().map(|keepalive| &*keepalive.state);
+        let Some(batch) = &self.batch else {
+            write_group(&mut self.track, &self.metadata, keepalive, vec![frame]);
+            return;
+        };
+
+        let mut pending = batch.lock().expect("outbound batch lock poisoned");
+        pending.frames.push(frame);
+
+        if pending.frames.len() >= pending.max_batch {
+            let frames = pending.take();
+            write_group(&mut self.track, &self.metadata, keepalive, frames);
+        } else if pending.frames.len() == 1 {
+            // First message of a new batch: flush it after max_delay at the latest.
+            let batch = Arc::clone(batch);
+            let mut track = self.track.clone();
+            let metadata = Arc::clone(&self.metadata);
+            let keepalive = self
+                .keepalive
+                .as_ref()
+                .map(|keepalive| Arc::clone(&keepalive.state));
+            let generation = pending.generation;
+            let max_delay = pending.max_delay;
+            tokio::spawn(async move {
+                tokio::time::sleep(max_delay).await;
+                let mut pending = batch.lock().expect("outbound batch lock poisoned");
+                if pending.generation == generation {
+                    let frames = pending.take();
+                    write_group(&mut track, &metadata, keepalive.as_deref(), frames);
+                }
+            });
+        }
+    }
+
+    /// Write any messages buffered by auto-flush as a single group.
+    ///
+    /// This is a no-op when auto-flush is disabled or nothing is buffered.
+    pub fn flush(&mut self) {
+        if let Some(batch) = &self.batch {
+            let frames = batch.lock().expect("outbound batch lock poisoned").take();
+            let keepalive = self.keepalive.as_deref().map(|keepalive| &*keepalive.state);
+            write_group(&mut self.track, &self.metadata, keepalive, frames);
+        }
+    }
+
+    /// Write any buffered messages, end any open group and close the track cleanly.
+    ///
+    /// Subscribers read the remaining messages and then see the stream end, rather than an
+    /// error. Closing affects every clone of this outbound.
+    pub fn finish(mut self) {
+        self.end_groups();
+        self.track.close();
+    }
+
+    /// Stop keepalives, write buffered messages and end any open group.
+    fn end_groups(&mut self) {
+        self.stop_keepalive();
+        self.flush();
+        if let Some(group) = self
+            .open_group
+            .lock()
+            .expect("outbound group lock poisoned")
+            .take()
+        {
+            group.close();
+        }
+    }
+
+    /// End the stream, wait up to `timeout` for every subscriber to let go of the track, then
+    /// [`finish`](Self::finish) it.
+    ///
+    /// moq-lite does not acknowledge delivery, and a subscriber that has not yet picked up the
+    /// latest group sees the track end without it once the track is closed. So the stream is
+    /// ended first, with the trailer from [`send_end`](Self::send_end) if status trailers are
+    /// enabled, and a client lets go of the track once it has read up to it. Returns whether
+    /// every subscriber let go in time.
+    ///
+    /// Without status trailers a client only learns of the end when the track closes, so
+    /// this waits all of `timeout` unless the client stops reading of its own accord, as a
+    /// unary client does after its response. So does a relay that holds its subscription
+    /// for as long as the broadcast is up.
+    ///
+    /// The track stops being offered to new subscribers on the broadcast, if this outbound
+    /// has one, as the broadcast's own reference would otherwise keep it in use. Draining the
+    /// track the broadcast was attached with drains the tracks opened with
+    /// [`track`](Self::track) along with it.
+    pub async fn drain(self, timeout: Duration) -> bool {
+        self.end_and_wait(timeout, false).await
+    }
+
+    /// End the stream on this track and any siblings, then wait up to `timeout` for every
+    /// subscriber to let go. With `finish_first` the tracks are closed after
+    /// [`ERROR_FRAME_GRACE`] rather than once the wait is over, so that a client without
+    /// status trailers also sees the end.
+    async fn end_and_wait(mut self, timeout: Duration, finish_first: bool) -> bool {
+        let mut siblings = match self.broadcast.as_ref().and_then(Weak::upgrade) {
+            Some(shared) => {
+                let mut shared = shared.lock().expect("outbound broadcast lock poisoned");
+                let name = &self.track.info.name;
+                shared.producer.remove_track(name);
+                if *name == shared.primary {
+                    let siblings: Vec<_> = shared.siblings.drain().map(|(_, s)| s).collect();
+                    for sibling in &siblings {
+                        shared.producer.remove_track(&sibling.track.info.name);
+                    }
+                    siblings
+                } else {
+                    shared.siblings.remove(name);
+                    Vec::new()
+                }
+            }
+            None => Vec::new(),
+        };
+
+        for outbound in std::iter::once(&mut self).chain(&mut siblings) {
+            if outbound.status_trailers {
+                outbound.send_end();
+            } else {
+                outbound.end_groups();
+            }
+        }
+        let unused = futures::future::join_all(
+            std::iter::once(&self)
+                .chain(&siblings)
+                .map(|outbound| outbound.track.unused()),
+        );
+        let deadline = tokio::time::Instant::now() + timeout;
+        let finish = move || {
+            self.finish();
+            for sibling in siblings {
+                sibling.finish();
+            }
+        };
+        if finish_first {
+            // Closing straight away would overtake the last group for a subscriber that has
+            // not picked it up yet
+            tokio::time::sleep(ERROR_FRAME_GRACE.min(timeout)).await;
+            finish();
+            tokio::time::timeout_at(deadline, unused).await.is_ok()
+        } else {
+            let drained = tokio::time::timeout_at(deadline, unused).await.is_ok();
+            finish();
+            drained
+        }
+    }
+
+    /// Send a last protobuf message, [`finish`](Self::finish) the track and wait up to
+    /// `timeout` for every subscriber to let go of it.
+    ///
+    /// moq-lite does not acknowledge delivery, so the send is confirmed once every subscriber
+    /// has let go, which a client does after reading the message and the end of the stream.
+    /// The track is closed after a short grace period, so the end reaches clients with or
+    /// without status trailers and the message is not overtaken by it. Fails with
+    /// [`RpcSendError::Unconfirmed`] if a subscriber still holds on after `timeout`, though it
+    /// may have read the message all the same.
+    ///
+    /// Where [`send`](Self::send) returns as soon as the frame is queued, this takes at least
+    /// the grace period and a round trip to the slowest subscriber, and all of `timeout` if
+    /// one has gone away without unsubscribing. It suits a single response, like a unary
+    /// call's, rather than each message of a stream.
+    pub async fn send_confirmed<M: Message>(
+        mut self,
+        msg: &M,
+        timeout: Duration,
+    ) -> Result<(), RpcSendError> {
+        self.send(msg)?;
+        self.confirm_end(timeout).await
+    }
+
+    /// Send a message encoded with codec `C` and wait for it to be read, as
+    /// [`send_confirmed`](Self::send_confirmed) does.
+    ///
+    /// The message is written by the call itself, so the returned future doesn't borrow it.
+    pub fn send_confirmed_with<C: MessageCodec<M>, M>(
+        mut self,
+        msg: &M,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<(), RpcSendError>> + use<C, M> {
+        let sent = self.send_with::<C, M>(msg);
+        async move {
+            sent?;
+            self.confirm_end(timeout).await
+        }
+    }
+
+    async fn confirm_end(self, timeout: Duration) -> Result<(), RpcSendError> {
+        if self.end_and_wait(timeout, true).await {
+            Ok(())
+        } else {
+            Err(RpcSendError::Unconfirmed(timeout))
+        }
     }
 
     /// Abort the underlying track with an application error code.
     pub fn abort_app(&self, code: u32) {
+        self.stop_keepalive();
         self.track.clone().abort(MoqError::App(code));
     }
+
+    /// Send an error frame carrying `code` and a human-readable `message`.
+    ///
+    /// Buffered messages are written first and any open group is ended. A client
+    /// reading the frame yields `RpcWireError::Server { code, message }` and ends
+    /// its stream. Clients that predate error frames fail to decode it.
+    pub fn send_error(&mut self, code: u32, message: impl Into<String>) {
+        let frame = RpcError {
+            code,
+            message: message.into(),
+            grpc_code: None,
+        }
+        .to_frame();
+        self.write_final_frame(frame);
+    }
+
+    /// Send the trailer ending a stream with an OK status.
+    ///
+    /// Buffered messages are written first and any open group is ended. A client reading the
+    /// trailer ends its stream cleanly, without waiting for the track to close. Like any group,
+    /// a client that has fallen behind may skip straight to it.
+    pub fn send_end(&mut self) {
+        self.write_final_frame(error_frame::end_frame());
+    }
+
+    /// Write buffered messages and end any open group, then write `frame` as a group of its own.
+    fn write_final_frame(&mut self, frame: Bytes) {
+        // A keepalive group after the final frame would supersede it
+        self.end_groups();
+        write_group(&mut self.track, &self.metadata, None, vec![frame]);
+    }
+
+    /// Abort the underlying track with `code`, explaining why with an error
+    /// frame first if error frames are enabled.
+    ///
+    /// With error frames the abort is delayed briefly so the frame isn't
+    /// overtaken, which requires a Tokio runtime.
+    pub fn abort_with_error(&self, code: u32, message: impl Into<String>) {
+        if !self.error_frames {
+            self.abort_app(code);
+            return;
+        }
+
+        let mut outbound = self.clone();
+        outbound.send_error(code, message);
+        outbound.abort_after_grace(code);
+    }
+
+    /// Abort the underlying track as a gRPC error, sending `status` in an error frame first if
+    /// status trailers are enabled and falling back to
+    /// [`abort_with_error`](Self::abort_with_error) otherwise.
+    pub fn abort_with_status(&self, status: &tonic::Status) {
+        let code = RpcWireError::Grpc.to_code_in(self.code_space);
+        if !self.status_trailers {
+            self.abort_with_error(code, status.message());
+            return;
+        }
+
+        let frame = RpcError {
+            code,
+            message: status.message().to_string(),
+            grpc_code: Some(status.code() as i32),
+        }
+        .to_frame();
+        let mut outbound = self.clone();
+        outbound.write_final_frame(frame);
+        outbound.abort_after_grace(code);
+    }
+
+    /// Abort with `code` once subscribers have had time to read a frame just written.
+    fn abort_after_grace(self, code: u32) {
+        tokio::spawn(async move {
+            tokio::time::sleep(ERROR_FRAME_GRACE).await;
+            self.abort_app(code);
+        });
+    }
+}
+
+/// A guard for a group started with [`RpcOutbound::begin_group`].
+///
+/// Dropping the guard ends the group, after which sends return to writing
+/// one group per message.
+#[must_use = "the group ends as soon as the guard is dropped"]
+pub struct OutboundGroup {
+    open_group: Arc<Mutex<Option<GroupProducer>>>,
+    keepalive: Option<Arc<Mutex<KeepaliveState>>>,
+    sequence: u64,
+}
+
+impl OutboundGroup {
+    /// The sequence number of this group on the track.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+impl Drop for OutboundGroup {
+    fn drop(&mut self) {
+        let mut open_group = self
+            .open_group
+            .lock()
+            .expect("outbound group lock poisoned");
+        // A later begin_group may already have replaced (and closed) this group.
+        if open_group
+            .as_ref()
+            .is_some_and(|group| group.info.sequence == self.sequence)
+            && let Some(group) = open_group.take()
+        {
+            // Kept open for keepalives, like a group written in one go
+            match &self.keepalive {
+                Some(state) => state
+                    .lock()
+                    .expect("keepalive lock poisoned")
+                    .keep_open(group),
+                None => group.close(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `frames` as a single group, so a reader that falls behind cannot skip any of them.
+    fn send_group(outbound: &mut RpcOutbound, frames: &[&'static [u8]]) {
+        let _group = outbound.begin_group();
+        for frame in frames {
+            outbound.send_raw(Bytes::from_static(frame));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_buffer_drop_oldest_counts_lagged_frames() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let inbound =
+            RpcInbound::from_track(track.consumer).with_buffer(2, InboundBufferPolicy::DropOldest);
+
+        send_group(&mut outbound, &[b"1", b"2", b"3", b"4", b"5"]);
+        // Nothing is read until the reader has dropped all but the last two frames
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while inbound.lagged_frames() < 3 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        outbound.finish();
+
+        let frames: Vec<_> = inbound.map(Result::unwrap).collect().await;
+        assert_eq!(frames, [Bytes::from_static(b"4"), Bytes::from_static(b"5")]);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_drop_oldest_keeps_control_frames() {
+        let error = RpcError {
+            code: RpcWireError::CODE_GRPC,
+            message: "backend failed".to_string(),
+            grpc_code: None,
+        }
+        .to_frame();
+        let frames = [
+            Ok((0, RpcMetadata::default().to_frame())),
+            Ok((0, Bytes::from_static(b"1"))),
+            Ok((0, Bytes::from_static(b"2"))),
+            Ok((0, error.clone())),
+        ];
+        let lagged = Arc::new(AtomicU64::new(0));
+        let inner = Box::pin(futures::stream::iter(frames));
+        let buffer = buffered(
+            inner,
+            2,
+            InboundBufferPolicy::DropOldest,
+            Arc::clone(&lagged),
+        );
+
+        let frames: Vec<_> = buffer.map(|next| next.unwrap().1).collect().await;
+        assert_eq!(frames, [RpcMetadata::default().to_frame(), error]);
+        assert_eq!(lagged.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_backpressure_keeps_every_frame() {
+        let frames: Vec<_> = (0..5u8).map(|n| Bytes::from(vec![n])).collect();
+        let pulled = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&pulled);
+        let inner = Box::pin(futures::stream::iter(frames.clone()).map(move |frame| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok((0, frame))
+        }));
+        let lagged = Arc::new(AtomicU64::new(0));
+        let mut buffer = Box::pin(buffered(
+            inner,
+            2,
+            InboundBufferPolicy::Backpressure,
+            Arc::clone(&lagged),
+        ));
+        // Polled once to start, so the reader fills the buffer and holds the next frame back
+        assert_eq!(buffer.next().await.unwrap().unwrap().1, frames[0]);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while pulled.load(Ordering::Relaxed) < 4 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(pulled.load(Ordering::Relaxed), 4);
+
+        let rest: Vec<_> = buffer.map(|next| next.unwrap().1).collect().await;
+        assert_eq!(rest, frames[1..]);
+        assert_eq!(lagged.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_max_rate_backpressure_enforces_the_rate() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let inbound = RpcInbound::from_track(track.consumer)
+            .with_max_rate(50.0, InboundRatePolicy::Backpressure);
+
+        // A burst of 50 goes through at once, the other 25 at 50 per second
+        let frames: Vec<&'static [u8]> = vec![b"frame"; 75];
+        send_group(&mut outbound, &frames);
+
+        let started = Instant::now();
+        let received = tokio::time::timeout(Duration::from_secs(2), inbound.take(75).count())
+            .await
+            .unwrap();
+        assert_eq!(received, 75);
+        assert!(started.elapsed() >= Duration::from_millis(450));
+    }
+
+    #[tokio::test]
+    async fn test_max_rate_drop_counts_throttled_frames() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let mut inbound =
+            RpcInbound::from_track(track.consumer).with_max_rate(10.0, InboundRatePolicy::Drop);
+
+        let frames: Vec<&'static [u8]> = vec![b"frame"; 30];
+        send_group(&mut outbound, &frames);
+        // Ends the stream once the reader has had time to go through the group
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            outbound.finish();
+        });
+
+        let received = tokio::time::timeout(Duration::from_secs(1), inbound.by_ref().count())
+            .await
+            .unwrap();
+        // Only the burst fits, give or take a token refilled while reading
+        assert!((10..=11).contains(&received), "received {received}");
+        assert_eq!(received as u64 + inbound.throttled_frames(), 30);
+    }
+
+    #[tokio::test]
+    async fn test_max_rate_backpressure_counts_skipped_groups() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let mut inbound = RpcInbound::from_track(track.consumer)
+            .with_max_rate(10.0, InboundRatePolicy::Backpressure);
+
+        // The burst of 10 goes through, the 11th is held back while three more groups arrive
+        let frames: Vec<&'static [u8]> = vec![b"frame"; 11];
+        send_group(&mut outbound, &frames);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            for frame in [b"late-1", b"late-2", b"late-3"] {
+                outbound.send_raw(Bytes::from_static(frame));
+            }
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            outbound.finish();
+        });
+
+        let received = tokio::time::timeout(Duration::from_secs(1), inbound.by_ref().count())
+            .await
+            .unwrap();
+        // The reader skips to the latest group, past the two before it
+        assert_eq!(received, 12);
+        assert_eq!(inbound.throttled_groups(), 2);
+        assert_eq!(inbound.throttled_frames(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_max_rate_backpressure_holds_the_keepalive_clock() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let inbound = RpcInbound::from_track(track.consumer)
+            .with_max_rate(10.0, InboundRatePolicy::Backpressure)
+            .with_keepalive_timeout(Duration::from_millis(50));
+
+        // The two frames over the burst are each held back for longer than the timeout
+        let frames: Vec<&'static [u8]> = vec![b"frame"; 12];
+        send_group(&mut outbound, &frames);
+
+        let received = tokio::time::timeout(Duration::from_secs(1), inbound.take(12).collect())
+            .await
+            .unwrap();
+        let received: Vec<_> = received;
+        assert!(received.iter().all(Result::is_ok), "{received:?}");
+        assert_eq!(received.len(), 12);
+    }
+
+    #[test]
+    fn test_max_rate_rejects_non_positive_rates() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let result = std::panic::catch_unwind(|| {
+                let track = Track::new("primary").produce();
+                RpcInbound::from_track(track.consumer)
+                    .with_max_rate(rate, InboundRatePolicy::Backpressure)
+            });
+            assert!(result.is_err(), "rate {rate} accepted");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frame_at_max_size_is_accepted() {
+        let mut track = Track::new("primary").produce();
+        let mut inbound = RpcInbound::from_track(track.consumer).with_max_frame_size(4);
+
+        // The limit covers the compression tag
+        track.producer.write_frame(Compression::None.encode(b"123"));
+
+        let frame = inbound.next().await.unwrap().unwrap();
+        assert_eq!(frame, Bytes::from_static(b"123"));
+    }
+
+    #[tokio::test]
+    async fn test_frame_over_max_size_is_rejected() {
+        let mut track = Track::new("primary").produce();
+        let mut inbound = RpcInbound::from_track(track.consumer).with_max_frame_size(4);
+
+        track.producer.write_frame(Bytes::from_static(b"12345"));
+
+        let err = inbound.next().await.unwrap().unwrap_err();
+        assert!(matches!(
+            RpcWireError::from(err),
+            RpcWireError::FrameTooLarge
+        ));
+
+        // The stream stays closed even if further frames arrive.
+        track.producer.write_frame(Bytes::from_static(b"1234"));
+        assert!(inbound.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_control_frame_over_max_size_is_rejected() {
+        let mut track = Track::new("primary").produce();
+        let mut inbound = RpcInbound::from_track(track.consumer).with_max_frame_size(8);
+
+        let frame = RpcError {
+            code: 1,
+            message: "a message longer than the limit".to_string(),
+            grpc_code: None,
+        }
+        .to_frame();
+        track.producer.write_frame(frame);
+
+        let err = inbound.next().await.unwrap().unwrap_err();
+        assert!(matches!(
+            RpcWireError::from(err),
+            RpcWireError::FrameTooLarge
+        ));
+        assert!(inbound.take_server_error().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_raw_track_access() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let mut inbound = RpcInbound::from_track(track.consumer.clone());
+
+        outbound.send_raw(Bytes::from_static(b"framed"));
+        let (sequence, frame) = inbound.next_with_group().await.unwrap().unwrap();
+        assert_eq!((sequence, frame), (0, Bytes::from_static(b"framed")));
+
+        // Skip ahead to a sequence of our choosing, framing the payload by hand
+        let mut group = outbound
+            .track_mut()
+            .create_group(moq_lite::Group { sequence: 10 })
+            .unwrap();
+        group.write_frame(Compression::None.encode(b"raw"));
+        group.close();
+        let (sequence, frame) = inbound.next_with_group().await.unwrap().unwrap();
+        assert_eq!((sequence, frame), (10, Bytes::from_static(b"raw")));
+
+        // The raw consumer reads the framed bytes as they are on the track
+        let mut raw = inbound.into_track().unwrap();
+        let mut group = raw.next_group().await.unwrap().unwrap();
+        assert_eq!(group.info.sequence, 10);
+        let frame = group.read_frame().await.unwrap().unwrap();
+        assert_eq!(Compression::None.decode(frame).unwrap(), "raw");
+
+        let broadcast = moq_lite::Broadcast::produce();
+        let resilient = RpcInbound::new_resilient(
+            broadcast.consumer,
+            "primary",
+            RetryPolicy::builder().build(),
+        );
+        assert!(resilient.into_track().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_size_stats_on_both_ends() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer).with_size_stats();
+        let mut inbound = RpcInbound::from_track(track.consumer).with_size_stats();
+
+        send_group(&mut outbound, &[b"1", b"12345", b"123"]);
+        let frames: Vec<_> = inbound.by_ref().take(3).collect().await;
+        assert_eq!(frames.len(), 3);
+
+        // Sizes are of the frames on the wire, each with its one-byte tag
+        let expected = SizeStats {
+            messages: 3,
+            total_bytes: 12,
+            max_bytes: 6,
+        };
+        assert_eq!(outbound.size_stats(), Some(expected));
+        assert_eq!(inbound.size_stats(), Some(expected));
+    }
+
+    #[tokio::test]
+    async fn test_size_stats_are_off_by_default() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let inbound = RpcInbound::from_track(track.consumer);
+
+        outbound.send_raw(Bytes::from_static(b"1234"));
+        assert_eq!(outbound.size_stats(), None);
+        assert_eq!(inbound.size_stats(), None);
+    }
+
+    /// Read every frame of the next group on `track`.
+    async fn next_group_frames(track: &mut TrackConsumer) -> Vec<Bytes> {
+        let mut group = track.next_group().await.unwrap().unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = group.read_frame().await.unwrap() {
+            frames.push(Compression::None.decode(frame).unwrap());
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn test_send_batch_writes_single_group() {
+        let mut track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+
+        outbound
+            .send_batch(&["a".to_string(), "b".to_string(), "c".to_string()])
+            .unwrap();
+
+        let frames = next_group_frames(&mut track.consumer).await;
+        assert_eq!(frames.len(), 3);
+        assert_eq!(String::decode(frames[2].clone()).unwrap(), "c");
+    }
+
+    #[tokio::test]
+    async fn test_auto_flush_on_count() {
+        let mut track = Track::new("primary").produce();
+        let mut outbound =
+            RpcOutbound::new(track.producer).with_auto_flush(2, Duration::from_secs(60));
+
+        outbound.send(&"a".to_string()).unwrap();
+        outbound.send(&"b".to_string()).unwrap();
+
+        let frames = next_group_frames(&mut track.consumer).await;
+        assert_eq!(frames.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_auto_flush_on_delay() {
+        let mut track = Track::new("primary").produce();
+        let mut outbound =
+            RpcOutbound::new(track.producer).with_auto_flush(10, Duration::from_millis(20));
+
+        outbound.send(&"a".to_string()).unwrap();
+        outbound.send(&"b".to_string()).unwrap();
+
+        let frames = tokio::time::timeout(
+            Duration::from_secs(1),
+            next_group_frames(&mut track.consumer),
+        )
+        .await
+        .unwrap();
+        assert_eq!(frames.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_explicit_flush() {
+        let mut track = Track::new("primary").produce();
+        let mut outbound =
+            RpcOutbound::new(track.producer).with_auto_flush(10, Duration::from_secs(60));
+
+        outbound.send(&"a".to_string()).unwrap();
+        outbound.flush();
+
+        let frames = next_group_frames(&mut track.consumer).await;
+        assert_eq!(frames.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_begin_group_routes_sends_into_group() {
+        let mut track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+
+        let group = outbound.begin_group();
+        outbound.send(&"snapshot".to_string()).unwrap();
+        outbound.send(&"delta".to_string()).unwrap();
+        drop(group);
+
+        let frames = next_group_frames(&mut track.consumer).await;
+        assert_eq!(frames.len(), 2);
+
+        // Without a guard each send is its own group again.
+        outbound.send(&"after".to_string()).unwrap();
+        let frames = next_group_frames(&mut track.consumer).await;
+        assert_eq!(frames.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_late_subscriber_starts_at_group_boundary() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer.clone());
+
+        let _group = outbound.begin_group();
+        outbound.send(&"snapshot".to_string()).unwrap();
+
+        let mut late = track.producer.consume();
+        outbound.send(&"delta".to_string()).unwrap();
+
+        let mut group = late.next_group().await.unwrap().unwrap();
+        let first = Compression::None.decode(group.read_frame().await.unwrap().unwrap());
+        assert_eq!(String::decode(first.unwrap()).unwrap(), "snapshot");
+    }
+
+    #[tokio::test]
+    async fn test_sub_tracks_are_subscribed_separately() {
+        let mut broadcast = moq_lite::Broadcast::produce();
+        let mut positions =
+            RpcOutbound::new(broadcast.producer.create_track(Track::new("primary")))
+                .with_broadcast(broadcast.producer.clone());
+        let mut events = positions.track("events").unwrap();
+
+        let mut position_inbound = RpcInbound::new(&broadcast.consumer, "primary");
+        let mut event_inbound = RpcInbound::new(&broadcast.consumer, "events");
+
+        positions.send_raw(Bytes::from_static(b"position"));
+        events.send_raw(Bytes::from_static(b"event"));
+
+        assert_eq!(position_inbound.next().await.unwrap().unwrap(), "position");
+        assert_eq!(event_inbound.next().await.unwrap().unwrap(), "event");
+    }
+
+    #[tokio::test]
+    async fn test_sub_track_requires_broadcast_and_new_name() {
+        let mut broadcast = moq_lite::Broadcast::produce();
+        let track = broadcast.producer.create_track(Track::new("primary"));
+
+        assert!(matches!(
+            RpcOutbound::new(track.clone()).track("events"),
+            Err(RpcTrackError::NoBroadcast)
+        ));
+        let outbound = RpcOutbound::new(track).with_broadcast(broadcast.producer.clone());
+        assert!(matches!(
+            outbound.track("primary"),
+            Err(RpcTrackError::DuplicateTrack(name)) if name == "primary"
+        ));
+        let events = outbound.track("events").unwrap();
+        assert!(matches!(
+            outbound.track("events"),
+            Err(RpcTrackError::DuplicateTrack(name)) if name == "events"
+        ));
+
+        events.drain(Duration::ZERO).await;
+        assert!(outbound.track("events").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_sub_track_does_not_keep_broadcast_open() {
+        let mut broadcast = moq_lite::Broadcast::produce();
+        let outbound = RpcOutbound::new(broadcast.producer.create_track(Track::new("primary")))
+            .with_broadcast(broadcast.producer);
+        let events = outbound.track("events").unwrap();
+
+        drop(outbound);
+        tokio::time::timeout(Duration::from_secs(1), broadcast.consumer.closed())
+            .await
+            .expect("the sibling track kept the broadcast open");
+        assert!(matches!(
+            events.track("more"),
+            Err(RpcTrackError::BroadcastClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_resilient_inbound_resubscribes_after_error() {
+        let mut broadcast = moq_lite::Broadcast::produce();
+        let policy = RetryPolicy::builder()
+            .initial_backoff(Duration::from_millis(10))
+            .build();
+        let mut inbound = RpcInbound::new_resilient(broadcast.consumer.clone(), "primary", policy);
+
+        let mut first = RpcOutbound::new(broadcast.producer.create_track(Track::new("primary")));
+        first.send_raw(Bytes::from_static(b"before"));
+        assert_eq!(inbound.next().await.unwrap().unwrap(), "before");
+
+        first.abort_app(RpcWireError::CODE_INTERNAL);
+        let mut second = RpcOutbound::new(broadcast.producer.create_track(Track::new("primary")));
+        second.send_raw(Bytes::from_static(b"after"));
+
+        let frame = tokio::time::timeout(Duration::from_secs(1), inbound.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, "after");
+    }
+
+    #[tokio::test]
+    async fn test_resilient_inbound_yields_error_when_retries_exhausted() {
+        let mut broadcast = moq_lite::Broadcast::produce();
+        let policy = RetryPolicy::builder().max_retries(0).build();
+        let mut inbound = RpcInbound::new_resilient(broadcast.consumer.clone(), "primary", policy);
+
+        let track = RpcOutbound::new(broadcast.producer.create_track(Track::new("primary")));
+        track.abort_app(RpcWireError::CODE_INTERNAL);
+
+        let err = inbound.next().await.unwrap().unwrap_err();
+        assert!(matches!(RpcWireError::from(err), RpcWireError::Internal));
+        assert!(inbound.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_compressed_round_trip() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer).with_compression(Compression::Zstd);
+        let mut inbound =
+            RpcInbound::from_track(track.consumer).with_compression(Compression::Zstd);
+
+        outbound.send(&"drone-123".to_string()).unwrap();
+
+        let frame = inbound.next().await.unwrap().unwrap();
+        assert_eq!(String::decode(frame).unwrap(), "drone-123");
+    }
+
+    #[tokio::test]
+    async fn test_compression_mismatch_is_rejected() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let mut inbound =
+            RpcInbound::from_track(track.consumer).with_compression(Compression::Gzip);
+
+        outbound.send(&"drone-123".to_string()).unwrap();
+
+        let err = inbound.next().await.unwrap().unwrap_err();
+        assert!(matches!(
+            RpcWireError::from(err),
+            RpcWireError::CompressionMismatch
+        ));
+        assert!(inbound.next().await.is_none());
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn test_json_codec_round_trip() {
+        use crate::codec::JsonCodec;
+
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let mut inbound = RpcInbound::from_track(track.consumer)
+            .with_codec_id(<JsonCodec as MessageCodec<String>>::ID);
+
+        outbound
+            .send_with::<JsonCodec, _>(&"drone-123".to_string())
+            .unwrap();
+
+        let frame = inbound.next().await.unwrap().unwrap();
+        let decoded: String = JsonCodec::decode(frame).unwrap();
+        assert_eq!(decoded, "drone-123");
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn test_codec_mismatch_is_rejected() {
+        use crate::codec::JsonCodec;
+
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let mut inbound = RpcInbound::from_track(track.consumer)
+            .with_codec_id(<JsonCodec as MessageCodec<String>>::ID);
+
+        outbound.send(&"drone-123".to_string()).unwrap();
+
+        let err = inbound.next().await.unwrap().unwrap_err();
+        assert!(matches!(
+            RpcWireError::from(err),
+            RpcWireError::CodecMismatch
+        ));
+        assert!(inbound.next().await.is_none());
+    }
+
+    fn metadata(key: &str, value: &str) -> RpcMetadata {
+        let mut metadata = RpcMetadata::default();
+        metadata.entries.insert(key.to_string(), value.to_string());
+        metadata
+    }
+
+    #[tokio::test]
+    async fn test_metadata_prepended_to_first_group_only() {
+        let mut track = Track::new("primary").produce();
+        let mut outbound =
+            RpcOutbound::new(track.producer).with_metadata(&metadata("key", "value"));
+
+        outbound.send(&"first".to_string()).unwrap();
+        let mut group = track.consumer.next_group().await.unwrap().unwrap();
+        let frame = group.read_frame().await.unwrap().unwrap();
+        assert_eq!(
+            RpcMetadata::from_frame(frame).unwrap(),
+            metadata("key", "value")
+        );
+
+        outbound.send(&"second".to_string()).unwrap();
+        let frames = next_group_frames(&mut track.consumer).await;
+        assert_eq!(frames.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_read_metadata_keeps_first_message() {
+        let track = Track::new("primary").produce();
+        let mut outbound =
+            RpcOutbound::new(track.producer).with_metadata(&metadata("key", "value"));
+        let mut inbound = RpcInbound::from_track(track.consumer);
+
+        outbound.send_raw(Bytes::from_static(b"first"));
+
+        assert_eq!(
+            inbound.read_metadata().await,
+            Some(&metadata("key", "value"))
+        );
+        assert_eq!(inbound.next().await.unwrap().unwrap(), "first");
+    }
+
+    #[tokio::test]
+    async fn test_read_metadata_without_metadata_frame() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let mut inbound = RpcInbound::from_track(track.consumer);
+
+        outbound.send_raw(Bytes::from_static(b"first"));
+
+        assert_eq!(inbound.read_metadata().await, None);
+        assert_eq!(inbound.next().await.unwrap().unwrap(), "first");
+    }
+
+    #[tokio::test]
+    async fn test_metadata_frames_are_skipped_by_stream() {
+        let track = Track::new("primary").produce();
+        let mut outbound =
+            RpcOutbound::new(track.producer).with_metadata(&metadata("key", "value"));
+        let mut inbound = RpcInbound::from_track(track.consumer);
+
+        outbound.send_raw(Bytes::from_static(b"first"));
+
+        assert_eq!(inbound.next().await.unwrap().unwrap(), "first");
+    }
+
+    #[tokio::test]
+    async fn test_error_frame_ends_stream_with_server_error() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let mut inbound = RpcInbound::from_track(track.consumer);
+
+        outbound.send_raw(Bytes::from_static(b"first"));
+        assert_eq!(inbound.next().await.unwrap().unwrap(), "first");
+
+        outbound.send_error(RpcWireError::CODE_GRPC, "backend unavailable");
+        let err = inbound.next().await.unwrap().unwrap_err();
+        assert!(matches!(RpcWireError::from(err), RpcWireError::Grpc));
+        assert!(matches!(
+            inbound.take_server_error(),
+            Some(RpcWireError::Server { code: RpcWireError::CODE_GRPC, message })
+                if message == "backend unavailable"
+        ));
+        assert!(inbound.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_end_frame_ends_stream_cleanly() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer).with_status_trailers(true);
+        let mut inbound = RpcInbound::from_track(track.consumer).with_status_trailers(true);
+
+        outbound.send_raw(Bytes::from_static(b"last"));
+        assert_eq!(inbound.next().await.unwrap().unwrap(), "last");
+
+        // The track is still open, the trailer alone ends the stream
+        outbound.send_end();
+        assert!(inbound.next().await.is_none());
+        assert!(inbound.take_server_error().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_end_frame_is_a_message_without_status_trailers() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer).with_status_trailers(true);
+        let mut inbound = RpcInbound::from_track(track.consumer);
+
+        outbound.send_end();
+        assert!(inbound.next().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_abort_with_status_sends_grpc_status() {
+        let track = Track::new("primary").produce();
+        let outbound = RpcOutbound::new(track.producer).with_status_trailers(true);
+        let mut inbound = RpcInbound::from_track(track.consumer);
+
+        outbound.abort_with_status(&tonic::Status::not_found("no such drone"));
+
+        let err = inbound.next().await.unwrap().unwrap_err();
+        assert!(matches!(RpcWireError::from(err), RpcWireError::Grpc));
+        assert!(matches!(
+            inbound.take_server_error(),
+            Some(RpcWireError::Status { code: tonic::Code::NotFound, message })
+                if message == "no such drone"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_abort_with_error_sends_frame_before_aborting() {
+        let track = Track::new("primary").produce();
+        let outbound = RpcOutbound::new(track.producer).with_error_frames(true);
+        let mut inbound = RpcInbound::from_track(track.consumer);
+
+        outbound.abort_with_error(RpcWireError::CODE_INTERNAL, "encode failed");
+
+        assert!(inbound.next().await.unwrap().is_err());
+        assert!(matches!(
+            inbound.take_server_error(),
+            Some(RpcWireError::Server { message, .. }) if message == "encode failed"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_abort_with_error_without_error_frames() {
+        let track = Track::new("primary").produce();
+        let outbound = RpcOutbound::new(track.producer);
+        let mut inbound = RpcInbound::from_track(track.consumer);
+
+        outbound.abort_with_error(RpcWireError::CODE_INTERNAL, "encode failed");
+
+        let err = inbound.next().await.unwrap().unwrap_err();
+        assert!(matches!(RpcWireError::from(err), RpcWireError::Internal));
+        assert!(inbound.take_server_error().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_keepalives_are_consumed_until_finish() {
+        let track = Track::new("primary").produce();
+        let outbound = RpcOutbound::new(track.producer).with_keepalive(Duration::from_millis(10));
+        let mut inbound = RpcInbound::from_track(track.consumer)
+            .with_keepalive_timeout(Duration::from_millis(100));
+
+        let next = tokio::time::timeout(Duration::from_millis(200), inbound.next()).await;
+        assert!(next.is_err());
+        assert!(inbound.last_seen().is_some());
+
+        // No keepalive follows the end of the track
+        outbound.finish();
+        let next = tokio::time::timeout(Duration::from_secs(1), inbound.next()).await;
+        assert!(next.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_keepalives_do_not_supersede_unread_groups() {
+        let track = Track::new("primary").produce();
+        let mut outbound =
+            RpcOutbound::new(track.producer).with_keepalive(Duration::from_millis(10));
+        let mut inbound = RpcInbound::from_track(track.consumer);
+
+        // The reader falls behind while keepalives are due
+        outbound.send_raw(Bytes::from_static(b"first"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(inbound.next().await.unwrap().unwrap(), "first");
+
+        send_group(&mut outbound, &[b"second", b"third"]);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(inbound.next().await.unwrap().unwrap(), "second");
+        assert_eq!(inbound.next().await.unwrap().unwrap(), "third");
+
+        outbound.finish();
+        assert!(inbound.next().await.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "keepalive interval must not be zero")]
+    fn test_zero_keepalive_interval_panics() {
+        let track = Track::new("primary").produce();
+        let _outbound = RpcOutbound::new(track.producer).with_keepalive(Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_send_confirmed_waits_for_slow_consumer() {
+        let track = Track::new("primary").produce();
+        let outbound = RpcOutbound::new(track.producer);
+        let mut inbound = RpcInbound::from_track(track.consumer);
+
+        let reader = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let frame = inbound.next().await.unwrap().unwrap();
+            assert_eq!(String::decode(frame).unwrap(), "pong");
+        });
+        let start = Instant::now();
+        outbound
+            .send_confirmed(&"pong".to_string(), Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        reader.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_confirmed_times_out_while_subscribed() {
+        let track = Track::new("primary").produce();
+        let outbound = RpcOutbound::new(track.producer);
+        let _inbound = RpcInbound::from_track(track.consumer);
+
+        let result = outbound
+            .send_confirmed(&"pong".to_string(), Duration::from_millis(50))
+            .await;
+        assert!(matches!(result, Err(RpcSendError::Unconfirmed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_drain_ends_once_client_reads_trailer() {
+        let track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer).with_status_trailers(true);
+        let mut inbound = RpcInbound::from_track(track.consumer).with_status_trailers(true);
+
+        outbound.send(&"pong".to_string()).unwrap();
+        let frame = inbound.next().await.unwrap().unwrap();
+        assert_eq!(String::decode(frame).unwrap(), "pong");
+
+        // Reads to the end but keeps the stream, as a client that hasn't dropped it yet does
+        let (ended, mut read_end) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            assert!(inbound.next().await.is_none());
+            let _ = ended.send(());
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            drop(inbound);
+        });
+
+        let start = Instant::now();
+        assert!(outbound.drain(Duration::from_secs(5)).await);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(read_end.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_drain_ends_sub_tracks() {
+        let mut broadcast = moq_lite::Broadcast::produce();
+        let outbound = RpcOutbound::new(broadcast.producer.create_track(Track::new("primary")))
+            .with_broadcast(broadcast.producer.clone())
+            .with_status_trailers(true);
+        let mut events = outbound.track("events").unwrap();
+        let mut event_inbound =
+            RpcInbound::new(&broadcast.consumer, "events").with_status_trailers(true);
+
+        events.send_raw(Bytes::from_static(b"event"));
+        assert_eq!(event_inbound.next().await.unwrap().unwrap(), "event");
+
+        let drain = tokio::spawn(outbound.drain(Duration::from_secs(5)));
+        assert!(event_inbound.next().await.is_none());
+        let drained = tokio::time::timeout(Duration::from_secs(1), drain)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(drained);
+    }
+
+    #[tokio::test]
+    async fn test_next_with_group_reports_sequence() {
+        let mut track = Track::new("primary").produce();
+        let mut outbound = RpcOutbound::new(track.producer.clone());
+        let mut inbound = RpcInbound::from_track(track.consumer);
+
+        outbound
+            .send_batch(&["a".to_string(), "b".to_string()])
+            .unwrap();
+        let (first, _) = inbound.next_with_group().await.unwrap().unwrap();
+        let (second, _) = inbound.next_with_group().await.unwrap().unwrap();
+        assert_eq!(first, second);
+
+        // A gap in sequence numbers reveals groups the reader never saw.
+        track.producer.append_group().close();
+        outbound.send(&"c".to_string()).unwrap();
+        let (third, frame) = inbound.next_with_group().await.unwrap().unwrap();
+        assert_eq!(third, first + 2);
+        assert_eq!(String::decode(frame).unwrap(), "c");
+    }
 }
@@ -1,5 +1,9 @@
+use std::time::Duration;
+
 use thiserror::Error;
 
+use crate::retry::RetryPolicy;
+
 /// Errors that can occur while parsing RPC or gRPC paths.
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -16,6 +20,16 @@ pub enum RpcClientError {
     #[error("failed to create broadcast: {0}")]
     BroadcastCreate(String),
 
+    /// Another client is already broadcasting at this client's path.
+    ///
+    /// Two clients were configured with the same `client_id`. See
+    /// `RpcClientConfig::with_random_client_suffix`.
+    #[error("client ID '{client_id}' is already in use at '{client_path}'")]
+    ClientIdInUse {
+        client_id: String,
+        client_path: String,
+    },
+
     /// Timeout waiting for server response broadcast.
     #[error("timeout waiting for server response")]
     Timeout(#[from] tokio::time::error::Elapsed),
@@ -27,6 +41,106 @@ pub enum RpcClientError {
     /// The RPC connection was closed.
     #[error("RPC connection closed")]
     ConnectionClosed,
+
+    /// The server refused the connection before handling it.
+    #[error("connection rejected: {reason}")]
+    Rejected { reason: RejectReason },
+
+    /// The server withdrew its response broadcast while the connection was open.
+    ///
+    /// Unlike the stream ending normally, this means the server went away and
+    /// the request may be worth retrying once it is announced again.
+    #[error("server disconnected")]
+    ServerDisconnected,
+
+    /// The client configuration is invalid.
+    #[error("invalid client config: {0}")]
+    Config(String),
+
+    /// Failed to send a request.
+    #[error(transparent)]
+    Send(#[from] RpcSendError),
+
+    /// The connection failed after it was established.
+    #[error(transparent)]
+    Wire(#[from] RpcWireError),
+}
+
+/// Why a server refused a connection, see [`RpcClientError::Rejected`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RejectReason {
+    /// The client already has a session on this path, typically one that has not been torn
+    /// down yet after a reconnect.
+    Duplicate,
+
+    /// The server is at its session limit.
+    Overloaded,
+
+    /// The server does not let this client connect.
+    Unauthorized,
+
+    /// The server has no handler for the path.
+    NoHandler,
+
+    /// The server is in maintenance and not taking new clients for now.
+    Maintenance,
+}
+
+/// How much longer than the retry policy's delay to wait after [`RejectReason::Overloaded`].
+const OVERLOADED_BACKOFF_FACTOR: u32 = 4;
+
+impl RejectReason {
+    /// The reason behind `err`, if it is a rejection. `space` is the code space of the
+    /// connection, for rejections explained in an error frame.
+    pub fn from_wire(err: &RpcWireError, space: CodeSpace) -> Option<Self> {
+        match err {
+            RpcWireError::SessionAlreadyActive => Some(Self::Duplicate),
+            RpcWireError::Overloaded => Some(Self::Overloaded),
+            RpcWireError::Unauthorized => Some(Self::Unauthorized),
+            RpcWireError::NoHandler => Some(Self::NoHandler),
+            RpcWireError::Maintenance => Some(Self::Maintenance),
+            RpcWireError::Server { code, .. } => {
+                Self::from_wire(&RpcWireError::from_code_in(*code, space), space)
+            }
+            _ => None,
+        }
+    }
+
+    /// How long to wait before reconnect attempt number `attempt` (starting at 0), or `None`
+    /// if reconnecting is pointless or the retry budget is exhausted.
+    ///
+    /// A duplicate session clears once the server tears down the old one, and maintenance ends
+    /// when the operator says so, so both wait the policy's delay. Maintenance may take longer
+    /// than any retry budget, so it is retried past `max_retries`. An overloaded server waits
+    /// four times as long, even beyond the policy's `max_backoff`. An unauthorized client or a
+    /// missing handler does not reconnect.
+    pub fn backoff(&self, policy: &RetryPolicy, attempt: u32) -> Option<Duration> {
+        match self {
+            Self::Duplicate => policy.backoff(attempt),
+            Self::Maintenance => {
+                let mut unlimited = policy.clone();
+                unlimited.max_retries = None;
+                unlimited.backoff(attempt)
+            }
+            Self::Overloaded => policy
+                .backoff(attempt)
+                .map(|delay| delay.saturating_mul(OVERLOADED_BACKOFF_FACTOR)),
+            Self::Unauthorized | Self::NoHandler => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Duplicate => "session already active",
+            Self::Overloaded => "server overloaded",
+            Self::Unauthorized => "unauthorized",
+            Self::NoHandler => "no handler registered",
+            Self::Maintenance => "server in maintenance",
+        })
+    }
 }
 
 /// Errors that can occur while running the RPC server router.
@@ -55,6 +169,36 @@ pub enum RpcServerError {
     /// Authorization failed for the requested operation.
     #[error("unauthorized: {0}")]
     Unauthorized(String),
+
+    /// The router is at its session limit.
+    #[error("too many active sessions, the limit is {max_sessions}")]
+    Overloaded { max_sessions: usize },
+
+    /// The router is in maintenance and not accepting new clients.
+    #[error("not accepting new sessions during maintenance")]
+    Maintenance,
+
+    /// The router configuration is invalid.
+    #[error("invalid router config: {0}")]
+    Config(String),
+}
+
+/// Errors that can occur while opening a sibling track with
+/// [`RpcOutbound::track`](crate::RpcOutbound::track).
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RpcTrackError {
+    /// The outbound is not attached to a broadcast.
+    #[error("outbound has no broadcast to open tracks on")]
+    NoBroadcast,
+
+    /// The broadcast has been dropped, for example because its session ended.
+    #[error("the response broadcast has closed")]
+    BroadcastClosed,
+
+    /// A track of this name is already open on the broadcast.
+    #[error("track '{0}' is already open on the broadcast")]
+    DuplicateTrack(String),
 }
 
 /// Errors that can occur while encoding outbound messages.
@@ -64,6 +208,18 @@ pub enum RpcSendError {
     /// Failed to encode a protobuf message.
     #[error("protobuf encode error")]
     Encode(#[from] prost::EncodeError),
+
+    /// A non-protobuf codec failed to encode a message.
+    #[error("encode error: {0}")]
+    Codec(String),
+
+    /// The server's response broadcast is gone, so nothing sent would be read.
+    #[error("connection closed by the server")]
+    Closed,
+
+    /// A subscriber was still reading when a confirmed send gave up waiting.
+    #[error("delivery not confirmed within {0:?}")]
+    Unconfirmed(Duration),
 }
 
 /// Errors that can occur on the wire after a connection is established.
@@ -90,6 +246,56 @@ pub enum RpcWireError {
     #[error("internal error")]
     Internal,
 
+    /// The session was closed because no requests arrived within the idle timeout.
+    #[error("session idle timeout")]
+    IdleTimeout,
+
+    /// A frame exceeded the configured maximum frame size.
+    #[error("frame too large")]
+    FrameTooLarge,
+
+    /// The peer compressed a frame with a different codec than expected.
+    #[error("compression mismatch")]
+    CompressionMismatch,
+
+    /// The peer encoded a frame with a different message codec than expected.
+    #[error("codec mismatch")]
+    CodecMismatch,
+
+    /// No response arrived within the client's first response timeout.
+    #[error("deadline exceeded waiting for the first response")]
+    Deadline,
+
+    /// The server is at its session limit.
+    #[error("server overloaded")]
+    Overloaded,
+
+    /// The server does not let this client connect.
+    #[error("unauthorized")]
+    Unauthorized,
+
+    /// Nothing, not even a keepalive frame, arrived from the peer within the keepalive
+    /// timeout.
+    #[error("keepalive timeout")]
+    KeepaliveTimeout,
+
+    /// The server is in maintenance and not accepting new clients.
+    #[error("server in maintenance")]
+    Maintenance,
+
+    /// The server terminated the session, e.g. at an operator's request.
+    #[error("session terminated by the server")]
+    Terminated,
+
+    /// The server sent an error frame explaining why it is closing the connection.
+    #[error("server error {code}: {message}")]
+    Server { code: u32, message: String },
+
+    /// The handler's response stream ended with a gRPC error status, sent by a router with
+    /// status trailers enabled.
+    #[error("gRPC status {code:?}: {message}")]
+    Status { code: tonic::Code, message: String },
+
     /// An error from the underlying MoQ transport.
     #[error("MoQ transport error")]
     Transport(#[source] moq_lite::Error),
@@ -99,41 +305,163 @@ pub enum RpcWireError {
     Unknown(u32),
 }
 
+/// MoQ application error codes used by [`RpcWireError`].
+///
+/// These are the values carried by `moq_lite::Error::App` when a track is
+/// aborted, so integrators can match on a raw code without building an
+/// `RpcWireError` first. They are stable across releases.
+pub mod codes {
+    pub const NO_HANDLER: u32 = 1;
+    pub const SESSION_ALREADY_ACTIVE: u32 = 2;
+    pub const DECODE: u32 = 3;
+    pub const GRPC: u32 = 4;
+    pub const INTERNAL: u32 = 5;
+    pub const IDLE_TIMEOUT: u32 = 6;
+    pub const FRAME_TOO_LARGE: u32 = 7;
+    pub const COMPRESSION_MISMATCH: u32 = 8;
+    pub const CODEC_MISMATCH: u32 = 9;
+    pub const DEADLINE: u32 = 10;
+    pub const OVERLOADED: u32 = 11;
+    pub const UNAUTHORIZED: u32 = 12;
+    pub const KEEPALIVE_TIMEOUT: u32 = 13;
+    pub const MAINTENANCE: u32 = 14;
+    pub const TERMINATED: u32 = 15;
+
+    /// Every code that [`RpcWireError::from_code`](super::RpcWireError::from_code)
+    /// maps to a dedicated variant.
+    pub const KNOWN: &[u32] = &[
+        NO_HANDLER,
+        SESSION_ALREADY_ACTIVE,
+        DECODE,
+        GRPC,
+        INTERNAL,
+        IDLE_TIMEOUT,
+        FRAME_TOO_LARGE,
+        COMPRESSION_MISMATCH,
+        CODEC_MISMATCH,
+        DEADLINE,
+        OVERLOADED,
+        UNAUTHORIZED,
+        KEEPALIVE_TIMEOUT,
+        MAINTENANCE,
+        TERMINATED,
+    ];
+}
+
+/// Where the RPC layer's codes sit among the MoQ application error codes on a relay.
+///
+/// The codes in [`codes`] are relative to `base`, so with a base of 1000 a decode error is
+/// sent as 1003. A non-zero base keeps them clear of codes other protocols sharing the relay
+/// abort tracks with. Codes outside the space decode to [`RpcWireError::Unknown`].
+///
+/// The router and its clients must use the same code space.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CodeSpace {
+    base: u32,
+}
+
+impl CodeSpace {
+    pub const fn new(base: u32) -> Self {
+        Self { base }
+    }
+
+    pub fn base(&self) -> u32 {
+        self.base
+    }
+}
+
 impl RpcWireError {
-    pub const CODE_NO_HANDLER: u32 = 1;
-    pub const CODE_SESSION_ALREADY_ACTIVE: u32 = 2;
-    pub const CODE_DECODE: u32 = 3;
-    pub const CODE_GRPC: u32 = 4;
-    pub const CODE_INTERNAL: u32 = 5;
+    pub const CODE_NO_HANDLER: u32 = codes::NO_HANDLER;
+    pub const CODE_SESSION_ALREADY_ACTIVE: u32 = codes::SESSION_ALREADY_ACTIVE;
+    pub const CODE_DECODE: u32 = codes::DECODE;
+    pub const CODE_GRPC: u32 = codes::GRPC;
+    pub const CODE_INTERNAL: u32 = codes::INTERNAL;
+    pub const CODE_IDLE_TIMEOUT: u32 = codes::IDLE_TIMEOUT;
+    pub const CODE_FRAME_TOO_LARGE: u32 = codes::FRAME_TOO_LARGE;
+    pub const CODE_COMPRESSION_MISMATCH: u32 = codes::COMPRESSION_MISMATCH;
+    pub const CODE_CODEC_MISMATCH: u32 = codes::CODEC_MISMATCH;
+    pub const CODE_DEADLINE: u32 = codes::DEADLINE;
+    pub const CODE_OVERLOADED: u32 = codes::OVERLOADED;
+    pub const CODE_UNAUTHORIZED: u32 = codes::UNAUTHORIZED;
+    pub const CODE_KEEPALIVE_TIMEOUT: u32 = codes::KEEPALIVE_TIMEOUT;
+    pub const CODE_MAINTENANCE: u32 = codes::MAINTENANCE;
+    pub const CODE_TERMINATED: u32 = codes::TERMINATED;
+
+    /// Whether `code` maps to a dedicated variant rather than `Unknown`.
+    pub fn is_known_code(code: u32) -> bool {
+        codes::KNOWN.contains(&code)
+    }
 
     pub fn transport_with(err: moq_lite::Error) -> Self {
+        Self::transport_in(err, CodeSpace::default())
+    }
+
+    /// Like [`transport_with`](Self::transport_with), for app codes in `space`.
+    pub fn transport_in(err: moq_lite::Error, space: CodeSpace) -> Self {
         match err {
-            moq_lite::Error::App(code) => RpcWireError::from_code(code),
+            moq_lite::Error::App(code) => RpcWireError::from_code_in(code, space),
             other => RpcWireError::Transport(other),
         }
     }
 
     pub fn to_code(&self) -> u32 {
-        match self {
+        self.to_code_in(CodeSpace::default())
+    }
+
+    /// The app code for this error in `space`.
+    ///
+    /// Server, transport and unknown errors keep the code they were received with.
+    pub fn to_code_in(&self, space: CodeSpace) -> u32 {
+        let relative = match self {
             RpcWireError::NoHandler => Self::CODE_NO_HANDLER,
             RpcWireError::SessionAlreadyActive => Self::CODE_SESSION_ALREADY_ACTIVE,
             RpcWireError::Decode => Self::CODE_DECODE,
-            RpcWireError::Grpc => Self::CODE_GRPC,
+            RpcWireError::Grpc | RpcWireError::Status { .. } => Self::CODE_GRPC,
             RpcWireError::Internal => Self::CODE_INTERNAL,
-            RpcWireError::Transport(e) => e.to_code(),
-            RpcWireError::Unknown(code) => *code,
-        }
+            RpcWireError::IdleTimeout => Self::CODE_IDLE_TIMEOUT,
+            RpcWireError::FrameTooLarge => Self::CODE_FRAME_TOO_LARGE,
+            RpcWireError::CompressionMismatch => Self::CODE_COMPRESSION_MISMATCH,
+            RpcWireError::CodecMismatch => Self::CODE_CODEC_MISMATCH,
+            RpcWireError::Deadline => Self::CODE_DEADLINE,
+            RpcWireError::Overloaded => Self::CODE_OVERLOADED,
+            RpcWireError::Unauthorized => Self::CODE_UNAUTHORIZED,
+            RpcWireError::KeepaliveTimeout => Self::CODE_KEEPALIVE_TIMEOUT,
+            RpcWireError::Maintenance => Self::CODE_MAINTENANCE,
+            RpcWireError::Terminated => Self::CODE_TERMINATED,
+            RpcWireError::Server { code, .. } => return *code,
+            RpcWireError::Transport(e) => return e.to_code(),
+            RpcWireError::Unknown(code) => return *code,
+        };
+        space.base + relative
     }
 
     pub fn from_code(code: u32) -> Self {
-        match code {
+        Self::from_code_in(code, CodeSpace::default())
+    }
+
+    /// The error an app code in `space` stands for.
+    pub fn from_code_in(code: u32, space: CodeSpace) -> Self {
+        let Some(relative) = code.checked_sub(space.base) else {
+            return RpcWireError::Unknown(code);
+        };
+        match relative {
             Self::CODE_NO_HANDLER => RpcWireError::NoHandler,
             Self::CODE_SESSION_ALREADY_ACTIVE => RpcWireError::SessionAlreadyActive,
             Self::CODE_DECODE => RpcWireError::Decode,
             Self::CODE_GRPC => RpcWireError::Grpc,
             Self::CODE_INTERNAL => RpcWireError::Internal,
+            Self::CODE_IDLE_TIMEOUT => RpcWireError::IdleTimeout,
+            Self::CODE_FRAME_TOO_LARGE => RpcWireError::FrameTooLarge,
+            Self::CODE_COMPRESSION_MISMATCH => RpcWireError::CompressionMismatch,
+            Self::CODE_CODEC_MISMATCH => RpcWireError::CodecMismatch,
+            Self::CODE_DEADLINE => RpcWireError::Deadline,
+            Self::CODE_OVERLOADED => RpcWireError::Overloaded,
+            Self::CODE_UNAUTHORIZED => RpcWireError::Unauthorized,
+            Self::CODE_KEEPALIVE_TIMEOUT => RpcWireError::KeepaliveTimeout,
+            Self::CODE_MAINTENANCE => RpcWireError::Maintenance,
+            Self::CODE_TERMINATED => RpcWireError::Terminated,
             // TODO: Go implement from_code in the moq-lite codebase
-            other => RpcWireError::Unknown(other),
+            _ => RpcWireError::Unknown(code),
         }
     }
 }
@@ -143,3 +471,172 @@ impl From<moq_lite::Error> for RpcWireError {
         RpcWireError::transport_with(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every variant that round-trips through its code. The match makes adding
+    /// a variant without updating this list a compile error.
+    fn coded_variants() -> Vec<RpcWireError> {
+        let variants = vec![
+            RpcWireError::NoHandler,
+            RpcWireError::SessionAlreadyActive,
+            RpcWireError::Decode,
+            RpcWireError::Grpc,
+            RpcWireError::Internal,
+            RpcWireError::IdleTimeout,
+            RpcWireError::FrameTooLarge,
+            RpcWireError::CompressionMismatch,
+            RpcWireError::CodecMismatch,
+            RpcWireError::Deadline,
+            RpcWireError::Overloaded,
+            RpcWireError::Unauthorized,
+            RpcWireError::KeepaliveTimeout,
+            RpcWireError::Maintenance,
+            RpcWireError::Terminated,
+        ];
+        for variant in &variants {
+            match variant {
+                RpcWireError::NoHandler
+                | RpcWireError::SessionAlreadyActive
+                | RpcWireError::Decode
+                | RpcWireError::Grpc
+                | RpcWireError::Internal
+                | RpcWireError::IdleTimeout
+                | RpcWireError::FrameTooLarge
+                | RpcWireError::CompressionMismatch
+                | RpcWireError::CodecMismatch
+                | RpcWireError::Deadline
+                | RpcWireError::Overloaded
+                | RpcWireError::Unauthorized
+                | RpcWireError::KeepaliveTimeout
+                | RpcWireError::Maintenance
+                | RpcWireError::Terminated => {}
+                RpcWireError::Server { .. }
+                | RpcWireError::Status { .. }
+                | RpcWireError::Transport(_)
+                | RpcWireError::Unknown(_) => unreachable!(),
+            }
+        }
+        variants
+    }
+
+    #[test]
+    fn test_codes_round_trip() {
+        let variants = coded_variants();
+        assert_eq!(variants.len(), codes::KNOWN.len());
+
+        for variant in variants {
+            let code = variant.to_code();
+            assert!(RpcWireError::is_known_code(code), "{variant:?}");
+
+            let decoded = RpcWireError::from_code(code);
+            assert_eq!(
+                std::mem::discriminant(&decoded),
+                std::mem::discriminant(&variant)
+            );
+            assert_eq!(decoded.to_code(), code);
+        }
+    }
+
+    #[test]
+    fn test_codes_round_trip_with_base() {
+        let space = CodeSpace::new(1000);
+
+        for variant in coded_variants() {
+            let code = variant.to_code_in(space);
+            assert_eq!(code, 1000 + variant.to_code(), "{variant:?}");
+
+            let decoded = RpcWireError::from_code_in(code, space);
+            assert_eq!(
+                std::mem::discriminant(&decoded),
+                std::mem::discriminant(&variant)
+            );
+            assert_eq!(decoded.to_code_in(space), code);
+        }
+
+        // The unshifted codes belong to someone else
+        assert!(matches!(
+            RpcWireError::from_code_in(codes::DECODE, space),
+            RpcWireError::Unknown(codes::DECODE)
+        ));
+        assert!(matches!(
+            RpcWireError::transport_in(moq_lite::Error::App(1003), space),
+            RpcWireError::Decode
+        ));
+    }
+
+    #[test]
+    fn test_unknown_codes() {
+        assert!(!RpcWireError::is_known_code(0));
+        assert!(!RpcWireError::is_known_code(1000));
+        assert!(matches!(
+            RpcWireError::from_code(1000),
+            RpcWireError::Unknown(1000)
+        ));
+        assert_eq!(RpcWireError::Unknown(1000).to_code(), 1000);
+    }
+
+    #[test]
+    fn test_server_error_keeps_code() {
+        let err = RpcWireError::Server {
+            code: codes::INTERNAL,
+            message: "boom".to_string(),
+        };
+        assert_eq!(err.to_code(), codes::INTERNAL);
+    }
+
+    #[test]
+    fn test_reject_reasons() {
+        let space = CodeSpace::new(1000);
+        for (err, reason) in [
+            (RpcWireError::SessionAlreadyActive, RejectReason::Duplicate),
+            (RpcWireError::Overloaded, RejectReason::Overloaded),
+            (RpcWireError::Unauthorized, RejectReason::Unauthorized),
+            (RpcWireError::NoHandler, RejectReason::NoHandler),
+            (RpcWireError::Maintenance, RejectReason::Maintenance),
+        ] {
+            assert_eq!(RejectReason::from_wire(&err, space), Some(reason));
+            let explained = RpcWireError::Server {
+                code: err.to_code_in(space),
+                message: "explained".to_string(),
+            };
+            assert_eq!(RejectReason::from_wire(&explained, space), Some(reason));
+        }
+        assert_eq!(RejectReason::from_wire(&RpcWireError::Grpc, space), None);
+    }
+
+    #[test]
+    fn test_overloaded_backs_off_longer_than_duplicate() {
+        let policy = RetryPolicy::default();
+        for attempt in 0..8 {
+            let duplicate = RejectReason::Duplicate.backoff(&policy, attempt).unwrap();
+            let overloaded = RejectReason::Overloaded.backoff(&policy, attempt).unwrap();
+            assert!(overloaded > duplicate, "attempt {attempt}");
+        }
+        assert_eq!(RejectReason::Unauthorized.backoff(&policy, 0), None);
+        assert_eq!(RejectReason::NoHandler.backoff(&policy, 0), None);
+    }
+
+    #[test]
+    fn test_maintenance_outlasts_retry_budget() {
+        let policy = RetryPolicy::builder().max_retries(2).build();
+        assert_eq!(RejectReason::Duplicate.backoff(&policy, 2), None);
+        assert_eq!(
+            RejectReason::Maintenance.backoff(&policy, 2),
+            Some(Duration::from_millis(400))
+        );
+    }
+
+    #[test]
+    fn test_codes_match_without_enum() {
+        let describe = |code| match code {
+            codes::IDLE_TIMEOUT => "idle",
+            RpcWireError::CODE_DECODE => "decode",
+            _ => "other",
+        };
+        assert_eq!(describe(RpcWireError::IdleTimeout.to_code()), "idle");
+        assert_eq!(describe(RpcWireError::Decode.to_code()), "decode");
+    }
+}
@@ -0,0 +1,115 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use prost::Message;
+
+use crate::error::RpcWireError;
+
+/// Tag byte reserved for error frames. See `metadata::METADATA_TAG`.
+pub(crate) const ERROR_TAG: u8 = 0xfe;
+
+/// Tag byte reserved for the trailer ending a response stream with an OK status.
+pub(crate) const END_TAG: u8 = 0xfd;
+
+/// Tag byte reserved for keepalive frames, sent on an otherwise idle track.
+pub(crate) const KEEPALIVE_TAG: u8 = 0xfc;
+
+/// The frame sent in place of a message when nothing has been sent for a while.
+pub(crate) fn keepalive_frame() -> Bytes {
+    Bytes::from_static(&[KEEPALIVE_TAG])
+}
+
+/// Whether `frame` is a keepalive sent by [`keepalive_frame`].
+pub(crate) fn is_keepalive_frame(frame: &[u8]) -> bool {
+    frame == [KEEPALIVE_TAG]
+}
+
+/// The trailer frame sent after the last response of a stream that ended with an OK status.
+pub(crate) fn end_frame() -> Bytes {
+    Bytes::from_static(&[END_TAG])
+}
+
+/// Whether `frame` is the trailer sent by [`end_frame`].
+pub(crate) fn is_end_frame(frame: &[u8]) -> bool {
+    frame == [END_TAG]
+}
+
+/// A structured error sent by the server ahead of aborting the response track.
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct RpcError {
+    #[prost(uint32, tag = "1")]
+    pub code: u32,
+    #[prost(string, tag = "2")]
+    pub message: String,
+    /// The gRPC status code the handler's response stream ended with, if it ended with one.
+    #[prost(int32, optional, tag = "3")]
+    pub grpc_code: Option<i32>,
+}
+
+impl RpcError {
+    /// Whether `frame` is an error frame rather than a message.
+    pub(crate) fn is_error_frame(frame: &[u8]) -> bool {
+        frame.first() == Some(&ERROR_TAG)
+    }
+
+    /// Encode as a tagged error frame.
+    pub(crate) fn to_frame(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.encoded_len() + 1);
+        buf.put_u8(ERROR_TAG);
+        self.encode(&mut buf)
+            .expect("BytesMut grows to fit the message");
+        buf.freeze()
+    }
+
+    /// Decode a frame for which [`is_error_frame`](Self::is_error_frame) holds.
+    pub(crate) fn from_frame(mut frame: Bytes) -> Result<Self, RpcWireError> {
+        let payload = frame.split_off(1);
+        Self::decode(payload).map_err(|_| RpcWireError::Decode)
+    }
+}
+
+impl From<RpcError> for RpcWireError {
+    fn from(err: RpcError) -> Self {
+        match err.grpc_code {
+            Some(grpc_code) => RpcWireError::Status {
+                code: tonic::Code::from_i32(grpc_code),
+                message: err.message,
+            },
+            None => RpcWireError::Server {
+                code: err.code,
+                message: err.message,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_round_trip() {
+        let err = RpcError {
+            code: RpcWireError::CODE_GRPC,
+            message: "backend unavailable".to_string(),
+            grpc_code: None,
+        };
+
+        let frame = err.to_frame();
+        assert!(RpcError::is_error_frame(&frame));
+        assert!(!is_end_frame(&frame));
+        assert_eq!(RpcError::from_frame(frame).unwrap(), err);
+    }
+
+    #[test]
+    fn test_grpc_code_becomes_status() {
+        let err = RpcError {
+            code: RpcWireError::CODE_GRPC,
+            message: "drone not found".to_string(),
+            grpc_code: Some(tonic::Code::NotFound as i32),
+        };
+
+        assert!(matches!(
+            RpcWireError::from(err),
+            RpcWireError::Status { code: tonic::Code::NotFound, message } if message == "drone not found"
+        ));
+    }
+}
@@ -12,11 +12,11 @@
 //! ```ignore
 //! use rpcmoq_lite::{RpcRouter, RpcRouterConfig, DecodedInbound};
 //!
-//! let mut router = RpcRouter::new(consumer, producer, RpcRouterConfig::builder().build());
+//! let mut router = RpcRouter::new(consumer, producer, RpcRouterConfig::builder().build())?;
 //!
 //! router.register::<Request, Response, _, _, _>(
 //!     "package.Service/Method",
-//!     |client_id, inbound| async move {
+//!     |_session, inbound| async move {
 //!         let mut client = GrpcServiceClient::connect(addr).await?;
 //!         let response = client.method(inbound.into_ok_stream()).await?;
 //!         Ok(response.into_inner())
@@ -73,21 +73,56 @@
 //! Example without prefixes:
 //! - Client announces: `drone-123/drone.EchoService/Echo`
 //! - Server responds: `drone-123/drone.EchoService/Echo`
+//!
+//! A method may be versioned by appending `@{version}`, e.g. `drone.EchoService/Echo@v2`.
+//! The router serves it with the handler registered for that version, falling back to the
+//! unversioned handler.
 
 // Shared modules at root level
+mod codec;
+mod compression;
 mod connection;
 mod error;
+mod error_frame;
+mod metadata;
 mod path;
+mod reflection;
+mod retry;
+mod stats;
+mod trace;
 
 // Submodules for client and server
 pub mod client;
 pub mod server;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test;
 
 // Re-export shared types
-pub use connection::{RpcInbound, RpcOutbound};
-pub use error::{RpcClientError, RpcPathError, RpcSendError, RpcServerError, RpcWireError};
+#[cfg(feature = "json")]
+pub use codec::JsonCodec;
+pub use codec::{MessageCodec, ProstCodec};
+pub use compression::Compression;
+pub use connection::{
+    InboundBufferPolicy, InboundRatePolicy, OutboundGroup, RpcInbound, RpcOutbound,
+};
+pub use error::{
+    CodeSpace, RejectReason, RpcClientError, RpcPathError, RpcSendError, RpcServerError,
+    RpcTrackError, RpcWireError, codes,
+};
 pub use path::{GrpcPath, RpcRequestPath};
+pub use reflection::{ListMethodsRequest, ListMethodsResponse, MethodDescriptor, REFLECTION_PATH};
+pub use retry::RetryPolicy;
+pub use stats::{RouteStats, RouterStats, SizeStats};
+pub use trace::{TraceContext, TracePropagator, set_trace_propagator};
 
 // Convenience re-exports for common use
-pub use client::{RpcClient, RpcClientConfig, RpcConnection, RpcReceiver, RpcSender};
-pub use server::{DecodedInbound, RpcRouter, RpcRouterConfig, SessionGuard, SessionKey, SessionMap};
+pub use client::{
+    ConnectInfo, MultiTrackConnection, RpcClient, RpcClientConfig, RpcConnection, RpcReceiver,
+    RpcSender,
+};
+pub use server::{
+    DecodeErrorPolicy, DecodedInbound, FallibleDecodedInbound, HEALTH_CHECK_PATH,
+    HealthCheckRequest, HealthCheckResponse, RawDecodedInbound, ResponsePathConflict, RpcRouter,
+    RpcRouterConfig, RpcRouterHandle, ServingStatus, SessionContext, SessionEndReason,
+    SessionGuard, SessionKey, SessionMap, SessionObserver,
+};
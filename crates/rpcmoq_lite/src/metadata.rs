@@ -0,0 +1,72 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use prost::Message;
+use std::collections::HashMap;
+
+use crate::error::RpcWireError;
+
+/// Tag byte reserved for metadata frames.
+///
+/// Message frames carry a compression tag in the low four bits, which never
+/// reaches `0xc`, so the reserved `0xfc` to `0xff` tags can't be confused with them.
+pub(crate) const METADATA_TAG: u8 = 0xff;
+
+/// Key/value pairs sent by the client ahead of its first message.
+///
+/// The frame is prepended to the first group the client writes, so it arrives
+/// with (and is never skipped independently of) the first request.
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct RpcMetadata {
+    #[prost(map = "string, string", tag = "1")]
+    pub entries: HashMap<String, String>,
+}
+
+impl RpcMetadata {
+    /// Whether `frame` is a metadata frame rather than a message.
+    pub(crate) fn is_metadata_frame(frame: &[u8]) -> bool {
+        frame.first() == Some(&METADATA_TAG)
+    }
+
+    /// Encode as a tagged metadata frame.
+    pub(crate) fn to_frame(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.encoded_len() + 1);
+        buf.put_u8(METADATA_TAG);
+        self.encode(&mut buf)
+            .expect("BytesMut grows to fit the message");
+        buf.freeze()
+    }
+
+    /// Decode a frame for which [`is_metadata_frame`](Self::is_metadata_frame) holds.
+    pub(crate) fn from_frame(mut frame: Bytes) -> Result<Self, RpcWireError> {
+        let payload = frame.split_off(1);
+        Self::decode(payload).map_err(|_| RpcWireError::Decode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_round_trip() {
+        let mut metadata = RpcMetadata::default();
+        metadata
+            .entries
+            .insert("traceparent".to_string(), "00-abc".to_string());
+
+        let frame = metadata.to_frame();
+        assert!(RpcMetadata::is_metadata_frame(&frame));
+        assert_eq!(RpcMetadata::from_frame(frame).unwrap(), metadata);
+    }
+
+    #[test]
+    fn test_message_frames_are_not_metadata() {
+        for compression in [
+            crate::Compression::None,
+            crate::Compression::Gzip,
+            crate::Compression::Zstd,
+        ] {
+            let frame = compression.encode_tagged(15, b"payload");
+            assert!(!RpcMetadata::is_metadata_frame(&frame));
+        }
+    }
+}
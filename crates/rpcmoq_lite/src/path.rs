@@ -56,23 +56,26 @@ impl RpcRequestPath {
     }
 }
 
-/// A parsed gRPC method path: `{package}.{service}/{method}`
+/// A parsed gRPC method path: `{package}.{service}/{method}`, optionally versioned as
+/// `{package}.{service}/{method}@{version}`
 ///
-/// Example: `drone.EchoService/Echo`
+/// Example: `drone.EchoService/Echo@v2`
 /// - `package`: `drone`
 /// - `service`: `EchoService`
 /// - `method`: `Echo`
+/// - `version`: `Some("v2")`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct GrpcPath {
     pub package: String,
     pub service: String,
     pub method: String,
+    pub(crate) version: Option<String>,
 }
 
 impl GrpcPath {
     /// Parse a gRPC path string.
     ///
-    /// Expected format: `{package}.{service}/{method}`
+    /// Expected format: `{package}.{service}/{method}`, with an optional `@{version}` suffix.
     pub fn parse(path: &str) -> Result<Self, RpcPathError> {
         let path = path.strip_prefix('/').unwrap_or(path);
 
@@ -86,30 +89,68 @@ impl GrpcPath {
             ))
         })?;
 
+        let (method, version) = match method.split_once('@') {
+            Some((method, version)) => (method, Some(version)),
+            None => (method, None),
+        };
+
         if package.is_empty() || service.is_empty() || method.is_empty() {
             return Err(RpcPathError::Invalid(format!(
                 "package, service, and method must all be non-empty: '{path}'"
             )));
         }
+        if version.is_some_and(|version| version.is_empty() || version.contains('@')) {
+            return Err(RpcPathError::Invalid(format!(
+                "version after '@' must be non-empty: '{path}'"
+            )));
+        }
 
         Ok(GrpcPath {
             package: package.to_owned(),
             service: service.to_owned(),
             method: method.to_owned(),
+            version: version.map(str::to_owned),
         })
     }
 
+    /// Returns the version after `@`, if the path is versioned
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Returns the path with its version set to `version`
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
     /// Returns the full service name: `{package}.{service}`
     pub fn full_service(&self) -> String {
         format!("{}.{}", self.package, self.service)
     }
 
-    /// Returns the full gRPC path: `{package}.{service}/{method}`
+    /// Returns the full gRPC path: `{package}.{service}/{method}`, followed by `@{version}` if
+    /// the path is versioned
     pub fn full_path(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}@{version}", self.unversioned_path()),
+            None => self.unversioned_path(),
+        }
+    }
+
+    /// Returns the gRPC path without its version: `{package}.{service}/{method}`
+    pub fn unversioned_path(&self) -> String {
         format!("{}.{}/{}", self.package, self.service, self.method)
     }
 }
 
+/// `grpc_path` without its `@{version}` suffix, if it has one.
+pub(crate) fn strip_version(grpc_path: &str) -> &str {
+    grpc_path
+        .split_once('@')
+        .map_or(grpc_path, |(unversioned, _)| unversioned)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -168,6 +209,34 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_grpc_path_with_version() {
+        let path = GrpcPath::parse("drone.EchoService/Echo@v2").unwrap();
+        assert_eq!(path.method, "Echo");
+        assert_eq!(path.version(), Some("v2"));
+        assert_eq!(path.full_path(), "drone.EchoService/Echo@v2");
+        assert_eq!(path.unversioned_path(), "drone.EchoService/Echo");
+
+        let unversioned = GrpcPath::parse("drone.EchoService/Echo").unwrap();
+        assert_eq!(unversioned.version(), None);
+        assert_eq!(unversioned.full_path(), "drone.EchoService/Echo");
+        assert_eq!(unversioned.with_version("v3").full_path(), "drone.EchoService/Echo@v3");
+    }
+
+    #[test]
+    fn test_rpc_request_path_with_version() {
+        let path = RpcRequestPath::parse("drone-123/drone.EchoService/Echo@v2").unwrap();
+        assert_eq!(path.client_id, "drone-123");
+        assert_eq!(path.grpc_path.version(), Some("v2"));
+    }
+
+    #[test]
+    fn test_grpc_path_empty_version() {
+        assert!(GrpcPath::parse("drone.EchoService/Echo@").is_err());
+        assert!(GrpcPath::parse("drone.EchoService/@v2").is_err());
+        assert!(GrpcPath::parse("drone.EchoService/Echo@v2@v3").is_err());
+    }
+
     #[test]
     fn test_grpc_path_missing_package() {
         let result = GrpcPath::parse("EchoService/Echo");
@@ -0,0 +1,91 @@
+use crate::path::GrpcPath;
+
+/// The reserved path of the built-in reflection service.
+pub const REFLECTION_PATH: &str = "rpcmoq.Reflection/ListMethods";
+
+/// Asks the router for the gRPC paths it has handlers for.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListMethodsRequest {}
+
+/// The gRPC paths a router has handlers for, sorted by full path.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListMethodsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub methods: Vec<MethodDescriptor>,
+}
+
+/// A registered gRPC path and its breakdown.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MethodDescriptor {
+    /// The full path, e.g. `drone.EchoService/Echo`.
+    #[prost(string, tag = "1")]
+    pub full_path: String,
+    #[prost(string, tag = "2")]
+    pub package: String,
+    #[prost(string, tag = "3")]
+    pub service: String,
+    #[prost(string, tag = "4")]
+    pub method: String,
+    /// The version after `@` in the full path, if any.
+    #[prost(string, optional, tag = "5")]
+    pub version: Option<String>,
+}
+
+impl From<GrpcPath> for MethodDescriptor {
+    fn from(path: GrpcPath) -> Self {
+        Self {
+            full_path: path.full_path(),
+            package: path.package,
+            service: path.service,
+            method: path.method,
+            version: path.version,
+        }
+    }
+}
+
+impl From<MethodDescriptor> for GrpcPath {
+    fn from(descriptor: MethodDescriptor) -> Self {
+        Self {
+            package: descriptor.package,
+            service: descriptor.service,
+            method: descriptor.method,
+            version: descriptor.version,
+        }
+    }
+}
+
+impl ListMethodsResponse {
+    /// Describe every path in `grpc_paths` that parses as a gRPC path.
+    pub(crate) fn from_paths<'a>(grpc_paths: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut methods: Vec<MethodDescriptor> = grpc_paths
+            .into_iter()
+            .filter_map(|path| GrpcPath::parse(path).ok())
+            .map(MethodDescriptor::from)
+            .collect();
+        methods.sort_by(|a, b| a.full_path.cmp(&b.full_path));
+        Self { methods }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_paths_sorts_and_breaks_down() {
+        let paths = [
+            "drone.EchoService/Echo".to_string(),
+            "drone.DroneService/DroneSession".to_string(),
+        ];
+        let response = ListMethodsResponse::from_paths(&paths);
+
+        let full_paths: Vec<_> = response.methods.iter().map(|m| &m.full_path).collect();
+        assert_eq!(
+            full_paths,
+            ["drone.DroneService/DroneSession", "drone.EchoService/Echo"]
+        );
+        assert_eq!(response.methods[1].package, "drone");
+        assert_eq!(response.methods[1].service, "EchoService");
+        assert_eq!(response.methods[1].method, "Echo");
+    }
+}
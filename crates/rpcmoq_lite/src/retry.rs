@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use bon::Builder;
+
+/// Exponential backoff policy for retrying transient MoQ failures.
+#[derive(Debug, Clone, Builder)]
+pub struct RetryPolicy {
+    /// Delay before the first retry.
+    #[builder(default = Duration::from_millis(100))]
+    pub initial_backoff: Duration,
+
+    /// Upper bound on the delay between retries.
+    #[builder(default = Duration::from_secs(5))]
+    pub max_backoff: Duration,
+
+    /// How much longer each delay is than the one before it, see
+    /// [`with_multiplier`](Self::with_multiplier).
+    #[builder(default = 2.0, with = |multiplier: f64| checked_multiplier(multiplier))]
+    multiplier: f64,
+
+    /// Maximum number of consecutive retries.
+    /// If not set, retries continue indefinitely.
+    pub max_retries: Option<u32>,
+}
+
+impl RetryPolicy {
+    /// Make each delay `multiplier` times the one before it. The default of 2 doubles it, and
+    /// 1 keeps every delay at `initial_backoff`.
+    ///
+    /// # Panics
+    ///
+    /// If `multiplier` is less than 1 or not a number. The builder's setter panics likewise.
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = checked_multiplier(multiplier);
+        self
+    }
+
+    /// How much longer each delay is than the one before it.
+    pub fn multiplier(&self) -> f64 {
+        self.multiplier
+    }
+
+    /// The delay before retry number `attempt` (starting at 0), or `None` once
+    /// the retry budget is exhausted.
+    pub fn backoff(&self, attempt: u32) -> Option<Duration> {
+        if self.max_retries.is_some_and(|max| attempt >= max) {
+            return None;
+        }
+
+        let exponent = i32::try_from(attempt).unwrap_or(i32::MAX);
+        let delay = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        // Too large to represent means the cap applies
+        let delay = Duration::try_from_secs_f64(delay).unwrap_or(self.max_backoff);
+        Some(delay.min(self.max_backoff))
+    }
+}
+
+fn checked_multiplier(multiplier: f64) -> f64 {
+    assert!(
+        multiplier >= 1.0,
+        "retry multiplier must be at least 1: {multiplier}"
+    );
+    multiplier
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = RetryPolicy::builder()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(500))
+            .build();
+
+        assert_eq!(policy.backoff(0), Some(Duration::from_millis(100)));
+        assert_eq!(policy.backoff(1), Some(Duration::from_millis(200)));
+        assert_eq!(policy.backoff(2), Some(Duration::from_millis(400)));
+        assert_eq!(policy.backoff(3), Some(Duration::from_millis(500)));
+        assert_eq!(policy.backoff(64), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_backoff_follows_multiplier() {
+        let policy = RetryPolicy::builder()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_secs(1))
+            .multiplier(1.5)
+            .build();
+
+        assert_eq!(policy.backoff(0), Some(Duration::from_millis(100)));
+        assert_eq!(policy.backoff(1), Some(Duration::from_millis(150)));
+        assert_eq!(policy.backoff(2), Some(Duration::from_millis(225)));
+
+        let constant = RetryPolicy::builder()
+            .initial_backoff(Duration::from_millis(100))
+            .multiplier(1.0)
+            .build();
+        assert_eq!(constant.backoff(10), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    #[should_panic(expected = "retry multiplier must be at least 1")]
+    fn test_multiplier_below_one_panics() {
+        let _ = RetryPolicy::default().with_multiplier(0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "retry multiplier must be at least 1")]
+    fn test_builder_rejects_nan_multiplier() {
+        let _ = RetryPolicy::builder().multiplier(f64::NAN);
+    }
+
+    #[test]
+    fn test_backoff_respects_max_retries() {
+        let policy = RetryPolicy::builder().max_retries(2).build();
+
+        assert!(policy.backoff(1).is_some());
+        assert_eq!(policy.backoff(2), None);
+    }
+}
@@ -1,6 +1,73 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use bon::Builder;
 
+use crate::compression::Compression;
+use crate::connection::{DEFAULT_MIN_FRAME_LEN, InboundBufferPolicy, InboundRatePolicy};
+use crate::error::{CodeSpace, RpcServerError};
+use crate::path::strip_version;
+
+/// How long a finished handler waits by default for its response track to drain.
+pub(crate) const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// What a handler does with a request frame that fails to decode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecodeErrorPolicy {
+    /// End the request stream and abort the response track with `RpcWireError::Decode`.
+    #[default]
+    CloseConnection,
+
+    /// Drop the frame and carry on with the next one.
+    SkipFrame,
+
+    /// Hand the connector a `Status::invalid_argument` in place of the message.
+    ///
+    /// The connector only sees the errors if it reads its inbound through
+    /// `DecodedInbound::with_errors`. A connector reading it as a plain stream of messages
+    /// has no way to receive them, so for it this acts like [`SkipFrame`](Self::SkipFrame),
+    /// with a warning logged for each frame skipped.
+    Propagate,
+}
+
+/// What the router does when a broadcast already exists at a new session's response path
+/// with no session behind it, say one left behind by a handler that never shut down.
+///
+/// A broadcast that still has a session is that session's, and the client is refused as a
+/// duplicate either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResponsePathConflict {
+    /// Publish the response broadcast over the existing one.
+    ///
+    /// Subscribers are handed the new broadcast, and a client that had already picked up the
+    /// old one sees it withdrawn and can reconnect. The old broadcast is announced again if it
+    /// outlives the new one.
+    #[default]
+    Replace,
+
+    /// Refuse the client with `RpcServerError::BroadcastCreate`.
+    ///
+    /// The client is told it already has a session, `RejectReason::Duplicate` on its side, so
+    /// it retries after a delay. The rejection is published over the existing broadcast for a
+    /// moment, and the existing one is announced again after. A client that had already picked
+    /// up the existing broadcast sees it withdrawn instead.
+    Reject,
+}
+
 /// Configuration for the RPC router.
+///
+/// Build one with [`RpcRouterConfig::builder`], or start from the defaults and chain the
+/// `with_*` setters:
+///
+/// ```
+/// # use std::time::Duration;
+/// # use rpcmoq_lite::RpcRouterConfig;
+/// let config = RpcRouterConfig::default()
+///     .with_client_prefix("drone")
+///     .with_response_prefix("server")
+///     .with_session_idle_timeout(Duration::from_secs(30));
+/// assert_eq!(config.client_prefix.as_deref(), Some("drone"));
+/// ```
 #[derive(Debug, Clone, Builder)]
 pub struct RpcRouterConfig {
     /// Optional prefix for client announcements (e.g., "drone").
@@ -13,17 +80,491 @@ pub struct RpcRouterConfig {
     /// If not set, responses are published at `{client_id}/{grpc_path}`.
     pub response_prefix: Option<String>,
 
+    /// Number of leading path segments a relay namespaces every announcement under,
+    /// e.g. 2 for `{region}/{tenant}/{client_prefix}/{client_id}/{grpc_path}`.
+    /// The segments may differ between announcements. They are stripped before the
+    /// client prefix, so a client ID containing slashes is still parsed correctly, and
+    /// responses are published under the same namespace. The session's client ID is
+    /// qualified with the namespace so clients of different tenants don't collide.
+    #[builder(default)]
+    pub root_depth: usize,
+
     /// Track name for RPC messages (e.g., "primary").
     #[builder(default = "primary".to_string())]
     pub track_name: String,
+
+    /// Track names used instead of `track_name` by individual routes, keyed by gRPC path.
+    /// Clients must be configured with the same overrides.
+    #[builder(default)]
+    pub route_track_names: HashMap<String, String>,
+
+    /// Optional idle timeout for sessions.
+    /// If set, a session is torn down when no inbound frame arrives within this window.
+    pub session_idle_timeout: Option<Duration>,
+
+    /// Send a keepalive frame on the response track whenever no response has been sent for
+    /// this long, so a client with a keepalive timeout doesn't mistake a quiet handler for a
+    /// dead server.
+    pub keepalive_interval: Option<Duration>,
+
+    /// End a session's request stream with `RpcWireError::KeepaliveTimeout` when no frame,
+    /// requests and keepalives alike, arrives within this window. Unlike
+    /// `session_idle_timeout`, keepalives count, so it only catches clients that are gone.
+    pub keepalive_timeout: Option<Duration>,
+
+    /// How long a handler that finished normally waits for the client to read the
+    /// rest of the response track before the response broadcast is dropped.
+    #[builder(default = DEFAULT_DRAIN_TIMEOUT)]
+    pub drain_timeout: Duration,
+
+    /// Optional limit on the number of active sessions.
+    /// If set, a client connecting while the router is at the limit is rejected with
+    /// `RpcWireError::Overloaded`.
+    pub max_sessions: Option<usize>,
+
+    /// Optional maximum size in bytes for inbound request frames.
+    /// If set, a larger frame ends the session's inbound stream.
+    pub max_frame_size: Option<usize>,
+
+    /// Request frames with a payload shorter than this are skipped rather than
+    /// decoded. The default of 1 skips empty frames, which protobuf would
+    /// otherwise decode into an all-default message. Set this to 0 if a route
+    /// legitimately exchanges messages whose fields are all default.
+    #[builder(default = DEFAULT_MIN_FRAME_LEN)]
+    pub min_frame_len: usize,
+
+    /// Optional number of request frames to read ahead of each handler.
+    /// If set, the router reads every request track into a buffer of this many frames, so a
+    /// handler waiting on a slow backend does not hold up the track reader.
+    pub inbound_buffer: Option<usize>,
+
+    /// What to do with a request frame that arrives while the inbound buffer is full.
+    #[builder(default)]
+    pub inbound_buffer_policy: InboundBufferPolicy,
+
+    /// Optional maximum number of request frames per second, per session.
+    /// Protects the backend from a client that floods its request track.
+    pub max_inbound_rate: Option<f64>,
+
+    /// What to do with a request frame over the maximum inbound rate.
+    #[builder(default)]
+    pub inbound_rate_policy: InboundRatePolicy,
+
+    /// Compression applied to every frame in both directions.
+    /// Clients must be configured with the same codec.
+    #[builder(default)]
+    pub compression: Compression,
+
+    /// Send a structured error frame with a human-readable message before
+    /// aborting a response track. Clients that predate error frames would fail
+    /// to decode it, so this is off by default.
+    #[builder(default)]
+    pub error_frames: bool,
+
+    /// End each response stream like a gRPC call: with a trailer after the last response when
+    /// the handler's stream ends cleanly, or an error frame carrying the gRPC status it ended
+    /// with. Clients that predate status trailers would fail to decode them, so this is off by
+    /// default.
+    #[builder(default)]
+    pub status_trailers: bool,
+
+    /// Where the RPC layer's error codes sit among MoQ app error codes.
+    /// Clients must be configured with the same code space.
+    #[builder(default)]
+    pub code_space: CodeSpace,
+
+    /// What to do when a leftover broadcast, one with no session, is at a response path.
+    #[builder(default)]
+    pub on_response_path_conflict: ResponsePathConflict,
+
+    /// What to do with request frames that fail to decode.
+    #[builder(default)]
+    pub decode_error_policy: DecodeErrorPolicy,
+
+    /// Wait for each client's first frame and parent the handler span to the
+    /// W3C trace context it carries, if any.
+    #[builder(default)]
+    pub trace_propagation: bool,
+
+    /// Track the sizes of every session's request and response frames, read with
+    /// `DecodedInbound::size_stats` and `SessionContext::response_size_stats`.
+    #[builder(default)]
+    pub size_stats: bool,
+}
+
+impl Default for RpcRouterConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
 }
 
 impl RpcRouterConfig {
-    /// Build the response path for a client/rpc combination.
-    pub(crate) fn response_path(&self, client_id: &str, grpc_path: &str) -> String {
-        match &self.response_prefix {
+    /// Listen for client announcements under `prefix`.
+    pub fn with_client_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.client_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Publish responses under `prefix`.
+    pub fn with_response_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.response_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Exchange messages on a track named `track_name` unless the route overrides it.
+    pub fn with_track_name(mut self, track_name: impl Into<String>) -> Self {
+        self.track_name = track_name.into();
+        self
+    }
+
+    /// Exchange the messages of `grpc_path` on a track named `track_name` instead of the
+    /// default one, say to give a control route a high-priority track of its own.
+    pub fn with_route_track_name(
+        mut self,
+        grpc_path: impl Into<String>,
+        track_name: impl Into<String>,
+    ) -> Self {
+        self.route_track_names
+            .insert(grpc_path.into(), track_name.into());
+        self
+    }
+
+    /// The name of the track the messages of `grpc_path` are exchanged on.
+    ///
+    /// A versioned path without an override of its own takes the override of its unversioned
+    /// path, like its requests fall back to the unversioned handler.
+    pub fn track_name_for(&self, grpc_path: &str) -> &str {
+        self.route_track_names
+            .get(grpc_path)
+            .or_else(|| self.route_track_names.get(strip_version(grpc_path)))
+            .unwrap_or(&self.track_name)
+    }
+
+    /// Tear down sessions that receive no inbound frame within `timeout`.
+    pub fn with_session_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.session_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Send a keepalive frame after `interval` without a response.
+    pub fn with_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_interval = Some(interval);
+        self
+    }
+
+    /// End request streams that hear nothing from the client within `timeout`.
+    pub fn with_keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive_timeout = Some(timeout);
+        self
+    }
+
+    /// Handle leftover broadcasts at response paths according to `policy`.
+    pub fn with_response_path_conflict(mut self, policy: ResponsePathConflict) -> Self {
+        self.on_response_path_conflict = policy;
+        self
+    }
+
+    /// Wait at most `timeout` for clients to read the end of a finished response.
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Reject new clients while `max` sessions are active.
+    pub fn with_max_sessions(mut self, max: usize) -> Self {
+        self.max_sessions = Some(max);
+        self
+    }
+
+    /// Reject inbound request frames larger than `max` bytes.
+    pub fn with_max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = Some(max);
+        self
+    }
+
+    /// Read up to `capacity` request frames ahead of each handler, applying `policy` once the
+    /// buffer is full.
+    pub fn with_inbound_buffer(mut self, capacity: usize, policy: InboundBufferPolicy) -> Self {
+        self.inbound_buffer = Some(capacity);
+        self.inbound_buffer_policy = policy;
+        self
+    }
+
+    /// Let each session send at most `per_second` request frames per second, applying `policy`
+    /// to frames over the limit. Dropped frames are reported to the observer with
+    /// `SessionObserver::on_inbound_throttled`, and groups skipped while a frame was held back
+    /// with `SessionObserver::on_inbound_groups_skipped`.
+    pub fn with_max_inbound_rate(mut self, per_second: f64, policy: InboundRatePolicy) -> Self {
+        self.max_inbound_rate = Some(per_second);
+        self.inbound_rate_policy = policy;
+        self
+    }
+
+    /// Compress every frame in both directions with `compression`.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Send an error frame with a message before aborting a response track.
+    pub fn with_error_frames(mut self, enabled: bool) -> Self {
+        self.error_frames = enabled;
+        self
+    }
+
+    /// End each response stream with a trailer carrying the handler's final status.
+    pub fn with_status_trailers(mut self, enabled: bool) -> Self {
+        self.status_trailers = enabled;
+        self
+    }
+
+    /// Track the sizes of every session's request and response frames.
+    pub fn with_size_stats(mut self, enabled: bool) -> Self {
+        self.size_stats = enabled;
+        self
+    }
+
+    /// Send and expect error codes offset into `space`.
+    pub fn with_code_space(mut self, space: CodeSpace) -> Self {
+        self.code_space = space;
+        self
+    }
+
+    /// Handle request frames that fail to decode according to `policy`.
+    pub fn with_decode_error_policy(mut self, policy: DecodeErrorPolicy) -> Self {
+        self.decode_error_policy = policy;
+        self
+    }
+
+    /// Extract the client's trace context before calling the connector.
+    pub fn with_trace_propagation(mut self, enabled: bool) -> Self {
+        self.trace_propagation = enabled;
+        self
+    }
+
+    /// Check the prefixes and track names, normalizing the prefixes: leading and trailing
+    /// slashes are stripped and repeated ones collapsed, so `/fleet//drone/` becomes
+    /// `fleet/drone`.
+    ///
+    /// A prefix that is empty once its slashes are stripped, an empty track name, or a maximum
+    /// inbound rate that is not a positive number, is rejected with [`RpcServerError::Config`].
+    pub fn validated(mut self) -> Result<Self, RpcServerError> {
+        for (name, prefix) in [
+            ("client_prefix", &mut self.client_prefix),
+            ("response_prefix", &mut self.response_prefix),
+        ] {
+            if let Some(prefix) = prefix {
+                let segments: Vec<_> = prefix.split('/').filter(|s| !s.is_empty()).collect();
+                if segments.is_empty() {
+                    return Err(RpcServerError::Config(format!(
+                        "{name} '{prefix}' has no path segments; leave it unset to use the root"
+                    )));
+                }
+                *prefix = segments.join("/");
+            }
+        }
+
+        if self.track_name.is_empty() {
+            return Err(RpcServerError::Config(
+                "track_name must not be empty".to_string(),
+            ));
+        }
+        if let Some(grpc_path) = self
+            .route_track_names
+            .iter()
+            .find_map(|(grpc_path, track_name)| track_name.is_empty().then_some(grpc_path))
+        {
+            return Err(RpcServerError::Config(format!(
+                "track name for '{grpc_path}' must not be empty"
+            )));
+        }
+        if let Some(rate) = self.max_inbound_rate
+            && !(rate.is_finite() && rate > 0.0)
+        {
+            return Err(RpcServerError::Config(format!(
+                "max_inbound_rate must be positive: {rate}"
+            )));
+        }
+        for (name, duration) in [
+            ("keepalive_interval", self.keepalive_interval),
+            ("keepalive_timeout", self.keepalive_timeout),
+        ] {
+            if duration.is_some_and(|duration| duration.is_zero()) {
+                return Err(RpcServerError::Config(format!("{name} must not be zero")));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Build the response path for a client/rpc combination within `namespace`.
+    pub(crate) fn response_path(
+        &self,
+        namespace: &str,
+        client_id: &str,
+        grpc_path: &str,
+    ) -> String {
+        let path = match &self.response_prefix {
             Some(prefix) => format!("{}/{}/{}", prefix, client_id, grpc_path),
             None => format!("{}/{}", client_id, grpc_path),
+        };
+        if namespace.is_empty() {
+            path
+        } else {
+            format!("{namespace}/{path}")
+        }
+    }
+
+    /// Split an announced path into its namespace and the `{client_id}/{grpc_path}` after it.
+    ///
+    /// With no [`root_depth`](Self::root_depth) the namespace is empty and the path is
+    /// returned as-is, as the client prefix was already stripped by the origin. Returns `None`
+    /// for a path that is not a client announcement, because it is too short for the namespace
+    /// or doesn't continue with the client prefix, like the router's own responses.
+    pub(crate) fn split_root<'a>(&self, path: &'a str) -> Option<(&'a str, &'a str)> {
+        if self.root_depth == 0 {
+            return Some(("", path));
         }
+
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let (namespace, rest) = path
+            .match_indices('/')
+            .nth(self.root_depth - 1)
+            .map(|(index, _)| (&path[..index], &path[index + 1..]))?;
+
+        let rest = match &self.client_prefix {
+            Some(prefix) => rest
+                .strip_prefix(prefix.as_str())
+                .and_then(|rest| rest.strip_prefix('/'))?,
+            None => rest,
+        };
+        Some((namespace, rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn namespaced(root_depth: usize) -> RpcRouterConfig {
+        RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .root_depth(root_depth)
+            .build()
+    }
+
+    #[test]
+    fn test_split_root_strips_namespace_and_prefix() {
+        let config = namespaced(2);
+        let (namespace, rest) = config
+            .split_root("us-east/acme/drone/fleet/drone-1/drone.EchoService/Echo")
+            .unwrap();
+        assert_eq!(namespace, "us-east/acme");
+        assert_eq!(rest, "fleet/drone-1/drone.EchoService/Echo");
+        assert_eq!(
+            config.response_path(namespace, "fleet/drone-1", "drone.EchoService/Echo"),
+            "us-east/acme/server/fleet/drone-1/drone.EchoService/Echo"
+        );
+    }
+
+    #[test]
+    fn test_split_root_requires_prefix_after_namespace() {
+        let config = namespaced(2);
+        assert!(
+            config
+                .split_root("us-east/drone/drone-1/drone.EchoService/Echo")
+                .is_none()
+        );
+        assert!(
+            config
+                .split_root("us-east/acme/server/fleet/drone-1/drone.EchoService/Echo")
+                .is_none()
+        );
+        assert!(config.split_root("us-east").is_none());
+    }
+
+    #[test]
+    fn test_setters_match_builder() {
+        let built = RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .track_name("control".to_string())
+            .build();
+        let chained = RpcRouterConfig::default()
+            .with_client_prefix("drone")
+            .with_response_prefix("server")
+            .with_track_name("control");
+        assert_eq!(format!("{chained:?}"), format!("{built:?}"));
+    }
+
+    #[test]
+    fn test_validated_strips_prefix_slashes() {
+        let config = RpcRouterConfig::default()
+            .with_client_prefix("/drone/")
+            .with_response_prefix("fleet//server/")
+            .validated()
+            .unwrap();
+        assert_eq!(config.client_prefix.as_deref(), Some("drone"));
+        assert_eq!(config.response_prefix.as_deref(), Some("fleet/server"));
+    }
+
+    #[test]
+    fn test_validated_rejects_empty_prefixes() {
+        for prefix in ["", "/", "//"] {
+            let result = RpcRouterConfig::default()
+                .with_client_prefix(prefix)
+                .validated();
+            assert!(matches!(result, Err(RpcServerError::Config(_))));
+
+            let result = RpcRouterConfig::default()
+                .with_response_prefix(prefix)
+                .validated();
+            assert!(matches!(result, Err(RpcServerError::Config(_))));
+        }
+    }
+
+    #[test]
+    fn test_validated_rejects_non_positive_inbound_rate() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let result = RpcRouterConfig::default()
+                .with_max_inbound_rate(rate, InboundRatePolicy::Drop)
+                .validated();
+            assert!(matches!(result, Err(RpcServerError::Config(_))));
+        }
+        let result = RpcRouterConfig::default()
+            .with_max_inbound_rate(0.5, InboundRatePolicy::Drop)
+            .validated();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_validated_rejects_zero_keepalives() {
+        let result = RpcRouterConfig::default()
+            .with_keepalive_interval(Duration::ZERO)
+            .validated();
+        assert!(matches!(result, Err(RpcServerError::Config(_))));
+
+        let result = RpcRouterConfig::default()
+            .with_keepalive_timeout(Duration::ZERO)
+            .validated();
+        assert!(matches!(result, Err(RpcServerError::Config(_))));
+    }
+
+    #[test]
+    fn test_validated_rejects_empty_track_names() {
+        let result = RpcRouterConfig::default().with_track_name("").validated();
+        assert!(matches!(result, Err(RpcServerError::Config(_))));
+
+        let result = RpcRouterConfig::default()
+            .with_route_track_name("drone.EchoService/Echo", "")
+            .validated();
+        assert!(matches!(result, Err(RpcServerError::Config(_))));
+    }
+
+    #[test]
+    fn test_split_root_without_depth_is_identity() {
+        let config = namespaced(0);
+        let path = "drone-1/drone.EchoService/Echo";
+        assert_eq!(config.split_root(path), Some(("", path)));
     }
 }
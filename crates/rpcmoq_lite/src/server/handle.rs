@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use moq_lite::{OriginConsumer, OriginProducer};
+use tokio::sync::mpsc;
+
+use crate::server::session::SessionMap;
+
+/// An origin handed to a router, see [`RpcRouterHandle::add_origin`].
+pub(crate) type AddedOrigin = (OriginConsumer, Arc<OriginProducer>);
+
+/// Controls a router while it runs, obtained from `RpcRouter::handle`.
+///
+/// Cloning the handle is cheap and every clone controls the same router.
+#[derive(Debug, Clone)]
+pub struct RpcRouterHandle {
+    accepting: Arc<AtomicBool>,
+    sessions: Arc<SessionMap>,
+    origins: mpsc::UnboundedSender<AddedOrigin>,
+}
+
+impl RpcRouterHandle {
+    pub(crate) fn new(
+        sessions: Arc<SessionMap>,
+        origins: mpsc::UnboundedSender<AddedOrigin>,
+    ) -> Self {
+        Self {
+            accepting: Arc::new(AtomicBool::new(true)),
+            sessions,
+            origins,
+        }
+    }
+
+    /// Accept clients announcing on another origin, e.g. a new session with a relay whose
+    /// previous session closed.
+    ///
+    /// Works like `RpcRouter::with_origin`, also while the router runs. Returns false once the
+    /// router has stopped, which happens when every origin it had has closed.
+    pub fn add_origin(&self, consumer: OriginConsumer, producer: Arc<OriginProducer>) -> bool {
+        self.origins.send((consumer, producer)).is_ok()
+    }
+
+    /// Start or stop accepting new clients, e.g. for maintenance.
+    ///
+    /// While not accepting, every new client is rejected with `RpcWireError::Maintenance`, which
+    /// clients see as `RejectReason::Maintenance`. Sessions that are already running are left
+    /// alone, and new health checks are still served, reporting `NotServing`. Routers start out
+    /// accepting.
+    pub fn set_accepting(&self, accepting: bool) {
+        self.accepting.store(accepting, Ordering::Relaxed);
+    }
+
+    /// Whether new clients are accepted, see [`set_accepting`](Self::set_accepting).
+    pub fn is_accepting(&self) -> bool {
+        self.accepting.load(Ordering::Relaxed)
+    }
+
+    /// Terminate the session of `client_id` on `grpc_path`, returning whether there was one.
+    ///
+    /// The client sees `RpcWireError::Terminated` and the session's handler stops, see
+    /// `SessionMap::terminate`. A client with sessions on several origins loses all of them.
+    /// Nothing stops it from reconnecting; combine with authorization to keep it out.
+    pub fn terminate_session(&self, client_id: &str, grpc_path: &str) -> bool {
+        let mut terminated = false;
+        for key in self.sessions.snapshot() {
+            if key.client_id == client_id && key.grpc_path == grpc_path {
+                terminated |= self.sessions.terminate(&key);
+            }
+        }
+        terminated
+    }
+}
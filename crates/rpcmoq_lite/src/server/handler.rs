@@ -1,15 +1,25 @@
-use futures::{Stream, StreamExt};
+use bytes::Bytes;
+use futures::{FutureExt, Stream, StreamExt};
 use moq_lite::BroadcastProducer;
 use std::future::Future;
 use std::marker::PhantomData;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::Notify;
 use tonic::Status;
+use tracing::Instrument;
 
-use crate::connection::{RpcInbound, RpcOutbound};
-use crate::error::RpcWireError;
-use crate::server::session::SessionGuard;
+use crate::codec::{MessageCodec, PROST_CODEC_ID, ProstCodec, codec_id};
+use crate::connection::{DEFAULT_MIN_FRAME_LEN, ERROR_FRAME_GRACE, RpcInbound, RpcOutbound};
+use crate::error::{RpcSendError, RpcWireError};
+use crate::server::config::DecodeErrorPolicy;
+use crate::server::observer::{SessionEndReason, SessionObserver};
+use crate::server::session::{SessionContext, SessionGuard};
+use crate::stats::{RouteCounters, SizeStats};
+use crate::trace::{TraceContext, trace_propagator};
 
 /// A type-erased handler that can be stored in a HashMap.
 ///
@@ -22,84 +32,316 @@ pub(crate) trait ErasedHandler: Send + Sync {
     /// encodes responses, and writes them back to MoQ.
     fn spawn_handler(
         &self,
-        client_id: String,
         inbound: RpcInbound,
         outbound: RpcOutbound,
         connection_guard: ConnectionGuard,
+        options: SessionOptions,
     );
 }
 
-/// A concrete typed inbound stream that decodes protobuf messages from `RpcInbound`.
-pub struct DecodedInbound<Req> {
+/// Turns a frame payload into a request, explaining a failure as the `Status` yielded under
+/// [`DecodeErrorPolicy::Propagate`].
+type RequestDecoder<Req> = Arc<dyn Fn(Bytes) -> Result<Req, Status> + Send + Sync>;
+
+/// A concrete typed inbound stream that decodes messages from `RpcInbound`
+/// with the codec `C`, protobuf by default.
+pub struct DecodedInbound<Req, C = ProstCodec> {
     inner: RpcInbound,
-    on_decode_error: Option<std::sync::Arc<dyn Fn() + Send + Sync>>,
-    _marker: PhantomData<fn() -> Req>,
+    decoder: RequestDecoder<Req>,
+    on_decode_error: Option<Arc<dyn Fn() + Send + Sync>>,
+    counters: Option<Arc<RouteCounters>>,
+    min_frame_len: usize,
+    decode_error_policy: DecodeErrorPolicy,
+    // Set once the stream has closed on an error, so later polls don't read past it
+    terminated: bool,
+    _marker: PhantomData<fn() -> (Req, C)>,
 }
 
-impl<Req> DecodedInbound<Req> {
+impl<Req, C> DecodedInbound<Req, C>
+where
+    Req: 'static,
+    C: MessageCodec<Req>,
+{
     pub fn new(inner: RpcInbound) -> Self {
+        Self::from_parts(inner, codec_id::<C, Req>(), decode_with(C::decode))
+    }
+}
+
+impl<Req> DecodedInbound<Req> {
+    /// Decode protobuf request frames with `decoder` instead of `Req::decode`.
+    ///
+    /// For request types that can't be decoded by [`ProstCodec`], say because they have no
+    /// meaningful `Default`. See [`with_decoder`](Self::with_decoder).
+    pub fn from_decoder<F, E>(inner: RpcInbound, decoder: F) -> Self
+    where
+        F: Fn(Bytes) -> Result<Req, E> + Send + Sync + 'static,
+        E: std::fmt::Display,
+    {
+        Self::from_parts(inner, PROST_CODEC_ID, decode_with(decoder))
+    }
+}
+
+/// Wrap `decode` so its errors read like those of the built-in codecs.
+pub(crate) fn decode_with<Req, E: std::fmt::Display>(
+    decode: impl Fn(Bytes) -> Result<Req, E> + Send + Sync + 'static,
+) -> RequestDecoder<Req> {
+    Arc::new(move |payload| {
+        decode(payload)
+            .map_err(|err| Status::invalid_argument(format!("failed to decode request: {err}")))
+    })
+}
+
+impl<Req, C> DecodedInbound<Req, C> {
+    fn from_parts(inner: RpcInbound, codec_id: u8, decoder: RequestDecoder<Req>) -> Self {
         Self {
-            inner,
+            inner: inner.with_codec_id(codec_id),
+            decoder,
             on_decode_error: None,
+            counters: None,
+            min_frame_len: DEFAULT_MIN_FRAME_LEN,
+            decode_error_policy: DecodeErrorPolicy::default(),
+            terminated: false,
             _marker: PhantomData,
         }
     }
 
-    /// Attach a callback that runs when a decode error occurs.
+    /// Decode request frames with `decoder` instead of the codec.
+    ///
+    /// The decoder gets the frame payload, decompressed and without the codec tag, and may
+    /// reject it for any reason, for example a message that decodes fine but whose zero value
+    /// is not a valid request. A rejected frame is handled like one that failed to decode,
+    /// according to the [`DecodeErrorPolicy`], with `err` in the message of the `Status`.
+    ///
+    /// ```ignore
+    /// let inbound = inbound.with_decoder(|payload: Bytes| {
+    ///     let command = DroneCommand::decode(payload).map_err(|err| err.to_string())?;
+    ///     if command.drone_id.is_empty() {
+    ///         return Err("drone_id is required".to_string());
+    ///     }
+    ///     Ok(command)
+    /// });
+    /// ```
+    pub fn with_decoder<F, E>(mut self, decoder: F) -> Self
+    where
+        F: Fn(Bytes) -> Result<Req, E> + Send + Sync + 'static,
+        E: std::fmt::Display,
+    {
+        self.decoder = decode_with(decoder);
+        self
+    }
+
+    /// Skip frames whose payload is shorter than `min` bytes instead of decoding them.
+    ///
+    /// Defaults to 1, so only empty frames are skipped: protobuf decodes an empty
+    /// payload into an all-default message that would otherwise look real.
+    pub fn with_min_frame_len(mut self, min: usize) -> Self {
+        self.min_frame_len = min;
+        self
+    }
+
+    /// Handle frames that fail to decode according to `policy`.
+    pub fn with_decode_error_policy(mut self, policy: DecodeErrorPolicy) -> Self {
+        self.decode_error_policy = policy;
+        self
+    }
+
+    /// Attach a callback that runs when a decode error occurs, whatever the policy.
     pub fn with_decode_error_handler<F>(mut self, f: F) -> Self
     where
         F: Fn() + Send + Sync + 'static,
     {
-        self.on_decode_error = Some(std::sync::Arc::new(f));
+        self.on_decode_error = Some(Arc::new(f));
         self
     }
-}
 
-impl<Req> Stream for DecodedInbound<Req>
-where
-    Req: prost::Message + Default,
-{
-    type Item = Req;
+    /// The number of request frames dropped by the router's inbound buffer so far.
+    ///
+    /// See [`RpcInbound::lagged_frames`].
+    pub fn lagged_frames(&self) -> u64 {
+        self.inner.lagged_frames()
+    }
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.as_mut().get_mut();
-        match Pin::new(&mut this.inner).poll_next(cx) {
-            Poll::Ready(Some(Ok(bytes))) => match Req::decode(bytes) {
-                Ok(msg) => Poll::Ready(Some(msg)),
-                // stop the stream, close the connection if we cannot decode the
-                // message
-                Err(_) => {
-                    if let Some(handler) = &this.on_decode_error {
-                        handler();
+    /// The number of request frames dropped for exceeding the router's maximum inbound rate so
+    /// far.
+    ///
+    /// See [`RpcInbound::throttled_frames`].
+    pub fn throttled_frames(&self) -> u64 {
+        self.inner.throttled_frames()
+    }
+
+    /// The number of request groups skipped while the router's maximum inbound rate held a
+    /// frame back so far.
+    ///
+    /// See [`RpcInbound::throttled_groups`].
+    pub fn throttled_groups(&self) -> u64 {
+        self.inner.throttled_groups()
+    }
+
+    /// The sizes of the request frames read so far, or `None` unless the router tracks them.
+    ///
+    /// See [`RpcInbound::size_stats`] and `RpcRouterConfig::size_stats`.
+    pub fn size_stats(&self) -> Option<SizeStats> {
+        self.inner.size_stats()
+    }
+
+    /// Count every decoded message towards `counters`.
+    pub(crate) fn with_counters(mut self, counters: Arc<RouteCounters>) -> Self {
+        self.counters = Some(counters);
+        self
+    }
+
+    /// Yield a `Status::invalid_argument` for each frame that fails to decode.
+    ///
+    /// Only frames rejected under [`DecodeErrorPolicy::Propagate`] are yielded as errors. Under
+    /// the other policies the stream behaves as it does without this adapter.
+    pub fn with_errors(self) -> FallibleDecodedInbound<Req, C> {
+        FallibleDecodedInbound { inner: self }
+    }
+
+    /// Yield each message along with the bytes it was decoded from.
+    ///
+    /// For handlers that need the request as the client sent it, for example to verify a
+    /// signature over it. Each frame is still only decoded once.
+    pub fn with_raw(self) -> RawDecodedInbound<Req, C> {
+        RawDecodedInbound { inner: self }
+    }
+
+    /// Decode the next message, keeping the bytes it was decoded from.
+    ///
+    /// Frames that fail to decode under [`DecodeErrorPolicy::Propagate`] are skipped, as the
+    /// item type has no room for the error.
+    fn poll_next_raw(&mut self, cx: &mut Context<'_>) -> Poll<Option<(Bytes, Req)>> {
+        loop {
+            return match self.poll_next_result(cx) {
+                Poll::Ready(Some(Err(status))) => {
+                    tracing::warn!(
+                        error = %status,
+                        "Skipping undecodable request frame, read the inbound with \
+                         `with_errors` to receive decode errors"
+                    );
+                    continue;
+                }
+                Poll::Ready(Some(Ok(next))) => Poll::Ready(Some(next)),
+                Poll::Ready(None) => Poll::Ready(None),
+                Poll::Pending => Poll::Pending,
+            };
+        }
+    }
+
+    /// Decode the next message, or report why a frame could not be decoded.
+    fn poll_next_result(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<(Bytes, Req), Status>>> {
+        loop {
+            if self.terminated {
+                return Poll::Ready(None);
+            }
+            return match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(bytes))) if bytes.len() < self.min_frame_len => {
+                    tracing::debug!(len = bytes.len(), "Skipping short request frame");
+                    continue;
+                }
+                Poll::Ready(Some(Ok(bytes))) => match (self.decoder)(bytes.clone()) {
+                    Ok(msg) => {
+                        if let Some(counters) = &self.counters {
+                            counters.record_inbound(bytes.len());
+                        }
+                        Poll::Ready(Some(Ok((bytes, msg))))
+                    }
+                    Err(err) => {
+                        if let Some(handler) = &self.on_decode_error {
+                            handler();
+                        }
+                        match self.decode_error_policy {
+                            DecodeErrorPolicy::CloseConnection => {
+                                self.terminated = true;
+                                Poll::Ready(None)
+                            }
+                            DecodeErrorPolicy::SkipFrame => {
+                                tracing::debug!(
+                                    len = bytes.len(),
+                                    "Skipping undecodable request frame"
+                                );
+                                continue;
+                            }
+                            DecodeErrorPolicy::Propagate => Poll::Ready(Some(Err(err))),
+                        }
                     }
+                },
+                // if we got an error, close the connection
+                Poll::Ready(Some(Err(err))) => {
+                    tracing::error!(%err, "Got an error from MoQ");
+                    self.terminated = true;
                     Poll::Ready(None)
                 }
-            },
-            // if we got an error, close the connection
-            Poll::Ready(Some(Err(err))) => {
-                tracing::error!(%err, "Got an error from MoQ");
-                Poll::Ready(None)
-            }
-            Poll::Ready(None) => Poll::Ready(None),
-            Poll::Pending => Poll::Pending,
+                Poll::Ready(None) => Poll::Ready(None),
+                Poll::Pending => Poll::Pending,
+            };
         }
     }
 }
 
+impl<Req, C> Stream for DecodedInbound<Req, C> {
+    type Item = Req;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.as_mut()
+            .get_mut()
+            .poll_next_raw(cx)
+            .map(|next| next.map(|(_, msg)| msg))
+    }
+}
+
+/// A [`DecodedInbound`] that also yields the bytes each message was decoded from.
+///
+/// Created with [`DecodedInbound::with_raw`]. The bytes are the message payload, after
+/// decompression and without the codec tag.
+pub struct RawDecodedInbound<Req, C = ProstCodec> {
+    inner: DecodedInbound<Req, C>,
+}
+
+impl<Req, C> Stream for RawDecodedInbound<Req, C> {
+    type Item = (Bytes, Req);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.as_mut().get_mut().inner.poll_next_raw(cx)
+    }
+}
+
+/// A [`DecodedInbound`] that also yields an error for each frame that fails to decode.
+///
+/// Created with [`DecodedInbound::with_errors`].
+pub struct FallibleDecodedInbound<Req, C = ProstCodec> {
+    inner: DecodedInbound<Req, C>,
+}
+
+impl<Req, C> Stream for FallibleDecodedInbound<Req, C> {
+    type Item = Result<Req, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.as_mut()
+            .get_mut()
+            .inner
+            .poll_next_result(cx)
+            .map(|next| next.map(|result| result.map(|(_, msg)| msg)))
+    }
+}
+
 /// A connector function that bridges MoQ streams to gRPC.
 ///
 /// The connector receives:
-/// - `client_id`: The ID of the connecting client
+/// - `session`: The session context (client ID, gRPC path, and per-session extensions)
 /// - `inbound`: A stream of decoded request messages from the client
 ///
 /// It should:
 /// 1. Connect to the appropriate gRPC service
 /// 2. Call the correct RPC method with the inbound stream
 /// 3. Return the response stream
-pub type ConnectorFn<Req, Resp> = Arc<
+pub type ConnectorFn<Req, Resp, C = ProstCodec> = Arc<
     dyn Fn(
-            String,
-            DecodedInbound<Req>,
+            &SessionContext,
+            DecodedInbound<Req, C>,
         ) -> Pin<
             Box<
                 dyn Future<
@@ -115,113 +357,446 @@ pub type ConnectorFn<Req, Resp> = Arc<
 >;
 
 /// A typed handler that wraps a connector function.
-pub(crate) struct TypedHandler<Req, Resp> {
-    connector: ConnectorFn<Req, Resp>,
-    _marker: std::marker::PhantomData<(Req, Resp)>,
+pub(crate) struct TypedHandler<Req, Resp, C = ProstCodec> {
+    connector: ConnectorFn<Req, Resp, C>,
+    // Shared by the `DecodedInbound` of every session
+    decoder: RequestDecoder<Req>,
+    // The codec ID request frames are expected to be tagged with
+    codec_id: u8,
+    _marker: std::marker::PhantomData<fn() -> (Req, Resp)>,
+}
+
+impl<Req, Resp, C> TypedHandler<Req, Resp, C>
+where
+    Req: Send + 'static,
+    Resp: Send,
+    C: MessageCodec<Req> + MessageCodec<Resp>,
+{
+    pub fn new(connector: ConnectorFn<Req, Resp, C>) -> Self {
+        let decoder = decode_with(<C as MessageCodec<Req>>::decode);
+        Self::with_decoder(connector, codec_id::<C, Req>(), decoder)
+    }
 }
 
-impl<Req, Resp> TypedHandler<Req, Resp>
+impl<Req, Resp, C> TypedHandler<Req, Resp, C>
 where
-    Req: prost::Message + Default + Send,
-    Resp: prost::Message + Send,
+    Req: Send,
+    Resp: Send,
+    C: MessageCodec<Resp>,
 {
-    pub fn new(connector: ConnectorFn<Req, Resp>) -> Self {
+    /// Decode requests tagged with `codec_id` with `decoder` instead of the codec.
+    pub fn with_decoder(
+        connector: ConnectorFn<Req, Resp, C>,
+        codec_id: u8,
+        decoder: RequestDecoder<Req>,
+    ) -> Self {
         Self {
             connector,
+            decoder,
+            codec_id,
             _marker: std::marker::PhantomData,
         }
     }
 }
 
-impl<Req, Resp> ErasedHandler for TypedHandler<Req, Resp>
+impl<Req, Resp, C> ErasedHandler for TypedHandler<Req, Resp, C>
 where
-    Req: prost::Message + Default + Send + 'static,
-    Resp: prost::Message + Send + 'static,
+    Req: Send + 'static,
+    Resp: Send + 'static,
+    C: MessageCodec<Resp>,
 {
     fn spawn_handler(
         &self,
-        client_id: String,
         inbound: RpcInbound,
         outbound: RpcOutbound,
         connection_guard: ConnectionGuard,
+        options: SessionOptions,
     ) {
         let connector = Arc::clone(&self.connector);
-        let grpc_path = connection_guard.session_guard.grpc_path().to_string();
+        let session = connection_guard
+            .session_guard
+            .context()
+            .clone()
+            .with_outbound(outbound.without_broadcast_owner());
+        connection_guard
+            .session_guard
+            .attach_outbound(outbound.without_broadcast_owner());
+        let terminated = connection_guard.session_guard.terminated();
+        let SessionOptions {
+            idle_timeout,
+            trace_propagation,
+            observer,
+            counters,
+            min_frame_len,
+            drain_timeout,
+            decode_error_policy,
+        } = options;
+        let decode = DecodeOptions {
+            decoder: Arc::clone(&self.decoder),
+            codec_id: self.codec_id,
+            counters,
+            min_frame_len,
+            policy: decode_error_policy,
+        };
 
-        tokio::spawn(async move {
-            // Keep the session guard alive for the duration of the task
-            let _guard = connection_guard;
+        let throttle = inbound.throttle_state();
 
-            // Decode inbound bytes to typed messages with a concrete stream type.
-            let abort_outbound = outbound.clone();
-            let decode_client_id = client_id.clone();
-            let decode_grpc_path = grpc_path.clone();
-            let typed_inbound =
-                DecodedInbound::<Req>::new(inbound).with_decode_error_handler(move || {
-                    tracing::warn!(
-                        client_id = %decode_client_id,
-                        grpc_path = %decode_grpc_path,
-                        "Failed to decode request from client"
-                    );
-                    abort_outbound.abort_app(RpcWireError::Decode.to_code());
-                });
+        // Every inbound frame resets the idle watchdog, and the end of the inbound stops it.
+        let activity = Arc::new(Notify::new());
+        let inbound_ended = Arc::new(AtomicBool::new(false));
+        let inbound = match idle_timeout {
+            Some(_) => {
+                let on_frame = Arc::clone(&activity);
+                let on_end = Arc::clone(&activity);
+                let ended = Arc::clone(&inbound_ended);
+                inbound
+                    .with_frame_handler(move || on_frame.notify_one())
+                    .with_end_handler(move || {
+                        ended.store(true, Ordering::Relaxed);
+                        on_end.notify_one();
+                    })
+            }
+            None => inbound,
+        };
 
-            // Call the connector to get the response stream
-            let mut outbound = outbound;
+        if let Some(observer) = &observer {
+            observer.on_session_started(&session);
+        }
 
-            let response_stream = match connector(client_id.clone(), typed_inbound).await {
-                Ok(stream) => stream,
-                Err(status) => {
-                    tracing::warn!(
-                        client_id = %client_id,
-                        grpc_path = %grpc_path,
-                        error = %status,
-                        "Connector failed to establish gRPC connection"
-                    );
-                    outbound.abort_app(RpcWireError::Grpc.to_code());
-                    return;
+        // Ties every log line of this connection together, including the connector's.
+        let span = tracing::info_span!(
+            "rpc_handler",
+            client_id = %session.client_id(),
+            grpc_path = %session.grpc_path(),
+            traceparent = tracing::field::Empty,
+        );
+
+        let task = async move {
+            let abort_outbound = outbound.clone();
+            let messages_sent = AtomicU64::new(0);
+            let mut single_response = None;
+            let run = async {
+                let mut inbound = inbound;
+                let mut session = session.clone();
+                if trace_propagation {
+                    let trace_context = extract_trace_context(&mut inbound).await;
+                    session = session.with_trace_context(trace_context);
                 }
+                run_session(
+                    connector,
+                    &session,
+                    inbound,
+                    outbound,
+                    decode,
+                    &messages_sent,
+                    &mut single_response,
+                )
+                .await
             };
 
-            // Pipe responses back to MoQ
-            let mut response_stream = response_stream;
-
-            while let Some(result) = response_stream.next().await {
-                match result {
-                    Ok(msg) => {
-                        if let Err(e) = outbound.send(&msg) {
+            let watched = async {
+                match idle_timeout {
+                    Some(timeout) => tokio::select! {
+                        reason = run => reason,
+                        () = idle_watchdog(timeout, &activity, &inbound_ended) => {
                             tracing::warn!(
-                                client_id = %client_id,
-                                grpc_path = %grpc_path,
-                                error = %e,
-                                "Failed to send response to MoQ"
+                                client_id = %session.client_id(),
+                                grpc_path = %session.grpc_path(),
+                                timeout_ms = %timeout.as_millis(),
+                                "Session idle timeout elapsed, closing"
+                            );
+                            abort_outbound.abort_with_error(
+                                RpcWireError::IdleTimeout.to_code_in(abort_outbound.code_space()),
+                                format!("no request received for {}ms", timeout.as_millis()),
                             );
-                            outbound.abort_app(RpcWireError::Internal.to_code());
-                            return;
+                            SessionEndReason::IdleTimeout
+                        }
+                    },
+                    None => run.await,
+                }
+            };
+            // The response track was already aborted by whoever terminated the session
+            let mut reason = tokio::select! {
+                reason = watched => reason,
+                () = terminated.notified() => {
+                    tracing::warn!(
+                        client_id = %session.client_id(),
+                        grpc_path = %session.grpc_path(),
+                        "Session terminated, closing"
+                    );
+                    SessionEndReason::Terminated
+                }
+            };
+
+            let ConnectionGuard {
+                session_guard,
+                _response_broadcast: response_broadcast,
+            } = connection_guard;
+            // The session is over, so a reconnecting client needn't wait out the drain
+            drop(session_guard);
+            // Let the client read the end of the response before the broadcast is dropped.
+            if matches!(reason, SessionEndReason::Completed) {
+                let read = match single_response.take() {
+                    // Closed soon after the message, so a client without trailers sees the end
+                    Some((outbound, msg)) => {
+                        match outbound
+                            .send_confirmed_with::<C, Resp>(&msg, drain_timeout)
+                            .await
+                        {
+                            Ok(()) => {
+                                messages_sent.fetch_add(1, Ordering::Relaxed);
+                                true
+                            }
+                            Err(RpcSendError::Unconfirmed(_)) => {
+                                messages_sent.fetch_add(1, Ordering::Relaxed);
+                                false
+                            }
+                            Err(e) => {
+                                tracing::warn!(error = %e, "Failed to send response to MoQ");
+                                abort_outbound.abort_with_error(
+                                    RpcWireError::Internal.to_code_in(abort_outbound.code_space()),
+                                    e.to_string(),
+                                );
+                                reason = SessionEndReason::Internal;
+                                if abort_outbound.sends_error_frames() {
+                                    tokio::time::sleep(ERROR_FRAME_GRACE).await;
+                                }
+                                true
+                            }
                         }
                     }
-                    Err(status) => {
-                        tracing::warn!(
-                            client_id = %client_id,
-                            grpc_path = %grpc_path,
-                            error = %status,
-                            "gRPC response stream error"
-                        );
-                        outbound.abort_app(RpcWireError::Grpc.to_code());
-                        return;
-                    }
+                    None => abort_outbound.drain(drain_timeout).await,
+                };
+                if !read {
+                    tracing::debug!("Response track still subscribed after draining");
+                }
+            } else {
+                if abort_outbound.sends_error_frames() {
+                    // Or the error frame, until the track is aborted after it
+                    tokio::time::sleep(ERROR_FRAME_GRACE).await;
+                }
+            }
+
+            let throttled = throttle.dropped.load(Ordering::Relaxed);
+            let skipped_groups = throttle.skipped_groups.load(Ordering::Relaxed);
+            tracing::info!(
+                messages_sent = messages_sent.load(Ordering::Relaxed),
+                throttled_frames = throttled,
+                throttled_groups = skipped_groups,
+                ?reason,
+                "Handler completed"
+            );
+
+            // Drop the broadcast before notifying so observers see the session as gone.
+            drop(response_broadcast);
+
+            if let Some(observer) = observer {
+                if throttled > 0 {
+                    observer.on_inbound_throttled(&session, throttled);
+                }
+                if skipped_groups > 0 {
+                    observer.on_inbound_groups_skipped(&session, skipped_groups);
                 }
+                observer.on_session_ended(&session, reason);
             }
+        };
 
-            tracing::debug!(
+        tokio::spawn(task.instrument(span));
+    }
+}
+
+/// Wait for the client's first frame and parent the current span to the trace
+/// context sent ahead of it, if any.
+async fn extract_trace_context(inbound: &mut RpcInbound) -> Option<TraceContext> {
+    let trace_context = inbound
+        .read_metadata()
+        .await
+        .and_then(TraceContext::extract)?;
+
+    let span = tracing::Span::current();
+    span.record("traceparent", trace_context.traceparent());
+    if let Some(propagator) = trace_propagator() {
+        propagator.set_parent(&span, &trace_context);
+    }
+    Some(trace_context)
+}
+
+/// How `run_session` decodes requests and counts the route's traffic.
+struct DecodeOptions<Req> {
+    decoder: RequestDecoder<Req>,
+    codec_id: u8,
+    counters: Arc<RouteCounters>,
+    min_frame_len: usize,
+    policy: DecodeErrorPolicy,
+}
+
+/// Decode inbound requests, call the connector, and pipe its responses back to MoQ.
+async fn run_session<Req, Resp, C>(
+    connector: ConnectorFn<Req, Resp, C>,
+    session: &SessionContext,
+    inbound: RpcInbound,
+    outbound: RpcOutbound,
+    decode: DecodeOptions<Req>,
+    messages_sent: &AtomicU64,
+    single_response: &mut Option<(RpcOutbound, Resp)>,
+) -> SessionEndReason
+where
+    Req: Send + 'static,
+    Resp: Send + 'static,
+    C: MessageCodec<Resp>,
+{
+    let client_id = session.client_id();
+    let grpc_path = session.grpc_path();
+    let DecodeOptions {
+        decoder,
+        codec_id,
+        counters,
+        min_frame_len,
+        policy: decode_error_policy,
+    } = decode;
+
+    // Decode inbound bytes to typed messages with a concrete stream type.
+    let abort_outbound = outbound.clone();
+    let decode_client_id = client_id.to_string();
+    let decode_grpc_path = grpc_path.to_string();
+    let typed_inbound = DecodedInbound::<Req, C>::from_parts(inbound, codec_id, decoder)
+        .with_decode_error_policy(decode_error_policy)
+        .with_decode_error_handler(move || {
+            tracing::warn!(
+                client_id = %decode_client_id,
+                grpc_path = %decode_grpc_path,
+                policy = ?decode_error_policy,
+                "Failed to decode request from client"
+            );
+            if decode_error_policy == DecodeErrorPolicy::CloseConnection {
+                abort_outbound.abort_with_error(
+                    RpcWireError::Decode.to_code_in(abort_outbound.code_space()),
+                    "failed to decode request",
+                );
+            }
+        })
+        .with_counters(Arc::clone(&counters))
+        .with_min_frame_len(min_frame_len);
+    let outbound = outbound.with_counters(counters);
+
+    // Call the connector to get the response stream
+    let response_stream = match connector(session, typed_inbound)
+        .instrument(tracing::info_span!("connector"))
+        .await
+    {
+        Ok(stream) => stream,
+        Err(status) => {
+            tracing::warn!(
                 client_id = %client_id,
                 grpc_path = %grpc_path,
-                "Handler completed"
+                error = %status,
+                "Connector failed to establish gRPC connection"
             );
-        });
+            outbound.abort_with_status(&status);
+            return SessionEndReason::Grpc;
+        }
+    };
+
+    pipe_responses::<Resp, C>(
+        session,
+        response_stream,
+        outbound,
+        messages_sent,
+        single_response,
+    )
+    .instrument(tracing::info_span!("pipe_responses"))
+    .await
+}
+
+/// Encode responses from the connector and write them back to MoQ until the stream ends.
+///
+/// A stream that has already ended when its first message arrives is a single response, like
+/// a unary call's. That message is left in `single_response` for the handler to send with
+/// [`RpcOutbound::send_confirmed_with`] once the session is released.
+async fn pipe_responses<Resp, C>(
+    session: &SessionContext,
+    response_stream: Pin<Box<dyn Stream<Item = Result<Resp, Status>> + Send>>,
+    mut outbound: RpcOutbound,
+    messages_sent: &AtomicU64,
+    single_response: &mut Option<(RpcOutbound, Resp)>,
+) -> SessionEndReason
+where
+    C: MessageCodec<Resp>,
+{
+    let client_id = session.client_id();
+    let grpc_path = session.grpc_path();
+
+    let mut response_stream = response_stream.peekable();
+    while let Some(result) = response_stream.next().await {
+        match result {
+            Ok(msg) => {
+                if messages_sent.load(Ordering::Relaxed) == 0
+                    && matches!(
+                        Pin::new(&mut response_stream).peek().now_or_never(),
+                        Some(None)
+                    )
+                {
+                    *single_response = Some((outbound, msg));
+                    return SessionEndReason::Completed;
+                }
+                if let Err(e) = outbound.send_with::<C, Resp>(&msg) {
+                    tracing::warn!(
+                        client_id = %client_id,
+                        grpc_path = %grpc_path,
+                        error = %e,
+                        "Failed to send response to MoQ"
+                    );
+                    outbound.abort_with_error(
+                        RpcWireError::Internal.to_code_in(outbound.code_space()),
+                        e.to_string(),
+                    );
+                    return SessionEndReason::Internal;
+                }
+                messages_sent.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(status) => {
+                tracing::warn!(
+                    client_id = %client_id,
+                    grpc_path = %grpc_path,
+                    error = %status,
+                    "gRPC response stream error"
+                );
+                outbound.abort_with_status(&status);
+                return SessionEndReason::Grpc;
+            }
+        }
+    }
+
+    // The handler drains the track, ending it with a trailer if status trailers are enabled
+    SessionEndReason::Completed
+}
+
+/// Resolves once `timeout` elapses without `activity` being notified.
+///
+/// Never resolves once the inbound has ended, as a handler still streaming responses after
+/// the client's last request is not idle.
+async fn idle_watchdog(timeout: Duration, activity: &Notify, inbound_ended: &AtomicBool) {
+    while tokio::time::timeout(timeout, activity.notified())
+        .await
+        .is_ok()
+    {
+        if inbound_ended.load(Ordering::Relaxed) {
+            std::future::pending::<()>().await;
+        }
     }
 }
 
+/// Per-session settings passed from the router to a handler.
+pub(crate) struct SessionOptions {
+    pub idle_timeout: Option<Duration>,
+    pub trace_propagation: bool,
+    pub observer: Option<Arc<dyn SessionObserver>>,
+    pub counters: Arc<RouteCounters>,
+    pub min_frame_len: usize,
+    pub drain_timeout: Duration,
+    pub decode_error_policy: DecodeErrorPolicy,
+}
+
 // A guard that keeps relevant pieces of data alive until they need to be dropped.
 pub(crate) struct ConnectionGuard {
     // Session guard needs to stay alive for the handler call duration
@@ -233,19 +808,707 @@ pub(crate) struct ConnectionGuard {
 /// Helper to create a boxed connector from an async closure.
 ///
 /// This handles the type gymnastics of boxing the closure and its return type.
-pub fn make_connector<Req, Resp, F, Fut, S>(f: F) -> ConnectorFn<Req, Resp>
+pub fn make_connector<Req, Resp, C, F, Fut, S>(f: F) -> ConnectorFn<Req, Resp, C>
 where
-    Req: prost::Message + Default + Send,
-    Resp: prost::Message + Send,
-    F: Fn(String, DecodedInbound<Req>) -> Fut + Send + Sync + 'static,
+    Req: Send,
+    Resp: Send,
+    C: MessageCodec<Resp>,
+    F: Fn(&SessionContext, DecodedInbound<Req, C>) -> Fut + Send + Sync + 'static,
     Fut: Future<Output = Result<S, Status>> + Send + 'static,
     S: Stream<Item = Result<Resp, Status>> + Send + 'static,
 {
-    Arc::new(move |client_id, inbound| {
-        let fut = f(client_id, inbound);
+    Arc::new(move |session, inbound| {
+        let fut = f(session, inbound);
         Box::pin(async move {
             let stream = fut.await?;
             Ok(Box::pin(stream) as Pin<Box<dyn Stream<Item = Result<Resp, Status>> + Send>>)
         })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::Compression;
+    use crate::server::config::DEFAULT_DRAIN_TIMEOUT;
+    use crate::server::session::{SessionKey, SessionMap};
+    use moq_lite::{Broadcast, Track, TrackProducer};
+    use tokio::sync::mpsc;
+    use tonic::Extensions;
+
+    struct ChannelObserver(mpsc::UnboundedSender<(SessionKey, SessionEndReason)>);
+
+    impl SessionObserver for ChannelObserver {
+        fn on_session_ended(&self, session: &SessionContext, reason: SessionEndReason) {
+            let _ = self.0.send((session.key().clone(), reason));
+        }
+    }
+
+    /// Spawn an echo session and return the request track plus the observer's event stream.
+    fn spawn_echo_session(
+        map: &Arc<SessionMap>,
+        idle_timeout: Option<Duration>,
+    ) -> (
+        TrackProducer,
+        mpsc::UnboundedReceiver<(SessionKey, SessionEndReason)>,
+    ) {
+        let request = Track::new("primary").produce();
+        let (tx, rx) = mpsc::unbounded_channel();
+        spawn_echo_handler(
+            map,
+            RpcInbound::from_track(request.consumer),
+            Arc::new(ChannelObserver(tx)),
+            idle_timeout,
+        );
+        (request.producer, rx)
+    }
+
+    /// The options a test session starts from: no idle timeout, observer or trace propagation.
+    fn session_options() -> SessionOptions {
+        SessionOptions {
+            idle_timeout: None,
+            trace_propagation: false,
+            observer: None,
+            counters: Arc::default(),
+            min_frame_len: DEFAULT_MIN_FRAME_LEN,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            decode_error_policy: DecodeErrorPolicy::default(),
+        }
+    }
+
+    /// Create the session of `drone-1` on the echo path.
+    fn session_guard(map: &Arc<SessionMap>) -> SessionGuard {
+        map.try_create(SessionKey::new("drone-1", "drone.EchoService/Echo"))
+            .unwrap()
+    }
+
+    /// Spawn `handler` for the session held by `session_guard`.
+    fn spawn_session(
+        handler: &dyn ErasedHandler,
+        session_guard: SessionGuard,
+        inbound: RpcInbound,
+        outbound: RpcOutbound,
+        options: SessionOptions,
+    ) {
+        let connection_guard = ConnectionGuard {
+            session_guard,
+            _response_broadcast: Broadcast::produce().producer,
+        };
+        handler.spawn_handler(inbound, outbound, connection_guard, options);
+    }
+
+    /// Spawn an echo handler reading `inbound`, reporting to `observer`.
+    fn spawn_echo_handler(
+        map: &Arc<SessionMap>,
+        inbound: RpcInbound,
+        observer: Arc<dyn SessionObserver>,
+        idle_timeout: Option<Duration>,
+    ) {
+        let response = Track::new("primary").produce();
+        let handler = TypedHandler::<String, String>::new(make_connector(
+            |_, inbound: DecodedInbound<String>| async move { Ok(inbound.map(Ok)) },
+        ));
+        spawn_session(
+            &handler,
+            session_guard(map),
+            inbound,
+            RpcOutbound::new(response.producer),
+            SessionOptions {
+                idle_timeout,
+                observer: Some(observer),
+                ..session_options()
+            },
+        );
+    }
+
+    /// Write raw, uncompressed request payloads into a single group.
+    fn write_payloads(track: &mut TrackProducer, payloads: &[&[u8]]) {
+        let mut group = track.append_group();
+        for payload in payloads {
+            group.write_frame(Compression::None.encode(payload));
+        }
+        group.close();
+    }
+
+    struct ThrottleObserver(mpsc::UnboundedSender<u64>);
+
+    impl SessionObserver for ThrottleObserver {
+        fn on_inbound_throttled(&self, _session: &SessionContext, dropped: u64) {
+            let _ = self.0.send(dropped);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observer_sees_throttled_frames() {
+        let map = Arc::new(SessionMap::new());
+        let mut request = Track::new("primary").produce();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let inbound = RpcInbound::from_track(request.consumer)
+            .with_max_rate(5.0, crate::connection::InboundRatePolicy::Drop);
+        spawn_echo_handler(&map, inbound, Arc::new(ThrottleObserver(tx)), None);
+
+        let ping = prost::Message::encode_to_vec(&"ping".to_string());
+        write_payloads(&mut request.producer, &[ping.as_slice(); 20]);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        request.producer.close();
+
+        let dropped = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        // The burst of 5 gets through, maybe with a token refilled while reading
+        assert!((14..=15).contains(&dropped), "dropped {dropped}");
+    }
+
+    #[tokio::test]
+    async fn test_decoded_inbound_skips_empty_frames() {
+        let mut request = Track::new("primary").produce();
+        let mut inbound = DecodedInbound::<String>::new(RpcInbound::from_track(request.consumer));
+
+        let ping = prost::Message::encode_to_vec(&"ping".to_string());
+        write_payloads(&mut request.producer, &[&[], &ping]);
+
+        let msg = tokio::time::timeout(Duration::from_secs(1), inbound.next())
+            .await
+            .unwrap();
+        assert_eq!(msg.as_deref(), Some("ping"));
+    }
+
+    #[tokio::test]
+    async fn test_decoded_inbound_min_frame_len() {
+        let mut request = Track::new("primary").produce();
+        let mut inbound = DecodedInbound::<String>::new(RpcInbound::from_track(request.consumer))
+            .with_min_frame_len(5);
+
+        let ok = prost::Message::encode_to_vec(&"ok".to_string());
+        let ping = prost::Message::encode_to_vec(&"ping".to_string());
+        assert!(ok.len() < 5);
+        write_payloads(&mut request.producer, &[&ok, &ping]);
+
+        let msg = tokio::time::timeout(Duration::from_secs(1), inbound.next())
+            .await
+            .unwrap();
+        assert_eq!(msg.as_deref(), Some("ping"));
+    }
+
+    /// Request payloads with an undecodable frame between two good ones.
+    fn interleaved_bad_frame(track: &mut TrackProducer) {
+        let ping = prost::Message::encode_to_vec(&"ping".to_string());
+        let pong = prost::Message::encode_to_vec(&"pong".to_string());
+        let mut truncated = ping.clone();
+        truncated.truncate(3);
+        write_payloads(track, &[&ping, &truncated, &pong]);
+    }
+
+    #[tokio::test]
+    async fn test_decode_error_close_connection_ends_stream() {
+        let mut request = Track::new("primary").produce();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut inbound = DecodedInbound::<String>::new(RpcInbound::from_track(request.consumer))
+            .with_decode_error_policy(DecodeErrorPolicy::CloseConnection)
+            .with_decode_error_handler(move || {
+                let _ = tx.send(());
+            });
+
+        interleaved_bad_frame(&mut request.producer);
+
+        let msgs =
+            tokio::time::timeout(Duration::from_secs(1), inbound.by_ref().collect::<Vec<_>>())
+                .await
+                .unwrap();
+        assert_eq!(msgs, ["ping"]);
+        assert!(rx.try_recv().is_ok());
+
+        // Stays closed rather than reading on to the frame after the bad one
+        assert_eq!(inbound.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_decode_error_skip_frame_continues() {
+        let mut request = Track::new("primary").produce();
+        let mut inbound = DecodedInbound::<String>::new(RpcInbound::from_track(request.consumer))
+            .with_decode_error_policy(DecodeErrorPolicy::SkipFrame);
+
+        interleaved_bad_frame(&mut request.producer);
+
+        let msgs = tokio::time::timeout(Duration::from_secs(1), async {
+            vec![inbound.next().await.unwrap(), inbound.next().await.unwrap()]
+        })
+        .await
+        .unwrap();
+        assert_eq!(msgs, ["ping", "pong"]);
+    }
+
+    #[tokio::test]
+    async fn test_decode_error_propagate_yields_status() {
+        let mut request = Track::new("primary").produce();
+        let mut inbound = DecodedInbound::<String>::new(RpcInbound::from_track(request.consumer))
+            .with_decode_error_policy(DecodeErrorPolicy::Propagate)
+            .with_errors();
+
+        interleaved_bad_frame(&mut request.producer);
+
+        let msgs = tokio::time::timeout(Duration::from_secs(1), async {
+            vec![
+                inbound.next().await.unwrap(),
+                inbound.next().await.unwrap(),
+                inbound.next().await.unwrap(),
+            ]
+        })
+        .await
+        .unwrap();
+        assert_eq!(msgs[0].as_deref().unwrap(), "ping");
+        assert_eq!(
+            msgs[1].as_ref().unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+        assert_eq!(msgs[2].as_deref().unwrap(), "pong");
+    }
+
+    #[tokio::test]
+    async fn test_custom_decoder_rejects_invalid_requests() {
+        let mut request = Track::new("primary").produce();
+        let inbound = DecodedInbound::<String>::new(RpcInbound::from_track(request.consumer))
+            .with_decoder(|payload: Bytes| {
+                let msg = <String as prost::Message>::decode(payload).map_err(|e| e.to_string())?;
+                if msg == "forbidden" {
+                    return Err(format!("'{msg}' is not a valid request"));
+                }
+                Ok(msg)
+            })
+            .with_decode_error_policy(DecodeErrorPolicy::Propagate)
+            .with_errors();
+
+        let payloads = ["ping", "forbidden", "pong"]
+            .map(|msg| prost::Message::encode_to_vec(&msg.to_string()));
+        write_payloads(
+            &mut request.producer,
+            &payloads.iter().map(Vec::as_slice).collect::<Vec<_>>(),
+        );
+
+        let msgs: Vec<_> = tokio::time::timeout(Duration::from_secs(1), inbound.take(3).collect())
+            .await
+            .unwrap();
+        assert_eq!(msgs[0].as_deref().unwrap(), "ping");
+        let status = msgs[1].as_ref().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(
+            status
+                .message()
+                .contains("'forbidden' is not a valid request")
+        );
+        assert_eq!(msgs[2].as_deref().unwrap(), "pong");
+    }
+
+    #[tokio::test]
+    async fn test_decoder_for_request_without_default() {
+        /// A drone id that is never empty, so it has no sensible default.
+        #[derive(Debug, PartialEq)]
+        struct DroneId(String);
+
+        let mut request = Track::new("primary").produce();
+        let mut inbound =
+            DecodedInbound::from_decoder(RpcInbound::from_track(request.consumer), |payload| {
+                match <String as prost::Message>::decode(payload) {
+                    Ok(id) if !id.is_empty() => Ok(DroneId(id)),
+                    _ => Err("expected a drone id"),
+                }
+            });
+
+        let id = prost::Message::encode_to_vec(&"drone-1".to_string());
+        write_payloads(&mut request.producer, &[&id]);
+
+        let msg = tokio::time::timeout(Duration::from_secs(1), inbound.next())
+            .await
+            .unwrap();
+        assert_eq!(msg, Some(DroneId("drone-1".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_decoded_inbound_with_raw_yields_original_bytes() {
+        let mut request = Track::new("primary").produce();
+        let mut inbound =
+            DecodedInbound::<String>::new(RpcInbound::from_track(request.consumer)).with_raw();
+
+        let ping = prost::Message::encode_to_vec(&"ping".to_string());
+        write_payloads(&mut request.producer, &[&[], &ping]);
+
+        let (raw, msg) = tokio::time::timeout(Duration::from_secs(1), inbound.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg, "ping");
+        assert_eq!(raw, ping);
+        assert_eq!(prost::Message::encode_to_vec(&msg), raw);
+    }
+
+    #[tokio::test]
+    async fn test_decoded_inbound_truncated_frame_ends_stream() {
+        let mut request = Track::new("primary").produce();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut inbound = DecodedInbound::<String>::new(RpcInbound::from_track(request.consumer))
+            .with_decode_error_handler(move || {
+                let _ = tx.send(());
+            });
+
+        let mut truncated = prost::Message::encode_to_vec(&"ping".to_string());
+        truncated.truncate(3);
+        write_payloads(&mut request.producer, &[&truncated]);
+
+        let msg = tokio::time::timeout(Duration::from_secs(1), inbound.next())
+            .await
+            .unwrap();
+        assert_eq!(msg, None);
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_idle_session_is_torn_down() {
+        let map = Arc::new(SessionMap::new());
+        let (_request, mut events) = spawn_echo_session(&map, Some(Duration::from_millis(50)));
+
+        let (key, reason) = tokio::time::timeout(Duration::from_secs(1), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(key, SessionKey::new("drone-1", "drone.EchoService/Echo"));
+        assert_eq!(reason, SessionEndReason::IdleTimeout);
+        assert!(map.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_inbound_frames_reset_idle_timer() {
+        let map = Arc::new(SessionMap::new());
+        let (request, mut events) = spawn_echo_session(&map, Some(Duration::from_millis(100)));
+        let mut outbound = RpcOutbound::new(request);
+
+        for _ in 0..6 {
+            outbound.send(&"ping".to_string()).unwrap();
+            tokio::time::sleep(Duration::from_millis(40)).await;
+        }
+        assert!(events.try_recv().is_err());
+        assert_eq!(map.len(), 1);
+
+        let (_, reason) = tokio::time::timeout(Duration::from_secs(1), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reason, SessionEndReason::IdleTimeout);
+    }
+
+    #[tokio::test]
+    async fn test_session_without_idle_timeout_completes() {
+        let map = Arc::new(SessionMap::new());
+        let (request, mut events) = spawn_echo_session(&map, None);
+
+        request.close();
+
+        let (_, reason) = tokio::time::timeout(Duration::from_secs(1), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reason, SessionEndReason::Completed);
+        assert!(map.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_stops_once_inbound_ends() {
+        let map = Arc::new(SessionMap::new());
+        let request = Track::new("primary").produce();
+        let response = Track::new("primary").produce();
+        let mut response_inbound = RpcInbound::from_track(response.consumer);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        // Reads the whole request stream, then keeps streaming well past the idle timeout
+        let handler = TypedHandler::<String, String>::new(make_connector(
+            |_: &SessionContext, inbound: DecodedInbound<String>| async move {
+                inbound.count().await;
+                Ok(futures::stream::iter(0..4).then(|_| async {
+                    tokio::time::sleep(Duration::from_millis(40)).await;
+                    Ok("tick".to_string())
+                }))
+            },
+        ));
+        spawn_session(
+            &handler,
+            session_guard(&map),
+            RpcInbound::from_track(request.consumer),
+            RpcOutbound::new(response.producer),
+            SessionOptions {
+                idle_timeout: Some(Duration::from_millis(50)),
+                observer: Some(Arc::new(ChannelObserver(tx))),
+                ..session_options()
+            },
+        );
+        request.producer.close();
+
+        for _ in 0..4 {
+            let frame = tokio::time::timeout(Duration::from_secs(1), response_inbound.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            assert_eq!(<String as prost::Message>::decode(frame).unwrap(), "tick");
+        }
+        drop(response_inbound);
+        let (_, reason) = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reason, SessionEndReason::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_completed_handler_drains_last_response() {
+        let map = Arc::new(SessionMap::new());
+        let request = Track::new("primary").produce();
+        let response = Track::new("primary").produce();
+        let mut response_inbound = RpcInbound::from_track(response.consumer);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        // Answers once and returns straight away, as a unary handler does
+        let handler = TypedHandler::<String, String>::new(make_connector(
+            |_: &SessionContext, _: DecodedInbound<String>| async move {
+                Ok(futures::stream::once(async { Ok("pong".to_string()) }))
+            },
+        ));
+        spawn_session(
+            &handler,
+            session_guard(&map),
+            RpcInbound::from_track(request.consumer),
+            RpcOutbound::new(response.producer),
+            SessionOptions {
+                observer: Some(Arc::new(ChannelObserver(tx))),
+                drain_timeout: Duration::from_secs(5),
+                ..session_options()
+            },
+        );
+
+        let frame = tokio::time::timeout(Duration::from_secs(1), response_inbound.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(<String as prost::Message>::decode(frame).unwrap(), "pong");
+
+        // The session only ends once the client has let go of the response track, though its
+        // key is free for a reconnecting client meanwhile
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(rx.try_recv().is_err());
+        assert!(map.is_empty());
+        drop(response_inbound);
+        let (_, reason) = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(reason, SessionEndReason::Completed));
+    }
+
+    #[tokio::test]
+    async fn test_single_response_ends_stream_without_trailers() {
+        let map = Arc::new(SessionMap::new());
+        let request = Track::new("primary").produce();
+        let response = Track::new("primary").produce();
+        let mut response_inbound = RpcInbound::from_track(response.consumer);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let handler = TypedHandler::<String, String>::new(make_connector(
+            |_: &SessionContext, _: DecodedInbound<String>| async move {
+                Ok(futures::stream::once(async { Ok("pong".to_string()) }))
+            },
+        ));
+        spawn_session(
+            &handler,
+            session_guard(&map),
+            RpcInbound::from_track(request.consumer),
+            RpcOutbound::new(response.producer),
+            SessionOptions {
+                observer: Some(Arc::new(ChannelObserver(tx))),
+                drain_timeout: Duration::from_secs(5),
+                ..session_options()
+            },
+        );
+
+        let frame = tokio::time::timeout(Duration::from_secs(1), response_inbound.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(<String as prost::Message>::decode(frame).unwrap(), "pong");
+
+        // Sent confirmed, so the track closes after the grace period instead of the drain
+        let end = tokio::time::timeout(Duration::from_secs(1), response_inbound.next()).await;
+        assert!(end.unwrap().is_none());
+        drop(response_inbound);
+        let (_, reason) = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(reason, SessionEndReason::Completed));
+    }
+
+    #[tokio::test]
+    async fn test_connector_receives_session_context() {
+        #[derive(Debug, Clone, PartialEq)]
+        struct User(&'static str);
+
+        let map = Arc::new(SessionMap::new());
+        let request = Track::new("primary").produce();
+        let response = Track::new("primary").produce();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let handler = TypedHandler::<String, String>::new(make_connector(
+            move |session: &SessionContext, inbound: DecodedInbound<String>| {
+                let _ = tx.send((
+                    session.client_id().to_string(),
+                    session.get::<User>().cloned(),
+                ));
+                async move { Ok(inbound.map(Ok)) }
+            },
+        ));
+
+        let mut extensions = Extensions::new();
+        extensions.insert(User("alice"));
+        let session_guard = map
+            .try_create_with_extensions(
+                SessionKey::new("drone-1", "drone.EchoService/Echo"),
+                extensions,
+            )
+            .unwrap();
+        spawn_session(
+            &handler,
+            session_guard,
+            RpcInbound::from_track(request.consumer),
+            RpcOutbound::new(response.producer),
+            session_options(),
+        );
+
+        let (client_id, user) = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(client_id, "drone-1");
+        assert_eq!(user, Some(User("alice")));
+    }
+
+    #[tokio::test]
+    async fn test_connector_opens_response_track() {
+        let map = Arc::new(SessionMap::new());
+        let request = Track::new("primary").produce();
+        let mut response = Broadcast::produce();
+        let outbound = RpcOutbound::new(response.producer.create_track(Track::new("primary")))
+            .with_broadcast(response.producer);
+        let response_consumer = response.consumer;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let handler = TypedHandler::<String, String>::new(make_connector(
+            move |session: &SessionContext, inbound: DecodedInbound<String>| {
+                let _ = tx.send(session.response_track("events"));
+                async move { Ok(inbound.map(Ok)) }
+            },
+        ));
+        spawn_session(
+            &handler,
+            session_guard(&map),
+            RpcInbound::from_track(request.consumer),
+            outbound,
+            session_options(),
+        );
+
+        let mut sender = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let mut events = RpcInbound::new(&response_consumer, "events");
+        sender.send(&"started".to_string()).unwrap();
+
+        let frame = events.next().await.unwrap().unwrap();
+        assert_eq!(
+            <String as prost::Message>::decode(frame).unwrap(),
+            "started"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connector_receives_trace_context() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let map = Arc::new(SessionMap::new());
+        let request = Track::new("primary").produce();
+        let response = Track::new("primary").produce();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let handler = TypedHandler::<String, String>::new(make_connector(
+            move |session: &SessionContext, inbound: DecodedInbound<String>| {
+                let _ = tx.send(session.trace_context().cloned());
+                async move { Ok(inbound.map(Ok)) }
+            },
+        ));
+        spawn_session(
+            &handler,
+            session_guard(&map),
+            RpcInbound::from_track(request.consumer),
+            RpcOutbound::new(response.producer),
+            SessionOptions {
+                trace_propagation: true,
+                ..session_options()
+            },
+        );
+
+        let mut metadata = crate::metadata::RpcMetadata::default();
+        TraceContext::new(traceparent, None)
+            .unwrap()
+            .inject(&mut metadata);
+        RpcOutbound::new(request.producer)
+            .with_metadata(&metadata)
+            .send(&"ping".to_string())
+            .unwrap();
+
+        let trace_context = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            trace_context.as_ref().map(TraceContext::traceparent),
+            Some(traceparent)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_counts_messages_and_bytes() {
+        let map = Arc::new(SessionMap::new());
+        let request = Track::new("primary").produce();
+        let response = Track::new("primary").produce();
+        let counters = Arc::new(RouteCounters::default());
+
+        let handler = TypedHandler::<String, String>::new(make_connector(
+            |_, inbound: DecodedInbound<String>| async move { Ok(inbound.map(Ok)) },
+        ));
+        spawn_session(
+            &handler,
+            session_guard(&map),
+            RpcInbound::from_track(request.consumer),
+            RpcOutbound::new(response.producer),
+            SessionOptions {
+                counters: Arc::clone(&counters),
+                ..session_options()
+            },
+        );
+
+        let mut outbound = RpcOutbound::new(request.producer);
+        let mut responses = RpcInbound::from_track(response.consumer);
+        outbound.send(&"ping".to_string()).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), responses.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.inbound_messages, 1);
+        assert_eq!(stats.outbound_messages, 1);
+        // A protobuf string is a one-byte tag, a one-byte length, and the bytes.
+        assert_eq!(stats.inbound_bytes, 6);
+        assert_eq!(stats.outbound_bytes, 6);
+    }
+}
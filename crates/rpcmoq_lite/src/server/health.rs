@@ -0,0 +1,52 @@
+use futures::{Stream, StreamExt};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tonic::Status;
+
+use crate::server::handle::RpcRouterHandle;
+use crate::server::handler::DecodedInbound;
+
+/// The reserved path of the built-in health check, matching the standard gRPC health service.
+pub const HEALTH_CHECK_PATH: &str = "grpc.health.v1.Health/Check";
+
+/// A health check request. `service` is accepted for compatibility with
+/// `grpc.health.v1` but ignored: the status always describes the whole router.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HealthCheckRequest {
+    #[prost(string, tag = "1")]
+    pub service: String,
+}
+
+/// The router's serving status.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HealthCheckResponse {
+    #[prost(enumeration = "ServingStatus", tag = "1")]
+    pub status: i32,
+}
+
+/// Serving status reported by the health check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ServingStatus {
+    Unknown = 0,
+    Serving = 1,
+    NotServing = 2,
+}
+
+/// Answer every inbound health check with the router's current status.
+pub(crate) fn health_responses(
+    running: Arc<AtomicBool>,
+    handle: RpcRouterHandle,
+    inbound: DecodedInbound<HealthCheckRequest>,
+) -> impl Stream<Item = Result<HealthCheckResponse, Status>> {
+    inbound.map(move |_| {
+        let status = if running.load(Ordering::Relaxed) && handle.is_accepting() {
+            ServingStatus::Serving
+        } else {
+            ServingStatus::NotServing
+        };
+        Ok(HealthCheckResponse {
+            status: status.into(),
+        })
+    })
+}
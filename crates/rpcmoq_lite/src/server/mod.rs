@@ -4,11 +4,17 @@
 //! servers that bridge MoQ clients to gRPC backends.
 
 mod config;
+mod handle;
 mod handler;
+mod health;
+mod observer;
 mod router;
 mod session;
 
-pub use config::RpcRouterConfig;
-pub use handler::DecodedInbound;
+pub use config::{DecodeErrorPolicy, ResponsePathConflict, RpcRouterConfig};
+pub use handle::RpcRouterHandle;
+pub use handler::{DecodedInbound, FallibleDecodedInbound, RawDecodedInbound};
+pub use health::{HEALTH_CHECK_PATH, HealthCheckRequest, HealthCheckResponse, ServingStatus};
+pub use observer::{SessionEndReason, SessionObserver};
 pub use router::RpcRouter;
-pub use session::{SessionGuard, SessionKey, SessionMap};
+pub use session::{SessionContext, SessionGuard, SessionKey, SessionMap};
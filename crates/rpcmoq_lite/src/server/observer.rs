@@ -0,0 +1,42 @@
+use crate::server::session::SessionContext;
+
+/// Why a session was torn down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SessionEndReason {
+    /// The gRPC response stream finished normally.
+    Completed,
+
+    /// The connector or the gRPC response stream returned an error.
+    Grpc,
+
+    /// A response could not be written back to MoQ.
+    Internal,
+
+    /// No inbound frame arrived within the configured idle timeout.
+    IdleTimeout,
+
+    /// The session was ended from outside, e.g. with `RpcRouterHandle::terminate_session`.
+    Terminated,
+}
+
+/// Receives lifecycle events for sessions managed by the `RpcRouter`.
+///
+/// All methods have empty default implementations, so implementors only need
+/// to override the events they care about.
+pub trait SessionObserver: Send + Sync {
+    /// Called once a handler has been spawned for a new session.
+    fn on_session_started(&self, _session: &SessionContext) {}
+
+    /// Called when a session's handler task finishes and its guard is dropped.
+    fn on_session_ended(&self, _session: &SessionContext, _reason: SessionEndReason) {}
+
+    /// Called just before `on_session_ended` if the session dropped request frames over the
+    /// router's maximum inbound rate, with how many it dropped.
+    fn on_inbound_throttled(&self, _session: &SessionContext, _dropped: u64) {}
+
+    /// Called just before `on_session_ended` if request groups were skipped while the router's
+    /// maximum inbound rate held a frame back, with how many were skipped. Their frames were
+    /// never read.
+    fn on_inbound_groups_skipped(&self, _session: &SessionContext, _groups: u64) {}
+}
@@ -1,52 +1,217 @@
-use futures::Stream;
-use moq_lite::{BroadcastConsumer, OriginConsumer, OriginProducer, Track};
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use moq_lite::{BroadcastConsumer, BroadcastProducer, OriginConsumer, OriginProducer, Track};
 use std::collections::HashMap;
 use std::future::Future;
-use std::sync::Arc;
-use tonic::Status;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tonic::{Extensions, Status};
 use tracing::{debug, info, warn};
 
+use crate::codec::{MessageCodec, PROST_CODEC_ID, ProstCodec};
 use crate::connection::{RpcInbound, RpcOutbound};
 use crate::error::{RpcServerError, RpcWireError};
-use crate::path::RpcRequestPath;
-use crate::server::config::RpcRouterConfig;
+use crate::path::{GrpcPath, RpcRequestPath};
+use crate::reflection::{ListMethodsRequest, ListMethodsResponse, REFLECTION_PATH};
+use crate::server::config::{ResponsePathConflict, RpcRouterConfig};
+use crate::server::handle::{AddedOrigin, RpcRouterHandle};
 use crate::server::handler::{
-    ConnectionGuard, DecodedInbound, ErasedHandler, TypedHandler, make_connector,
+    ConnectionGuard, DecodedInbound, ErasedHandler, SessionOptions, TypedHandler, decode_with,
+    make_connector,
 };
-use crate::server::session::{SessionKey, SessionMap};
+use crate::server::health::{
+    HEALTH_CHECK_PATH, HealthCheckRequest, HealthCheckResponse, health_responses,
+};
+use crate::server::observer::SessionObserver;
+use crate::server::session::{SessionContext, SessionKey, SessionMap};
+use crate::stats::{RouteStats, RouterStats};
 
 /// The main RPC router that manages connections and dispatches to handlers.
 pub struct RpcRouter {
-    consumer: OriginConsumer,
-    producer: Arc<OriginProducer>,
+    // Every origin the router accepts clients on, in the order they were added
+    origins: Vec<AddedOrigin>,
+    // Origins added through the handle, see `RpcRouterHandle::add_origin`
+    added_origins: mpsc::UnboundedReceiver<AddedOrigin>,
     sessions: Arc<SessionMap>,
-    handlers: HashMap<String, Arc<dyn ErasedHandler>>,
+    handlers: HandlerMap,
     config: RpcRouterConfig,
+    hooks: SessionHooks,
+    stats: RouterStats,
+    // True while `run` is processing announcements.
+    running: Arc<AtomicBool>,
+    handle: RpcRouterHandle,
+}
+
+/// Registered handlers keyed by gRPC path, shared with the reflection service.
+type HandlerMap = Arc<RwLock<HashMap<String, Arc<dyn ErasedHandler>>>>;
+
+/// A callback that populates the extensions of a newly created session.
+type ExtensionsFn = Arc<dyn Fn(&SessionKey, &mut Extensions) + Send + Sync>;
+
+/// A callback that decides whether a client may open a session.
+type AuthorizeFn = Arc<dyn Fn(&SessionKey) -> bool + Send + Sync>;
+
+/// How long a rejected client's response broadcast stays up at most, so the client has time
+/// to subscribe and read why it was rejected.
+const REJECTION_LINGER: Duration = Duration::from_secs(1);
+
+/// Abort a rejected client's response track with `code`, explained by `err` if error frames
+/// are enabled.
+///
+/// The response broadcast is dropped once the client's `request` broadcast goes away, so a
+/// client retrying after reading the rejection finds the path free again.
+fn reject(
+    outbound: &RpcOutbound,
+    response_broadcast: BroadcastProducer,
+    request: BroadcastConsumer,
+    code: RpcWireError,
+    err: &RpcServerError,
+) {
+    outbound.abort_with_error(code.to_code_in(outbound.code_space()), err.to_string());
+    tokio::spawn(async move {
+        let _ = tokio::time::timeout(REJECTION_LINGER, request.closed()).await;
+        drop(response_broadcast);
+    });
+}
+
+/// What an origin's announcement stream yields, tagged with the origin's index: an
+/// announcement, or `None` once the origin has closed.
+type OriginAnnouncement = (
+    usize,
+    Option<(moq_lite::PathOwned, Option<BroadcastConsumer>)>,
+);
+
+/// The announcements of client broadcasts on `consumer`, the origin at index `origin`.
+fn origin_announcements(
+    config: &RpcRouterConfig,
+    origin: usize,
+    consumer: OriginConsumer,
+) -> Result<BoxStream<'static, OriginAnnouncement>, RpcServerError> {
+    // A namespace varies between announcements, so the prefix below it can't be a root.
+    let consumer = match &config.client_prefix {
+        Some(_) if config.root_depth > 0 => consumer,
+        Some(prefix) => consumer.with_root(prefix).ok_or_else(|| {
+            RpcServerError::Unauthorized(format!("prefix '{prefix}' not authorized"))
+        })?,
+        None => consumer,
+    };
+    Ok(
+        futures::stream::unfold(consumer, |mut consumer| async move {
+            let announced = consumer.announced().await?;
+            Some((announced, consumer))
+        })
+        .map(Some)
+        .chain(futures::stream::once(async { None }))
+        .map(move |announced| (origin, announced))
+        .boxed(),
+    )
+}
+
+/// The handler for `grpc_path` and the path it was registered under.
+///
+/// A versioned path prefers a handler registered for its version and falls back to the
+/// unversioned one, so unversioned handlers serve every version without one of its own.
+fn resolve_handler(
+    handlers: &HandlerMap,
+    grpc_path: &GrpcPath,
+) -> Option<(String, Arc<dyn ErasedHandler>)> {
+    let handlers = handlers.read().expect("handler map lock poisoned");
+    let exact = grpc_path.full_path();
+    if let Some(handler) = handlers.get(&exact) {
+        return Some((exact, Arc::clone(handler)));
+    }
+
+    grpc_path.version()?;
+    let unversioned = grpc_path.unversioned_path();
+    let handler = Arc::clone(handlers.get(&unversioned)?);
+    Some((unversioned, handler))
+}
+
+/// Router-level callbacks applied to every new session.
+#[derive(Default)]
+struct SessionHooks {
+    observer: Option<Arc<dyn SessionObserver>>,
+    extensions: Option<ExtensionsFn>,
+    authorize: Option<AuthorizeFn>,
 }
 
 impl RpcRouter {
     /// Create a new RPC router.
+    ///
+    /// An invalid `config` is rejected with [`RpcServerError::Config`], see
+    /// [`RpcRouterConfig::validated`].
     pub fn new(
         consumer: OriginConsumer,
         producer: Arc<OriginProducer>,
         config: RpcRouterConfig,
-    ) -> Self {
-        Self {
-            consumer,
-            producer,
-            sessions: Arc::new(SessionMap::new()),
-            handlers: HashMap::new(),
-            config,
-        }
+    ) -> Result<Self, RpcServerError> {
+        let sessions = Arc::new(SessionMap::new());
+        let (origins, added_origins) = mpsc::unbounded_channel();
+        Ok(Self {
+            origins: vec![(consumer, producer)],
+            added_origins,
+            handle: RpcRouterHandle::new(Arc::clone(&sessions), origins),
+            sessions,
+            handlers: HandlerMap::default(),
+            config: config.validated()?,
+            hooks: SessionHooks::default(),
+            stats: RouterStats::default(),
+            running: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Also accept clients announcing on another origin, e.g. a session with a second relay.
+    ///
+    /// Responses are published on the origin the request came from. All origins share the
+    /// router's handlers and session limit, while [`SessionKey::origin`] keeps clients with the
+    /// same id on different origins apart. A running router takes more origins through
+    /// [`RpcRouterHandle::add_origin`], e.g. to reconnect to a relay whose session closed.
+    pub fn with_origin(mut self, consumer: OriginConsumer, producer: Arc<OriginProducer>) -> Self {
+        self.origins.push((consumer, producer));
+        self
+    }
+
+    /// Attach an observer that is notified when sessions start and end.
+    pub fn with_observer(mut self, observer: impl SessionObserver + 'static) -> Self {
+        self.hooks.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Only let clients open a session when `f` returns true for its key, rejecting the rest
+    /// with `RpcWireError::Unauthorized`.
+    pub fn with_authorizer<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&SessionKey) -> bool + Send + Sync + 'static,
+    {
+        self.hooks.authorize = Some(Arc::new(f));
+        self
+    }
+
+    /// Populate the per-session extensions when a session is created.
+    ///
+    /// The values are readable from the connector and observer via `SessionContext`.
+    pub fn with_session_extensions<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&SessionKey, &mut Extensions) + Send + Sync + 'static,
+    {
+        self.hooks.extensions = Some(Arc::new(f));
+        self
     }
 
     /// Register a handler for a specific gRPC path.
     ///
+    /// The path may carry a version, as in `drone.EchoService/Echo@v2`. Clients connecting to
+    /// a versioned path are served by the handler registered for that version, or by the
+    /// unversioned handler if there is none.
+    ///
     /// # Example
     /// ```ignore
     /// router.register::<DronePosition, DronePosition, _, _, _>(
     ///     "drone.EchoService/Echo",
-    ///     |client_id, inbound| async move {
+    ///     |_session, inbound| async move {
     ///         let mut client = EchoServiceClient::connect(GRPC_ADDR).await
     ///             .map_err(|e| tonic::Status::internal(e.to_string()))?;
     ///         let response = client.echo(inbound.into_ok_stream()).await?;
@@ -61,90 +226,376 @@ impl RpcRouter {
     ) -> Result<(), RpcServerError>
     where
         Req: prost::Message + Default + Send + 'static,
-        Resp: prost::Message + Send + 'static,
-        F: Fn(String, DecodedInbound<Req>) -> Fut + Send + Sync + 'static,
+        Resp: prost::Message + Default + Send + 'static,
+        F: Fn(&SessionContext, DecodedInbound<Req>) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = Result<S, Status>> + Send + 'static,
         S: Stream<Item = Result<Resp, Status>> + Send + 'static,
     {
-        let grpc_path = grpc_path.into();
-        let boxed_connector = make_connector(connector);
-        let handler = TypedHandler::<Req, Resp>::new(boxed_connector);
-        self.handlers.insert(grpc_path.clone(), Arc::new(handler));
+        self.register_with_codec::<Req, Resp, ProstCodec, F, Fut, S>(grpc_path, connector)
+    }
 
-        info!(grpc_path = %grpc_path, "Registered RPC handler");
+    /// Register a handler for a specific gRPC path, passing the parsed path to the connector.
+    ///
+    /// This lets one connector serve several methods and dispatch on
+    /// [`GrpcPath::method`]. Unlike [`register`](Self::register), the path is validated up front
+    /// and a malformed one is rejected with [`RpcServerError::Path`].
+    ///
+    /// # Example
+    /// ```ignore
+    /// let connector = |_session: &SessionContext, path: &GrpcPath, inbound: DecodedInbound<Echo>| {
+    ///     let shout = path.method == "Shout";
+    ///     async move {
+    ///         Ok(inbound.map(move |mut msg| {
+    ///             if shout {
+    ///                 msg.text = msg.text.to_uppercase();
+    ///             }
+    ///             Ok(msg)
+    ///         }))
+    ///     }
+    /// };
+    /// router.register_with_path("drone.EchoService/Echo", connector)?;
+    /// router.register_with_path("drone.EchoService/Shout", connector)?;
+    /// ```
+    pub fn register_with_path<Req, Resp, F, Fut, S>(
+        &mut self,
+        grpc_path: impl AsRef<str>,
+        connector: F,
+    ) -> Result<(), RpcServerError>
+    where
+        Req: prost::Message + Default + Send + 'static,
+        Resp: prost::Message + Default + Send + 'static,
+        F: Fn(&SessionContext, &GrpcPath, DecodedInbound<Req>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<S, Status>> + Send + 'static,
+        S: Stream<Item = Result<Resp, Status>> + Send + 'static,
+    {
+        let grpc_path = GrpcPath::parse(grpc_path.as_ref())?;
+        let full_path = grpc_path.full_path();
+        self.register::<Req, Resp, _, _, _>(full_path, move |session, inbound| {
+            connector(session, &grpc_path, inbound)
+        })
+    }
+
+    /// Register a handler for a specific gRPC path whose connector shares `state` across sessions.
+    ///
+    /// Every session is handed the same `state`, so something expensive to set up, like a gRPC
+    /// channel to the backend, is created once and reused instead of being rebuilt or cloned into
+    /// each connector.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let channel = Endpoint::from_static(GRPC_ADDR).connect_lazy();
+    /// router.register_with_state::<Channel, DronePosition, DronePosition, _, _, _>(
+    ///     "drone.EchoService/Echo",
+    ///     Arc::new(channel),
+    ///     |channel, _session, inbound| async move {
+    ///         let mut client = EchoServiceClient::new((*channel).clone());
+    ///         let response = client.echo(inbound).await?;
+    ///         Ok(response.into_inner())
+    ///     },
+    /// )?;
+    /// ```
+    pub fn register_with_state<State, Req, Resp, F, Fut, S>(
+        &mut self,
+        grpc_path: impl Into<String>,
+        state: Arc<State>,
+        connector: F,
+    ) -> Result<(), RpcServerError>
+    where
+        State: Send + Sync + 'static,
+        Req: prost::Message + Default + Send + 'static,
+        Resp: prost::Message + Default + Send + 'static,
+        F: Fn(Arc<State>, &SessionContext, DecodedInbound<Req>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<S, Status>> + Send + 'static,
+        S: Stream<Item = Result<Resp, Status>> + Send + 'static,
+    {
+        self.register::<Req, Resp, _, _, _>(grpc_path, move |session, inbound| {
+            connector(Arc::clone(&state), session, inbound)
+        })
+    }
+
+    /// Register a handler for a specific gRPC path, encoding messages with codec `C`.
+    ///
+    /// Clients must connect to the path with the same codec, e.g. via
+    /// `RpcClient::connect_with_codec`.
+    pub fn register_with_codec<Req, Resp, C, F, Fut, S>(
+        &mut self,
+        grpc_path: impl Into<String>,
+        connector: F,
+    ) -> Result<(), RpcServerError>
+    where
+        Req: Send + 'static,
+        Resp: Send + 'static,
+        C: MessageCodec<Req> + MessageCodec<Resp>,
+        F: Fn(&SessionContext, DecodedInbound<Req, C>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<S, Status>> + Send + 'static,
+        S: Stream<Item = Result<Resp, Status>> + Send + 'static,
+    {
+        let handler = TypedHandler::<Req, Resp, C>::new(make_connector(connector));
+        self.insert_handler(grpc_path.into(), Arc::new(handler));
         Ok(())
     }
 
+    /// Register a handler for a specific gRPC path whose protobuf requests are decoded with
+    /// `decoder` instead of `Req::decode`.
+    ///
+    /// `Req` need not be a prost message, so this serves request types [`ProstCodec`] can't
+    /// decode, say because they have no meaningful `Default`. The decoder may also reject
+    /// requests that decode fine but are not valid, see [`DecodedInbound::with_decoder`].
+    /// Clients send requests as usual and may decode responses the same way with
+    /// `RpcClient::connect_with_decoder`.
+    pub fn register_with_decoder<Req, Resp, D, E, F, Fut, S>(
+        &mut self,
+        grpc_path: impl Into<String>,
+        decoder: D,
+        connector: F,
+    ) -> Result<(), RpcServerError>
+    where
+        Req: Send + 'static,
+        Resp: prost::Message + Default + Send + 'static,
+        D: Fn(Bytes) -> Result<Req, E> + Send + Sync + 'static,
+        E: std::fmt::Display,
+        F: Fn(&SessionContext, DecodedInbound<Req>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<S, Status>> + Send + 'static,
+        S: Stream<Item = Result<Resp, Status>> + Send + 'static,
+    {
+        let handler = TypedHandler::<Req, Resp>::with_decoder(
+            make_connector(connector),
+            PROST_CODEC_ID,
+            decode_with(decoder),
+        );
+        self.insert_handler(grpc_path.into(), Arc::new(handler));
+        Ok(())
+    }
+
+    fn insert_handler(&mut self, grpc_path: String, handler: Arc<dyn ErasedHandler>) {
+        self.handlers
+            .write()
+            .expect("handler map lock poisoned")
+            .insert(grpc_path.clone(), handler);
+        self.stats.route(&grpc_path);
+
+        info!(grpc_path = %grpc_path, "Registered RPC handler");
+    }
+
+    /// Register the built-in health check at [`HEALTH_CHECK_PATH`].
+    ///
+    /// Every `HealthCheckRequest` is answered with `ServingStatus::Serving` while
+    /// the router is accepting connections and `NotServing` once it has stopped or
+    /// while it is in maintenance, see [`RpcRouterHandle::set_accepting`].
+    pub fn enable_health_service(&mut self) -> Result<(), RpcServerError> {
+        let running = Arc::clone(&self.running);
+        let handle = self.handle.clone();
+        self.register::<HealthCheckRequest, HealthCheckResponse, _, _, _>(
+            HEALTH_CHECK_PATH,
+            move |_session, inbound| {
+                // An empty HealthCheckRequest encodes to zero bytes
+                let inbound = inbound.with_min_frame_len(0);
+                let responses = health_responses(Arc::clone(&running), handle.clone(), inbound);
+                async move { Ok(responses) }
+            },
+        )
+    }
+
+    /// Register the built-in reflection service at [`REFLECTION_PATH`].
+    ///
+    /// Every `ListMethodsRequest` is answered with the gRPC paths that have a
+    /// handler at the time of the request, including the reflection service itself.
+    pub fn enable_reflection(&mut self) -> Result<(), RpcServerError> {
+        let handlers = Arc::clone(&self.handlers);
+        self.register::<ListMethodsRequest, ListMethodsResponse, _, _, _>(
+            REFLECTION_PATH,
+            move |_session, inbound| {
+                let handlers = Arc::clone(&handlers);
+                // ListMethodsRequest has no fields, so it always encodes to zero bytes
+                let responses = inbound.with_min_frame_len(0).map(move |_| {
+                    let handlers = handlers.read().expect("handler map lock poisoned");
+                    Ok(ListMethodsResponse::from_paths(handlers.keys()))
+                });
+                async move { Ok(responses) }
+            },
+        )
+    }
+
     /// Run the router, processing connections until shutdown.
     ///
-    /// This method consumes the router and runs until the consumer is closed
-    /// or a fatal error occurs. Handler tasks continue to run independently.
+    /// This method consumes the router and runs until the consumers of all its origins are
+    /// closed or a fatal error occurs. Handler tasks continue to run independently.
     pub async fn run(self) -> Result<(), RpcServerError> {
-        // Extract fields we need before consuming consumer
-        let producer = self.producer;
-        let sessions = self.sessions;
-        let handlers = self.handlers;
+        // Extract fields we need before consuming the origins
         let config = self.config;
+        let running = self.running;
+        let mut added_origins = self.added_origins;
 
-        let mut announcements = match &config.client_prefix {
-            Some(prefix) => self.consumer.with_root(prefix).ok_or_else(|| {
-                RpcServerError::Unauthorized(format!("prefix '{prefix}' not authorized"))
-            })?,
-            None => self.consumer,
-        };
+        // Indexed by origin, and emptied once an origin closes
+        let mut producers = Vec::with_capacity(self.origins.len());
+        let mut streams = Vec::with_capacity(self.origins.len());
+        for (origin, (consumer, producer)) in self.origins.into_iter().enumerate() {
+            streams.push(origin_announcements(&config, origin, consumer)?);
+            producers.push(Some(producer));
+        }
+        let mut announcements = futures::stream::select_all(streams);
 
         info!(
             prefix = ?config.client_prefix,
+            origins = producers.len(),
             "RPC router started, listening for announcements"
         );
+        let dispatch = Dispatch {
+            sessions: self.sessions,
+            handlers: self.handlers,
+            config,
+            hooks: self.hooks,
+            stats: self.stats,
+            handle: self.handle,
+        };
+        running.store(true, Ordering::Relaxed);
 
         loop {
-            match announcements.announced().await {
-                Some((path, Some(broadcast))) => {
+            let announcement = tokio::select! {
+                // Ahead of the announcements, which end as soon as the last origin closes
+                biased;
+                Some((consumer, producer)) = added_origins.recv() => {
+                    let origin = producers.len();
+                    match origin_announcements(&dispatch.config, origin, consumer) {
+                        Ok(stream) => {
+                            info!(origin, "Added origin");
+                            announcements.push(stream);
+                            producers.push(Some(producer));
+                        }
+                        Err(e) => warn!(origin, error = %e, "Failed to add origin"),
+                    }
+                    continue;
+                }
+                announcement = announcements.next() => announcement,
+            };
+
+            match announcement {
+                Some((origin, Some((path, Some(broadcast))))) => {
                     let path_str = path.to_string();
-                    debug!(path = %path_str, "Received announcement");
+                    debug!(path = %path_str, origin, "Received announcement");
 
-                    if let Err(e) = Self::handle_announcement(
-                        &producer, &sessions, &handlers, &config, &path_str, broadcast,
-                    ) {
-                        warn!(path = %path_str, error = %e, "Failed to handle announcement");
+                    let Some(producer) = &producers[origin] else {
+                        continue;
+                    };
+                    if let Err(e) =
+                        dispatch.handle_announcement(producer, origin, &path_str, broadcast)
+                    {
+                        warn!(path = %path_str, origin, error = %e, "Failed to handle announcement");
                     }
                 }
 
-                Some((path, None)) => {
-                    debug!(path = %path.to_string(), "Client disconnected");
+                Some((origin, Some((path, None)))) => {
+                    debug!(path = %path.to_string(), origin, "Client disconnected");
                     // Session cleanup happens automatically via SessionGuard drop
                 }
 
+                Some((origin, None)) => {
+                    // Nothing reconnects on its own; see `RpcRouterHandle::add_origin`
+                    warn!(origin, "Origin closed, no longer accepting clients on it");
+                    producers[origin] = None;
+                }
+
                 None => {
-                    info!("Announcement stream closed, router shutting down");
+                    info!("Announcement streams closed, router shutting down");
                     break;
                 }
             }
         }
+        running.store(false, Ordering::Relaxed);
 
         Ok(())
     }
 
+    /// Get the number of active sessions.
+    pub fn active_sessions(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Get the keys of all active sessions as `(client_id, grpc_path)` pairs.
+    pub fn active_session_keys(&self) -> Vec<SessionKey> {
+        self.sessions.snapshot()
+    }
+
+    /// Get a snapshot of the traffic counters of every registered route, keyed by gRPC path.
+    pub fn stats(&self) -> HashMap<String, RouteStats> {
+        self.stats.snapshot()
+    }
+
+    /// Reset the traffic counters of every route to zero.
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
+    /// Get a handle to the traffic counters that stays valid after [`run`](Self::run)
+    /// consumes the router.
+    pub fn stats_handle(&self) -> RouterStats {
+        self.stats.clone()
+    }
+
+    /// Get a handle for controlling the router that stays valid after [`run`](Self::run)
+    /// consumes it.
+    pub fn handle(&self) -> RpcRouterHandle {
+        self.handle.clone()
+    }
+
+    /// Check if a handler is registered for the given path.
+    pub fn has_handler(&self, grpc_path: &str) -> bool {
+        self.handlers
+            .read()
+            .expect("handler map lock poisoned")
+            .contains_key(grpc_path)
+    }
+}
+
+/// What the router needs to handle an announcement, taken from it when it starts running.
+struct Dispatch {
+    sessions: Arc<SessionMap>,
+    handlers: HandlerMap,
+    config: RpcRouterConfig,
+    hooks: SessionHooks,
+    stats: RouterStats,
+    handle: RpcRouterHandle,
+}
+
+impl Dispatch {
     /// Handle a new client announcement.
     fn handle_announcement(
+        &self,
         producer: &Arc<OriginProducer>,
-        sessions: &Arc<SessionMap>,
-        handlers: &HashMap<String, Arc<dyn ErasedHandler>>,
-        config: &RpcRouterConfig,
+        origin: usize,
         path: &str,
         broadcast: BroadcastConsumer,
     ) -> Result<(), RpcServerError> {
-        let (client_id, grpc_path) = match RpcRequestPath::parse(path) {
-            Ok(request_path) => (
-                request_path.client_id.clone(),
-                request_path.grpc_path.full_path(),
-            ),
+        let Self {
+            sessions,
+            handlers,
+            config,
+            hooks,
+            stats,
+            handle,
+        } = self;
+        let Some((namespace, path)) = config.split_root(path) else {
+            // Everything below the namespace is announced here, including our own responses.
+            debug!(path, "Ignoring announcement outside the client prefix");
+            return Ok(());
+        };
+        let (client_id, parsed_path) = match RpcRequestPath::parse(path) {
+            Ok(request_path) => (request_path.client_id, request_path.grpc_path),
             Err(e) => return Err(e.into()),
         };
+        let grpc_path = parsed_path.full_path();
 
         // Create the response broadcast early so we can surface errors like "no handler".
-        let response_path = config.response_path(&client_id, &grpc_path);
+        let response_path = config.response_path(namespace, &client_id, &grpc_path);
+        let client_id = if namespace.is_empty() {
+            client_id
+        } else {
+            format!("{namespace}/{client_id}")
+        };
+        let existing = producer
+            .consume()
+            .consume_broadcast(&response_path)
+            .is_some();
         let mut response_broadcast =
             producer.create_broadcast(&response_path).ok_or_else(|| {
                 RpcServerError::BroadcastCreate(format!(
@@ -152,30 +603,152 @@ impl RpcRouter {
                 ))
             })?;
 
-        let outbound_track = response_broadcast.create_track(Track::new(&config.track_name));
-        let outbound = RpcOutbound::new(outbound_track);
+        let track_name = config.track_name_for(&grpc_path);
+        let outbound_track = response_broadcast.create_track(Track::new(track_name));
+        let outbound = RpcOutbound::new(outbound_track)
+            .with_broadcast(response_broadcast.clone())
+            .with_compression(config.compression)
+            .with_error_frames(config.error_frames)
+            .with_status_trailers(config.status_trailers)
+            .with_code_space(config.code_space);
 
-        let handler = handlers.get(&grpc_path).ok_or_else(|| {
+        let Some((route, handler)) = resolve_handler(handlers, &parsed_path) else {
             warn!(
                 client_id = %client_id,
                 grpc_path = %grpc_path,
                 "No handler registered for gRPC path"
             );
-            outbound.abort_app(RpcWireError::NoHandler.to_code());
-            RpcServerError::NoHandler(grpc_path.clone())
-        })?;
+            let err = RpcServerError::NoHandler(grpc_path.clone());
+            reject(
+                &outbound,
+                response_broadcast,
+                broadcast,
+                RpcWireError::NoHandler,
+                &err,
+            );
+            return Err(err);
+        };
+
+        // Keyed on the route, so a client can't open a second session by naming another version
+        let session_key = SessionKey::new(&client_id, &route).with_origin(origin);
+        // A broadcast with a session behind it is that session's, and refused as a duplicate below
+        if existing && !sessions.contains(&session_key) {
+            match config.on_response_path_conflict {
+                ResponsePathConflict::Replace => warn!(
+                    client_id = %client_id,
+                    response_path = %response_path,
+                    "Leftover response broadcast found, published over it"
+                ),
+                ResponsePathConflict::Reject => {
+                    warn!(
+                        client_id = %client_id,
+                        response_path = %response_path,
+                        "Leftover response broadcast found, rejecting client"
+                    );
+                    let err = RpcServerError::BroadcastCreate(format!(
+                        "a leftover broadcast exists at '{response_path}'"
+                    ));
+                    reject(
+                        &outbound,
+                        response_broadcast,
+                        broadcast,
+                        RpcWireError::SessionAlreadyActive,
+                        &err,
+                    );
+                    return Err(err);
+                }
+            }
+        }
+        if let Some(authorize) = &hooks.authorize
+            && !authorize(&session_key)
+        {
+            warn!(
+                client_id = %client_id,
+                grpc_path = %grpc_path,
+                "Client not authorized, rejecting"
+            );
+            let err = RpcServerError::Unauthorized(format!(
+                "client '{client_id}' may not call '{grpc_path}'"
+            ));
+            reject(
+                &outbound,
+                response_broadcast,
+                broadcast,
+                RpcWireError::Unauthorized,
+                &err,
+            );
+            return Err(err);
+        }
+
+        // Health checks still get through, to be told the router is not serving
+        if !handle.is_accepting() && route != HEALTH_CHECK_PATH {
+            info!(
+                client_id = %client_id,
+                grpc_path = %grpc_path,
+                "Router is in maintenance, rejecting client"
+            );
+            let err = RpcServerError::Maintenance;
+            reject(
+                &outbound,
+                response_broadcast,
+                broadcast,
+                RpcWireError::Maintenance,
+                &err,
+            );
+            return Err(err);
+        }
 
         // Try to create a session (prevents duplicate connections)
-        let session_key = SessionKey::new(&client_id, &grpc_path);
-        let session_guard = match sessions.try_create(session_key) {
+        let mut extensions = Extensions::new();
+        if let Some(init) = &hooks.extensions {
+            init(&session_key, &mut extensions);
+        }
+        let created = sessions.try_create_within(session_key, extensions, config.max_sessions);
+        let session_guard = match created {
             Ok(guard) => guard,
-            Err(e @ RpcServerError::SessionAlreadyActive { .. }) => {
-                outbound.abort_app(RpcWireError::SessionAlreadyActive.to_code());
+            Err(e) => {
+                let code = match &e {
+                    RpcServerError::SessionAlreadyActive { .. } => {
+                        RpcWireError::SessionAlreadyActive
+                    }
+                    RpcServerError::Overloaded { max_sessions } => {
+                        warn!(
+                            client_id = %client_id,
+                            grpc_path = %grpc_path,
+                            max_sessions,
+                            "Router is at its session limit, rejecting client"
+                        );
+                        RpcWireError::Overloaded
+                    }
+                    _ => return Err(e),
+                };
+                reject(&outbound, response_broadcast, broadcast, code, &e);
                 return Err(e);
             }
-            Err(e) => return Err(e),
         };
-        let inbound = RpcInbound::new(&broadcast, &config.track_name);
+        let mut inbound = RpcInbound::new(&broadcast, track_name)
+            .with_compression(config.compression)
+            .with_code_space(config.code_space);
+        if let Some(max) = config.max_frame_size {
+            inbound = inbound.with_max_frame_size(max);
+        }
+        if let Some(capacity) = config.inbound_buffer {
+            inbound = inbound.with_buffer(capacity, config.inbound_buffer_policy);
+        }
+        if let Some(per_second) = config.max_inbound_rate {
+            inbound = inbound.with_max_rate(per_second, config.inbound_rate_policy);
+        }
+        if let Some(timeout) = config.keepalive_timeout {
+            inbound = inbound.with_keepalive_timeout(timeout);
+        }
+        let mut outbound = match config.keepalive_interval {
+            Some(interval) => outbound.with_keepalive(interval),
+            None => outbound,
+        };
+        if config.size_stats {
+            inbound = inbound.with_size_stats();
+            outbound = outbound.with_size_stats();
+        }
 
         info!(
             client_id = %client_id,
@@ -189,18 +762,893 @@ impl RpcRouter {
             _response_broadcast: response_broadcast,
         };
 
-        handler.spawn_handler(client_id, inbound, outbound, connection_guard);
+        let options = SessionOptions {
+            idle_timeout: config.session_idle_timeout,
+            trace_propagation: config.trace_propagation,
+            observer: hooks.observer.clone(),
+            counters: stats.route(&route),
+            min_frame_len: config.min_frame_len,
+            drain_timeout: config.drain_timeout,
+            decode_error_policy: config.decode_error_policy,
+        };
+
+        handler.spawn_handler(inbound, outbound, connection_guard, options);
 
         Ok(())
     }
+}
 
-    /// Get the number of active sessions.
-    pub fn active_sessions(&self) -> usize {
-        self.sessions.len()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{RpcClient, RpcClientConfig, RpcConnection};
+    use crate::error::{RejectReason, RpcClientError};
+    use crate::server::health::ServingStatus;
+    use crate::test::router_and_client;
+    use futures::SinkExt;
+    use moq_lite::Origin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_health_service_reports_serving() {
+        let mut client = router_and_client(|router| router.enable_health_service().unwrap());
+
+        let mut conn = client
+            .connect::<HealthCheckRequest, HealthCheckResponse>(HEALTH_CHECK_PATH)
+            .await
+            .unwrap();
+        conn.send(HealthCheckRequest::default()).await.unwrap();
+
+        let response = tokio::time::timeout(Duration::from_secs(1), conn.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), ServingStatus::Serving);
     }
 
-    /// Check if a handler is registered for the given path.
-    pub fn has_handler(&self, grpc_path: &str) -> bool {
-        self.handlers.contains_key(grpc_path)
+    #[tokio::test]
+    async fn test_reflection_lists_registered_routes() {
+        let mut client = router_and_client(|router| {
+            router.enable_health_service().unwrap();
+            router.enable_reflection().unwrap();
+        });
+
+        let methods = client.list_methods().await.unwrap();
+        let full_paths: Vec<_> = methods.iter().map(GrpcPath::full_path).collect();
+        assert_eq!(full_paths, [HEALTH_CHECK_PATH, REFLECTION_PATH]);
+        assert_eq!(methods[0].package, "grpc.health.v1");
+        assert_eq!(methods[0].service, "Health");
+    }
+
+    #[tokio::test]
+    async fn test_versioned_handler_resolution() {
+        let (producer, consumer, _, _) = crate::test::loopback();
+        let mut router =
+            RpcRouter::new(consumer, Arc::new(producer), RpcRouterConfig::default()).unwrap();
+        let echo = |_: &SessionContext, inbound: DecodedInbound<String>| async move {
+            Ok(inbound.map(Ok))
+        };
+        router.register("drone.EchoService/Echo", echo).unwrap();
+        router.register("drone.EchoService/Echo@v2", echo).unwrap();
+        router.register("drone.EchoService/Shout@v2", echo).unwrap();
+
+        let resolve = |path: &str| {
+            resolve_handler(&router.handlers, &GrpcPath::parse(path).unwrap())
+                .map(|(route, _)| route)
+        };
+        assert_eq!(
+            resolve("drone.EchoService/Echo@v2").as_deref(),
+            Some("drone.EchoService/Echo@v2")
+        );
+        // Unversioned handlers serve any version without a handler of its own
+        assert_eq!(
+            resolve("drone.EchoService/Echo@v3").as_deref(),
+            Some("drone.EchoService/Echo")
+        );
+        assert_eq!(
+            resolve("drone.EchoService/Echo").as_deref(),
+            Some("drone.EchoService/Echo")
+        );
+        // But versioned handlers only serve their own version
+        assert_eq!(resolve("drone.EchoService/Shout"), None);
+        assert_eq!(resolve("drone.EchoService/Shout@v3"), None);
+    }
+
+    #[tokio::test]
+    async fn test_handlers_share_registered_state() {
+        let sessions = Arc::new(AtomicUsize::new(0));
+        let mut client = router_and_client(|router| {
+            let count = |sessions: Arc<AtomicUsize>,
+                         _: &SessionContext,
+                         inbound: DecodedInbound<String>| async move {
+                let n = sessions.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(inbound.map(move |msg| Ok(format!("{n} {msg}"))))
+            };
+            router
+                .register_with_state("drone.EchoService/Echo", Arc::clone(&sessions), count)
+                .unwrap();
+            router
+                .register_with_state("drone.EchoService/Shout", Arc::clone(&sessions), count)
+                .unwrap();
+        });
+
+        for (path, expected) in [
+            ("drone.EchoService/Echo", "1 hi"),
+            ("drone.EchoService/Shout", "2 hi"),
+        ] {
+            let mut conn = client.connect::<String, String>(path).await.unwrap();
+            conn.send("hi".to_string()).await.unwrap();
+            let reply = tokio::time::timeout(Duration::from_secs(1), conn.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            assert_eq!(reply, expected);
+        }
+        assert_eq!(sessions.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_client_reaches_versioned_handler() {
+        let mut client = router_and_client(|router| {
+            router
+                .register(
+                    "drone.EchoService/Echo",
+                    |_, inbound: DecodedInbound<String>| async move {
+                        Ok(inbound.map(|msg| Ok(format!("v1 {msg}"))))
+                    },
+                )
+                .unwrap();
+            router
+                .register(
+                    "drone.EchoService/Echo@v2",
+                    |_, inbound: DecodedInbound<String>| async move {
+                        Ok(inbound.map(|msg| Ok(format!("v2 {msg}"))))
+                    },
+                )
+                .unwrap();
+        });
+
+        for (path, expected) in [
+            ("drone.EchoService/Echo@v2", "v2 ping"),
+            ("drone.EchoService/Echo@v3", "v1 ping"),
+        ] {
+            let mut conn = client.connect::<String, String>(path).await.unwrap();
+            let echoed = crate::test::resend_until(
+                &mut conn,
+                async |conn| conn.send("ping".to_string()).await.unwrap(),
+                async |conn| conn.next().await,
+            )
+            .await;
+            assert_eq!(echoed.unwrap().unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_versions_of_one_route_share_a_session() {
+        let mut client = router_and_client(|router| {
+            router
+                .register(
+                    "drone.EchoService/Echo",
+                    |_, inbound: DecodedInbound<String>| async move { Ok(inbound.map(Ok)) },
+                )
+                .unwrap();
+        });
+
+        let mut v2 = client
+            .connect::<String, String>("drone.EchoService/Echo@v2")
+            .await
+            .unwrap();
+        let echoed = crate::test::resend_until(
+            &mut v2,
+            async |conn| conn.send("ping".to_string()).await.unwrap(),
+            async |conn| conn.next().await,
+        )
+        .await;
+        assert_eq!(echoed.unwrap().unwrap(), "ping");
+
+        // Both versions are served by the unversioned handler, so this is a duplicate
+        let mut v3 = client
+            .connect::<String, String>("drone.EchoService/Echo@v3")
+            .await
+            .unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(1), v3.next())
+            .await
+            .unwrap();
+        assert!(matches!(
+            next,
+            Some(Err(RpcClientError::Rejected {
+                reason: RejectReason::Duplicate
+            }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_routes_use_their_own_track_names() {
+        let (router_producer, router_consumer, client_producer, client_consumer) =
+            crate::test::loopback();
+
+        let config = RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .build()
+            .with_route_track_name(HEALTH_CHECK_PATH, "control")
+            .with_route_track_name("drone.EchoService/Echo", "bulk");
+        let mut router =
+            RpcRouter::new(router_consumer, Arc::new(router_producer), config).unwrap();
+        router.enable_health_service().unwrap();
+        router.enable_reflection().unwrap();
+        router
+            .register(
+                "drone.EchoService/Echo",
+                |_, inbound: DecodedInbound<String>| async move { Ok(inbound.map(Ok)) },
+            )
+            .unwrap();
+        tokio::spawn(router.run());
+
+        let config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .client_prefix("drone".to_string())
+            .server_prefix("server".to_string())
+            .timeout(Duration::from_secs(1))
+            .build()
+            .with_route_track_name(HEALTH_CHECK_PATH, "control")
+            .with_route_track_name("drone.EchoService/Echo", "bulk");
+        assert_eq!(config.track_name_for(HEALTH_CHECK_PATH), "control");
+        assert_eq!(config.track_name_for(REFLECTION_PATH), "primary");
+        assert_eq!(config.track_name_for("drone.EchoService/Echo@v2"), "bulk");
+        let mut client = RpcClient::new(Arc::new(client_producer), client_consumer.clone(), config);
+
+        let mut health = client
+            .connect::<HealthCheckRequest, HealthCheckResponse>(HEALTH_CHECK_PATH)
+            .await
+            .unwrap();
+        health.send(HealthCheckRequest::default()).await.unwrap();
+        let response = tokio::time::timeout(Duration::from_secs(1), health.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), ServingStatus::Serving);
+
+        let mut echo = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        let echoed = crate::test::resend_until(
+            &mut echo,
+            async |conn| conn.send("ping".to_string()).await.unwrap(),
+            async |conn| conn.next().await,
+        )
+        .await;
+        assert_eq!(echoed.unwrap().unwrap(), "ping");
+
+        // Each response broadcast carries its route's track and neither the default nor the
+        // other route's
+        for (path, own, others) in [
+            (HEALTH_CHECK_PATH, "control", ["primary", "bulk"]),
+            ("drone.EchoService/Echo", "bulk", ["primary", "control"]),
+        ] {
+            let responses = client_consumer
+                .consume_broadcast(format!("server/drone-1/{path}"))
+                .unwrap();
+            let mut inbound = RpcInbound::new(&responses, own);
+            let answered = tokio::time::timeout(Duration::from_secs(1), inbound.next()).await;
+            assert!(
+                matches!(answered, Ok(Some(Ok(_)))),
+                "{path} has no {own} track"
+            );
+            for other in others {
+                let mut inbound = RpcInbound::new(&responses, other);
+                let unanswered =
+                    tokio::time::timeout(Duration::from_millis(100), inbound.next()).await;
+                assert!(unanswered.is_err(), "{path} has a {other} track");
+            }
+        }
+
+        // Other routes keep the default track
+        let methods = client.list_methods().await.unwrap();
+        assert_eq!(methods.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_register_with_path_dispatches_on_method() {
+        let mut client = router_and_client(|router| {
+            let connector =
+                |_: &SessionContext,
+                 path: &GrpcPath,
+                 inbound: DecodedInbound<HealthCheckRequest>| {
+                    let status = match path.method.as_str() {
+                        "Check" => ServingStatus::Serving,
+                        _ => ServingStatus::NotServing,
+                    };
+                    let responses = inbound.with_min_frame_len(0).map(move |_| {
+                        Ok(HealthCheckResponse {
+                            status: status.into(),
+                        })
+                    });
+                    async move { Ok(responses) }
+                };
+            router
+                .register_with_path("test.Status/Check", connector)
+                .unwrap();
+            router
+                .register_with_path("test.Status/Drain", connector)
+                .unwrap();
+        });
+
+        for (path, expected) in [
+            ("test.Status/Check", ServingStatus::Serving),
+            ("test.Status/Drain", ServingStatus::NotServing),
+        ] {
+            let mut conn = client
+                .connect::<HealthCheckRequest, HealthCheckResponse>(path)
+                .await
+                .unwrap();
+            conn.send(HealthCheckRequest::default()).await.unwrap();
+
+            let response = tokio::time::timeout(Duration::from_secs(1), conn.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.status(), expected);
+        }
+    }
+
+    #[test]
+    fn test_register_with_path_rejects_malformed_path() {
+        let (producer, consumer, _, _) = crate::test::loopback();
+        let mut router = RpcRouter::new(
+            consumer,
+            Arc::new(producer),
+            RpcRouterConfig::builder().build(),
+        )
+        .unwrap();
+        let result = router.register_with_path(
+            "EchoService",
+            |_, _, inbound: DecodedInbound<HealthCheckRequest>| async move {
+                Ok(inbound.map(|_| Ok(HealthCheckResponse::default())))
+            },
+        );
+        assert!(matches!(result, Err(RpcServerError::Path(_))));
+        assert!(!router.has_handler("EchoService"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_clients_over_session_limit() {
+        let (router_producer, router_consumer, client_producer, client_consumer) =
+            crate::test::loopback();
+
+        let config = RpcRouterConfig::default()
+            .with_client_prefix("drone")
+            .with_response_prefix("server")
+            .with_max_sessions(1);
+        let mut router =
+            RpcRouter::new(router_consumer, Arc::new(router_producer), config).unwrap();
+        router.enable_health_service().unwrap();
+        router.enable_reflection().unwrap();
+        tokio::spawn(router.run());
+
+        let config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .client_prefix("drone".to_string())
+            .server_prefix("server".to_string())
+            .timeout(Duration::from_secs(1))
+            .build();
+        let mut client = RpcClient::new(Arc::new(client_producer), client_consumer, config);
+        let _health = client
+            .connect::<HealthCheckRequest, HealthCheckResponse>(HEALTH_CHECK_PATH)
+            .await
+            .unwrap();
+
+        let mut conn = client
+            .connect::<ListMethodsRequest, ListMethodsResponse>(REFLECTION_PATH)
+            .await
+            .unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(1), conn.next())
+            .await
+            .unwrap();
+        assert!(matches!(
+            next,
+            Some(Err(RpcClientError::Rejected {
+                reason: RejectReason::Overloaded
+            }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_leftover_response_broadcast_rejects_client() {
+        let (router_producer, router_consumer, client_producer, client_consumer) =
+            crate::test::loopback();
+        let config = RpcRouterConfig::default()
+            .with_client_prefix("drone")
+            .with_response_prefix("server")
+            .with_response_path_conflict(ResponsePathConflict::Reject);
+        let response_path = config.response_path("", "drone-1", HEALTH_CHECK_PATH);
+        let _leftover = router_producer.create_broadcast(&response_path).unwrap();
+        let mut router =
+            RpcRouter::new(router_consumer, Arc::new(router_producer), config).unwrap();
+        router.enable_health_service().unwrap();
+        let sessions = Arc::clone(&router.sessions);
+        tokio::spawn(router.run());
+
+        let mut announcements = client_consumer
+            .consume_only(&[moq_lite::Path::new(&response_path)])
+            .unwrap();
+        let (_, leftover) = announcements.announced().await.unwrap();
+        assert!(leftover.is_some());
+
+        // A client that has not picked up the leftover finds a rejection published over it
+        let mut request = client_producer
+            .create_broadcast(format!("drone/drone-1/{HEALTH_CHECK_PATH}"))
+            .unwrap();
+        let _track = request.create_track(Track::new("primary"));
+        let rejection = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                if let (_, Some(broadcast)) = announcements.announced().await.unwrap() {
+                    return broadcast;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        let mut inbound = RpcInbound::new(&rejection, "primary");
+        let next = tokio::time::timeout(Duration::from_secs(1), inbound.next())
+            .await
+            .unwrap();
+        assert!(matches!(
+            next,
+            Some(Err(moq_lite::Error::App(
+                RpcWireError::CODE_SESSION_ALREADY_ACTIVE
+            )))
+        ));
+        assert!(sessions.is_empty());
+
+        // The leftover is announced again once the client gives up
+        drop(request);
+        let reannounced = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                if let (_, Some(_)) = announcements.announced().await.unwrap() {
+                    return;
+                }
+            }
+        })
+        .await;
+        assert!(reannounced.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_client_retries_right_after_rejection() {
+        let (router_producer, router_consumer, client_producer, client_consumer) =
+            crate::test::loopback();
+
+        // A leftover response broadcast would be refused rather than published over
+        let config = RpcRouterConfig::default()
+            .with_client_prefix("drone")
+            .with_response_prefix("server")
+            .with_max_sessions(1)
+            .with_response_path_conflict(ResponsePathConflict::Reject);
+        let mut router =
+            RpcRouter::new(router_consumer, Arc::new(router_producer), config).unwrap();
+        router.enable_health_service().unwrap();
+        router.enable_reflection().unwrap();
+        tokio::spawn(router.run());
+
+        let config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .client_prefix("drone".to_string())
+            .server_prefix("server".to_string())
+            .timeout(Duration::from_secs(1))
+            .build();
+        let mut client = RpcClient::new(Arc::new(client_producer), client_consumer, config);
+        let health = client
+            .connect::<HealthCheckRequest, HealthCheckResponse>(HEALTH_CHECK_PATH)
+            .await
+            .unwrap();
+        let mut rejected = client
+            .connect::<ListMethodsRequest, ListMethodsResponse>(REFLECTION_PATH)
+            .await
+            .unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(1), rejected.next())
+            .await
+            .unwrap();
+        assert!(matches!(next, Some(Err(RpcClientError::Rejected { .. }))));
+        drop(rejected);
+        drop(health);
+
+        // Well within the time a rejection lingers for a client that stays
+        let methods = tokio::time::timeout(Duration::from_millis(500), async {
+            loop {
+                match client.list_methods().await {
+                    Ok(methods) => return methods,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(methods.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_authorizer_rejects_client_as_unauthorized() {
+        let (router_producer, router_consumer, client_producer, client_consumer) =
+            crate::test::loopback();
+        let config = RpcRouterConfig::default()
+            .with_client_prefix("drone")
+            .with_response_prefix("server");
+        let mut router = RpcRouter::new(router_consumer, Arc::new(router_producer), config)
+            .unwrap()
+            .with_authorizer(|key| key.client_id != "drone-1");
+        router.enable_health_service().unwrap();
+        tokio::spawn(router.run());
+        let origin = (client_producer, client_consumer);
+
+        let mut conn = crate::test::client(&origin, "drone-1")
+            .connect::<HealthCheckRequest, HealthCheckResponse>(HEALTH_CHECK_PATH)
+            .await
+            .unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(1), conn.next())
+            .await
+            .unwrap();
+        assert!(matches!(
+            next,
+            Some(Err(RpcClientError::Rejected {
+                reason: RejectReason::Unauthorized
+            }))
+        ));
+
+        let mut conn = crate::test::client(&origin, "drone-2")
+            .connect::<HealthCheckRequest, HealthCheckResponse>(HEALTH_CHECK_PATH)
+            .await
+            .unwrap();
+        conn.send(HealthCheckRequest::default()).await.unwrap();
+        let response = tokio::time::timeout(Duration::from_secs(1), conn.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), ServingStatus::Serving);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_rejects_only_new_clients() {
+        let mut handle = None;
+        let mut client = router_and_client(|router| {
+            let echo = |_: &SessionContext, inbound: DecodedInbound<String>| async move {
+                Ok(inbound.map(Ok))
+            };
+            for path in [
+                "drone.EchoService/Echo",
+                "drone.EchoService/Shout",
+                "drone.EchoService/Whisper",
+            ] {
+                router.register(path, echo).unwrap();
+            }
+            router.enable_health_service().unwrap();
+            handle = Some(router.handle());
+        });
+        let handle = handle.unwrap();
+        async fn next(
+            conn: &mut RpcConnection<String, String>,
+        ) -> Option<Result<String, RpcClientError>> {
+            tokio::time::timeout(Duration::from_secs(1), conn.next())
+                .await
+                .unwrap()
+        }
+
+        let mut active = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        active.send("one".to_string()).await.unwrap();
+        assert_eq!(next(&mut active).await.unwrap().unwrap(), "one");
+
+        handle.set_accepting(false);
+        let mut health = client
+            .connect::<HealthCheckRequest, HealthCheckResponse>(HEALTH_CHECK_PATH)
+            .await
+            .unwrap();
+        health.send(HealthCheckRequest::default()).await.unwrap();
+        let status = tokio::time::timeout(Duration::from_secs(1), health.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.status(), ServingStatus::NotServing);
+        let mut rejected = client
+            .connect::<String, String>("drone.EchoService/Shout")
+            .await
+            .unwrap();
+        assert!(matches!(
+            next(&mut rejected).await,
+            Some(Err(RpcClientError::Rejected {
+                reason: RejectReason::Maintenance
+            }))
+        ));
+        // The session from before maintenance is still served
+        active.send("two".to_string()).await.unwrap();
+        assert_eq!(next(&mut active).await.unwrap().unwrap(), "two");
+
+        handle.set_accepting(true);
+        let mut resumed = client
+            .connect::<String, String>("drone.EchoService/Whisper")
+            .await
+            .unwrap();
+        resumed.send("three".to_string()).await.unwrap();
+        assert_eq!(next(&mut resumed).await.unwrap().unwrap(), "three");
+    }
+
+    #[tokio::test]
+    async fn test_terminate_session_ends_client_connection() {
+        let mut handle = None;
+        let mut sessions = None;
+        let mut client = router_and_client(|router| {
+            router
+                .register(
+                    "drone.EchoService/Echo",
+                    |_, inbound: DecodedInbound<String>| async move { Ok(inbound.map(Ok)) },
+                )
+                .unwrap();
+            handle = Some(router.handle());
+            sessions = Some(Arc::clone(&router.sessions));
+        });
+        let (handle, sessions) = (handle.unwrap(), sessions.unwrap());
+
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        conn.send("ping".to_string()).await.unwrap();
+        let reply = tokio::time::timeout(Duration::from_secs(1), conn.next())
+            .await
+            .unwrap();
+        assert_eq!(reply.unwrap().unwrap(), "ping");
+
+        assert!(!handle.terminate_session("drone-2", "drone.EchoService/Echo"));
+        assert!(handle.terminate_session("drone-1", "drone.EchoService/Echo"));
+        let next = tokio::time::timeout(Duration::from_secs(1), conn.next())
+            .await
+            .unwrap();
+        assert!(
+            matches!(
+                next,
+                Some(Err(RpcClientError::Wire(RpcWireError::Terminated)))
+            ),
+            "{next:?}"
+        );
+
+        // The handler winds down and releases the session
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while !sessions.snapshot().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_terminate_session_spares_completed_response() {
+        let mut handle = None;
+        let mut sessions = None;
+        let mut client = router_and_client(|router| {
+            router
+                .register(
+                    "drone.EchoService/Echo",
+                    |_, mut inbound: DecodedInbound<String>| async move {
+                        let request = inbound.next().await.unwrap_or_default();
+                        Ok(futures::stream::once(async move { Ok(request) }))
+                    },
+                )
+                .unwrap();
+            handle = Some(router.handle());
+            sessions = Some(Arc::clone(&router.sessions));
+        });
+        let (handle, sessions) = (handle.unwrap(), sessions.unwrap());
+
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        conn.send("ping".to_string()).await.unwrap();
+        let reply = tokio::time::timeout(Duration::from_secs(1), conn.next())
+            .await
+            .unwrap();
+        assert_eq!(reply.unwrap().unwrap(), "ping");
+
+        // The session is released before the response is drained, which the held connection
+        // keeps going
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while !sessions.snapshot().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(!handle.terminate_session("drone-1", "drone.EchoService/Echo"));
+        let next = tokio::time::timeout(Duration::from_secs(2), conn.next())
+            .await
+            .unwrap();
+        assert!(next.is_none(), "{next:?}");
+    }
+
+    #[test]
+    fn test_new_rejects_empty_client_prefix() {
+        let (producer, consumer, _, _) = crate::test::loopback();
+        let config = RpcRouterConfig::default().with_client_prefix("/");
+        let result = RpcRouter::new(consumer, Arc::new(producer), config);
+        assert!(matches!(result, Err(RpcServerError::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_namespaced_client_id_with_slashes() {
+        let origin = Origin::produce();
+        let producer = Arc::new(origin.producer);
+
+        let config = RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .root_depth(2)
+            .build();
+        let mut router =
+            RpcRouter::new(origin.consumer.clone(), Arc::clone(&producer), config).unwrap();
+        router.enable_health_service().unwrap();
+        let sessions = Arc::clone(&router.sessions);
+        tokio::spawn(router.run());
+
+        let config = RpcClientConfig::builder()
+            .client_id("fleet/drone-1".to_string())
+            .client_prefix("us-east/acme/drone".to_string())
+            .server_prefix("us-east/acme/server".to_string())
+            .timeout(Duration::from_secs(1))
+            .build();
+        let mut client = RpcClient::new(producer, origin.consumer, config);
+
+        let mut conn = client
+            .connect::<HealthCheckRequest, HealthCheckResponse>(HEALTH_CHECK_PATH)
+            .await
+            .unwrap();
+        conn.send(HealthCheckRequest::default()).await.unwrap();
+        let response = tokio::time::timeout(Duration::from_secs(1), conn.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), ServingStatus::Serving);
+
+        let keys = sessions.snapshot();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].client_id, "us-east/acme/fleet/drone-1");
+        assert_eq!(keys[0].grpc_path, HEALTH_CHECK_PATH);
+    }
+
+    #[tokio::test]
+    async fn test_router_serves_several_origins() {
+        let (first_producer, first_consumer, first_client_producer, first_client_consumer) =
+            crate::test::loopback();
+        let (second_producer, second_consumer, second_client_producer, second_client_consumer) =
+            crate::test::loopback();
+
+        let config = RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .build();
+        let mut router = RpcRouter::new(first_consumer, Arc::new(first_producer), config)
+            .unwrap()
+            .with_origin(second_consumer, Arc::new(second_producer));
+        router.enable_health_service().unwrap();
+        let sessions = Arc::clone(&router.sessions);
+        tokio::spawn(router.run());
+
+        // The same client id on both origins must not collide
+        let clients = [
+            (first_client_producer, first_client_consumer),
+            (second_client_producer, second_client_consumer),
+        ];
+        let mut conns = Vec::new();
+        for (producer, consumer) in clients {
+            let config = RpcClientConfig::builder()
+                .client_id("drone-1".to_string())
+                .client_prefix("drone".to_string())
+                .server_prefix("server".to_string())
+                .timeout(Duration::from_secs(1))
+                .build();
+            let mut client = RpcClient::new(Arc::new(producer), consumer, config);
+            let mut conn = client
+                .connect::<HealthCheckRequest, HealthCheckResponse>(HEALTH_CHECK_PATH)
+                .await
+                .unwrap();
+            conn.send(HealthCheckRequest::default()).await.unwrap();
+            let response = tokio::time::timeout(Duration::from_secs(1), conn.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.status(), ServingStatus::Serving);
+            conns.push(conn);
+        }
+
+        let mut origins: Vec<_> = sessions.snapshot().iter().map(|key| key.origin).collect();
+        origins.sort();
+        assert_eq!(origins, [0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_running_router_takes_added_origin() {
+        let (first_producer, first_consumer, _, _) = crate::test::loopback();
+        let (second_producer, second_consumer, client_producer, client_consumer) =
+            crate::test::loopback();
+
+        let config = RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .build();
+        let mut router = RpcRouter::new(first_consumer, Arc::new(first_producer), config).unwrap();
+        router.enable_health_service().unwrap();
+        let sessions = Arc::clone(&router.sessions);
+        let handle = router.handle();
+        tokio::spawn(router.run());
+
+        // As a server would after reconnecting to a relay whose session closed
+        assert!(handle.add_origin(second_consumer, Arc::new(second_producer)));
+
+        let mut client = crate::test::client(&(client_producer, client_consumer), "drone-1");
+        let mut conn = client
+            .connect::<HealthCheckRequest, HealthCheckResponse>(HEALTH_CHECK_PATH)
+            .await
+            .unwrap();
+        conn.send(HealthCheckRequest::default()).await.unwrap();
+        let response = tokio::time::timeout(Duration::from_secs(1), conn.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), ServingStatus::Serving);
+        assert_eq!(sessions.snapshot()[0].origin, 1);
+    }
+
+    #[tokio::test]
+    async fn test_replaces_leftover_response_broadcast() {
+        let (router_producer, router_consumer, client_producer, client_consumer) =
+            crate::test::loopback();
+        let response_path = format!("server/drone-1/{HEALTH_CHECK_PATH}");
+        // Left behind by a handler that never shut down, it will never respond
+        let leftover = router_producer.create_broadcast(&response_path).unwrap();
+
+        let config = RpcRouterConfig::default()
+            .with_client_prefix("drone")
+            .with_response_prefix("server");
+        let mut router =
+            RpcRouter::new(router_consumer, Arc::new(router_producer), config).unwrap();
+        router.enable_health_service().unwrap();
+        tokio::spawn(router.run());
+
+        let config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .client_prefix("drone".to_string())
+            .server_prefix("server".to_string())
+            .timeout(Duration::from_secs(1))
+            .build();
+        let mut client = RpcClient::new(Arc::new(client_producer), client_consumer.clone(), config);
+
+        // The client finds the leftover before the router has seen it connect
+        let mut conn = client
+            .connect::<HealthCheckRequest, HealthCheckResponse>(HEALTH_CHECK_PATH)
+            .await
+            .unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(1), conn.next())
+            .await
+            .unwrap();
+        assert!(matches!(
+            next,
+            Some(Err(RpcClientError::ServerDisconnected))
+        ));
+
+        let served = client_consumer.consume_broadcast(&response_path).unwrap();
+        assert!(!served.is_clone(&leftover.consume()));
     }
 }
@@ -1,14 +1,28 @@
 use dashmap::DashMap;
 use std::fmt;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Notify;
+use tonic::Extensions;
 
-use crate::error::RpcServerError;
+use crate::connection::RpcOutbound;
+use crate::error::{RpcServerError, RpcTrackError, RpcWireError};
+use crate::stats::SizeStats;
+use crate::trace::TraceContext;
 
-/// A composite key for session tracking: (client_id, grpc_path).
+/// A composite key for session tracking: (origin, client_id, grpc_path).
+///
+/// Build one with [`SessionKey::new`]; more fields may be added.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub struct SessionKey {
     pub client_id: String,
     pub grpc_path: String,
+    /// Index of the origin the client announced on, in the order the router was given them.
+    /// An origin added again after closing, see `RpcRouterHandle::add_origin`, gets a new index.
+    ///
+    /// Keeps clients with the same id on different relays apart.
+    pub origin: usize,
 }
 
 impl SessionKey {
@@ -16,30 +30,60 @@ impl SessionKey {
         Self {
             client_id: client_id.into(),
             grpc_path: grpc_path.into(),
+            origin: 0,
         }
     }
+
+    /// Set the index of the origin the client announced on.
+    pub fn with_origin(mut self, origin: usize) -> Self {
+        self.origin = origin;
+        self
+    }
 }
 
 impl fmt::Display for SessionKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{}", self.client_id, self.grpc_path)
+        write!(f, "{}:{}", self.client_id, self.grpc_path)?;
+        if self.origin > 0 {
+            write!(f, "@{}", self.origin)?;
+        }
+        Ok(())
     }
 }
 
 /// Tracks active RPC sessions.
 ///
-/// A session is identified by (client_id, grpc_path). Only one active session
+/// A session is identified by (origin, client_id, grpc_path). Only one active session
 /// is allowed per key at a time. When a session is created, a guard is returned
 /// that automatically removes the session when dropped.
 #[derive(Debug)]
 pub struct SessionMap {
-    sessions: DashMap<SessionKey, (), ahash::RandomState>,
+    sessions: DashMap<SessionKey, SessionEntry, ahash::RandomState>,
+    // Sessions created and not yet removed, reserved before each insert to enforce a limit
+    count: AtomicUsize,
+}
+
+/// What the map keeps of an active session, so it can be terminated from outside its handler.
+#[derive(Default)]
+struct SessionEntry {
+    // Set once the handler is spawned
+    outbound: Option<RpcOutbound>,
+    // Set by a termination that came before the outbound, which is then aborted on attaching
+    terminate_requested: bool,
+    terminated: Arc<Notify>,
+}
+
+impl fmt::Debug for SessionEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionEntry").finish_non_exhaustive()
+    }
 }
 
 impl SessionMap {
     pub fn new() -> Self {
         Self {
             sessions: DashMap::default(),
+            count: AtomicUsize::new(0),
         }
     }
 
@@ -47,6 +91,30 @@ impl SessionMap {
     ///
     /// Returns an error if a session already exists for this key.
     pub fn try_create(self: &Arc<Self>, key: SessionKey) -> Result<SessionGuard, RpcServerError> {
+        self.try_create_with_extensions(key, Extensions::new())
+    }
+
+    /// Try to create a new session carrying the given per-session `extensions`.
+    ///
+    /// Returns an error if a session already exists for this key.
+    pub fn try_create_with_extensions(
+        self: &Arc<Self>,
+        key: SessionKey,
+        extensions: Extensions,
+    ) -> Result<SessionGuard, RpcServerError> {
+        self.try_create_within(key, extensions, None)
+    }
+
+    /// Try to create a new session, unless there are already `max_sessions` of them.
+    ///
+    /// The limit is checked and the session inserted as one step, so concurrent creations
+    /// can't overshoot it. A duplicate is reported ahead of the limit.
+    pub(crate) fn try_create_within(
+        self: &Arc<Self>,
+        key: SessionKey,
+        extensions: Extensions,
+        max_sessions: Option<usize>,
+    ) -> Result<SessionGuard, RpcServerError> {
         use dashmap::mapref::entry::Entry;
 
         match self.sessions.entry(key.clone()) {
@@ -55,10 +123,28 @@ impl SessionMap {
                 grpc_path: key.grpc_path,
             }),
             Entry::Vacant(slot) => {
-                slot.insert(());
+                let reserved =
+                    self.count
+                        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                            max_sessions
+                                .is_none_or(|max| count < max)
+                                .then_some(count + 1)
+                        });
+                if let (Err(_), Some(max_sessions)) = (reserved, max_sessions) {
+                    return Err(RpcServerError::Overloaded { max_sessions });
+                }
+                let entry = SessionEntry::default();
+                let terminated = Arc::clone(&entry.terminated);
+                slot.insert(entry);
                 Ok(SessionGuard {
-                    key,
+                    context: SessionContext {
+                        key,
+                        extensions: Arc::new(extensions),
+                        trace_context: None,
+                        outbound: None,
+                    },
                     map: Arc::clone(self),
+                    terminated,
                 })
             }
         }
@@ -79,25 +165,74 @@ impl SessionMap {
         self.sessions.is_empty()
     }
 
+    /// Get a point-in-time copy of all active session keys.
+    ///
+    /// Keys are cloned while iterating, so concurrent inserts and removes are
+    /// safe but may or may not be reflected in the result.
+    pub fn snapshot(&self) -> Vec<SessionKey> {
+        self.sessions
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Terminate the session at `key`, returning whether there was one.
+    ///
+    /// The session's response track is aborted with `RpcWireError::Terminated`, which its
+    /// client sees as the end of the connection, and its handler stops as soon as it next
+    /// yields. The session is removed once the handler has wound down, so a client reconnecting
+    /// right away may briefly be rejected as a duplicate. A session whose handler has not
+    /// started yet has its response track aborted as soon as the handler attaches it.
+    ///
+    /// A session that completed is removed before its response track is drained, so
+    /// terminating it then returns `false` and leaves the client to read the end of the
+    /// response.
+    pub fn terminate(&self, key: &SessionKey) -> bool {
+        let Some(mut entry) = self.sessions.get_mut(key) else {
+            return false;
+        };
+        match &entry.outbound {
+            Some(outbound) => abort_terminated(outbound),
+            None => entry.terminate_requested = true,
+        }
+        entry.terminated.notify_one();
+        true
+    }
+
     /// Remove a session directly (used internally by SessionGuard).
     fn remove(&self, key: &SessionKey) {
-        self.sessions.remove(key);
+        if self.sessions.remove(key).is_some() {
+            self.count.fetch_sub(1, Ordering::AcqRel);
+        }
     }
 }
 
+/// Abort a terminated session's response track with `RpcWireError::Terminated`.
+fn abort_terminated(outbound: &RpcOutbound) {
+    outbound.abort_with_error(
+        RpcWireError::Terminated.to_code_in(outbound.code_space()),
+        "session terminated by the server",
+    );
+}
+
 impl Default for SessionMap {
     fn default() -> Self {
         Self::new()
     }
 }
 
-/// A guard that holds an active session. When dropped, the session is removed.
-pub struct SessionGuard {
+/// Read-only view of an active session, shared with connectors and observers.
+///
+/// Cloning is cheap: the extensions are shared, not copied.
+#[derive(Clone)]
+pub struct SessionContext {
     key: SessionKey,
-    map: Arc<SessionMap>,
+    extensions: Arc<Extensions>,
+    trace_context: Option<TraceContext>,
+    outbound: Option<RpcOutbound>,
 }
 
-impl SessionGuard {
+impl SessionContext {
     /// Get the session key.
     pub fn key(&self) -> &SessionKey {
         &self.key
@@ -112,18 +247,133 @@ impl SessionGuard {
     pub fn grpc_path(&self) -> &str {
         &self.key.grpc_path
     }
+
+    /// Get the index of the origin the client announced on.
+    pub fn origin(&self) -> usize {
+        self.key.origin
+    }
+
+    /// Get the per-session extensions.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Get a typed value from the per-session extensions.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get::<T>()
+    }
+
+    /// Get the W3C trace context the client sent, if the router has trace
+    /// propagation enabled and the client sent one.
+    ///
+    /// Connectors can forward it to the gRPC backend as request metadata.
+    pub fn trace_context(&self) -> Option<&TraceContext> {
+        self.trace_context.as_ref()
+    }
+
+    /// Open an additional response track named `name`, alongside the one the connector's
+    /// response stream is written to.
+    ///
+    /// Clients subscribe to it by name on the session's response broadcast. Fails with
+    /// [`RpcTrackError::NoBroadcast`] outside a running session, and otherwise as
+    /// [`RpcOutbound::track`] does. The context does not keep the broadcast open, so this
+    /// fails with [`RpcTrackError::BroadcastClosed`] once the session has ended.
+    pub fn response_track(&self, name: &str) -> Result<RpcOutbound, RpcTrackError> {
+        self.outbound
+            .as_ref()
+            .ok_or(RpcTrackError::NoBroadcast)?
+            .track(name)
+    }
+
+    /// The sizes of the frames sent on the session's response track so far, or `None` unless
+    /// the router tracks them.
+    ///
+    /// See [`RpcOutbound::size_stats`] and `RpcRouterConfig::size_stats`. Tracks opened with
+    /// [`response_track`](Self::response_track) keep sizes of their own.
+    pub fn response_size_stats(&self) -> Option<SizeStats> {
+        self.outbound.as_ref()?.size_stats()
+    }
+
+    pub(crate) fn with_trace_context(mut self, trace_context: Option<TraceContext>) -> Self {
+        self.trace_context = trace_context;
+        self
+    }
+
+    pub(crate) fn with_outbound(mut self, outbound: RpcOutbound) -> Self {
+        self.outbound = Some(outbound);
+        self
+    }
+}
+
+impl fmt::Debug for SessionContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionContext")
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard that holds an active session. When dropped, the session is removed.
+pub struct SessionGuard {
+    context: SessionContext,
+    map: Arc<SessionMap>,
+    terminated: Arc<Notify>,
+}
+
+impl SessionGuard {
+    /// Get the session key.
+    pub fn key(&self) -> &SessionKey {
+        self.context.key()
+    }
+
+    /// Get the client ID.
+    pub fn client_id(&self) -> &str {
+        self.context.client_id()
+    }
+
+    /// Get the gRPC path.
+    pub fn grpc_path(&self) -> &str {
+        self.context.grpc_path()
+    }
+
+    /// Get the shared context for this session.
+    pub fn context(&self) -> &SessionContext {
+        &self.context
+    }
+
+    /// Get a typed value from the per-session extensions.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.context.get::<T>()
+    }
+
+    /// Record the session's response track, so [`SessionMap::terminate`] can abort it. The
+    /// track is aborted right away if the session was terminated before this.
+    pub(crate) fn attach_outbound(&self, outbound: RpcOutbound) {
+        if let Some(mut entry) = self.map.sessions.get_mut(self.context.key()) {
+            if entry.terminate_requested {
+                abort_terminated(&outbound);
+            }
+            entry.outbound = Some(outbound);
+        }
+    }
+
+    /// The signal [`SessionMap::terminate`] raises. It keeps a permit, so waiting for it after
+    /// the session was terminated still returns.
+    pub(crate) fn terminated(&self) -> Arc<Notify> {
+        Arc::clone(&self.terminated)
+    }
 }
 
 impl Drop for SessionGuard {
     fn drop(&mut self) {
-        self.map.remove(&self.key);
+        self.map.remove(self.context.key());
     }
 }
 
 impl fmt::Debug for SessionGuard {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SessionGuard")
-            .field("key", &self.key)
+            .field("key", self.context.key())
             .finish()
     }
 }
@@ -160,6 +410,41 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_concurrent_creates_respect_limit() {
+        let map = Arc::new(SessionMap::new());
+        let guards: Vec<_> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..16)
+                .map(|n| {
+                    let map = &map;
+                    scope.spawn(move || {
+                        let key = SessionKey::new(format!("drone-{n}"), "drone.EchoService/Echo");
+                        map.try_create_within(key, Extensions::new(), Some(4))
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .filter_map(|worker| worker.join().unwrap().ok())
+                .collect()
+        });
+        assert_eq!(guards.len(), 4);
+        assert_eq!(map.len(), 4);
+
+        let key = SessionKey::new("drone-late", "drone.EchoService/Echo");
+        let over = map.try_create_within(key.clone(), Extensions::new(), Some(4));
+        assert!(matches!(
+            over,
+            Err(RpcServerError::Overloaded { max_sessions: 4 })
+        ));
+
+        drop(guards);
+        assert!(
+            map.try_create_within(key, Extensions::new(), Some(4))
+                .is_ok()
+        );
+    }
+
     #[test]
     fn test_different_clients_same_rpc() {
         let map = Arc::new(SessionMap::new());
@@ -184,6 +469,55 @@ mod tests {
         assert_eq!(map.len(), 2);
     }
 
+    #[test]
+    fn test_same_client_different_origins() {
+        let map = Arc::new(SessionMap::new());
+        let first = SessionKey::new("drone-1", "drone.EchoService/Echo");
+        let second = first.clone().with_origin(1);
+
+        let _guard1 = map.try_create(first).unwrap();
+        let _guard2 = map.try_create(second.clone()).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(second.to_string(), "drone-1:drone.EchoService/Echo@1");
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let map = Arc::new(SessionMap::new());
+        let key1 = SessionKey::new("drone-1", "drone.EchoService/Echo");
+        let key2 = SessionKey::new("drone-2", "drone.EchoService/Echo");
+
+        let _guard1 = map.try_create(key1.clone()).unwrap();
+        let guard2 = map.try_create(key2.clone()).unwrap();
+
+        let mut keys = map.snapshot();
+        keys.sort_by(|a, b| a.client_id.cmp(&b.client_id));
+        assert_eq!(keys, vec![key1.clone(), key2]);
+
+        drop(guard2);
+        assert_eq!(map.snapshot(), vec![key1]);
+    }
+
+    #[test]
+    fn test_session_extensions() {
+        #[derive(Debug, Clone, PartialEq)]
+        struct User(&'static str);
+
+        let map = Arc::new(SessionMap::new());
+        let key = SessionKey::new("drone-1", "drone.EchoService/Echo");
+
+        let mut extensions = Extensions::new();
+        extensions.insert(User("alice"));
+        let guard = map.try_create_with_extensions(key, extensions).unwrap();
+
+        assert_eq!(guard.get::<User>(), Some(&User("alice")));
+        assert_eq!(guard.get::<u32>(), None);
+
+        let context = guard.context().clone();
+        drop(guard);
+        assert_eq!(context.get::<User>(), Some(&User("alice")));
+    }
+
     #[test]
     fn test_reconnect_after_drop() {
         let map = Arc::new(SessionMap::new());
@@ -197,4 +531,20 @@ mod tests {
         let _guard = map.try_create(key).unwrap();
         assert_eq!(map.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_terminate_before_outbound_is_attached() {
+        let map = Arc::new(SessionMap::new());
+        let key = SessionKey::new("drone-1", "drone.EchoService/Echo");
+        let guard = map.try_create(key.clone()).unwrap();
+        assert!(map.terminate(&key));
+
+        let track = moq_lite::Track::new("primary").produce();
+        guard.attach_outbound(RpcOutbound::new(track.producer));
+        let closed = track.consumer.closed().await;
+        assert!(matches!(
+            closed,
+            Err(moq_lite::Error::App(code)) if code == RpcWireError::CODE_TERMINATED
+        ));
+    }
 }
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// A point-in-time copy of a route's traffic counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RouteStats {
+    /// Request messages decoded from clients.
+    pub inbound_messages: u64,
+    /// Response messages sent to clients.
+    pub outbound_messages: u64,
+    /// Payload bytes of decoded request messages, after decompression.
+    pub inbound_bytes: u64,
+    /// Payload bytes of sent response messages, before compression.
+    pub outbound_bytes: u64,
+}
+
+/// Live traffic counters for a single route.
+///
+/// Updated with relaxed atomics: totals are exact, but a snapshot taken while
+/// traffic is flowing may mix counts from slightly different moments.
+#[derive(Debug, Default)]
+pub(crate) struct RouteCounters {
+    inbound_messages: AtomicU64,
+    outbound_messages: AtomicU64,
+    inbound_bytes: AtomicU64,
+    outbound_bytes: AtomicU64,
+}
+
+impl RouteCounters {
+    pub(crate) fn record_inbound(&self, bytes: usize) {
+        self.inbound_messages.fetch_add(1, Ordering::Relaxed);
+        self.inbound_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_outbound(&self, bytes: usize) {
+        self.outbound_messages.fetch_add(1, Ordering::Relaxed);
+        self.outbound_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> RouteStats {
+        RouteStats {
+            inbound_messages: self.inbound_messages.load(Ordering::Relaxed),
+            outbound_messages: self.outbound_messages.load(Ordering::Relaxed),
+            inbound_bytes: self.inbound_bytes.load(Ordering::Relaxed),
+            outbound_bytes: self.outbound_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        self.inbound_messages.store(0, Ordering::Relaxed);
+        self.outbound_messages.store(0, Ordering::Relaxed);
+        self.inbound_bytes.store(0, Ordering::Relaxed);
+        self.outbound_bytes.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A point-in-time copy of the sizes of the message frames through one stream.
+///
+/// Sizes are of frames as written to the track: after compression and including the codec
+/// tag if there is one, i.e. what `RpcInbound::with_max_frame_size` is checked against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SizeStats {
+    /// Message frames seen.
+    pub messages: u64,
+    /// Bytes of all message frames seen.
+    pub total_bytes: u64,
+    /// Bytes of the largest message frame seen.
+    pub max_bytes: u64,
+}
+
+impl SizeStats {
+    /// The mean frame size in bytes, or `None` before any message was seen.
+    pub fn mean_bytes(&self) -> Option<f64> {
+        (self.messages > 0).then(|| self.total_bytes as f64 / self.messages as f64)
+    }
+}
+
+/// Live size counters for a single stream, updated like [`RouteCounters`].
+#[derive(Debug, Default)]
+pub(crate) struct SizeCounters {
+    messages: AtomicU64,
+    total_bytes: AtomicU64,
+    max_bytes: AtomicU64,
+}
+
+impl SizeCounters {
+    pub(crate) fn record(&self, bytes: usize) {
+        let bytes = bytes as u64;
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        self.total_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.max_bytes.fetch_max(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> SizeStats {
+        SizeStats {
+            messages: self.messages.load(Ordering::Relaxed),
+            total_bytes: self.total_bytes.load(Ordering::Relaxed),
+            max_bytes: self.max_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Traffic counters for every route registered on an `RpcRouter`, keyed by gRPC path.
+///
+/// Cloning is cheap and every clone sees the same counters, so a handle taken
+/// before `RpcRouter::run` keeps reporting while the router runs.
+#[derive(Debug, Clone, Default)]
+pub struct RouterStats {
+    routes: Arc<RwLock<HashMap<String, Arc<RouteCounters>>>>,
+}
+
+impl RouterStats {
+    /// Get (or create) the counters for `grpc_path`.
+    pub(crate) fn route(&self, grpc_path: &str) -> Arc<RouteCounters> {
+        if let Some(counters) = self
+            .routes
+            .read()
+            .expect("router stats lock poisoned")
+            .get(grpc_path)
+        {
+            return Arc::clone(counters);
+        }
+
+        let mut routes = self.routes.write().expect("router stats lock poisoned");
+        Arc::clone(routes.entry(grpc_path.to_string()).or_default())
+    }
+
+    /// Get a snapshot of every route's counters.
+    pub fn snapshot(&self) -> HashMap<String, RouteStats> {
+        self.routes
+            .read()
+            .expect("router stats lock poisoned")
+            .iter()
+            .map(|(path, counters)| (path.clone(), counters.snapshot()))
+            .collect()
+    }
+
+    /// Reset every route's counters to zero.
+    pub fn reset(&self) {
+        for counters in self
+            .routes
+            .read()
+            .expect("router stats lock poisoned")
+            .values()
+        {
+            counters.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_accumulate_and_reset() {
+        let stats = RouterStats::default();
+        let route = stats.route("drone.EchoService/Echo");
+        route.record_inbound(10);
+        route.record_inbound(5);
+        stats.route("drone.EchoService/Echo").record_outbound(7);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(
+            snapshot["drone.EchoService/Echo"],
+            RouteStats {
+                inbound_messages: 2,
+                outbound_messages: 1,
+                inbound_bytes: 15,
+                outbound_bytes: 7,
+            }
+        );
+
+        stats.reset();
+        assert_eq!(
+            stats.snapshot()["drone.EchoService/Echo"],
+            RouteStats::default()
+        );
+    }
+
+    #[test]
+    fn test_size_counters_track_the_largest_message() {
+        let sizes = SizeCounters::default();
+        assert_eq!(sizes.snapshot().mean_bytes(), None);
+
+        sizes.record(10);
+        sizes.record(30);
+        sizes.record(20);
+
+        let snapshot = sizes.snapshot();
+        assert_eq!(
+            snapshot,
+            SizeStats {
+                messages: 3,
+                total_bytes: 60,
+                max_bytes: 30,
+            }
+        );
+        assert_eq!(snapshot.mean_bytes(), Some(20.0));
+    }
+}
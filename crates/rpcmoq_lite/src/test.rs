@@ -0,0 +1,515 @@
+//! Helpers for testing routers and clients without a relay, behind the `test-util` feature.
+//!
+//! [`loopback`] returns the origin halves a router and a client would get from their relay
+//! sessions, wired together in memory. Whatever one side publishes the other can consume, so
+//! `register`, `run` and `connect` work end-to-end with no network.
+//!
+//! As on a relay, the router and client need distinct client and server prefixes, or the router
+//! would take its own responses for clients.
+//!
+//! ```
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use std::sync::Arc;
+//! use futures::StreamExt;
+//! use rpcmoq_lite::{DecodedInbound, RpcClient, RpcClientConfig, RpcRouter, RpcRouterConfig};
+//!
+//! let (router_producer, router_consumer, client_producer, client_consumer) =
+//!     rpcmoq_lite::test::loopback();
+//!
+//! let config = RpcRouterConfig::builder()
+//!     .client_prefix("drone".to_string())
+//!     .response_prefix("server".to_string())
+//!     .build();
+//! let mut router = RpcRouter::new(router_consumer, Arc::new(router_producer), config)?;
+//! router.register("drone.EchoService/Echo", |_, inbound: DecodedInbound<String>| async move {
+//!     Ok(inbound.map(Ok))
+//! })?;
+//! tokio::spawn(router.run());
+//!
+//! let config = RpcClientConfig::builder()
+//!     .client_id("drone-1".to_string())
+//!     .client_prefix("drone".to_string())
+//!     .server_prefix("server".to_string())
+//!     .build();
+//! let mut client = RpcClient::new(Arc::new(client_producer), client_consumer, config);
+//! let conn = client.connect::<String, String>("drone.EchoService/Echo").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use moq_lite::{Origin, OriginConsumer, OriginProducer};
+
+use crate::{RpcClient, RpcClientConfig, RpcRouter, RpcRouterConfig};
+
+/// The `(producer, consumer)` origin halves for a router, followed by those for a client,
+/// connected through a single in-memory origin.
+///
+/// Call it again for an isolated pair, or clone the client halves to connect more clients to
+/// the same router.
+pub fn loopback() -> (
+    OriginProducer,
+    OriginConsumer,
+    OriginProducer,
+    OriginConsumer,
+) {
+    let origin = Origin::produce();
+    (
+        origin.producer.clone(),
+        origin.consumer.clone(),
+        origin.producer,
+        origin.consumer,
+    )
+}
+
+/// Run a router on a fresh [`loopback`], with handlers registered by `configure`, and return
+/// the client halves of the loopback.
+///
+/// The router serves clients under the `drone` prefix and answers under `server`, as the
+/// clients made by [`client`] expect.
+pub fn spawn_router(configure: impl FnOnce(&mut RpcRouter)) -> (OriginProducer, OriginConsumer) {
+    let (router_producer, router_consumer, client_producer, client_consumer) = loopback();
+
+    let config = RpcRouterConfig::builder()
+        .client_prefix("drone".to_string())
+        .response_prefix("server".to_string())
+        .build();
+    let mut router = RpcRouter::new(router_consumer, Arc::new(router_producer), config).unwrap();
+    configure(&mut router);
+    tokio::spawn(router.run());
+
+    (client_producer, client_consumer)
+}
+
+/// A client called `client_id` of the router started by [`spawn_router`], which gives up on
+/// connecting after a second.
+pub fn client(origin: &(OriginProducer, OriginConsumer), client_id: &str) -> RpcClient {
+    let config = RpcClientConfig::builder()
+        .client_id(client_id.to_string())
+        .client_prefix("drone".to_string())
+        .server_prefix("server".to_string())
+        .timeout(Duration::from_secs(1))
+        .build();
+    RpcClient::new(Arc::new(origin.0.clone()), origin.1.clone(), config)
+}
+
+/// Run a router as [`spawn_router`] does and return a client of it called `drone-1`.
+pub fn router_and_client(configure: impl FnOnce(&mut RpcRouter)) -> RpcClient {
+    client(&spawn_router(configure), "drone-1")
+}
+
+/// Call `send` on `state` every 10ms until `receive` returns, and return what it returned.
+///
+/// A subscriber only sees what was written after it subscribed, and nothing says when that
+/// happened, so a test keeps writing until something comes back. `receive` is cancelled each
+/// time it has not returned within 10ms, so it has to be cancel safe.
+///
+/// # Panics
+///
+/// If nothing comes back within a second.
+pub async fn resend_until<S, T>(
+    state: &mut S,
+    mut send: impl AsyncFnMut(&mut S),
+    mut receive: impl AsyncFnMut(&mut S) -> T,
+) -> T {
+    let resend = async {
+        loop {
+            send(state).await;
+            tokio::select! {
+                received = receive(state) => return received,
+                _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(1), resend)
+        .await
+        .expect("nothing was received within a second")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DecodedInbound, RpcClient, RpcClientConfig, RpcClientError, RpcConnection, RpcRouter,
+        RpcRouterConfig, RpcSendError, RpcWireError,
+    };
+    use futures::{SinkExt, StreamExt};
+    use std::sync::Arc;
+
+    async fn send_ping(conn: &mut RpcConnection<String, String>) {
+        conn.send("ping".to_string()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_echo_round_trip() {
+        let (router_producer, router_consumer, client_producer, client_consumer) = loopback();
+
+        let config = RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .build();
+        let mut router =
+            RpcRouter::new(router_consumer, Arc::new(router_producer), config).unwrap();
+        router
+            .register(
+                "drone.EchoService/Echo",
+                |_, inbound: DecodedInbound<String>| async move { Ok(inbound.map(Ok)) },
+            )
+            .unwrap();
+        tokio::spawn(router.run());
+
+        let config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .client_prefix("drone".to_string())
+            .server_prefix("server".to_string())
+            .timeout(Duration::from_secs(1))
+            .build();
+        let mut client = RpcClient::new(Arc::new(client_producer), client_consumer, config);
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+
+        let echoed = resend_until(&mut conn, send_ping, async |conn| conn.next().await).await;
+        assert_eq!(echoed.unwrap().unwrap(), "ping");
+    }
+
+    #[tokio::test]
+    async fn test_status_trailer_reaches_client() {
+        let (router_producer, router_consumer, client_producer, client_consumer) = loopback();
+
+        let config = RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .status_trailers(true)
+            .build();
+        let mut router =
+            RpcRouter::new(router_consumer, Arc::new(router_producer), config).unwrap();
+        router
+            .register(
+                "drone.EchoService/Echo",
+                |_, inbound: DecodedInbound<String>| async move {
+                    let failed = futures::stream::once(async {
+                        Err(tonic::Status::not_found("drone-1 is not connected"))
+                    });
+                    let echoed = inbound.take_while(|msg| std::future::ready(msg != "stop"));
+                    Ok(echoed.map(Ok).chain(failed))
+                },
+            )
+            .unwrap();
+        tokio::spawn(router.run());
+
+        let config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .client_prefix("drone".to_string())
+            .server_prefix("server".to_string())
+            .timeout(Duration::from_secs(1))
+            .status_trailers(true)
+            .build();
+        let mut client = RpcClient::new(Arc::new(client_producer), client_consumer, config);
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+
+        let echoed = resend_until(&mut conn, send_ping, async |conn| conn.next().await).await;
+        assert_eq!(echoed.unwrap().unwrap(), "ping");
+
+        conn.send("stop".to_string()).await.unwrap();
+        let err = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                // Skip echoes of the pings sent before the first echo arrived
+                if let Err(err) = conn.next().await.unwrap() {
+                    return err;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            err,
+            RpcClientError::Wire(RpcWireError::Status { code: tonic::Code::NotFound, message })
+                if message == "drone-1 is not connected"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_fails_once_server_session_ends() {
+        let (router_producer, router_consumer, client_producer, client_consumer) = loopback();
+
+        let config = RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .build();
+        let mut router =
+            RpcRouter::new(router_consumer, Arc::new(router_producer), config).unwrap();
+        router
+            .register(
+                "drone.EchoService/Echo",
+                |_, inbound: DecodedInbound<String>| async move {
+                    Ok(inbound
+                        .take_while(|msg| std::future::ready(msg != "stop"))
+                        .map(Ok))
+                },
+            )
+            .unwrap();
+        tokio::spawn(router.run());
+
+        let config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .client_prefix("drone".to_string())
+            .server_prefix("server".to_string())
+            .timeout(Duration::from_secs(1))
+            .build();
+        let mut client = RpcClient::new(Arc::new(client_producer), client_consumer, config);
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+
+        resend_until(&mut conn, send_ping, async |conn| conn.next().await).await;
+
+        // Later frames would supersede the stop before the router reads it, so only poll
+        conn.send("stop".to_string()).await.unwrap();
+        let err = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                if let Err(err) = futures::future::poll_fn(|cx| conn.poll_ready_unpin(cx)).await {
+                    return err;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(matches!(err, RpcSendError::Closed));
+        assert!(matches!(
+            conn.send("ping".to_string()).await,
+            Err(RpcSendError::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_fails_once_response_track_ends() {
+        let (server_producer, _, client_producer, client_consumer) = loopback();
+        // A server whose broadcast stays up after it aborts the response track
+        let mut server_broadcast = server_producer
+            .create_broadcast("server/drone-1/drone.EchoService/Echo")
+            .unwrap();
+        let response_track = server_broadcast.create_track(moq_lite::Track::new("primary"));
+
+        let mut client = client(&(client_producer, client_consumer), "drone-1");
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+        conn.send("ping".to_string()).await.unwrap();
+
+        response_track.abort(moq_lite::Error::Cancel);
+        let sent = tokio::time::timeout(Duration::from_secs(1), conn.send("ping".to_string()))
+            .await
+            .unwrap();
+        assert!(matches!(sent, Err(RpcSendError::Closed)));
+    }
+
+    #[tokio::test]
+    async fn test_size_stats_from_configs() {
+        let (router_producer, router_consumer, client_producer, client_consumer) = loopback();
+
+        let config = RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .size_stats(true)
+            .build();
+        let mut router =
+            RpcRouter::new(router_consumer, Arc::new(router_producer), config).unwrap();
+        router
+            .register(
+                "drone.EchoService/Echo",
+                |_, inbound: DecodedInbound<String>| async move {
+                    // Answer each request with the size of the largest request frame seen
+                    Ok(futures::stream::unfold(inbound, |mut inbound| async move {
+                        inbound.next().await?;
+                        let max_bytes = inbound.size_stats().unwrap().max_bytes;
+                        Some((Ok(max_bytes.to_string()), inbound))
+                    }))
+                },
+            )
+            .unwrap();
+        tokio::spawn(router.run());
+
+        let config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .client_prefix("drone".to_string())
+            .server_prefix("server".to_string())
+            .timeout(Duration::from_secs(1))
+            .size_stats(true)
+            .build();
+        let mut client = RpcClient::new(Arc::new(client_producer), client_consumer, config);
+        let mut conn = client
+            .connect::<String, String>("drone.EchoService/Echo")
+            .await
+            .unwrap();
+
+        let response = resend_until(&mut conn, send_ping, async |conn| conn.next().await)
+            .await
+            .unwrap()
+            .unwrap();
+        let sent = conn.request_size_stats().unwrap();
+        assert!(sent.messages >= 1);
+        assert_eq!(response, sent.max_bytes.to_string());
+        assert_eq!(conn.response_size_stats().unwrap().messages, 1);
+    }
+
+    #[tokio::test]
+    async fn test_router_keepalive_timeout_ends_quiet_sessions() {
+        let (router_producer, router_consumer, client_producer, client_consumer) = loopback();
+
+        let config = RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .keepalive_timeout(Duration::from_millis(200))
+            .build();
+        let mut router =
+            RpcRouter::new(router_consumer, Arc::new(router_producer), config).unwrap();
+        router
+            .register(
+                "drone.EchoService/Echo",
+                |_, inbound: DecodedInbound<String>| async move { Ok(inbound.map(Ok)) },
+            )
+            .unwrap();
+        tokio::spawn(router.run());
+
+        let origin = (client_producer, client_consumer);
+        let mut quiet = client(&origin, "drone-1");
+        let alive = client(&origin, "drone-2");
+        let mut alive = RpcClient::new(
+            Arc::new(origin.0.clone()),
+            origin.1.clone(),
+            alive
+                .config()
+                .clone()
+                .with_keepalive_interval(Duration::from_millis(50)),
+        );
+
+        let mut connections = Vec::new();
+        for client in [&mut quiet, &mut alive] {
+            let mut conn = client
+                .connect::<String, String>("drone.EchoService/Echo")
+                .await
+                .unwrap();
+            let response = resend_until(&mut conn, send_ping, async |conn| conn.next().await)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response, "ping");
+            connections.push(conn);
+        }
+        let mut alive = connections.pop().unwrap();
+        let mut quiet = connections.pop().unwrap();
+
+        // Without keepalives the request stream times out, ending the echo and the session
+        let next = tokio::time::timeout(Duration::from_secs(1), async {
+            while quiet.next().await.is_some_and(|next| next.is_ok()) {}
+        })
+        .await;
+        assert!(next.is_ok());
+
+        // A client sending keepalives outlives the timeout
+        let next = tokio::time::timeout(Duration::from_millis(500), async {
+            while let Some(next) = alive.next().await {
+                next.unwrap();
+            }
+        })
+        .await;
+        assert!(next.is_err());
+    }
+
+    /// A request or response that is not a prost message and can't be empty.
+    #[derive(Debug, PartialEq)]
+    struct Word(String);
+
+    fn decode_word(payload: bytes::Bytes) -> Result<Word, String> {
+        use prost::Message;
+        let word = String::decode(payload).map_err(|err| err.to_string())?;
+        if word == "bad" {
+            return Err(format!("'{word}' is not a word"));
+        }
+        Ok(Word(word))
+    }
+
+    fn spawn_word_router() -> RpcClient {
+        crate::test::router_and_client(|router| {
+            router
+                .register_with_decoder(
+                    "drone.EchoService/Echo",
+                    decode_word,
+                    |_, inbound: DecodedInbound<Word>| async move {
+                        Ok(inbound.map(|Word(word)| Ok(word)))
+                    },
+                )
+                .unwrap();
+        })
+    }
+
+    #[tokio::test]
+    async fn test_decoders_on_both_ends() {
+        let mut client = spawn_word_router();
+        let mut conn = client
+            .connect_with_decoder::<String, Word, _, _>("drone.EchoService/Echo", decode_word)
+            .await
+            .unwrap();
+
+        let echoed = resend_until(
+            &mut conn,
+            async |conn| conn.send("ping".to_string()).await.unwrap(),
+            async |conn| conn.next().await,
+        )
+        .await;
+        assert_eq!(echoed.unwrap().unwrap(), Word("ping".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_decoder_rejections_fail_the_connection() {
+        let mut client = spawn_word_router();
+
+        // Rejected by the router, which closes the connection by default
+        let mut conn = client
+            .connect_with_decoder::<String, Word, _, _>("drone.EchoService/Echo", decode_word)
+            .await
+            .unwrap();
+        let rejected = resend_until(
+            &mut conn,
+            async |conn| {
+                let _ = conn.send("bad".to_string()).await;
+            },
+            async |conn| conn.next().await,
+        )
+        .await;
+        assert!(matches!(
+            rejected,
+            Some(Err(RpcClientError::Wire(RpcWireError::Decode)))
+        ));
+        drop(conn);
+
+        // Echoed by the router, then rejected by the receiver
+        let conn = client
+            .connect::<String, String>("drone.EchoService/Echo@v2")
+            .await
+            .unwrap();
+        let (mut sender, receiver) = conn.split();
+        let mut receiver = receiver.with_decoder(|_| Err::<String, _>("no responses wanted"));
+        let rejected = resend_until(
+            &mut sender,
+            async |sender| sender.send("ping".to_string()).await.unwrap(),
+            async |_| receiver.next().await,
+        )
+        .await;
+        assert!(matches!(
+            rejected,
+            Some(Err(RpcClientError::Wire(RpcWireError::Decode)))
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::OnceLock;
+
+use crate::metadata::RpcMetadata;
+
+const TRACEPARENT: &str = "traceparent";
+const TRACESTATE: &str = "tracestate";
+
+/// A W3C trace context carried from a client to the server handling its RPC.
+///
+/// See <https://www.w3.org/TR/trace-context/>.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    traceparent: String,
+    tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// Create a trace context from W3C `traceparent` and `tracestate` header values.
+    ///
+    /// Returns `None` if `traceparent` is not of the form
+    /// `{version}-{trace-id}-{parent-id}-{trace-flags}` in lowercase hex.
+    pub fn new(traceparent: impl Into<String>, tracestate: Option<String>) -> Option<Self> {
+        let traceparent = traceparent.into();
+        if !is_valid_traceparent(&traceparent) {
+            return None;
+        }
+        Some(Self {
+            traceparent,
+            tracestate: tracestate.filter(|state| !state.is_empty()),
+        })
+    }
+
+    /// The `traceparent` header value.
+    pub fn traceparent(&self) -> &str {
+        &self.traceparent
+    }
+
+    /// The `tracestate` header value, if any.
+    pub fn tracestate(&self) -> Option<&str> {
+        self.tracestate.as_deref()
+    }
+
+    /// Write the context into a metadata frame.
+    pub(crate) fn inject(&self, metadata: &mut RpcMetadata) {
+        metadata
+            .entries
+            .insert(TRACEPARENT.to_string(), self.traceparent.clone());
+        if let Some(state) = &self.tracestate {
+            metadata
+                .entries
+                .insert(TRACESTATE.to_string(), state.clone());
+        }
+    }
+
+    /// Read the context from a metadata frame, ignoring a malformed `traceparent`.
+    pub(crate) fn extract(metadata: &RpcMetadata) -> Option<Self> {
+        let traceparent = metadata.entries.get(TRACEPARENT)?;
+        Self::new(
+            traceparent.clone(),
+            metadata.entries.get(TRACESTATE).cloned(),
+        )
+    }
+}
+
+fn is_valid_traceparent(value: &str) -> bool {
+    let is_hex = |part: &str, len: usize| {
+        part.len() == len
+            && part
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    };
+
+    let parts: Vec<&str> = value.split('-').collect();
+    match parts.as_slice() {
+        [version, trace_id, parent_id, flags] => {
+            is_hex(version, 2)
+                && *version != "ff"
+                && is_hex(trace_id, 32)
+                && trace_id.bytes().any(|b| b != b'0')
+                && is_hex(parent_id, 16)
+                && parent_id.bytes().any(|b| b != b'0')
+                && is_hex(flags, 2)
+        }
+        _ => false,
+    }
+}
+
+/// Bridges rpcmoq_lite to the application's tracing backend, e.g. OpenTelemetry.
+///
+/// Install one with [`set_trace_propagator`]. Clients with trace propagation
+/// enabled send [`current`](Self::current) to the server, and routers with it
+/// enabled call [`set_parent`](Self::set_parent) on each handler span.
+pub trait TracePropagator: Send + Sync + 'static {
+    /// The trace context of the current span, if any.
+    fn current(&self) -> Option<TraceContext>;
+
+    /// Make the remote context `parent` the parent of `span`.
+    fn set_parent(&self, span: &tracing::Span, parent: &TraceContext);
+}
+
+static PROPAGATOR: OnceLock<Box<dyn TracePropagator>> = OnceLock::new();
+
+/// Install the global trace propagator.
+///
+/// Only the first call takes effect; later calls return `false`.
+pub fn set_trace_propagator(propagator: impl TracePropagator) -> bool {
+    PROPAGATOR.set(Box::new(propagator)).is_ok()
+}
+
+/// The installed trace propagator, if any.
+pub(crate) fn trace_propagator() -> Option<&'static dyn TracePropagator> {
+    PROPAGATOR.get().map(|propagator| propagator.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT_VALUE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_valid_traceparent() {
+        let ctx = TraceContext::new(TRACEPARENT_VALUE, Some("congo=t61rcWkgMzE".to_string()));
+        let ctx = ctx.unwrap();
+        assert_eq!(ctx.traceparent(), TRACEPARENT_VALUE);
+        assert_eq!(ctx.tracestate(), Some("congo=t61rcWkgMzE"));
+    }
+
+    #[test]
+    fn test_invalid_traceparent_rejected() {
+        for value in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ] {
+            assert!(TraceContext::new(value, None).is_none(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_inject_extract_round_trip() {
+        let ctx =
+            TraceContext::new(TRACEPARENT_VALUE, Some("congo=t61rcWkgMzE".to_string())).unwrap();
+        let mut metadata = RpcMetadata::default();
+        ctx.inject(&mut metadata);
+
+        assert_eq!(TraceContext::extract(&metadata), Some(ctx));
+    }
+
+    #[test]
+    fn test_extract_without_traceparent() {
+        assert_eq!(TraceContext::extract(&RpcMetadata::default()), None);
+    }
+}
@@ -0,0 +1,241 @@
+use anyhow::{Context, Result, bail};
+use futures::StreamExt;
+use moq_prototype::connect::ConnectOptions;
+use moq_prototype::drone::{DroneTelemetry, TelemetryTrack, subscribe_acks, subscribe_telemetry};
+use moq_prototype::drone_proto::{
+    BatteryState, CommandType, DroneCommand, DronePosition, DroneStatus,
+};
+use moq_prototype::grpc::DroneServiceClient;
+use moq_prototype::{connect_bidirectional_opts, shutdown_signal};
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::info;
+
+const GRPC_CLIENT_ADDR: &str = "http://[::1]:50051";
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The `DRONE_ID` that sends the command to every connected drone.
+const ALL_DRONES: &str = "all";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+    let url = std::env::var("RELAY_URL").unwrap_or_else(|_| "https://localhost:4443".to_string());
+    let mut connect_options = ConnectOptions::default();
+    if let Ok(token) = std::env::var("RELAY_TOKEN") {
+        connect_options = connect_options.with_auth_token(token);
+    }
+    let grpc_addr = std::env::var("GRPC_ADDR").unwrap_or_else(|_| GRPC_CLIENT_ADDR.to_string());
+    let drone_id = std::env::var("DRONE_ID").context("DRONE_ID must be set")?;
+    let command_name = std::env::var("COMMAND").unwrap_or_else(|_| "return_home".to_string());
+
+    let mut shutdown = pin!(shutdown_signal());
+
+    if drone_id == ALL_DRONES {
+        let command = parse_command("", &command_name)?;
+        let mut client = DroneServiceClient::connect(grpc_addr).await?;
+        let broadcast = tokio::select! {
+            broadcast = client.broadcast_command(command) => broadcast?.into_inner(),
+            () = &mut shutdown => {
+                println!("[EXIT] interrupted before the server replied");
+                return Ok(());
+            }
+        };
+        let accepted = broadcast.acks.values().filter(|ack| ack.accepted).count();
+        let total = broadcast.acks.len();
+        for (drone_id, ack) in broadcast.acks {
+            println!(
+                "[SENT] {drone_id} {} accepted={} {}",
+                ack.command_id, ack.accepted, ack.message
+            );
+        }
+        println!("[SUMMARY] {accepted} of {total} connected drones accepted the command");
+        return Ok(());
+    }
+
+    let command = parse_command(&drone_id, &command_name)?;
+
+    info!(relay = %url, "Controller connecting to relay");
+    let (_session, _producer, consumer) = connect_bidirectional_opts(&url, connect_options).await?;
+
+    // Subscribe before sending so the ack cannot be missed
+    let acks = tokio::select! {
+        acks = subscribe_acks(&consumer, &drone_id, ACK_TIMEOUT) => acks,
+        () = &mut shutdown => {
+            println!("[EXIT] interrupted before {drone_id} came online");
+            return Ok(());
+        }
+    };
+    let mut acks =
+        Box::pin(acks.with_context(|| {
+            format!("{drone_id} did not announce its acks within {ACK_TIMEOUT:?}")
+        })?);
+
+    // Follow the drone's telemetry while waiting, if it publishes any
+    let tracks = parse_tracks()?;
+    let telemetry_consumer = consumer.clone();
+    let telemetry_drone_id = drone_id.clone();
+    let last_seen = Arc::new(Mutex::new(LastSeen::default()));
+    let telemetry_last_seen = Arc::clone(&last_seen);
+    tokio::spawn(async move {
+        let Some(telemetry) =
+            subscribe_telemetry(&telemetry_consumer, &telemetry_drone_id, &tracks).await
+        else {
+            return;
+        };
+        let mut telemetry = Box::pin(telemetry);
+        while let Some(Ok(report)) = telemetry.next().await {
+            print_telemetry(&report);
+            telemetry_last_seen
+                .lock()
+                .expect("telemetry summary lock poisoned")
+                .record(report);
+        }
+    });
+
+    let mut client = DroneServiceClient::connect(grpc_addr).await?;
+    let queued = client.send_command(command).await?.into_inner();
+    println!("[SENT] {} accepted={}", queued.command_id, queued.accepted);
+    if !queued.accepted {
+        bail!("server rejected command: {}", queued.message);
+    }
+
+    let waiting = tokio::time::timeout(ACK_TIMEOUT, async {
+        while let Some(ack) = acks.next().await {
+            match ack {
+                Ok(ack) if ack.command_id == queued.command_id => return Some(ack),
+                Ok(_) => continue,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Ack stream error");
+                    return None;
+                }
+            }
+        }
+        None
+    });
+    let waited = tokio::select! {
+        waited = waiting => Some(waited),
+        () = &mut shutdown => None,
+    };
+    last_seen
+        .lock()
+        .expect("telemetry summary lock poisoned")
+        .print_summary(&drone_id);
+    let Some(waited) = waited else {
+        println!("[EXIT] interrupted before the drone acknowledged");
+        return Ok(());
+    };
+    let ack = waited
+        .context("timed out waiting for the drone to acknowledge")?
+        .context("ack stream closed before the drone acknowledged")?;
+
+    println!(
+        "[ACK] {} accepted={} {}",
+        ack.command_id, ack.accepted, ack.message
+    );
+    Ok(())
+}
+
+/// The telemetry tracks named by the comma-separated `TRACKS`, every track if unset.
+fn parse_tracks() -> Result<Vec<TelemetryTrack>> {
+    let Ok(names) = std::env::var("TRACKS") else {
+        return Ok(TelemetryTrack::ALL.to_vec());
+    };
+    names
+        .split(',')
+        .map(|name| {
+            TelemetryTrack::from_name(name.trim())
+                .with_context(|| format!("unknown telemetry track '{name}'"))
+        })
+        .collect()
+}
+
+/// The latest report on each telemetry track, summarised on exit.
+#[derive(Debug, Default)]
+struct LastSeen {
+    position: Option<DronePosition>,
+    battery: Option<BatteryState>,
+    status: Option<DroneStatus>,
+}
+
+impl LastSeen {
+    fn record(&mut self, report: DroneTelemetry) {
+        match report {
+            DroneTelemetry::Position(pos) => self.position = Some(pos),
+            DroneTelemetry::Battery(battery) => self.battery = Some(battery),
+            DroneTelemetry::Status(status) => self.status = Some(status),
+        }
+    }
+
+    fn print_summary(&self, drone_id: &str) {
+        if self.position.is_none() && self.battery.is_none() && self.status.is_none() {
+            println!("[SUMMARY] {drone_id} published no telemetry");
+            return;
+        }
+        println!("[SUMMARY] {drone_id}");
+        for report in [
+            self.position.clone().map(DroneTelemetry::Position),
+            self.battery.clone().map(DroneTelemetry::Battery),
+            self.status.clone().map(DroneTelemetry::Status),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            print_telemetry(&report);
+        }
+    }
+}
+
+fn print_telemetry(report: &DroneTelemetry) {
+    match report {
+        DroneTelemetry::Position(pos) => println!(
+            "[POS] lat={:.6} lon={:.6} alt={:.1}",
+            pos.latitude, pos.longitude, pos.altitude_m
+        ),
+        DroneTelemetry::Battery(battery) => {
+            println!("[BAT] {:.1}%", battery.charge_percent)
+        }
+        DroneTelemetry::Status(status) => println!(
+            "[STATUS] command={} at_target={}",
+            status.command_id, status.at_target
+        ),
+    }
+}
+
+/// Build the command named by `name`, taking a takeoff altitude from `ALTITUDE_M`, a goto
+/// target from `TARGET` and an optional expiry from `TTL_MS`.
+fn parse_command(drone_id: &str, name: &str) -> Result<DroneCommand> {
+    let command_type =
+        CommandType::from_str_name(&format!("COMMAND_TYPE_{}", name.to_ascii_uppercase()))
+            .with_context(|| format!("unknown command '{name}'"))?;
+
+    let mut command = match command_type {
+        CommandType::Takeoff => {
+            let altitude_m = std::env::var("ALTITUDE_M")
+                .map_or(Ok(10.0), |altitude| altitude.parse())
+                .context("ALTITUDE_M must be a number")?;
+            DroneCommand::takeoff(drone_id, altitude_m)
+        }
+        CommandType::Goto => {
+            let target = std::env::var("TARGET").context("goto requires TARGET=lat,lon,alt")?;
+            let coords = target
+                .split(',')
+                .map(|coord| coord.trim().parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .context("TARGET must be lat,lon,alt")?;
+            let [latitude, longitude, altitude_m] = coords[..] else {
+                bail!("TARGET must be lat,lon,alt");
+            };
+            DroneCommand::goto(drone_id, latitude, longitude, altitude_m)
+        }
+        CommandType::Arm => DroneCommand::arm(drone_id),
+        CommandType::ReturnHome => DroneCommand::return_home(drone_id),
+        CommandType::Land => DroneCommand::land(drone_id),
+        CommandType::Unspecified => bail!("unknown command '{name}'"),
+    };
+    if let Ok(ttl_ms) = std::env::var("TTL_MS") {
+        command.ttl_ms = ttl_ms.parse().context("TTL_MS must be a whole number")?;
+    }
+    Ok(command)
+}
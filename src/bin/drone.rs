@@ -1,28 +1,41 @@
-use anyhow::Result;
-use futures::{SinkExt, StreamExt};
+use anyhow::{Context, Result};
 use moq_prototype::PRIMARY_TRACK;
-use moq_prototype::connect_bidirectional;
-use moq_prototype::drone_proto::DronePosition;
+use moq_prototype::connect::ConnectOptions;
+use moq_prototype::drone::{
+    AckPublisher, DroneLoopConfig, MovementModelKind, TelemetryPublisher, run_drone_loop_until,
+};
+use moq_prototype::{connect_bidirectional_opts, shutdown_signal, stable_client_id};
 use rpcmoq_lite::{RpcClient, RpcClientConfig};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::time::interval;
-use tracing::{debug, info, warn};
+use std::time::Duration;
+use tracing::{info, warn};
 use uuid::Uuid;
 
+/// How far a hovering drone may drift, in metres, before it reports on every tick again.
+const DEFAULT_KEEPALIVE_TOLERANCE_M: f64 = 0.5;
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
     let url = std::env::var("RELAY_URL").unwrap_or_else(|_| "https://localhost:4443".to_string());
-    let drone_id = std::env::var("DRONE_ID").unwrap_or_else(|_| Uuid::new_v4().to_string());
+    let mut connect_options = ConnectOptions::default();
+    if let Ok(token) = std::env::var("RELAY_TOKEN") {
+        connect_options = connect_options.with_auth_token(token);
+    }
+    let drone_id = drone_id();
+    let movement_model = match std::env::var("MOVEMENT_MODEL") {
+        Ok(name) => name.parse::<MovementModelKind>()?,
+        Err(_) => MovementModelKind::default(),
+    };
 
     info!(
         drone_id = %drone_id,
         relay = %url,
+        movement_model = ?movement_model,
         "Drone connecting to relay"
     );
 
-    let (_session, producer, consumer) = connect_bidirectional(&url).await?;
+    let (_session, producer, consumer) = connect_bidirectional_opts(&url, connect_options).await?;
 
     let config = RpcClientConfig::builder()
         .client_id(drone_id.clone())
@@ -34,58 +47,73 @@ async fn main() -> Result<()> {
         .timeout(Duration::from_secs(60))
         .build();
 
-    let mut client = RpcClient::new(Arc::new(producer), consumer, config);
-
-    let conn = client
-        .connect::<DronePosition, DronePosition>("drone.EchoService/Echo")
-        .await?;
-
-    info!(drone_id = %drone_id, "Drone is online");
-
-    let (mut sender, mut receiver) = conn.split();
-
-    // Spawn a task to send position updates
-    let send_drone_id = drone_id.clone();
-    tokio::spawn(async move {
-        let mut ticker = interval(Duration::from_secs(1));
-
-        loop {
-            ticker.tick().await;
-
-            let pos = DronePosition {
-                drone_id: send_drone_id.clone(),
-                latitude: 37.7749,
-                longitude: -122.4194,
-                altitude_m: 100.0,
-                heading_deg: 0.0,
-                speed_mps: 0.0,
-                timestamp: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-            };
-
-            if let Err(e) = sender.send(pos).await {
-                warn!(error = %e, "Failed to send position, stopping sender");
-                break;
-            }
-
-            debug!(lat = 37.7749, lon = -122.4194, alt = 100.0, "Sent position");
-        }
-    });
-
-    // Receive echoed responses in the main task
-    while let Some(result) = receiver.next().await {
-        match result {
-            Ok(_echo) => {
-                info!("Received echo");
-            }
-            Err(e) => {
-                warn!(error = %e, "Echo receive error");
-            }
-        }
+    let mut loop_config = DroneLoopConfig::new(drone_id.clone(), movement_model.build());
+    let mut retry = loop_config.retry.clone();
+    if let Ok(initial_ms) = std::env::var("RECONNECT_INITIAL_MS") {
+        let initial_ms: u64 = initial_ms
+            .parse()
+            .context("RECONNECT_INITIAL_MS must be a whole number")?;
+        retry.initial_backoff = Duration::from_millis(initial_ms);
+    }
+    if let Ok(max_ms) = std::env::var("RECONNECT_MAX_MS") {
+        let max_ms: u64 = max_ms
+            .parse()
+            .context("RECONNECT_MAX_MS must be a whole number")?;
+        retry.max_backoff = Duration::from_millis(max_ms);
+    }
+    if let Ok(multiplier) = std::env::var("RECONNECT_MULTIPLIER") {
+        let multiplier = multiplier
+            .parse()
+            .ok()
+            .filter(|multiplier: &f64| *multiplier >= 1.0)
+            .context("RECONNECT_MULTIPLIER must be a number of at least 1")?;
+        retry = retry.with_multiplier(multiplier);
+    }
+    if let Ok(max_retries) = std::env::var("RECONNECT_MAX_RETRIES") {
+        let max_retries: u32 = max_retries
+            .parse()
+            .context("RECONNECT_MAX_RETRIES must be a whole number")?;
+        retry.max_retries = Some(max_retries);
+    }
+    loop_config = loop_config.with_retry(retry);
+    // A hovering drone only reports every HEARTBEAT_INTERVAL_MS, keep it below the server's
+    // staleness threshold
+    if let Ok(heartbeat_ms) = std::env::var("HEARTBEAT_INTERVAL_MS") {
+        let heartbeat_ms: u64 = heartbeat_ms
+            .parse()
+            .context("HEARTBEAT_INTERVAL_MS must be a whole number")?;
+        let tolerance_m: f64 = match std::env::var("KEEPALIVE_TOLERANCE_M") {
+            Ok(tolerance_m) => tolerance_m
+                .parse()
+                .context("KEEPALIVE_TOLERANCE_M must be a number")?,
+            Err(_) => DEFAULT_KEEPALIVE_TOLERANCE_M,
+        };
+        loop_config =
+            loop_config.with_keepalive(tolerance_m, Duration::from_millis(heartbeat_ms))?;
+    }
+    match AckPublisher::new(&producer, &drone_id) {
+        Some(acks) => loop_config = loop_config.with_ack_publisher(acks),
+        None => warn!(drone_id = %drone_id, "Not permitted to publish command acks"),
+    }
+    match TelemetryPublisher::new(&producer, &drone_id) {
+        Some(telemetry) => loop_config = loop_config.with_telemetry_publisher(telemetry),
+        None => warn!(drone_id = %drone_id, "Not permitted to publish telemetry"),
     }
 
-    info!("Echo stream closed, drone shutting down");
+    let client = RpcClient::new(Arc::new(producer), consumer, config);
+    run_drone_loop_until(client, loop_config, shutdown_signal()).await?;
     Ok(())
 }
+
+/// The drone's id: `DRONE_ID` if set, otherwise one derived from `DRONE_ID_SEED` so that it
+/// survives restarts, and otherwise a random one, so that drones started side by side don't
+/// share an id.
+fn drone_id() -> String {
+    if let Ok(drone_id) = std::env::var("DRONE_ID") {
+        return drone_id;
+    }
+    match std::env::var("DRONE_ID_SEED") {
+        Ok(seed) => stable_client_id(&seed),
+        Err(_) => Uuid::new_v4().to_string(),
+    }
+}
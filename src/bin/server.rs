@@ -1,32 +1,59 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use futures::StreamExt;
 use moq_prototype::PRIMARY_TRACK;
-use moq_prototype::connect_bidirectional;
-use moq_prototype::drone::DroneSessionMap;
-use moq_prototype::drone_proto::DronePosition;
-use moq_prototype::grpc::{self, EchoServiceClient};
+use moq_prototype::connect::ConnectOptions;
+use moq_prototype::drone::{DRONE_SESSION_PATH, DroneSessionMap, SEND_COMMAND_PATH};
+use moq_prototype::drone_proto::{DroneCommand, DroneMessage, DronePosition};
+use moq_prototype::grpc::{self, DroneServiceClient, DroneServiceImpl, EchoServiceClient};
+use moq_prototype::telemetry::TelemetryRateLimit;
 use moq_prototype::unit_context::UnitContext;
 use moq_prototype::unit_map::UnitMap;
+use moq_prototype::{connect_bidirectional_opts, wait_ready};
 use rpcmoq_lite::DecodedInbound;
 use rpcmoq_lite::{RpcRouter, RpcRouterConfig};
 use std::sync::Arc;
-use tracing::{error, info};
+use std::time::Duration;
+use tonic::transport::{Channel, Endpoint};
+use tracing::{error, info, warn};
 
 const GRPC_ADDR: &str = "[::1]:50051";
 const GRPC_CLIENT_ADDR: &str = "http://[::1]:50051";
+/// How long to wait for the relay to echo our announcements after connecting.
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
     let url = std::env::var("RELAY_URL").unwrap_or_else(|_| "https://localhost:4443".to_string());
+    let mut connect_options = ConnectOptions::default();
+    if let Ok(token) = std::env::var("RELAY_TOKEN") {
+        connect_options = connect_options.with_auth_token(token);
+    }
 
     let unit_map: Arc<UnitMap<UnitContext>> = Arc::new(UnitMap::new());
     let session_map: Arc<DroneSessionMap> = Arc::new(DroneSessionMap::new());
 
+    let mut service = DroneServiceImpl::new(Arc::clone(&unit_map), Arc::clone(&session_map));
+    if let Ok(rate) = std::env::var("TELEMETRY_RATE_HZ") {
+        let rate: f64 = rate
+            .parse()
+            .ok()
+            .filter(|rate: &f64| rate.is_finite() && *rate > 0.0)
+            .context("TELEMETRY_RATE_HZ must be a positive number")?;
+        // Allow a second's worth of reports in a burst
+        service =
+            service.with_telemetry_rate_limit(TelemetryRateLimit::new(rate, rate.ceil() as u32));
+    }
+    if let Ok(grace_ms) = std::env::var("SESSION_TAKEOVER_GRACE_MS") {
+        let grace_ms: u64 = grace_ms
+            .parse()
+            .context("SESSION_TAKEOVER_GRACE_MS must be a whole number")?;
+        service = service.with_session_takeover(Duration::from_millis(grace_ms));
+    }
+
     let grpc_addr = GRPC_ADDR.parse()?;
-    let server_unit_map = Arc::clone(&unit_map);
-    let server_session_map = Arc::clone(&session_map);
     tokio::spawn(async move {
-        if let Err(e) = grpc::start_server(grpc_addr, server_unit_map, server_session_map).await {
+        if let Err(e) = grpc::serve(grpc_addr, service).await {
             error!("gRPC server error: {e}");
         }
     });
@@ -36,7 +63,7 @@ async fn main() -> Result<()> {
 
     info!("Server connecting to relay at {url}");
 
-    let (_session, producer, consumer) = connect_bidirectional(&url).await?;
+    let (_session, producer, consumer) = connect_bidirectional_opts(&url, connect_options).await?;
     let producer = Arc::new(producer);
 
     let config = RpcRouterConfig::builder()
@@ -47,23 +74,54 @@ async fn main() -> Result<()> {
         .track_name(PRIMARY_TRACK.to_string())
         .build();
 
-    let mut router = RpcRouter::new(consumer.clone(), producer.clone(), config);
+    let mut router = RpcRouter::new(consumer.clone(), producer.clone(), config)?;
+
+    // One channel to the gRPC server for every session, multiplexed over a single connection
+    // that is only opened once the first session needs it
+    let channel = Arc::new(Endpoint::from_static(GRPC_CLIENT_ADDR).connect_lazy());
 
-    router.register(
+    router.register_with_state(
         "drone.EchoService/Echo",
+        Arc::clone(&channel),
         // TODO: Wrap Grpc struct with something that looks similar to EchoServiceClient. This will
         // be generic and no closure will be required here. The downside is you lose per service
         // interceptors and have to do them globally. Maybe there is a way around this?
-        |_, inbound: DecodedInbound<DronePosition>| async move {
-            let mut client = EchoServiceClient::connect(GRPC_CLIENT_ADDR)
-                .await
-                .inspect_err(|e| tracing::error!(?e))
-                .map_err(|e| tonic::Status::internal(e.to_string()))?;
+        |channel: Arc<Channel>, _, inbound: DecodedInbound<DronePosition>| async move {
+            let mut client = EchoServiceClient::new((*channel).clone());
             let response = client.echo(inbound).await?;
             Ok(response.into_inner())
         },
     )?;
 
+    router.register_with_state(
+        DRONE_SESSION_PATH,
+        Arc::clone(&channel),
+        |channel: Arc<Channel>, _, inbound: DecodedInbound<DroneMessage>| async move {
+            let mut client = DroneServiceClient::new((*channel).clone());
+            let response = client.drone_session(inbound).await?;
+            Ok(response.into_inner())
+        },
+    )?;
+
+    router.register_with_state(
+        SEND_COMMAND_PATH,
+        channel,
+        |channel: Arc<Channel>, _, inbound: DecodedInbound<DroneCommand>| async move {
+            let client = DroneServiceClient::new((*channel).clone());
+            // A unary call, so each command on the stream is sent as its own request
+            Ok(inbound.then(move |command| {
+                let mut client = client.clone();
+                async move { Ok(client.send_command(command).await?.into_inner()) }
+            }))
+        },
+    )?;
+
+    // Drones that connected before us are already announced, but give the relay time to learn
+    // about our own broadcasts before telling anyone we're up
+    if let Err(e) = wait_ready(&producer, &consumer, READY_TIMEOUT).await {
+        warn!("Relay session not confirmed ready, continuing anyway: {e}");
+    }
+
     info!("Waiting for drones to connect...");
 
     router.run().await?;
@@ -0,0 +1,87 @@
+//! Sources of the current time for time-based logic such as staleness and command expiry.
+//!
+//! Components that read the time take an `Arc<dyn Clock>` and default to [`SystemClock`]. Tests
+//! can hand them a `TestClock` instead and advance it explicitly, rather than sleeping or
+//! passing an `Instant` to every `*_at` method.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A source of the current monotonic time.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// The clock used when none is configured.
+pub(crate) fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// A clock that stands still until it is advanced.
+///
+/// Clones share the same time, so a test can keep one and hand another to the code under test.
+/// Only built for tests or with the `test-util` feature.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Clone)]
+pub struct TestClock {
+    now: Arc<std::sync::Mutex<Instant>>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl TestClock {
+    /// A clock stopped at the current time.
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(std::sync::Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Move the clock forward by `by`.
+    pub fn advance(&self, by: std::time::Duration) {
+        *self.now.lock().expect("test clock lock poisoned") += by;
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Default for TestClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Clock for TestClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().expect("test clock lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_clock_only_moves_when_advanced() {
+        let clock = TestClock::new();
+        let shared = clock.clone();
+        let start = clock.now();
+
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(clock.now(), start);
+
+        shared.advance(Duration::from_secs(3));
+        assert_eq!(clock.now(), start + Duration::from_secs(3));
+    }
+}
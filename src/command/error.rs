@@ -0,0 +1,37 @@
+//! Error types for the drone command queue.
+
+use super::Command;
+use crate::state_machine::flight::FlightState;
+
+/// Indicates that a command could not be queued because the queue is at capacity.
+#[derive(Debug, thiserror::Error)]
+#[error("command queue is full (capacity {capacity})")]
+pub struct QueueFull {
+    pub capacity: usize,
+}
+
+/// Indicates that a command is not valid for the drone's current flight state.
+#[derive(Debug, thiserror::Error)]
+#[error("cannot accept {command:?} while {state:?}")]
+pub struct CommandRejected {
+    pub state: FlightState,
+    pub command: Command,
+}
+
+/// Indicates why a command could not be queued for a drone.
+#[derive(Debug, thiserror::Error)]
+pub enum EnqueueError {
+    #[error(transparent)]
+    QueueFull(#[from] QueueFull),
+
+    #[error(transparent)]
+    Rejected(#[from] CommandRejected),
+}
+
+/// Indicates that a `DroneCommand` carries a command type this build does not know, say one
+/// added by a newer controller.
+#[derive(Debug, thiserror::Error)]
+#[error("unknown command type {value}")]
+pub struct UnknownCommand {
+    pub value: i32,
+}
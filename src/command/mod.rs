@@ -0,0 +1,408 @@
+pub mod error;
+mod proto;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use self::error::QueueFull;
+
+/// The default number of commands a [`CommandQueue`] holds before rejecting new ones.
+pub const DEFAULT_COMMAND_CAPACITY: usize = 64;
+
+/// Correlates a queued command with the acknowledgement the drone sends back for it.
+#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+pub struct CommandId(Uuid);
+
+impl CommandId {
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4())
+    }
+
+    pub fn as_uuid(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl From<Uuid> for CommandId {
+    fn from(uuid: Uuid) -> Self {
+        Self(uuid)
+    }
+}
+
+impl fmt::Debug for CommandId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CommandId({})", self.0)
+    }
+}
+
+impl fmt::Display for CommandId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// An instruction for a drone.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Arm,
+    Takeoff {
+        altitude_m: f64,
+    },
+    Goto {
+        latitude: f64,
+        longitude: f64,
+        altitude_m: f64,
+    },
+    ReturnHome,
+    Land,
+}
+
+impl Command {
+    /// The delivery priority of this command.
+    ///
+    /// Safety commands that bring the drone down or home preempt everything else.
+    pub fn priority(&self) -> CommandPriority {
+        match self {
+            Command::ReturnHome | Command::Land => CommandPriority::Safety,
+            Command::Arm | Command::Takeoff { .. } | Command::Goto { .. } => {
+                CommandPriority::Normal
+            }
+        }
+    }
+}
+
+/// A command waiting in a [`CommandQueue`] along with its correlation ID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedCommand {
+    pub id: CommandId,
+    pub command: Command,
+    /// When the command goes stale and should be discarded rather than delivered.
+    pub expires_at: Option<Instant>,
+}
+
+impl QueuedCommand {
+    /// Whether the command has passed its deadline at `now`.
+    pub fn is_expired_at(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|at| now >= at)
+    }
+}
+
+/// The drone's response to a delivered command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandReceipt {
+    pub accepted: bool,
+    pub message: String,
+}
+
+/// Ordering used by [`CommandQueue`] when choosing which command to deliver next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CommandPriority {
+    Normal,
+    Safety,
+}
+
+/// A bounded priority queue of commands waiting to be sent to a drone.
+///
+/// Commands are delivered highest [`CommandPriority`] first, and in FIFO order within a
+/// priority.
+///
+/// The queue never grows past its capacity, so a controller that outpaces a slow drone receives
+/// a [`QueueFull`] error instead of buffering without limit.
+///
+/// Commands past their [`QueuedCommand::expires_at`] are discarded when popped, so a drone that
+/// reconnects after an outage isn't sent instructions that no longer apply.
+#[derive(Debug)]
+pub struct CommandQueue {
+    pending: VecDeque<QueuedCommand>,
+    capacity: usize,
+    expired: u64,
+}
+
+impl CommandQueue {
+    /// Construct an empty queue holding at most `capacity` commands.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            pending: VecDeque::with_capacity(capacity),
+            capacity,
+            expired: 0,
+        }
+    }
+
+    /// Insert `command` behind every pending command of equal or higher priority.
+    pub fn push(&mut self, command: QueuedCommand) -> Result<(), QueueFull> {
+        if self.pending.len() >= self.capacity {
+            return Err(QueueFull {
+                capacity: self.capacity,
+            });
+        }
+
+        // The queue is kept sorted by descending priority.
+        let index = self.insertion_point(command.command.priority());
+        self.pending.insert(index, command);
+        Ok(())
+    }
+
+    /// The pending commands that will be delivered before a command of `priority` pushed now.
+    pub fn ahead_of(&self, priority: CommandPriority) -> impl Iterator<Item = &QueuedCommand> {
+        self.pending.range(..self.insertion_point(priority))
+    }
+
+    fn insertion_point(&self, priority: CommandPriority) -> usize {
+        self.pending
+            .partition_point(|queued| queued.command.priority() >= priority)
+    }
+
+    /// Merge `command` into the most recently queued pending command if both carry the same
+    /// [`Command`], returning the ID of the pending command if it did.
+    ///
+    /// The pending command keeps its ID and position and takes the later of the two deadlines.
+    /// Safety commands are never merged: a repeated [`Command::Land`] is queued again.
+    pub fn coalesce(&mut self, command: &QueuedCommand) -> Option<CommandId> {
+        if command.command.priority() == CommandPriority::Safety {
+            return None;
+        }
+        // Normal commands are pushed to the back, behind every safety command
+        let newest = self
+            .pending
+            .back_mut()
+            .filter(|newest| newest.command == command.command)?;
+        newest.expires_at = newest
+            .expires_at
+            .zip(command.expires_at)
+            .map(|(a, b)| a.max(b));
+        Some(newest.id)
+    }
+
+    /// Take the highest priority pending command that has not expired, if any.
+    pub fn pop(&mut self) -> Option<QueuedCommand> {
+        self.pop_at(Instant::now())
+    }
+
+    /// Take the highest priority pending command that has not expired at `now`, if any.
+    ///
+    /// Expired commands ahead of it are discarded and counted in [`expired`](Self::expired).
+    pub fn pop_at(&mut self, now: Instant) -> Option<QueuedCommand> {
+        while let Some(command) = self.pending.pop_front() {
+            if !command.is_expired_at(now) {
+                return Some(command);
+            }
+            self.expired += 1;
+        }
+        None
+    }
+
+    /// Remove and return every pending command that has expired at `now`.
+    ///
+    /// They are counted in [`expired`](Self::expired) like those [`pop_at`](Self::pop_at)
+    /// discards.
+    pub fn remove_expired(&mut self, now: Instant) -> Vec<QueuedCommand> {
+        let mut expired = Vec::new();
+        self.pending.retain(|command| {
+            let is_expired = command.is_expired_at(now);
+            if is_expired {
+                expired.push(command.clone());
+            }
+            !is_expired
+        });
+        self.expired += expired.len() as u64;
+        expired
+    }
+
+    /// The number of commands discarded for expiring before they were delivered.
+    pub fn expired(&self) -> u64 {
+        self.expired
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl Default for CommandQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_COMMAND_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn queued(command: Command) -> QueuedCommand {
+        QueuedCommand {
+            id: CommandId::generate(),
+            command,
+            expires_at: None,
+        }
+    }
+
+    fn pop(queue: &mut CommandQueue) -> Option<Command> {
+        queue.pop().map(|queued| queued.command)
+    }
+
+    #[test]
+    fn test_fifo_order() {
+        let mut queue = CommandQueue::new(4);
+        queue.push(queued(Command::Arm)).unwrap();
+        queue
+            .push(queued(Command::Takeoff { altitude_m: 10.0 }))
+            .unwrap();
+
+        assert_eq!(pop(&mut queue), Some(Command::Arm));
+        assert_eq!(pop(&mut queue), Some(Command::Takeoff { altitude_m: 10.0 }));
+        assert_eq!(pop(&mut queue), None);
+    }
+
+    #[test]
+    fn test_safety_command_jumps_queue() {
+        let mut queue = CommandQueue::new(4);
+        let goto = Command::Goto {
+            latitude: 37.0,
+            longitude: -122.0,
+            altitude_m: 50.0,
+        };
+        queue.push(queued(goto.clone())).unwrap();
+        queue.push(queued(Command::Land)).unwrap();
+
+        assert_eq!(pop(&mut queue), Some(Command::Land));
+        assert_eq!(pop(&mut queue), Some(goto));
+    }
+
+    #[test]
+    fn test_ahead_of_follows_delivery_order() {
+        let mut queue = CommandQueue::new(4);
+        queue.push(queued(Command::Arm)).unwrap();
+        queue.push(queued(Command::ReturnHome)).unwrap();
+
+        let ahead = |queue: &CommandQueue, priority| {
+            queue
+                .ahead_of(priority)
+                .map(|queued| queued.command.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ahead(&queue, CommandPriority::Safety),
+            [Command::ReturnHome]
+        );
+        assert_eq!(
+            ahead(&queue, CommandPriority::Normal),
+            [Command::ReturnHome, Command::Arm]
+        );
+    }
+
+    #[test]
+    fn test_equal_priority_keeps_fifo_order() {
+        let mut queue = CommandQueue::new(8);
+        queue.push(queued(Command::Arm)).unwrap();
+        queue.push(queued(Command::ReturnHome)).unwrap();
+        queue
+            .push(queued(Command::Takeoff { altitude_m: 10.0 }))
+            .unwrap();
+        queue.push(queued(Command::Land)).unwrap();
+
+        assert_eq!(pop(&mut queue), Some(Command::ReturnHome));
+        assert_eq!(pop(&mut queue), Some(Command::Land));
+        assert_eq!(pop(&mut queue), Some(Command::Arm));
+        assert_eq!(pop(&mut queue), Some(Command::Takeoff { altitude_m: 10.0 }));
+    }
+
+    #[test]
+    fn test_coalesce_merges_into_newest_pending_command() {
+        let mut queue = CommandQueue::new(4);
+        let now = Instant::now();
+        let goto = Command::Goto {
+            latitude: 37.0,
+            longitude: -122.0,
+            altitude_m: 50.0,
+        };
+        let mut first = queued(goto.clone());
+        first.expires_at = Some(now + Duration::from_secs(1));
+        queue.push(first.clone()).unwrap();
+
+        let mut repeat = queued(goto);
+        repeat.expires_at = Some(now + Duration::from_secs(5));
+        assert_eq!(queue.coalesce(&repeat), Some(first.id));
+        assert_eq!(queue.coalesce(&queued(Command::Arm)), None);
+        assert_eq!(queue.len(), 1);
+
+        let merged = queue.pop_at(now + Duration::from_secs(2)).unwrap();
+        assert_eq!(merged.id, first.id);
+        assert_eq!(merged.expires_at, repeat.expires_at);
+    }
+
+    #[test]
+    fn test_safety_commands_are_never_coalesced() {
+        let mut queue = CommandQueue::new(4);
+        queue.push(queued(Command::Land)).unwrap();
+        assert_eq!(queue.coalesce(&queued(Command::Land)), None);
+    }
+
+    #[test]
+    fn test_full_queue_rejects() {
+        let mut queue = CommandQueue::new(1);
+        queue.push(queued(Command::Arm)).unwrap();
+
+        let result = queue.push(queued(Command::Land));
+        assert!(matches!(result, Err(QueueFull { capacity: 1 })));
+        assert_eq!(queue.len(), 1);
+
+        // Draining frees up room again
+        assert_eq!(pop(&mut queue), Some(Command::Arm));
+        assert!(queue.push(queued(Command::Land)).is_ok());
+    }
+
+    #[test]
+    fn test_expired_commands_are_skipped() {
+        let mut queue = CommandQueue::new(4);
+        let now = Instant::now();
+        queue
+            .push(QueuedCommand {
+                expires_at: Some(now + Duration::from_secs(1)),
+                ..queued(Command::Arm)
+            })
+            .unwrap();
+        queue
+            .push(queued(Command::Takeoff { altitude_m: 10.0 }))
+            .unwrap();
+
+        let later = now + Duration::from_secs(2);
+        assert_eq!(
+            queue.pop_at(later).map(|queued| queued.command),
+            Some(Command::Takeoff { altitude_m: 10.0 })
+        );
+        assert_eq!(queue.expired(), 1);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_remove_expired_keeps_live_commands_in_order() {
+        let mut queue = CommandQueue::new(4);
+        let now = Instant::now();
+        let stale = QueuedCommand {
+            expires_at: Some(now),
+            ..queued(Command::Arm)
+        };
+        queue.push(queued(Command::Land)).unwrap();
+        queue.push(stale.clone()).unwrap();
+        queue
+            .push(queued(Command::Takeoff { altitude_m: 10.0 }))
+            .unwrap();
+
+        assert_eq!(queue.remove_expired(now), [stale]);
+        assert_eq!(queue.expired(), 1);
+        assert_eq!(pop(&mut queue), Some(Command::Land));
+        assert_eq!(pop(&mut queue), Some(Command::Takeoff { altitude_m: 10.0 }));
+    }
+}
@@ -0,0 +1,114 @@
+//! Constructors and checked accessors for the wire form of a command.
+
+use super::error::UnknownCommand;
+use crate::drone_proto::{CommandType, DroneCommand};
+
+impl DroneCommand {
+    /// The command type, or an error if it is a value this build does not know.
+    ///
+    /// The generated `command_type()` reads unknown values as `Unspecified`, which is
+    /// indistinguishable from a command that left the type out.
+    pub fn try_command_type(&self) -> Result<CommandType, UnknownCommand> {
+        CommandType::try_from(self.command_type).map_err(|_| UnknownCommand {
+            value: self.command_type,
+        })
+    }
+
+    pub fn arm(drone_id: impl Into<String>) -> Self {
+        Self::of_type(drone_id, CommandType::Arm)
+    }
+
+    pub fn takeoff(drone_id: impl Into<String>, altitude_m: f64) -> Self {
+        Self {
+            altitude_m,
+            ..Self::of_type(drone_id, CommandType::Takeoff)
+        }
+    }
+
+    pub fn goto(
+        drone_id: impl Into<String>,
+        latitude: f64,
+        longitude: f64,
+        altitude_m: f64,
+    ) -> Self {
+        Self {
+            latitude,
+            longitude,
+            altitude_m,
+            ..Self::of_type(drone_id, CommandType::Goto)
+        }
+    }
+
+    pub fn return_home(drone_id: impl Into<String>) -> Self {
+        Self::of_type(drone_id, CommandType::ReturnHome)
+    }
+
+    pub fn land(drone_id: impl Into<String>) -> Self {
+        Self::of_type(drone_id, CommandType::Land)
+    }
+
+    fn of_type(drone_id: impl Into<String>, command_type: CommandType) -> Self {
+        Self {
+            drone_id: drone_id.into(),
+            command_type: command_type.into(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constructors_set_type_and_target() {
+        let goto = DroneCommand::goto("drone-1", 1.0, 2.0, 3.0);
+        assert_eq!(goto.drone_id, "drone-1");
+        assert_eq!(goto.try_command_type().unwrap(), CommandType::Goto);
+        assert_eq!(
+            (goto.latitude, goto.longitude, goto.altitude_m),
+            (1.0, 2.0, 3.0)
+        );
+
+        let land = DroneCommand::land("drone-1");
+        assert_eq!(land.try_command_type().unwrap(), CommandType::Land);
+        assert_eq!(
+            (land.latitude, land.longitude, land.altitude_m),
+            (0.0, 0.0, 0.0)
+        );
+        assert!(land.command_id.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_command_type_is_an_error() {
+        let command = DroneCommand {
+            command_type: 42,
+            ..DroneCommand::land("drone-1")
+        };
+        let err = command.try_command_type().unwrap_err();
+        assert_eq!(err.value, 42);
+        assert_eq!(err.to_string(), "unknown command type 42");
+        // Unlike the generated getter, which can't tell it from a missing type
+        assert_eq!(command.command_type(), CommandType::Unspecified);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_codec_round_trip() {
+        use rpcmoq_lite::{JsonCodec, MessageCodec};
+
+        let goto = DroneCommand::goto("drone-1", 1.0, 2.0, 3.0);
+        let payload = JsonCodec::encode(&goto).unwrap();
+        let decoded: DroneCommand = JsonCodec::decode(payload.into()).unwrap();
+        assert_eq!(decoded, goto);
+    }
+
+    #[test]
+    fn test_unspecified_command_type_is_known() {
+        let command = DroneCommand::default();
+        assert_eq!(
+            command.try_command_type().unwrap(),
+            CommandType::Unspecified
+        );
+    }
+}
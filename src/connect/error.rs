@@ -0,0 +1,86 @@
+//! Error types for establishing a relay session.
+
+use std::time::Duration;
+
+use crate::tls::error::TlsError;
+
+/// Why a single attempt to connect to the relay failed.
+#[derive(Debug, thiserror::Error)]
+pub enum AttemptError {
+    #[error("transport error: {0}")]
+    Transport(#[from] web_transport_quinn::ClientError),
+
+    #[error("session setup error: {0}")]
+    Session(#[from] moq_lite::Error),
+
+    #[error("timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+impl AttemptError {
+    /// Whether the same attempt might succeed if tried again later.
+    ///
+    /// Connection failures and timeouts are retryable, the relay may be restarting. Name
+    /// resolution and TLS failures are not, retrying them only delays reporting a
+    /// misconfiguration.
+    pub fn is_retryable(&self) -> bool {
+        use web_transport_quinn::ClientError;
+
+        match self {
+            AttemptError::Transport(
+                ClientError::InvalidDnsName(_)
+                | ClientError::Rustls(_)
+                | ClientError::QuinnError(_),
+            ) => false,
+            AttemptError::Transport(_) | AttemptError::Session(_) | AttemptError::Timeout(_) => {
+                true
+            }
+        }
+    }
+}
+
+/// Indicates that a session with the relay could not be established.
+#[derive(Debug, thiserror::Error)]
+pub enum ConnectError {
+    #[error("invalid relay url: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+
+    #[error(transparent)]
+    Tls(#[from] TlsError),
+
+    #[error("failed to create WebTransport client: {0}")]
+    Client(#[source] web_transport_quinn::ClientError),
+
+    #[error("cannot scope the session to origin root '{root}'")]
+    InvalidRoot { root: String },
+
+    #[error(
+        "relay connection failed after {attempts} attempt(s) with a non-retryable error: {source}"
+    )]
+    Fatal { attempts: u32, source: AttemptError },
+
+    #[error("relay connection failed after {attempts} attempt(s): {source}")]
+    RetriesExhausted { attempts: u32, source: AttemptError },
+
+    #[error("relay did not echo our announcements within {0:?}")]
+    NotReady(Duration),
+
+    #[error("the consume root does not cover the publish root, readiness cannot be observed")]
+    ReadinessUnobservable,
+}
+
+impl ConnectError {
+    /// The number of connection attempts made before giving up, if any were made.
+    pub fn attempts(&self) -> Option<u32> {
+        match self {
+            ConnectError::Fatal { attempts, .. }
+            | ConnectError::RetriesExhausted { attempts, .. } => Some(*attempts),
+            ConnectError::InvalidUrl(_)
+            | ConnectError::Tls(_)
+            | ConnectError::Client(_)
+            | ConnectError::InvalidRoot { .. }
+            | ConnectError::NotReady(_)
+            | ConnectError::ReadinessUnobservable => None,
+        }
+    }
+}
@@ -0,0 +1,245 @@
+pub mod error;
+mod ready;
+mod stats;
+
+pub use ready::{READY_PROBE_PREFIX, wait_ready};
+pub use stats::{ConnectionStats, connection_stats};
+
+use std::time::Duration;
+
+use moq_lite::{Origin, OriginConsumer, OriginProducer, Path};
+use rpcmoq_lite::RetryPolicy;
+use url::Url;
+
+use crate::connect::error::ConnectError;
+use crate::tls::TlsConfig;
+
+/// How [`connect_bidirectional_opts`](crate::connect_bidirectional_opts) reaches the relay.
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    /// Upper bound on a single connection attempt.
+    pub connect_timeout: Duration,
+    /// Backoff between attempts that failed with a retryable error.
+    pub retry: RetryPolicy,
+    /// Certificate verification for the relay.
+    /// If not set, the relay's certificate is NOT verified.
+    pub tls: Option<TlsConfig>,
+    /// Bearer token granting publish/subscribe rights on the relay.
+    pub auth_token: Option<String>,
+    /// Only publish broadcasts under this root. The returned producer is rooted here.
+    pub publish_root: Option<String>,
+    /// Only consume broadcasts under this root. The returned consumer is rooted here.
+    pub consume_root: Option<String>,
+}
+
+impl ConnectOptions {
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    pub fn with_auth_token(mut self, token: impl Into<String>) -> Self {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    pub fn with_publish_root(mut self, root: impl Into<String>) -> Self {
+        self.publish_root = Some(root.into());
+        self
+    }
+
+    pub fn with_consume_root(mut self, root: impl Into<String>) -> Self {
+        self.consume_root = Some(root.into());
+        self
+    }
+}
+
+/// An established relay session, as returned by
+/// [`connect_bidirectional_with_stats`](crate::connect_bidirectional_with_stats).
+pub struct RelayConnection {
+    /// The MoQ session, closed when dropped.
+    pub session: moq_lite::Session,
+    /// The WebTransport session underneath, for [`connection_stats`].
+    pub transport: web_transport_quinn::Session,
+    /// Publishes broadcasts to the relay.
+    pub producer: OriginProducer,
+    /// Consumes broadcasts from the relay.
+    pub consumer: OriginConsumer,
+}
+
+/// The query parameter the relay reads the auth token from.
+pub const AUTH_TOKEN_PARAM: &str = "jwt";
+
+/// Attach `token` to the relay `url` so it is presented when the session is established.
+pub(crate) fn authorize_url(url: &mut Url, token: &str) {
+    url.query_pairs_mut().append_pair(AUTH_TOKEN_PARAM, token);
+}
+
+/// The origins one relay session is wired to.
+pub(crate) struct SessionOrigins {
+    /// Read by the session for broadcasts to publish to the relay.
+    pub publish: OriginConsumer,
+    /// Written by the session with broadcasts consumed from the relay.
+    pub consume: OriginProducer,
+    /// Handed to the caller to publish with.
+    pub producer: OriginProducer,
+    /// Handed to the caller to consume with.
+    pub consumer: OriginConsumer,
+}
+
+impl SessionOrigins {
+    /// Fresh origins for a session, scoped to the roots in `options`.
+    ///
+    /// A session with a consume root only asks the relay for broadcasts under it, and one with a
+    /// publish root can only publish under it, whatever the relay would allow.
+    pub(crate) fn new(options: &ConnectOptions) -> Result<Self, ConnectError> {
+        let published = Origin::produce();
+        let consumed = Origin::produce();
+        let mut origins = Self {
+            publish: published.consumer,
+            consume: consumed.producer,
+            producer: published.producer,
+            consumer: consumed.consumer,
+        };
+
+        if let Some(root) = &options.publish_root {
+            let path = scope_path(root)?;
+            let invalid = || ConnectError::InvalidRoot { root: root.clone() };
+            origins.producer = origins
+                .producer
+                .publish_only(std::slice::from_ref(&path))
+                .and_then(|producer| producer.with_root(&path))
+                .ok_or_else(invalid)?;
+        }
+        if let Some(root) = &options.consume_root {
+            let path = scope_path(root)?;
+            let invalid = || ConnectError::InvalidRoot { root: root.clone() };
+            origins.consume = origins
+                .consume
+                .publish_only(std::slice::from_ref(&path))
+                .ok_or_else(invalid)?;
+            origins.consumer = origins.consumer.with_root(&path).ok_or_else(invalid)?;
+        }
+        Ok(origins)
+    }
+}
+
+/// The path of an origin root, which has to name something narrower than the whole origin.
+fn scope_path(root: &str) -> Result<Path<'static>, ConnectError> {
+    let trimmed = root.trim_matches('/');
+    if trimmed.is_empty() {
+        return Err(ConnectError::InvalidRoot {
+            root: root.to_string(),
+        });
+    }
+    Ok(Path::new(trimmed).to_owned())
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            retry: RetryPolicy::builder()
+                .initial_backoff(Duration::from_millis(500))
+                .max_backoff(Duration::from_secs(10))
+                .max_retries(5)
+                .build(),
+            tls: None,
+            auth_token: None,
+            publish_root: None,
+            consume_root: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect::error::{AttemptError, ConnectError};
+    use crate::connect_bidirectional_opts;
+
+    #[tokio::test]
+    async fn test_invalid_url_is_not_retried() {
+        let result = connect_bidirectional_opts("not a url", ConnectOptions::default()).await;
+        assert!(matches!(result, Err(ConnectError::InvalidUrl(_))));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_relay_reports_attempts() {
+        let options = ConnectOptions::default()
+            .with_connect_timeout(Duration::from_millis(50))
+            .with_retry(
+                RetryPolicy::builder()
+                    .initial_backoff(Duration::from_millis(1))
+                    .max_retries(2)
+                    .build(),
+            );
+
+        // Nothing listens on the discard port
+        let result = connect_bidirectional_opts("https://127.0.0.1:9", options).await;
+
+        let Err(error) = result else {
+            panic!("expected the connection to fail");
+        };
+        assert!(matches!(error, ConnectError::RetriesExhausted { .. }));
+        assert_eq!(error.attempts(), Some(3));
+    }
+
+    #[test]
+    fn test_authorize_url_appends_token() {
+        let mut url: Url = "https://relay.example:4443/anon?region=us".parse().unwrap();
+        authorize_url(&mut url, "abc.def+ghi");
+
+        assert_eq!(
+            url.as_str(),
+            "https://relay.example:4443/anon?region=us&jwt=abc.def%2Bghi"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scoped_origins_stay_under_their_roots() {
+        let options = ConnectOptions::default()
+            .with_publish_root("/drone/")
+            .with_consume_root("server");
+        let origins = SessionOrigins::new(&options).unwrap();
+
+        // Published relative to the publish root
+        let _broadcast = origins.producer.create_broadcast("drone-1").unwrap();
+        assert!(origins.publish.consume_broadcast("drone/drone-1").is_some());
+
+        // Only broadcasts under the consume root are accepted from the relay
+        assert!(origins.consume.create_broadcast("other/acks").is_none());
+        let _broadcast = origins.consume.create_broadcast("server/drone-1").unwrap();
+        assert!(origins.consumer.consume_broadcast("drone-1").is_some());
+    }
+
+    #[test]
+    fn test_empty_root_is_rejected() {
+        let options = ConnectOptions::default().with_consume_root("/");
+        assert!(matches!(
+            SessionOrigins::new(&options),
+            Err(ConnectError::InvalidRoot { root }) if root == "/"
+        ));
+    }
+
+    #[test]
+    fn test_timeout_is_retryable() {
+        assert!(AttemptError::Timeout(Duration::from_secs(1)).is_retryable());
+        assert!(
+            !AttemptError::Transport(web_transport_quinn::ClientError::InvalidDnsName(
+                "relay.invalid".to_string()
+            ))
+            .is_retryable()
+        );
+    }
+}
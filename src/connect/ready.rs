@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use moq_lite::{OriginConsumer, OriginProducer};
+use uuid::Uuid;
+
+use crate::connect::error::ConnectError;
+
+/// The prefix readiness probes are published under, relative to the publish root.
+///
+/// Consumers that watch every announcement should skip paths under it.
+pub const READY_PROBE_PREFIX: &str = ".ready";
+
+/// Wait until a freshly connected session is ready, giving up after `timeout`.
+///
+/// `producer` and `consumer` are the pair returned by
+/// [`connect_bidirectional_opts`](crate::connect_bidirectional_opts) and friends. Connecting
+/// already waits for the relay's initial announcements, so `consumer` sees every broadcast that
+/// was live on the relay when the session came up. What it does not wait for is the relay
+/// learning about our own broadcasts. This publishes a probe broadcast under
+/// [`READY_PROBE_PREFIX`] and waits for the relay to announce it back. The relay forwards
+/// announcements in order, so once the probe comes back:
+///
+/// - every broadcast created on `producer` before the call is known to the relay, and other
+///   sessions subscribing from now on will find it;
+/// - the relay is forwarding announcements to `consumer`, not just the initial set.
+///
+/// It says nothing about broadcasts created after the call, or about other sessions' broadcasts
+/// still on their way to the relay.
+///
+/// The probe has to come back under the consume root, so a session scoped to disjoint publish
+/// and consume roots fails with [`ConnectError::ReadinessUnobservable`] straight away.
+pub async fn wait_ready(
+    producer: &OriginProducer,
+    consumer: &OriginConsumer,
+    timeout: Duration,
+) -> Result<(), ConnectError> {
+    let probe = format!("{READY_PROBE_PREFIX}/{}", Uuid::new_v4());
+    let absolute = producer.absolute(probe.as_str()).to_owned();
+    let relative = absolute
+        .strip_prefix(consumer.root())
+        .map(|path| path.to_owned())
+        .ok_or(ConnectError::ReadinessUnobservable)?;
+    let mut announcements = consumer
+        .consume_only(&[relative.borrow()])
+        .ok_or(ConnectError::ReadinessUnobservable)?;
+
+    // Announced for as long as it is alive, i.e. until the relay has echoed it
+    let _probe = producer
+        .create_broadcast(probe.as_str())
+        .ok_or(ConnectError::ReadinessUnobservable)?;
+
+    let echoed = async {
+        loop {
+            match announcements.announced().await {
+                Some((path, Some(_))) if path == relative => return true,
+                Some(_) => {}
+                None => return false,
+            }
+        }
+    };
+    match tokio::time::timeout(timeout, echoed).await {
+        Ok(true) => Ok(()),
+        Ok(false) | Err(_) => Err(ConnectError::NotReady(timeout)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use moq_lite::Origin;
+
+    #[tokio::test]
+    async fn test_ready_once_the_probe_is_echoed() {
+        // One origin stands in for a relay that forwards our broadcasts back to us
+        let origin = Origin::produce();
+        wait_ready(&origin.producer, &origin.consumer, Duration::from_secs(1))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_without_an_echo() {
+        let published = Origin::produce();
+        let consumed = Origin::produce();
+        let result = wait_ready(
+            &published.producer,
+            &consumed.consumer,
+            Duration::from_millis(50),
+        )
+        .await;
+        assert!(matches!(result, Err(ConnectError::NotReady(_))));
+    }
+
+    #[tokio::test]
+    async fn test_disjoint_roots_are_unobservable() {
+        let origin = Origin::produce();
+        let producer = origin.producer.with_root("drone").unwrap();
+        let consumer = origin.consumer.with_root("server").unwrap();
+        let result = wait_ready(&producer, &consumer, Duration::from_secs(1)).await;
+        assert!(matches!(result, Err(ConnectError::ReadinessUnobservable)));
+    }
+}
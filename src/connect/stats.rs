@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use web_transport_quinn::quinn;
+
+/// A snapshot of the transport metrics for a relay connection, read on demand.
+///
+/// A metric is `None` when the transport cannot report it. Path metrics are only reported while
+/// the connection is open, the byte counters stay available after it closes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// Smoothed round trip time.
+    pub rtt: Option<Duration>,
+    /// UDP payload bytes sent.
+    pub bytes_sent: Option<u64>,
+    /// UDP payload bytes received.
+    pub bytes_received: Option<u64>,
+    /// Congestion window in bytes.
+    pub congestion_window: Option<u64>,
+    /// Packets declared lost.
+    pub lost_packets: Option<u64>,
+}
+
+impl ConnectionStats {
+    fn from_quinn(stats: &quinn::ConnectionStats, open: bool) -> Self {
+        let path = open.then_some(&stats.path);
+        Self {
+            rtt: path.map(|path| path.rtt),
+            bytes_sent: Some(stats.udp_tx.bytes),
+            bytes_received: Some(stats.udp_rx.bytes),
+            congestion_window: path.map(|path| path.cwnd),
+            lost_packets: path.map(|path| path.lost_packets),
+        }
+    }
+}
+
+/// Read the current metrics of the transport under a relay session, as returned by
+/// [`connect_bidirectional_with_stats`](crate::connect_bidirectional_with_stats).
+pub fn connection_stats(transport: &web_transport_quinn::Session) -> ConnectionStats {
+    // Through the QUIC connection, whose stats have kept their shape across web-transport-quinn
+    // releases, unlike the session's own
+    let connection: &quinn::Connection = transport;
+    let open = connection.close_reason().is_none();
+    ConnectionStats::from_quinn(&connection.stats(), open)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quinn_stats() -> quinn::ConnectionStats {
+        let mut stats = quinn::ConnectionStats::default();
+        stats.udp_tx.bytes = 1200;
+        stats.udp_rx.bytes = 3400;
+        stats.path.rtt = Duration::from_millis(25);
+        stats.path.cwnd = 12_000;
+        stats.path.lost_packets = 3;
+        stats
+    }
+
+    #[test]
+    fn test_open_connection_reports_every_metric() {
+        let stats = ConnectionStats::from_quinn(&quinn_stats(), true);
+        assert_eq!(
+            stats,
+            ConnectionStats {
+                rtt: Some(Duration::from_millis(25)),
+                bytes_sent: Some(1200),
+                bytes_received: Some(3400),
+                congestion_window: Some(12_000),
+                lost_packets: Some(3),
+            }
+        );
+    }
+
+    #[test]
+    fn test_closed_connection_reports_only_byte_counters() {
+        let stats = ConnectionStats::from_quinn(&quinn_stats(), false);
+        assert_eq!(
+            stats,
+            ConnectionStats {
+                bytes_sent: Some(1200),
+                bytes_received: Some(3400),
+                ..Default::default()
+            }
+        );
+    }
+}
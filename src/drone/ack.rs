@@ -0,0 +1,110 @@
+//! Command acknowledgements published by a drone on a track of their own.
+//!
+//! The drone also acknowledges commands over its session stream, but that only reaches the
+//! server. Publishing them on a separate broadcast lets any controller on the relay confirm a
+//! command landed without going through the server.
+
+use std::fmt;
+use std::time::Duration;
+
+use futures::Stream;
+use moq_lite::{BroadcastProducer, OriginConsumer, OriginProducer, Track};
+use rpcmoq_lite::{RpcOutbound, RpcSendError, RpcWireError};
+
+use crate::drone_proto::CommandAck;
+use crate::track::announced_broadcast;
+use crate::{ACK_TRACK, ack_broadcast_path, decoded_track_stream};
+
+/// Publishes a drone's command acknowledgements on [`ACK_TRACK`].
+pub struct AckPublisher {
+    // Keeps the broadcast announced for as long as the publisher lives
+    _broadcast: BroadcastProducer,
+    outbound: RpcOutbound,
+}
+
+impl AckPublisher {
+    /// Announce the ack broadcast for `drone_id` on `producer`.
+    ///
+    /// Returns `None` if `producer` may not publish the broadcast.
+    pub fn new(producer: &OriginProducer, drone_id: &str) -> Option<Self> {
+        let mut broadcast = producer.create_broadcast(ack_broadcast_path(drone_id))?;
+        let track = broadcast.create_track(Track::new(ACK_TRACK));
+        Some(Self {
+            _broadcast: broadcast,
+            outbound: RpcOutbound::new(track),
+        })
+    }
+
+    pub fn publish(&mut self, ack: &CommandAck) -> Result<(), RpcSendError> {
+        self.outbound.send(ack)
+    }
+}
+
+impl fmt::Debug for AckPublisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AckPublisher").finish_non_exhaustive()
+    }
+}
+
+/// Subscribe to the acknowledgements published by `drone_id`.
+///
+/// Waits up to `timeout` for the drone to announce its ack broadcast. Returns `None` if it
+/// does not, if `consumer` may not subscribe to it, or if the origin closes first.
+pub async fn subscribe_acks(
+    consumer: &OriginConsumer,
+    drone_id: &str,
+    timeout: Duration,
+) -> Option<impl Stream<Item = Result<CommandAck, RpcWireError>> + use<>> {
+    let path = ack_broadcast_path(drone_id);
+    let broadcast = tokio::time::timeout(timeout, announced_broadcast(consumer, &path))
+        .await
+        .ok()??;
+    let track = broadcast.subscribe_track(&Track::new(ACK_TRACK));
+    Some(decoded_track_stream::<CommandAck>(track))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use moq_lite::Origin;
+    use rpcmoq_lite::test::resend_until;
+
+    #[tokio::test]
+    async fn test_acks_reach_subscriber() {
+        let origin = Origin::produce();
+
+        let mut subscriber = tokio::spawn({
+            let consumer = origin.consumer.clone();
+            async move {
+                let acks = subscribe_acks(&consumer, "drone-1", Duration::from_secs(1))
+                    .await
+                    .unwrap();
+                let mut acks = Box::pin(acks);
+                acks.next().await.unwrap().unwrap()
+            }
+        });
+
+        let mut publisher = AckPublisher::new(&origin.producer, "drone-1").unwrap();
+        let ack = CommandAck {
+            accepted: true,
+            message: String::new(),
+            command_id: "cmd-1".to_string(),
+        };
+
+        let received = resend_until(
+            &mut subscriber,
+            async |_| publisher.publish(&ack).unwrap(),
+            async |subscriber| subscriber.await,
+        )
+        .await
+        .unwrap();
+        assert_eq!(received, ack);
+    }
+    #[tokio::test]
+    async fn test_subscribe_gives_up_on_a_silent_drone() {
+        let origin = Origin::produce();
+        let acks = subscribe_acks(&origin.consumer, "drone-1", Duration::from_millis(20)).await;
+        assert!(acks.is_none());
+    }
+}
@@ -0,0 +1,351 @@
+//! A typed client for the drone service as bridged onto MoQ.
+
+use std::fmt;
+use std::pin::pin;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use moq_lite::OriginConsumer;
+use rpcmoq_lite::{RpcClient, RpcClientError, RpcConnection};
+use tracing::warn;
+
+use crate::drone::movement::distance_m;
+use crate::drone::{DRONE_SESSION_PATH, subscribe_drone};
+use crate::drone_proto::{CommandAck, DroneCommand, DroneMessage};
+use crate::state_machine::echo::Position;
+
+/// The gRPC path of the send command RPC.
+pub const SEND_COMMAND_PATH: &str = "drone.DroneService/SendCommand";
+
+/// Calls the drone service over MoQ, the way `DroneServiceClient` does over gRPC.
+///
+/// The server must bridge each path onto the gRPC service with an `RpcRouter`.
+pub struct DroneRpcClient {
+    client: RpcClient,
+    // Opened by the first command and kept for the ones after it, as the router only allows
+    // one session per client and path.
+    commands: Option<RpcConnection<DroneCommand, CommandAck>>,
+}
+
+impl DroneRpcClient {
+    pub fn new(client: RpcClient) -> Self {
+        Self {
+            client,
+            commands: None,
+        }
+    }
+
+    /// Open a drone session, reporting [`DroneMessage`]s and receiving [`DroneCommand`]s.
+    pub async fn drone_session(
+        &mut self,
+    ) -> Result<RpcConnection<DroneMessage, DroneCommand>, RpcClientError> {
+        self.client.connect(DRONE_SESSION_PATH).await
+    }
+
+    /// Queue `command` for its drone, returning the server's acknowledgement.
+    ///
+    /// Commands share one connection, opened by the first. Waits at most the client's
+    /// configured timeout for the acknowledgement. After an error, or if the call is cancelled
+    /// before the acknowledgement arrives, the connection is dropped, so a late
+    /// acknowledgement can't be taken for the next command's, and the next call connects again.
+    pub async fn send_command(
+        &mut self,
+        command: DroneCommand,
+    ) -> Result<CommandAck, RpcClientError> {
+        let timeout = self.client.config().timeout;
+        // Held outside `self` until the acknowledgement is in, so dropping this future drops it
+        let mut conn = match self.commands.take() {
+            Some(conn) => conn,
+            None => self.client.connect(SEND_COMMAND_PATH).await?,
+        };
+
+        let ack = async {
+            conn.send(command).await?;
+            tokio::time::timeout(timeout, conn.next())
+                .await?
+                .ok_or(RpcClientError::ConnectionClosed)?
+        }
+        .await;
+        if ack.is_ok() {
+            self.commands = Some(conn);
+        }
+        ack
+    }
+
+    /// Send `drone_id` to `target` and follow its position reports until it is within
+    /// `tolerance_m` metres of it.
+    ///
+    /// `telemetry` is where the drone announces its telemetry broadcast. Returns whether the
+    /// drone got there within `timeout`, which covers waiting for the broadcast too. The
+    /// command's acknowledgement is always awaited in full, under the client's own timeout, so
+    /// it can't be left unread for the next command to take. A command the server refuses
+    /// returns `false` straight away, as does the position track ending.
+    pub async fn goto_and_wait(
+        &mut self,
+        telemetry: &OriginConsumer,
+        drone_id: &str,
+        target: &Position,
+        tolerance_m: f64,
+        timeout: Duration,
+    ) -> Result<bool, RpcClientError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        // Subscribe first, so no report sent after the command is missed
+        let Ok(Some(positions)) =
+            tokio::time::timeout_at(deadline, subscribe_drone(telemetry, drone_id)).await
+        else {
+            return Ok(false);
+        };
+        let command = DroneCommand::goto(
+            drone_id,
+            target.latitude,
+            target.longitude,
+            target.altitude_m,
+        );
+        let ack = self.send_command(command).await?;
+        if !ack.accepted {
+            warn!(drone_id, message = %ack.message, "Goto refused");
+            return Ok(false);
+        }
+
+        let reached = async {
+            let mut positions = pin!(positions);
+            while let Some(position) = positions.next().await {
+                if distance_m(&Position::from(position), target) <= tolerance_m {
+                    return true;
+                }
+            }
+            false
+        };
+        Ok(tokio::time::timeout_at(deadline, reached)
+            .await
+            .unwrap_or(false))
+    }
+
+    pub fn into_inner(self) -> RpcClient {
+        self.client
+    }
+}
+
+impl fmt::Debug for DroneRpcClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DroneRpcClient")
+            .field("client_id", &self.client.client_id())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drone::{DroneLoopConfig, LinearModel, TelemetryPublisher, run_drone_loop};
+    use crate::drone_proto::drone_message::Payload;
+    use crate::drone_proto::{CommandType, DronePosition};
+    use rpcmoq_lite::DecodedInbound;
+    use rpcmoq_lite::test::{client, resend_until, spawn_router};
+    use std::sync::{Arc, Mutex};
+
+    /// A router answering both drone service paths on an in-memory origin, and a client for it.
+    fn drone_rpc_client() -> DroneRpcClient {
+        let origin = spawn_router(|router| {
+            router
+                .register(
+                    SEND_COMMAND_PATH,
+                    |_, inbound: DecodedInbound<DroneCommand>| async move {
+                        Ok(inbound.map(|command| {
+                            Ok(CommandAck {
+                                accepted: true,
+                                message: String::new(),
+                                command_id: command.command_id,
+                            })
+                        }))
+                    },
+                )
+                .unwrap();
+            router
+                .register(
+                    DRONE_SESSION_PATH,
+                    |_, inbound: DecodedInbound<DroneMessage>| async move {
+                        Ok(inbound.filter_map(|msg| async move {
+                            let Some(Payload::Position(pos)) = msg.payload else {
+                                return None;
+                            };
+                            Some(Ok(DroneCommand::land(pos.drone_id)))
+                        }))
+                    },
+                )
+                .unwrap();
+        });
+        DroneRpcClient::new(client(&origin, "controller-1"))
+    }
+
+    #[tokio::test]
+    async fn test_send_command_returns_ack() {
+        let mut client = drone_rpc_client();
+
+        // The second command reuses the first one's session rather than colliding with it
+        for command_id in ["cmd-1", "cmd-2"] {
+            let command = DroneCommand {
+                drone_id: "drone-1".to_string(),
+                command_id: command_id.to_string(),
+                ..Default::default()
+            };
+            let ack = client.send_command(command).await.unwrap();
+            assert!(ack.accepted);
+            assert_eq!(ack.command_id, command_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_after_timed_out_goto_gets_its_own_ack() {
+        // Acknowledges a goto only after the caller has given up on it
+        let origin = spawn_router(|router| {
+            router
+                .register(
+                    SEND_COMMAND_PATH,
+                    |_, inbound: DecodedInbound<DroneCommand>| async move {
+                        Ok(inbound.then(|command| async move {
+                            if command.command_type() == CommandType::Goto {
+                                tokio::time::sleep(Duration::from_millis(300)).await;
+                            }
+                            Ok(CommandAck {
+                                accepted: true,
+                                message: String::new(),
+                                command_id: command.command_id,
+                            })
+                        }))
+                    },
+                )
+                .unwrap();
+        });
+        let _telemetry = TelemetryPublisher::new(&origin.0, "drone-1").unwrap();
+        let mut controller = DroneRpcClient::new(client(&origin, "controller-1"));
+
+        let target = Position {
+            drone_id: "drone-1".to_string(),
+            latitude: 37.0,
+            longitude: -122.0,
+            altitude_m: 50.0,
+            heading_deg: 0.0,
+            speed_mps: 0.0,
+            timestamp: 0,
+        };
+        let reached = controller
+            .goto_and_wait(
+                &origin.1,
+                "drone-1",
+                &target,
+                1.0,
+                Duration::from_millis(100),
+            )
+            .await
+            .unwrap();
+        assert!(!reached);
+
+        // Must not be answered with the goto's acknowledgement
+        let mut land = DroneCommand::land("drone-1");
+        land.command_id = "land-1".to_string();
+        let ack = controller.send_command(land).await.unwrap();
+        assert_eq!(ack.command_id, "land-1");
+    }
+
+    #[tokio::test]
+    async fn test_drone_session_receives_commands() {
+        let mut client = drone_rpc_client();
+        let mut session = client.drone_session().await.unwrap();
+        let report = DroneMessage {
+            payload: Some(Payload::Position(DronePosition {
+                drone_id: "drone-1".to_string(),
+                ..Default::default()
+            })),
+        };
+
+        let command = resend_until(
+            &mut session,
+            async |session| session.send(report.clone()).await.unwrap(),
+            async |session| session.next().await,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(command.drone_id, "drone-1");
+        assert_eq!(command.command_type(), CommandType::Land);
+    }
+
+    #[tokio::test]
+    async fn test_goto_and_wait_against_simulated_drone() {
+        // Forward every command sent to the drone's session, the way the server does
+        let (commands, queued) = futures::channel::mpsc::unbounded::<DroneCommand>();
+        let queued = Arc::new(Mutex::new(Some(queued)));
+        let origin = spawn_router(|router| {
+            router
+                .register(
+                    SEND_COMMAND_PATH,
+                    move |_, inbound: DecodedInbound<DroneCommand>| {
+                        let commands = commands.clone();
+                        async move {
+                            Ok(inbound.map(move |command| {
+                                let command_id = command.command_id.clone();
+                                commands.unbounded_send(command).unwrap();
+                                Ok(CommandAck {
+                                    accepted: true,
+                                    message: String::new(),
+                                    command_id,
+                                })
+                            }))
+                        }
+                    },
+                )
+                .unwrap();
+            router
+                .register(
+                    DRONE_SESSION_PATH,
+                    move |_, inbound: DecodedInbound<DroneMessage>| {
+                        let queued = queued.lock().unwrap().take().unwrap();
+                        async move {
+                            let reports = inbound.filter_map(|_| async { None });
+                            Ok(futures::stream::select(reports, queued.map(Ok)))
+                        }
+                    },
+                )
+                .unwrap();
+        });
+
+        let config = DroneLoopConfig::new("drone-1", LinearModel::default())
+            .with_telemetry_interval(Duration::from_millis(10))
+            .with_telemetry_publisher(TelemetryPublisher::new(&origin.0, "drone-1").unwrap());
+        let home = config.home.clone();
+        let drone = tokio::spawn(run_drone_loop(client(&origin, "drone-1"), config));
+        let mut controller = DroneRpcClient::new(client(&origin, "controller-1"));
+
+        // Five steps of the linear model away
+        let target = Position {
+            latitude: home.latitude + 0.0005,
+            altitude_m: home.altitude_m + 5.0,
+            ..home.clone()
+        };
+        let reached = controller
+            .goto_and_wait(&origin.1, "drone-1", &target, 1.0, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(reached);
+
+        // Far enough away to take minutes
+        let target = Position {
+            latitude: home.latitude + 1.0,
+            ..home
+        };
+        let reached = controller
+            .goto_and_wait(
+                &origin.1,
+                "drone-1",
+                &target,
+                1.0,
+                Duration::from_millis(200),
+            )
+            .await
+            .unwrap();
+        assert!(!reached);
+
+        drone.abort();
+    }
+}
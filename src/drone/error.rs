@@ -15,3 +15,33 @@ pub struct SessionAlreadyActive {
 pub struct SessionNotFound {
     pub unit_id: UnitId,
 }
+
+/// Indicates that [`run_drone_loop`](super::run_drone_loop) gave up re-establishing its session.
+#[derive(Debug, thiserror::Error)]
+pub enum DroneLoopError {
+    #[error("drone session lost after {attempts} attempts: {source}")]
+    RetriesExhausted {
+        attempts: u32,
+        source: rpcmoq_lite::RpcClientError,
+    },
+    #[error("server refused the drone session: {reason}")]
+    Refused { reason: rpcmoq_lite::RejectReason },
+}
+
+/// Indicates that a drone loop keepalive was configured with an unusable tolerance or interval.
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "invalid keepalive: tolerance {tolerance_m}m must be a non-negative distance and the \
+     heartbeat interval ({heartbeat_interval:?}) non-zero"
+)]
+pub struct InvalidKeepalive {
+    pub tolerance_m: f64,
+    pub heartbeat_interval: std::time::Duration,
+}
+
+/// Indicates that a movement model name is not one of the supported models.
+#[derive(Debug, thiserror::Error)]
+#[error("unknown movement model '{name}', expected hover, linear or kinematic")]
+pub struct UnknownMovementModel {
+    pub name: String,
+}
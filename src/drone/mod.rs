@@ -1,13 +1,31 @@
+mod ack;
+mod client;
 pub mod error;
+mod movement;
+mod runner;
+mod telemetry;
 
 use crate::unit::UnitId;
+use dashmap::mapref::entry::OccupiedEntry;
 use dashmap::{DashMap, Entry};
 use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
 use uuid::Uuid;
 
 use self::error::{SessionAlreadyActive, SessionNotFound};
 
+pub use self::ack::{AckPublisher, subscribe_acks};
+pub use self::client::{DroneRpcClient, SEND_COMMAND_PATH};
+pub use self::movement::{Hover, KinematicModel, LinearModel, MovementModel, MovementModelKind};
+pub use self::runner::{
+    DRONE_SESSION_PATH, DroneLoopConfig, Keepalive, run_drone_loop, run_drone_loop_until,
+};
+pub use self::telemetry::{
+    DroneTelemetry, TelemetryPublisher, TelemetryTrack, subscribe_drone, subscribe_telemetry,
+};
+
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub struct DroneSessionId(Arc<Uuid>);
 
@@ -37,17 +55,44 @@ impl fmt::Display for DroneSessionId {
 pub struct DroneSession {
     pub session_id: DroneSessionId,
     pub unit_id: UnitId,
+    /// Dropped with the session, closing every [`DroneSessionMap::session_closed`] watcher.
+    closed: watch::Sender<()>,
+    /// Notified by [`DroneSessionMap::session_active`].
+    activity: watch::Sender<()>,
+}
+
+impl DroneSession {
+    fn new(unit_id: &UnitId) -> Self {
+        Self {
+            session_id: DroneSessionId::generate(),
+            unit_id: unit_id.clone(),
+            closed: watch::Sender::new(()),
+            activity: watch::Sender::new(()),
+        }
+    }
+}
+
+/// The number of events buffered per [`watch`](DroneSessionMap::watch) receiver before it lags.
+const EVENT_CAPACITY: usize = 256;
+
+/// A drone connecting or disconnecting, as seen by a [`DroneSessionMap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DroneSessionEvent {
+    DroneConnected(UnitId),
+    DroneDisconnected(UnitId),
 }
 
 #[derive(Debug)]
 pub struct DroneSessionMap {
     sessions: DashMap<UnitId, DroneSession, ahash::RandomState>,
+    events: broadcast::Sender<DroneSessionEvent>,
 }
 
 impl DroneSessionMap {
     pub fn new() -> Self {
         Self {
             sessions: DashMap::default(),
+            events: broadcast::Sender::new(EVENT_CAPACITY),
         }
     }
 
@@ -57,23 +102,153 @@ impl DroneSessionMap {
                 unit_id: unit_id.clone(),
             }),
             Entry::Vacant(slot) => {
-                let session_id = DroneSessionId::generate();
-                slot.insert(DroneSession {
-                    session_id: session_id.clone(),
-                    unit_id: unit_id.clone(),
-                });
-                Ok(session_id)
+                let session = slot.insert(DroneSession::new(unit_id));
+                self.emit(DroneSessionEvent::DroneConnected(unit_id.clone()));
+                Ok(session.session_id.clone())
+            }
+        }
+    }
+
+    /// Create a session for `unit_id`, evicting the one it already has.
+    ///
+    /// Returns the new session's ID and the evicted session's, if there was one. The evicted
+    /// session is closed, ending its [`session_closed`](Self::session_closed) watchers.
+    ///
+    /// The drone stays connected through a takeover, so only a takeover without an existing
+    /// session emits [`DroneSessionEvent::DroneConnected`].
+    pub fn create_session_takeover(
+        &self,
+        unit_id: &UnitId,
+    ) -> (DroneSessionId, Option<DroneSessionId>) {
+        let session = DroneSession::new(unit_id);
+        let session_id = session.session_id.clone();
+        match self.sessions.entry(unit_id.clone()) {
+            Entry::Occupied(mut entry) => {
+                let evicted = entry.insert(session);
+                (session_id, Some(evicted.session_id))
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(session);
+                self.emit(DroneSessionEvent::DroneConnected(unit_id.clone()));
+                (session_id, None)
             }
         }
     }
 
     pub fn remove_session(&self, unit_id: &UnitId) -> Result<DroneSession, SessionNotFound> {
-        self.sessions
-            .remove(unit_id)
-            .map(|(_, session)| session)
-            .ok_or_else(|| SessionNotFound {
+        match self.sessions.entry(unit_id.clone()) {
+            Entry::Occupied(entry) => Ok(self.remove_entry(entry)),
+            Entry::Vacant(_) => Err(SessionNotFound {
                 unit_id: unit_id.clone(),
-            })
+            }),
+        }
+    }
+
+    /// Remove `unit_id`'s session only if it is still `session_id`, so a session that was taken
+    /// over does not remove its replacement.
+    pub fn remove_session_if_current(
+        &self,
+        unit_id: &UnitId,
+        session_id: &DroneSessionId,
+    ) -> Option<DroneSession> {
+        match self.sessions.entry(unit_id.clone()) {
+            Entry::Occupied(entry) if entry.get().session_id == *session_id => {
+                Some(self.remove_entry(entry))
+            }
+            _ => None,
+        }
+    }
+
+    /// Remove a session, emitting its disconnect while the entry is still locked so it cannot be
+    /// reordered with the next session's connect.
+    fn remove_entry(&self, entry: OccupiedEntry<'_, UnitId, DroneSession>) -> DroneSession {
+        let (unit_id, session) = entry.remove_entry();
+        self.emit(DroneSessionEvent::DroneDisconnected(unit_id));
+        session
+    }
+
+    /// Subscribe to drones connecting and disconnecting.
+    ///
+    /// Each connect is followed by exactly one disconnect for the same drone, a session taken
+    /// over by a reconnecting drone emits neither. Only events emitted after subscribing are
+    /// received, and a receiver that falls more than a fixed number of events behind gets
+    /// [`broadcast::error::RecvError::Lagged`].
+    pub fn watch(&self) -> broadcast::Receiver<DroneSessionEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, event: DroneSessionEvent) {
+        // Sending only fails when nobody is watching
+        let _ = self.events.send(event);
+    }
+
+    /// Run `f` if `unit_id` has no session, holding off a new one from being created meanwhile.
+    pub fn without_session<R>(&self, unit_id: &UnitId, f: impl FnOnce() -> R) -> Option<R> {
+        match self.sessions.entry(unit_id.clone()) {
+            Entry::Occupied(_) => None,
+            Entry::Vacant(_) => Some(f()),
+        }
+    }
+
+    /// Record that session `session_id` of `unit_id` has heard from its drone, so it is not
+    /// taken for [`stale`](Self::session_stale).
+    pub fn session_active(&self, unit_id: &UnitId, session_id: &DroneSessionId) {
+        if let Some(session) = self
+            .sessions
+            .get(unit_id)
+            .filter(|session| session.session_id == *session_id)
+        {
+            session.activity.send_replace(());
+        }
+    }
+
+    /// Resolves to whether session `session_id` of `unit_id` is stale: removed or taken over,
+    /// or `quiet` without [`session_active`](Self::session_active) being called for it.
+    ///
+    /// Resolves to `false` as soon as the session shows activity.
+    pub fn session_stale(
+        &self,
+        unit_id: &UnitId,
+        session_id: &DroneSessionId,
+        quiet: Duration,
+    ) -> impl Future<Output = bool> + Send + use<> {
+        let closed = self.session_closed(unit_id, session_id);
+        let activity = self
+            .sessions
+            .get(unit_id)
+            .filter(|session| session.session_id == *session_id)
+            .map(|session| session.activity.subscribe());
+        async move {
+            let Some(mut activity) = activity else {
+                return true;
+            };
+            tokio::select! {
+                () = closed => true,
+                active = tokio::time::timeout(quiet, activity.changed()) => {
+                    !matches!(active, Ok(Ok(())))
+                }
+            }
+        }
+    }
+
+    /// Resolves once session `session_id` of `unit_id` is removed or taken over, immediately if
+    /// it already has been.
+    pub fn session_closed(
+        &self,
+        unit_id: &UnitId,
+        session_id: &DroneSessionId,
+    ) -> impl Future<Output = ()> + Send + use<> {
+        let closed = self
+            .sessions
+            .get(unit_id)
+            .filter(|session| session.session_id == *session_id)
+            .map(|session| session.closed.subscribe());
+        async move {
+            if let Some(mut closed) = closed {
+                // Nothing is ever sent, this only returns once the sender is dropped
+                while closed.changed().await.is_ok() {}
+            }
+        }
     }
 
     pub fn has_active_session(&self, unit_id: &UnitId) -> bool {
@@ -147,6 +322,97 @@ mod tests {
         assert!(matches!(result.unwrap_err(), SessionNotFound { .. }));
     }
 
+    #[tokio::test]
+    async fn test_takeover_evicts_existing_session() {
+        let map = DroneSessionMap::new();
+        let unit_id = UnitId::from("drone-1");
+
+        let stale = map.create_session(&unit_id).unwrap();
+        let closed = map.session_closed(&unit_id, &stale);
+
+        let (session_id, evicted) = map.create_session_takeover(&unit_id);
+        assert_eq!(evicted, Some(stale.clone()));
+        assert_ne!(session_id, stale);
+        assert_eq!(map.get_session_id(&unit_id), Some(session_id.clone()));
+        tokio::time::timeout(std::time::Duration::from_secs(1), closed)
+            .await
+            .expect("the evicted session should be closed");
+
+        // The stale session's cleanup must leave its replacement alone
+        assert!(map.remove_session_if_current(&unit_id, &stale).is_none());
+        assert!(map.has_active_session(&unit_id));
+        assert!(
+            map.remove_session_if_current(&unit_id, &session_id)
+                .is_some()
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_session_stale_only_when_quiet() {
+        let map = Arc::new(DroneSessionMap::new());
+        let unit_id = UnitId::from("drone-1");
+        let session_id = map.create_session(&unit_id).unwrap();
+
+        let stale = map.session_stale(&unit_id, &session_id, Duration::from_secs(2));
+        let active = {
+            let map = Arc::clone(&map);
+            let (unit_id, session_id) = (unit_id.clone(), session_id.clone());
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                map.session_active(&unit_id, &session_id);
+            })
+        };
+        assert!(!stale.await);
+        active.await.unwrap();
+
+        let quiet = map.session_stale(&unit_id, &session_id, Duration::from_secs(2));
+        assert!(quiet.await);
+
+        map.remove_session(&unit_id).unwrap();
+        assert!(
+            map.session_stale(&unit_id, &session_id, Duration::from_secs(2))
+                .await
+        );
+    }
+
+    #[test]
+    fn test_takeover_without_existing_session() {
+        let map = DroneSessionMap::new();
+        let unit_id = UnitId::from("drone-1");
+
+        let (session_id, evicted) = map.create_session_takeover(&unit_id);
+        assert!(evicted.is_none());
+        assert_eq!(map.get_session_id(&unit_id), Some(session_id));
+    }
+
+    #[test]
+    fn test_watch_events_once_per_transition() {
+        let map = DroneSessionMap::new();
+        let mut events = map.watch();
+        let unit_id = UnitId::from("drone-1");
+
+        let stale = map.create_session(&unit_id).unwrap();
+        let _ = map.create_session(&unit_id).unwrap_err();
+        let (session_id, _) = map.create_session_takeover(&unit_id);
+        // The stale session cleaning up after the takeover
+        assert!(map.remove_session_if_current(&unit_id, &stale).is_none());
+        map.remove_session_if_current(&unit_id, &session_id)
+            .unwrap();
+        let _ = map.remove_session(&unit_id).unwrap_err();
+        map.create_session_takeover(&unit_id);
+        map.remove_session(&unit_id).unwrap();
+
+        for expected in [
+            DroneSessionEvent::DroneConnected(unit_id.clone()),
+            DroneSessionEvent::DroneDisconnected(unit_id.clone()),
+            DroneSessionEvent::DroneConnected(unit_id.clone()),
+            DroneSessionEvent::DroneDisconnected(unit_id.clone()),
+        ] {
+            assert_eq!(events.try_recv().unwrap(), expected);
+        }
+        assert!(events.try_recv().is_err());
+    }
+
     #[test]
     fn test_reconnect_after_disconnect() {
         let map = DroneSessionMap::new();
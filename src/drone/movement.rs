@@ -0,0 +1,274 @@
+//! Movement models for simulated drones.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::drone::error::UnknownMovementModel;
+use crate::state_machine::echo::Position;
+
+/// Metres per degree of latitude, and of longitude at the equator.
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+/// How a simulated drone moves between telemetry reports.
+pub trait MovementModel: Send {
+    /// The position reached after moving from `current` towards `target` for `dt`.
+    fn advance(&mut self, current: Position, target: Position, dt: Duration) -> Position;
+}
+
+impl<M: MovementModel + ?Sized> MovementModel for Box<M> {
+    fn advance(&mut self, current: Position, target: Position, dt: Duration) -> Position {
+        (**self).advance(current, target, dt)
+    }
+}
+
+/// A movement model that holds position regardless of the target.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hover;
+
+impl MovementModel for Hover {
+    fn advance(&mut self, current: Position, _target: Position, _dt: Duration) -> Position {
+        current
+    }
+}
+
+/// A movement model that moves a fixed step towards the target on every report.
+///
+/// Each axis is moved independently, so the drone flies diagonally until one axis reaches the
+/// target. Ignores `dt`, as well as heading and speed.
+#[derive(Debug, Clone, Copy)]
+pub struct LinearModel {
+    /// The furthest latitude and longitude move per report, in degrees.
+    pub step_deg: f64,
+    /// The furthest altitude move per report, in metres.
+    pub step_m: f64,
+}
+
+impl Default for LinearModel {
+    fn default() -> Self {
+        Self {
+            step_deg: 0.0001,
+            step_m: 1.0,
+        }
+    }
+}
+
+impl MovementModel for LinearModel {
+    fn advance(&mut self, current: Position, target: Position, _dt: Duration) -> Position {
+        Position {
+            latitude: approach(current.latitude, target.latitude, self.step_deg),
+            longitude: approach(current.longitude, target.longitude, self.step_deg),
+            altitude_m: approach(current.altitude_m, target.altitude_m, self.step_m),
+            ..current
+        }
+    }
+}
+
+/// A movement model that flies along its heading at its speed.
+///
+/// The drone turns towards the target no faster than `turn_rate_deg_s`, accelerates and
+/// decelerates no faster than `accel_mps2` up to `max_speed_mps`, and slows down to arrive at
+/// the target at rest. Altitude changes independently at up to `climb_rate_mps`.
+#[derive(Debug, Clone, Copy)]
+pub struct KinematicModel {
+    pub max_speed_mps: f64,
+    pub accel_mps2: f64,
+    pub turn_rate_deg_s: f64,
+    pub climb_rate_mps: f64,
+}
+
+impl Default for KinematicModel {
+    fn default() -> Self {
+        Self {
+            max_speed_mps: 15.0,
+            accel_mps2: 3.0,
+            turn_rate_deg_s: 45.0,
+            climb_rate_mps: 3.0,
+        }
+    }
+}
+
+impl MovementModel for KinematicModel {
+    fn advance(&mut self, current: Position, target: Position, dt: Duration) -> Position {
+        let dt = dt.as_secs_f64();
+        let altitude_m = approach(
+            current.altitude_m,
+            target.altitude_m,
+            self.climb_rate_mps * dt,
+        );
+
+        // Local flat-earth offsets are accurate enough over the distances a tick covers
+        let metres_per_lon = METRES_PER_DEGREE * current.latitude.to_radians().cos();
+        let north_m = (target.latitude - current.latitude) * METRES_PER_DEGREE;
+        let east_m = (target.longitude - current.longitude) * metres_per_lon;
+        let distance_m = north_m.hypot(east_m);
+
+        let max_step_m = current.speed_mps * dt + self.accel_mps2 * dt * dt;
+        if distance_m <= max_step_m.max(f64::EPSILON) {
+            return Position {
+                latitude: target.latitude,
+                longitude: target.longitude,
+                altitude_m,
+                speed_mps: 0.0,
+                ..current
+            };
+        }
+
+        let bearing_deg = east_m.atan2(north_m).to_degrees().rem_euclid(360.0);
+        let turn_deg = (bearing_deg - current.heading_deg + 180.0).rem_euclid(360.0) - 180.0;
+        let max_turn_deg = self.turn_rate_deg_s * dt;
+        let heading_deg =
+            (current.heading_deg + turn_deg.clamp(-max_turn_deg, max_turn_deg)).rem_euclid(360.0);
+
+        // The fastest speed that still allows stopping at the target
+        let stopping_speed = (2.0 * self.accel_mps2 * distance_m).sqrt();
+        let desired_speed = self.max_speed_mps.min(stopping_speed);
+        let speed_mps = approach(current.speed_mps, desired_speed, self.accel_mps2 * dt);
+
+        let travelled_m = speed_mps * dt;
+        let heading_rad = heading_deg.to_radians();
+        Position {
+            latitude: current.latitude + travelled_m * heading_rad.cos() / METRES_PER_DEGREE,
+            longitude: current.longitude + travelled_m * heading_rad.sin() / metres_per_lon,
+            altitude_m,
+            heading_deg,
+            speed_mps,
+            ..current
+        }
+    }
+}
+
+/// The straight-line distance between two positions in metres, on a flat-earth approximation
+/// that holds over the short distances a drone covers between reports.
+pub(crate) fn distance_m(a: &Position, b: &Position) -> f64 {
+    let north_m = (b.latitude - a.latitude) * METRES_PER_DEGREE;
+    let east_m = (b.longitude - a.longitude) * METRES_PER_DEGREE * a.latitude.to_radians().cos();
+    let up_m = b.altitude_m - a.altitude_m;
+    (north_m * north_m + east_m * east_m + up_m * up_m).sqrt()
+}
+
+/// Move `from` towards `to` by at most `step`.
+fn approach(from: f64, to: f64, step: f64) -> f64 {
+    from + (to - from).clamp(-step, step)
+}
+
+/// The movement models a drone can be configured with by name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MovementModelKind {
+    Hover,
+    #[default]
+    Linear,
+    Kinematic,
+}
+
+impl MovementModelKind {
+    /// Construct this model with its default parameters.
+    pub fn build(self) -> Box<dyn MovementModel> {
+        match self {
+            MovementModelKind::Hover => Box::new(Hover),
+            MovementModelKind::Linear => Box::new(LinearModel::default()),
+            MovementModelKind::Kinematic => Box::new(KinematicModel::default()),
+        }
+    }
+}
+
+impl FromStr for MovementModelKind {
+    type Err = UnknownMovementModel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hover" => Ok(MovementModelKind::Hover),
+            "linear" => Ok(MovementModelKind::Linear),
+            "kinematic" => Ok(MovementModelKind::Kinematic),
+            _ => Err(UnknownMovementModel {
+                name: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::position_at;
+
+    /// Advance `model` from `start` towards `target` in one-second ticks, returning every position.
+    fn fly(
+        model: &mut impl MovementModel,
+        start: Position,
+        target: &Position,
+        ticks: usize,
+    ) -> Vec<Position> {
+        let mut current = start;
+        let mut track = Vec::with_capacity(ticks);
+        for _ in 0..ticks {
+            current = model.advance(current, target.clone(), Duration::from_secs(1));
+            track.push(current.clone());
+        }
+        track
+    }
+
+    #[test]
+    fn test_linear_model_steps_towards_target() {
+        let mut model = LinearModel::default();
+        let target = position_at(0.00025, -0.00005, 3.0);
+
+        let track = fly(&mut model, position_at(0.0, 0.0, 0.0), &target, 3);
+        assert_eq!(track[0].latitude, 0.0001);
+        assert_eq!(track[0].longitude, -0.00005);
+        assert_eq!(track[0].altitude_m, 1.0);
+        assert_eq!(track[2], target);
+    }
+
+    #[test]
+    fn test_kinematic_model_respects_speed_limit() {
+        let mut model = KinematicModel::default();
+        let target = position_at(0.01, 0.0, 0.0);
+
+        let track = fly(&mut model, position_at(0.0, 0.0, 0.0), &target, 30);
+        for pair in track.windows(2) {
+            assert!(pair[1].speed_mps <= model.max_speed_mps);
+            assert!((pair[1].speed_mps - pair[0].speed_mps).abs() <= model.accel_mps2 + 1e-9);
+        }
+        assert_eq!(track.last().unwrap().speed_mps, model.max_speed_mps);
+    }
+
+    #[test]
+    fn test_kinematic_model_turns_gradually() {
+        let mut model = KinematicModel::default();
+        // Due east, a 90 degree turn from the initial heading
+        let target = position_at(0.0, 0.01, 0.0);
+
+        let track = fly(&mut model, position_at(0.0, 0.0, 0.0), &target, 3);
+        assert_eq!(track[0].heading_deg, 45.0);
+        // The first tick drifted north, so the bearing is now just past due east
+        assert!((track[1].heading_deg - 90.0).abs() < 1.0);
+        assert!((track[2].heading_deg - 90.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_kinematic_model_arrives_at_rest() {
+        let mut model = KinematicModel::default();
+        let target = position_at(0.001, 0.001, 20.0);
+
+        let track = fly(&mut model, position_at(0.0, 0.0, 0.0), &target, 120);
+        let last = track.last().unwrap();
+        assert_eq!(
+            (last.latitude, last.longitude, last.altitude_m),
+            (target.latitude, target.longitude, target.altitude_m)
+        );
+        assert_eq!(last.speed_mps, 0.0);
+    }
+
+    #[test]
+    fn test_movement_model_kind_from_str() {
+        assert_eq!(
+            "Kinematic".parse::<MovementModelKind>().unwrap(),
+            MovementModelKind::Kinematic
+        );
+        assert_eq!(
+            "linear".parse::<MovementModelKind>().unwrap(),
+            MovementModelKind::Linear
+        );
+        assert!("teleport".parse::<MovementModelKind>().is_err());
+    }
+}
@@ -0,0 +1,1032 @@
+//! The drone side of a session: publishing telemetry and carrying out commands over MoQ.
+
+use std::pin::{Pin, pin};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::{SinkExt, StreamExt};
+use rpcmoq_lite::{RejectReason, RetryPolicy, RpcClient, RpcClientError, RpcConnection};
+use tokio::time::{Instant, MissedTickBehavior, interval};
+use tracing::{debug, info, warn};
+
+use crate::drone::ack::AckPublisher;
+use crate::drone::error::{DroneLoopError, InvalidKeepalive};
+use crate::drone::movement::{MovementModel, distance_m};
+use crate::drone::telemetry::{TelemetryPublisher, TelemetryTrack};
+use crate::drone_proto::drone_message::Payload;
+use crate::drone_proto::{
+    BatteryState, CommandAck, CommandType, DroneCommand, DroneMessage, DronePosition, DroneStatus,
+};
+use crate::state_machine::echo::Position;
+
+/// The gRPC path of the drone session RPC.
+pub const DRONE_SESSION_PATH: &str = "drone.DroneService/DroneSession";
+
+/// How fast the simulated battery drains while the drone is airborne, in percent per second.
+const BATTERY_DRAIN_PERCENT_PER_S: f64 = 0.05;
+
+/// How close the drone has to be to its target, in metres, to be reported as having arrived.
+const AT_TARGET_M: f64 = 1.0;
+
+/// Reporting less often while the drone holds position.
+///
+/// A position report that is within `tolerance_m` of the last one sent is skipped, unless
+/// `heartbeat_interval` has passed since then, in which case it is sent anyway as a heartbeat.
+/// Keep `heartbeat_interval` below the server's staleness threshold so a hovering drone is not
+/// taken for a lost one.
+#[derive(Debug, Clone, Copy)]
+pub struct Keepalive {
+    /// How far the drone can drift, in metres, before it is reported as having moved.
+    pub tolerance_m: f64,
+    /// How often a stationary drone still reports its position.
+    pub heartbeat_interval: Duration,
+}
+
+/// Configuration for [`run_drone_loop`].
+#[derive(Debug)]
+pub struct DroneLoopConfig<M> {
+    pub drone_id: String,
+    /// Where the drone starts, and where it flies on `ReturnHome`.
+    pub home: Position,
+    /// How often a position report is published.
+    pub telemetry_interval: Duration,
+    /// Backoff between attempts to re-establish the session after it is lost.
+    pub retry: RetryPolicy,
+    pub model: M,
+    /// Where command acknowledgements are published, in addition to the session stream.
+    pub acks: Option<AckPublisher>,
+    /// Where position, battery and status reports are published, in addition to the session
+    /// stream.
+    pub telemetry: Option<TelemetryPublisher>,
+    /// Report a stationary drone only as a periodic heartbeat, if set. Every tick is reported
+    /// otherwise.
+    pub keepalive: Option<Keepalive>,
+}
+
+impl<M: MovementModel> DroneLoopConfig<M> {
+    pub fn new(drone_id: impl Into<String>, model: M) -> Self {
+        let drone_id = drone_id.into();
+        Self {
+            home: Position {
+                drone_id: drone_id.clone(),
+                latitude: 37.7749,
+                longitude: -122.4194,
+                altitude_m: 100.0,
+                heading_deg: 0.0,
+                speed_mps: 0.0,
+                timestamp: 0,
+            },
+            drone_id,
+            telemetry_interval: Duration::from_secs(1),
+            retry: RetryPolicy::builder()
+                .initial_backoff(Duration::from_millis(500))
+                .max_backoff(Duration::from_secs(10))
+                .build(),
+            model,
+            acks: None,
+            telemetry: None,
+            keepalive: None,
+        }
+    }
+
+    pub fn with_home(mut self, latitude: f64, longitude: f64, altitude_m: f64) -> Self {
+        self.home.latitude = latitude;
+        self.home.longitude = longitude;
+        self.home.altitude_m = altitude_m;
+        self
+    }
+
+    pub fn with_telemetry_interval(mut self, telemetry_interval: Duration) -> Self {
+        self.telemetry_interval = telemetry_interval;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_ack_publisher(mut self, acks: AckPublisher) -> Self {
+        self.acks = Some(acks);
+        self
+    }
+
+    pub fn with_telemetry_publisher(mut self, telemetry: TelemetryPublisher) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Report the drone only every `heartbeat_interval` while it stays within `tolerance_m`
+    /// metres of the last position sent, see [`Keepalive`].
+    ///
+    /// Fails unless `tolerance_m` is a finite, non-negative distance and `heartbeat_interval`
+    /// is non-zero.
+    pub fn with_keepalive(
+        mut self,
+        tolerance_m: f64,
+        heartbeat_interval: Duration,
+    ) -> Result<Self, InvalidKeepalive> {
+        if !(tolerance_m.is_finite() && tolerance_m >= 0.0) || heartbeat_interval.is_zero() {
+            return Err(InvalidKeepalive {
+                tolerance_m,
+                heartbeat_interval,
+            });
+        }
+        self.keepalive = Some(Keepalive {
+            tolerance_m,
+            heartbeat_interval,
+        });
+        Ok(self)
+    }
+}
+
+/// Where the simulated drone is, where it has been told to go and how much charge it has left.
+#[derive(Debug)]
+struct Vehicle {
+    home: Position,
+    current: Position,
+    target: Position,
+    battery_percent: f64,
+    /// The last command accepted, empty before the first.
+    command_id: String,
+}
+
+impl Vehicle {
+    /// A grounded drone at `home` with a full battery.
+    fn new(home: Position) -> Self {
+        Self {
+            current: home.clone(),
+            target: home.clone(),
+            home,
+            battery_percent: 100.0,
+            command_id: String::new(),
+        }
+    }
+
+    /// Retarget the drone according to `command`, returning the acknowledgement to send back.
+    fn apply(&mut self, command: &DroneCommand) -> CommandAck {
+        let mut ack = CommandAck {
+            accepted: true,
+            message: String::new(),
+            command_id: command.command_id.clone(),
+        };
+
+        match command.try_command_type() {
+            Err(e) => {
+                ack.accepted = false;
+                ack.message = e.to_string();
+            }
+            Ok(CommandType::Unspecified) => {
+                ack.accepted = false;
+                ack.message = "command type is required".to_string();
+            }
+            Ok(CommandType::Arm) => {}
+            Ok(CommandType::Takeoff) => self.target.altitude_m = command.altitude_m,
+            Ok(CommandType::Goto) => {
+                self.target.latitude = command.latitude;
+                self.target.longitude = command.longitude;
+                self.target.altitude_m = command.altitude_m;
+            }
+            Ok(CommandType::ReturnHome) => self.target = self.home.clone(),
+            Ok(CommandType::Land) => self.target.altitude_m = 0.0,
+        }
+
+        if ack.accepted {
+            self.command_id = ack.command_id.clone();
+        }
+        ack
+    }
+
+    /// Drain the battery for `dt` of flight, it holds its charge on the ground.
+    fn drain_battery(&mut self, dt: Duration) {
+        if self.current.altitude_m > 0.0 {
+            let drained = BATTERY_DRAIN_PERCENT_PER_S * dt.as_secs_f64();
+            self.battery_percent = (self.battery_percent - drained).max(0.0);
+        }
+    }
+
+    fn battery(&self) -> BatteryState {
+        BatteryState {
+            drone_id: self.current.drone_id.clone(),
+            charge_percent: self.battery_percent,
+            timestamp: self.current.timestamp,
+        }
+    }
+
+    fn status(&self) -> DroneStatus {
+        DroneStatus {
+            drone_id: self.current.drone_id.clone(),
+            command_id: self.command_id.clone(),
+            at_target: distance_m(&self.current, &self.target) <= AT_TARGET_M,
+            timestamp: self.current.timestamp,
+        }
+    }
+}
+
+/// Run a drone until its session can no longer be re-established.
+///
+/// Opens a [`DRONE_SESSION_PATH`] session on `client`, publishes a position report every
+/// `telemetry_interval` as moved by the config's [`MovementModel`], and acknowledges every
+/// command it receives. With a [`Keepalive`], reports of a stationary drone are thinned out to
+/// heartbeats. With a [`TelemetryPublisher`], every report sent is also published, and the
+/// drone's battery and status on every tick.
+///
+/// When the session fails or the server closes it, the session is opened again after backing
+/// off according to `retry`. The backoff restarts once a session is established, unless the
+/// server rejects it. A server that has not been announced yet is retried quietly; any other
+/// failure is logged as a warning.
+///
+/// Only returns once `retry` is exhausted, or with [`DroneLoopError::Refused`] once the server
+/// rejects the drone as unauthorized or has no handler for it, so a drone with unlimited
+/// retries runs forever against a server that accepts it. See [`run_drone_loop_until`] to stop
+/// it.
+pub async fn run_drone_loop<M: MovementModel>(
+    client: RpcClient,
+    config: DroneLoopConfig<M>,
+) -> Result<(), DroneLoopError> {
+    run_drone_loop_until(client, config, std::future::pending()).await
+}
+
+/// Run a drone like [`run_drone_loop`] until `shutdown` resolves.
+///
+/// On shutdown the session stream is flushed and closed, and the ack and telemetry broadcasts
+/// are closed with it, before returning `Ok(())`.
+pub async fn run_drone_loop_until<M: MovementModel>(
+    mut client: RpcClient,
+    config: DroneLoopConfig<M>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), DroneLoopError> {
+    let DroneLoopConfig {
+        drone_id,
+        home,
+        telemetry_interval,
+        retry,
+        model,
+        acks,
+        telemetry,
+        keepalive,
+    } = config;
+
+    let mut drone = Drone {
+        vehicle: Vehicle::new(home),
+        model,
+        acks,
+        telemetry,
+        telemetry_interval,
+        keepalive,
+    };
+    let mut shutdown = pin!(shutdown);
+
+    let mut attempt = 0;
+    loop {
+        let connected = tokio::select! {
+            connected = client.connect::<DroneMessage, DroneCommand>(DRONE_SESSION_PATH) => connected,
+            () = &mut shutdown => break,
+        };
+        let error = match connected {
+            Ok(conn) => {
+                info!(drone_id = %drone_id, "Drone is online");
+                let ended = drone.run_session(conn, shutdown.as_mut()).await;
+                match ended {
+                    // The server turns a drone away on the session itself, so a rejection
+                    // doesn't reset the retry budget
+                    Some(e @ RpcClientError::Rejected { .. }) => e,
+                    Some(e) => {
+                        attempt = 0;
+                        e
+                    }
+                    None => break,
+                }
+            }
+            Err(e) => e,
+        };
+
+        // A server that refuses the drone outright is not retried
+        if let RpcClientError::Rejected {
+            reason: reason @ (RejectReason::Unauthorized | RejectReason::NoHandler),
+        } = error
+        {
+            return Err(DroneLoopError::Refused { reason });
+        }
+        // An overloaded server gets longer to recover than a stale session takes to clear
+        let backoff = match &error {
+            RpcClientError::Rejected { reason } => reason.backoff(&retry, attempt),
+            _ => retry.backoff(attempt),
+        };
+        let Some(backoff) = backoff else {
+            return Err(DroneLoopError::RetriesExhausted {
+                attempts: attempt + 1,
+                source: error,
+            });
+        };
+        // Maintenance lasts as long as it lasts, it doesn't use up the budget
+        if !matches!(
+            error,
+            RpcClientError::Rejected {
+                reason: RejectReason::Maintenance
+            }
+        ) {
+            attempt += 1;
+        }
+
+        if is_server_missing(&error) {
+            debug!(
+                drone_id = %drone_id,
+                attempt,
+                error = %error,
+                backoff = ?backoff,
+                "Server not up yet, retrying"
+            );
+        } else {
+            warn!(
+                drone_id = %drone_id,
+                attempt,
+                error = %error,
+                backoff = ?backoff,
+                "Drone session lost, reconnecting"
+            );
+        }
+        tokio::select! {
+            () = tokio::time::sleep(backoff) => {}
+            () = &mut shutdown => break,
+        }
+    }
+
+    info!(drone_id = %drone_id, "Drone shut down");
+    Ok(())
+}
+
+/// Whether `error` only means the server has not announced itself yet, which is expected while
+/// it is starting and not worth more than a debug line.
+fn is_server_missing(error: &RpcClientError) -> bool {
+    matches!(
+        error,
+        RpcClientError::Timeout(_) | RpcClientError::ServerNotFound(_)
+    )
+}
+
+/// What a drone carries over from one session to the next.
+struct Drone<M> {
+    vehicle: Vehicle,
+    model: M,
+    acks: Option<AckPublisher>,
+    telemetry: Option<TelemetryPublisher>,
+    telemetry_interval: Duration,
+    keepalive: Option<Keepalive>,
+}
+
+impl<M: MovementModel> Drone<M> {
+    /// Drive one session until it fails, returning why it ended, or `None` if `shutdown`
+    /// resolved first.
+    async fn run_session(
+        &mut self,
+        conn: RpcConnection<DroneMessage, DroneCommand>,
+        mut shutdown: Pin<&mut impl Future<Output = ()>>,
+    ) -> Option<RpcClientError> {
+        let Drone {
+            vehicle,
+            model,
+            acks,
+            telemetry,
+            telemetry_interval,
+            keepalive,
+        } = self;
+        let (mut sender, mut receiver) = conn.split();
+
+        let mut ticker = interval(*telemetry_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut last_tick: Option<Instant> = None;
+        // The last position sent, and when, so a stationary drone can skip reports
+        let mut last_sent: Option<(Position, Instant)> = None;
+
+        loop {
+            tokio::select! {
+                () = &mut shutdown => {
+                    // Write out anything batched before the broadcast is dropped
+                    if let Err(e) = sender.close().await {
+                        warn!(error = %e, "Failed to flush the session on shutdown");
+                    }
+                    return None;
+                }
+                now = ticker.tick() => {
+                    let dt = last_tick.map_or(Duration::ZERO, |last| now - last);
+                    last_tick = Some(now);
+
+                    let mut position =
+                        model.advance(vehicle.current.clone(), vehicle.target.clone(), dt);
+                    position.timestamp = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs();
+                    vehicle.current = position.clone();
+                    vehicle.drain_battery(dt);
+
+                    let held = if let (Some(keepalive), Some((sent, sent_at))) =
+                        (*keepalive, &last_sent)
+                    {
+                        distance_m(sent, &position) <= keepalive.tolerance_m
+                            && now - *sent_at < keepalive.heartbeat_interval
+                    } else {
+                        false
+                    };
+
+                    // Only position reports are thinned, battery and status go out every tick
+                    let report = DronePosition::from(position.clone());
+                    if let Some(telemetry) = telemetry.as_mut() {
+                        publish_telemetry(telemetry, vehicle, (!held).then_some(&report));
+                    }
+                    if held {
+                        continue;
+                    }
+                    last_sent = Some((position, now));
+
+                    let message = DroneMessage {
+                        payload: Some(Payload::Position(report)),
+                    };
+                    if let Err(e) = sender.send(message).await {
+                        return Some(e.into());
+                    }
+                    debug!(
+                        lat = vehicle.current.latitude,
+                        lon = vehicle.current.longitude,
+                        alt = vehicle.current.altitude_m,
+                        "Sent position"
+                    );
+                }
+                command = receiver.next() => {
+                    let command = match command {
+                        Some(Ok(command)) => command,
+                        Some(Err(e)) => return Some(e),
+                        None => return Some(RpcClientError::ConnectionClosed),
+                    };
+
+                    let ack = vehicle.apply(&command);
+                    info!(
+                        command_id = %ack.command_id,
+                        command_type = ?command.command_type(),
+                        accepted = ack.accepted,
+                        "Received command"
+                    );
+
+                    if let Some(acks) = acks.as_mut()
+                        && let Err(e) = acks.publish(&ack)
+                    {
+                        warn!(command_id = %ack.command_id, error = %e, "Failed to publish ack");
+                    }
+
+                    let message = DroneMessage {
+                        payload: Some(Payload::Ack(ack)),
+                    };
+                    if let Err(e) = sender.send(message).await {
+                        return Some(e.into());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Publish the vehicle's battery and status on the telemetry broadcast, and `position` unless
+/// the keepalive held it back.
+fn publish_telemetry(
+    telemetry: &mut TelemetryPublisher,
+    vehicle: &Vehicle,
+    position: Option<&DronePosition>,
+) {
+    // Each track is published independently, one failing doesn't hold back the others
+    let published = [
+        (
+            TelemetryTrack::Position,
+            position.map_or(Ok(()), |position| telemetry.publish_position(position)),
+        ),
+        (
+            TelemetryTrack::Battery,
+            telemetry.publish_battery(&vehicle.battery()),
+        ),
+        (
+            TelemetryTrack::Status,
+            telemetry.publish_status(&vehicle.status()),
+        ),
+    ];
+    for (track, result) in published {
+        if let Err(e) = result {
+            warn!(
+                drone_id = %vehicle.current.drone_id,
+                track = track.name(),
+                error = %e,
+                "Failed to publish telemetry"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drone::ack::subscribe_acks;
+    use crate::drone::movement::Hover;
+    use crate::drone::telemetry::{DroneTelemetry, subscribe_telemetry};
+    use moq_lite::Origin;
+    use rpcmoq_lite::test::router_and_client;
+    use rpcmoq_lite::{DecodedInbound, RpcClientConfig, RpcRouter, RpcRouterConfig};
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    fn test_config() -> DroneLoopConfig<Hover> {
+        DroneLoopConfig::new("drone-1", Hover)
+            .with_telemetry_interval(Duration::from_millis(10))
+            .with_retry(
+                RetryPolicy::builder()
+                    .initial_backoff(Duration::from_millis(10))
+                    .max_backoff(Duration::from_millis(10))
+                    .build(),
+            )
+    }
+
+    fn goto(command_id: &str) -> DroneCommand {
+        DroneCommand {
+            command_id: command_id.to_string(),
+            ..DroneCommand::goto("drone-1", 1.0, 2.0, 3.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drone_reports_position_and_acks_commands() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = router_and_client(|router| {
+            router
+                .register(
+                    DRONE_SESSION_PATH,
+                    move |_, inbound: DecodedInbound<DroneMessage>| {
+                        let tx = tx.clone();
+                        async move {
+                            Ok(inbound.filter_map(move |msg| {
+                                let reply = match &msg.payload {
+                                    Some(Payload::Position(_)) => Some(Ok(goto("cmd-1"))),
+                                    _ => None,
+                                };
+                                let _ = tx.send(msg);
+                                async move { reply }
+                            }))
+                        }
+                    },
+                )
+                .unwrap();
+        });
+
+        let ack_origin = Origin::produce();
+        let config = test_config()
+            .with_ack_publisher(AckPublisher::new(&ack_origin.producer, "drone-1").unwrap());
+        let mut published = Box::pin(
+            subscribe_acks(&ack_origin.consumer, "drone-1", Duration::from_secs(1))
+                .await
+                .unwrap(),
+        );
+        let drone = tokio::spawn(run_drone_loop(client, config));
+
+        let ack = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                match rx.recv().await.unwrap().payload {
+                    Some(Payload::Ack(ack)) => return ack,
+                    Some(Payload::Position(pos)) => assert_eq!(pos.drone_id, "drone-1"),
+                    Some(Payload::Hello(_)) | None => {}
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert!(ack.accepted);
+        assert_eq!(ack.command_id, "cmd-1");
+
+        // Every position report draws another command, so acks keep being published
+        let published = tokio::time::timeout(Duration::from_secs(2), published.next())
+            .await
+            .unwrap();
+        assert_eq!(published.unwrap().unwrap().command_id, "cmd-1");
+
+        drone.abort();
+    }
+
+    // Paused, so the gaps between reports are the drone's own timing and not the scheduler's
+    #[tokio::test(start_paused = true)]
+    async fn test_stationary_drone_sends_heartbeats() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = router_and_client(|router| {
+            router
+                .register(
+                    DRONE_SESSION_PATH,
+                    move |_, inbound: DecodedInbound<DroneMessage>| {
+                        let tx = tx.clone();
+                        async move {
+                            Ok(inbound.filter_map(move |msg| {
+                                if let Some(Payload::Position(_)) = msg.payload {
+                                    let _ = tx.send(Instant::now());
+                                }
+                                async { None::<Result<DroneCommand, tonic::Status>> }
+                            }))
+                        }
+                    },
+                )
+                .unwrap();
+        });
+
+        // Five ticks long, and well under the staleness threshold
+        let tick = Duration::from_millis(10);
+        let heartbeat = Duration::from_millis(50);
+        let staleness = Duration::from_millis(200);
+        let config = test_config().with_keepalive(1.0, heartbeat).unwrap();
+        let drone = tokio::spawn(run_drone_loop(client, config));
+
+        // The router only sees reports sent after it subscribes, so start from the first one
+        let mut last = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        for _ in 0..10 {
+            let at = tokio::time::timeout_at(last + staleness, rx.recv())
+                .await
+                .expect("the stationary drone went stale")
+                .unwrap();
+            // Ticking every 10ms, the drone would report on every tick without the keepalive
+            let gap = at - last;
+            assert!(
+                gap >= heartbeat && gap < heartbeat + tick,
+                "reported after {gap:?}"
+            );
+            last = at;
+        }
+        drone.abort();
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_only_thins_position_reports() {
+        let client = router_and_client(|router| {
+            router
+                .register(
+                    DRONE_SESSION_PATH,
+                    |_, inbound: DecodedInbound<DroneMessage>| async move {
+                        Ok(inbound
+                            .filter_map(|_| async { None::<Result<DroneCommand, tonic::Status>> }))
+                    },
+                )
+                .unwrap();
+        });
+
+        let origin = Origin::produce();
+        // No heartbeat within the test, so only the first position report goes out
+        let config = test_config()
+            .with_keepalive(1.0, Duration::from_secs(60))
+            .unwrap()
+            .with_telemetry_publisher(
+                TelemetryPublisher::new(&origin.producer, "drone-1").unwrap(),
+            );
+        let drone = tokio::spawn(run_drone_loop(client, config));
+
+        let tracks = [TelemetryTrack::Position, TelemetryTrack::Battery];
+        let telemetry = subscribe_telemetry(&origin.consumer, "drone-1", &tracks)
+            .await
+            .unwrap();
+        let reports: Vec<_> = telemetry
+            .take_until(tokio::time::sleep(Duration::from_millis(200)))
+            .collect()
+            .await;
+        drone.abort();
+
+        let positions = reports
+            .iter()
+            .filter(|report| matches!(report, Ok(DroneTelemetry::Position(_))))
+            .count();
+        let batteries = reports
+            .iter()
+            .filter(|report| matches!(report, Ok(DroneTelemetry::Battery(_))))
+            .count();
+        assert!(positions <= 1, "{positions} position reports");
+        // Ticking every 10ms
+        assert!(batteries >= 5, "{batteries} battery reports");
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_session_and_broadcasts() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = router_and_client(|router| {
+            router
+                .register(
+                    DRONE_SESSION_PATH,
+                    move |_, inbound: DecodedInbound<DroneMessage>| {
+                        let tx = tx.clone();
+                        async move {
+                            Ok(inbound.filter_map(move |_| {
+                                let _ = tx.send(());
+                                async { None::<Result<DroneCommand, tonic::Status>> }
+                            }))
+                        }
+                    },
+                )
+                .unwrap();
+        });
+
+        let origin = Origin::produce();
+        let config = test_config().with_telemetry_publisher(
+            TelemetryPublisher::new(&origin.producer, "drone-1").unwrap(),
+        );
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let drone = tokio::spawn(run_drone_loop_until(client, config, async {
+            let _ = stopped.await;
+        }));
+
+        tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        stop.send(()).unwrap();
+
+        let result = tokio::time::timeout(Duration::from_secs(1), drone)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(result.is_ok());
+
+        // The telemetry broadcast is unannounced once the publisher is dropped
+        let path = crate::drone_broadcast_path("drone-1");
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while origin.consumer.consume_broadcast(&path).is_some() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_while_backing_off() {
+        // Every session ends straight away, so the drone spends its time backing off
+        let client = router_and_client(|router| {
+            router
+                .register(
+                    DRONE_SESSION_PATH,
+                    |_, _: DecodedInbound<DroneMessage>| async {
+                        Ok(futures::stream::empty::<Result<DroneCommand, tonic::Status>>())
+                    },
+                )
+                .unwrap();
+        });
+        let config = test_config().with_retry(
+            RetryPolicy::builder()
+                .initial_backoff(Duration::from_secs(60))
+                .build(),
+        );
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            run_drone_loop_until(
+                client,
+                config,
+                tokio::time::sleep(Duration::from_millis(200)),
+            ),
+        )
+        .await
+        .unwrap();
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_drone_resubscribes_after_session_ends() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let client = router_and_client(|router| {
+            router
+                .register(
+                    DRONE_SESSION_PATH,
+                    move |_, inbound: DecodedInbound<DroneMessage>| {
+                        let _ = tx.send(());
+                        // End the session after the first report
+                        async move {
+                            Ok(inbound.take(1).filter_map(|_| async {
+                                None::<Result<DroneCommand, tonic::Status>>
+                            }))
+                        }
+                    },
+                )
+                .unwrap();
+        });
+
+        let drone = tokio::spawn(run_drone_loop(client, test_config()));
+
+        for _ in 0..2 {
+            tokio::time::timeout(Duration::from_secs(2), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        drone.abort();
+    }
+
+    #[tokio::test]
+    async fn test_drone_gives_up_when_retries_exhausted() {
+        // Nothing ever announces the server broadcast, so every connect times out
+        let origin = Origin::produce();
+        let client_config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .server_prefix("server".to_string())
+            .timeout(Duration::from_millis(10))
+            .build();
+        let client = RpcClient::new(Arc::new(origin.producer), origin.consumer, client_config);
+        let config = test_config().with_retry(
+            RetryPolicy::builder()
+                .initial_backoff(Duration::from_millis(1))
+                .max_retries(1)
+                .build(),
+        );
+
+        let result = tokio::time::timeout(Duration::from_secs(5), run_drone_loop(client, config))
+            .await
+            .unwrap();
+        assert!(matches!(
+            result,
+            Err(DroneLoopError::RetriesExhausted { attempts: 2, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_drone_gives_up_on_repeated_rejections() {
+        // The server is up but refuses every session
+        let origin = Origin::produce();
+        let producer = Arc::new(origin.producer);
+        let config = RpcRouterConfig::builder()
+            .client_prefix("drone".to_string())
+            .response_prefix("server".to_string())
+            .build()
+            .with_max_sessions(0);
+        let mut router =
+            RpcRouter::new(origin.consumer.clone(), Arc::clone(&producer), config).unwrap();
+        router
+            .register(
+                DRONE_SESSION_PATH,
+                |_, inbound: DecodedInbound<DroneMessage>| async move {
+                    Ok(inbound
+                        .filter_map(|_| async { None::<Result<DroneCommand, tonic::Status>> }))
+                },
+            )
+            .unwrap();
+        tokio::spawn(router.run());
+        let client_config = RpcClientConfig::builder()
+            .client_id("drone-1".to_string())
+            .client_prefix("drone".to_string())
+            .server_prefix("server".to_string())
+            .timeout(Duration::from_secs(1))
+            .build();
+        let client = RpcClient::new(producer, origin.consumer, client_config);
+        let config = test_config().with_retry(
+            RetryPolicy::builder()
+                .initial_backoff(Duration::from_millis(1))
+                .max_backoff(Duration::from_millis(5))
+                .multiplier(1.5)
+                .max_retries(3)
+                .build(),
+        );
+
+        let result = tokio::time::timeout(Duration::from_secs(5), run_drone_loop(client, config))
+            .await
+            .unwrap();
+        assert!(matches!(
+            result,
+            Err(DroneLoopError::RetriesExhausted {
+                attempts: 4,
+                source: RpcClientError::Rejected {
+                    reason: rpcmoq_lite::RejectReason::Overloaded
+                },
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_drone_waits_out_maintenance() {
+        let (started, mut sessions) = mpsc::unbounded_channel();
+        let mut handle = None;
+        let client = router_and_client(|router| {
+            router
+                .register(
+                    DRONE_SESSION_PATH,
+                    move |_, inbound: DecodedInbound<DroneMessage>| {
+                        let _ = started.send(());
+                        async move {
+                            Ok(inbound.filter_map(|_| async {
+                                None::<Result<DroneCommand, tonic::Status>>
+                            }))
+                        }
+                    },
+                )
+                .unwrap();
+            handle = Some(router.handle());
+        });
+        let handle = handle.unwrap();
+        handle.set_accepting(false);
+        let config = test_config().with_retry(
+            RetryPolicy::builder()
+                .initial_backoff(Duration::from_millis(1))
+                .max_backoff(Duration::from_millis(5))
+                .max_retries(1)
+                .build(),
+        );
+        let drone = tokio::spawn(run_drone_loop(client, config));
+
+        // Far more rejections than the budget allows
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(!drone.is_finished());
+
+        handle.set_accepting(true);
+        tokio::time::timeout(Duration::from_secs(1), sessions.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        drone.abort();
+    }
+
+    #[tokio::test]
+    async fn test_drone_stops_when_server_has_no_handler() {
+        // The server is up but serves nothing the drone can call
+        let client = router_and_client(|_| {});
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            run_drone_loop(client, test_config()),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            result,
+            Err(DroneLoopError::Refused {
+                reason: RejectReason::NoHandler
+            })
+        ));
+    }
+
+    #[test]
+    fn test_keepalive_rejects_invalid_settings() {
+        let heartbeat = Duration::from_secs(1);
+        for tolerance_m in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(
+                test_config()
+                    .with_keepalive(tolerance_m, heartbeat)
+                    .is_err()
+            );
+        }
+        assert!(test_config().with_keepalive(1.0, Duration::ZERO).is_err());
+        assert!(test_config().with_keepalive(0.0, heartbeat).is_ok());
+    }
+
+    #[test]
+    fn test_is_server_missing() {
+        assert!(is_server_missing(&RpcClientError::ServerNotFound(
+            "server/drone-1".to_string()
+        )));
+        assert!(!is_server_missing(&RpcClientError::ConnectionClosed));
+    }
+
+    #[test]
+    fn test_apply_retargets_flight() {
+        let home = test_config().home;
+        let mut vehicle = Vehicle::new(home.clone());
+
+        assert!(vehicle.apply(&goto("cmd-1")).accepted);
+        assert_eq!(
+            (
+                vehicle.target.latitude,
+                vehicle.target.longitude,
+                vehicle.target.altitude_m
+            ),
+            (1.0, 2.0, 3.0)
+        );
+
+        let mut return_home = goto("cmd-2");
+        return_home.set_command_type(CommandType::ReturnHome);
+        vehicle.apply(&return_home);
+        assert_eq!(vehicle.target, home);
+
+        let ack = vehicle.apply(&DroneCommand::default());
+        assert!(!ack.accepted);
+        let unknown = DroneCommand {
+            command_type: 42,
+            ..goto("cmd-3")
+        };
+        let ack = vehicle.apply(&unknown);
+        assert!(!ack.accepted);
+        assert_eq!(ack.message, "unknown command type 42");
+        // A rejected command leaves the status on the last accepted one
+        assert_eq!(vehicle.status().command_id, "cmd-2");
+        assert!(vehicle.status().at_target);
+    }
+
+    #[test]
+    fn test_battery_drains_only_in_flight() {
+        let mut vehicle = Vehicle::new(test_config().home);
+        vehicle.drain_battery(Duration::from_secs(100));
+        assert_eq!(vehicle.battery().charge_percent, 95.0);
+
+        vehicle.current.altitude_m = 0.0;
+        vehicle.drain_battery(Duration::from_secs(100));
+        assert_eq!(vehicle.battery().charge_percent, 95.0);
+
+        vehicle.current.altitude_m = 10.0;
+        vehicle.drain_battery(Duration::from_secs(10_000));
+        assert_eq!(vehicle.battery().charge_percent, 0.0);
+    }
+}
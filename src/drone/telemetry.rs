@@ -0,0 +1,285 @@
+//! Telemetry published by a drone on a broadcast of its own, one track per kind of report.
+//!
+//! Position reports also reach the server over the session stream. The telemetry broadcast lets
+//! any controller on the relay follow a drone, and since each kind of report has its own track a
+//! controller only receives the ones it subscribes to.
+
+use std::fmt;
+
+use futures::{Stream, StreamExt};
+use moq_lite::{BroadcastProducer, OriginConsumer, OriginProducer, Track};
+use rpcmoq_lite::{RpcOutbound, RpcSendError, RpcWireError};
+
+use crate::drone_proto::{BatteryState, DronePosition, DroneStatus};
+use crate::track::announced_broadcast;
+use crate::{
+    BATTERY_TRACK, POSITION_TRACK, STATUS_TRACK, decoded_track_stream, drone_broadcast_path,
+};
+
+/// A track of a drone's telemetry broadcast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TelemetryTrack {
+    Position,
+    Battery,
+    Status,
+}
+
+impl TelemetryTrack {
+    pub const ALL: [Self; 3] = [Self::Position, Self::Battery, Self::Status];
+
+    /// The name of the track on the broadcast.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Position => POSITION_TRACK,
+            Self::Battery => BATTERY_TRACK,
+            Self::Status => STATUS_TRACK,
+        }
+    }
+
+    /// The track called `name`, if there is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|track| track.name() == name)
+    }
+}
+
+/// A report received on one of a drone's telemetry tracks.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DroneTelemetry {
+    Position(DronePosition),
+    Battery(BatteryState),
+    Status(DroneStatus),
+}
+
+/// Publishes a drone's telemetry on [`POSITION_TRACK`], [`BATTERY_TRACK`] and [`STATUS_TRACK`].
+pub struct TelemetryPublisher {
+    // Keeps the broadcast announced for as long as the publisher lives
+    _broadcast: BroadcastProducer,
+    position: RpcOutbound,
+    battery: RpcOutbound,
+    status: RpcOutbound,
+}
+
+impl TelemetryPublisher {
+    /// Announce the telemetry broadcast for `drone_id` on `producer`.
+    ///
+    /// Returns `None` if `producer` may not publish the broadcast.
+    pub fn new(producer: &OriginProducer, drone_id: &str) -> Option<Self> {
+        let mut broadcast = producer.create_broadcast(drone_broadcast_path(drone_id))?;
+        let mut outbound = |track: TelemetryTrack| {
+            RpcOutbound::new(broadcast.create_track(Track::new(track.name())))
+        };
+        let position = outbound(TelemetryTrack::Position);
+        let battery = outbound(TelemetryTrack::Battery);
+        let status = outbound(TelemetryTrack::Status);
+        Some(Self {
+            _broadcast: broadcast,
+            position,
+            battery,
+            status,
+        })
+    }
+
+    pub fn publish_position(&mut self, position: &DronePosition) -> Result<(), RpcSendError> {
+        self.position.send(position)
+    }
+
+    pub fn publish_battery(&mut self, battery: &BatteryState) -> Result<(), RpcSendError> {
+        self.battery.send(battery)
+    }
+
+    pub fn publish_status(&mut self, status: &DroneStatus) -> Result<(), RpcSendError> {
+        self.status.send(status)
+    }
+}
+
+impl fmt::Debug for TelemetryPublisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TelemetryPublisher").finish_non_exhaustive()
+    }
+}
+
+/// Subscribe to `tracks` of the telemetry published by `drone_id`, interleaved as they arrive.
+///
+/// Tracks left out are never subscribed to, so their reports are not sent at all. Waits for the
+/// drone to announce its telemetry broadcast. Returns `None` if `consumer` may not subscribe to
+/// it, or the origin closes first.
+pub async fn subscribe_telemetry(
+    consumer: &OriginConsumer,
+    drone_id: &str,
+    tracks: &[TelemetryTrack],
+) -> Option<impl Stream<Item = Result<DroneTelemetry, RpcWireError>> + use<>> {
+    let broadcast = announced_broadcast(consumer, &drone_broadcast_path(drone_id)).await?;
+
+    let streams = tracks.iter().map(|&track| {
+        let consumer = broadcast.subscribe_track(&Track::new(track.name()));
+        match track {
+            TelemetryTrack::Position => decoded_track_stream(consumer)
+                .map(|pos| pos.map(DroneTelemetry::Position))
+                .boxed(),
+            TelemetryTrack::Battery => decoded_track_stream(consumer)
+                .map(|battery| battery.map(DroneTelemetry::Battery))
+                .boxed(),
+            TelemetryTrack::Status => decoded_track_stream(consumer)
+                .map(|status| status.map(DroneTelemetry::Status))
+                .boxed(),
+        }
+    });
+    Some(futures::stream::select_all(streams))
+}
+
+/// Follow the position reports of `drone_id` alone, without subscribing to the rest of the
+/// fleet or to the drone's other tracks.
+///
+/// Waits for the drone to announce its telemetry broadcast. The stream ends when the position
+/// track does, undecodable reports are skipped. Returns `None` if `consumer` may not subscribe
+/// to the broadcast, or the origin closes first.
+pub async fn subscribe_drone(
+    consumer: &OriginConsumer,
+    drone_id: &str,
+) -> Option<impl Stream<Item = DronePosition> + use<>> {
+    let broadcast = announced_broadcast(consumer, &drone_broadcast_path(drone_id)).await?;
+
+    let track = broadcast.subscribe_track(&Track::new(POSITION_TRACK));
+    let positions = decoded_track_stream::<DronePosition>(track)
+        .take_while(|pos| std::future::ready(!matches!(pos, Err(RpcWireError::Transport(_)))))
+        .filter_map(|pos| std::future::ready(pos.ok()));
+    Some(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use moq_lite::Origin;
+    use rpcmoq_lite::test::resend_until;
+    use std::time::Duration;
+
+    fn publish_all(publisher: &mut TelemetryPublisher) {
+        let drone_id = "drone-1".to_string();
+        publisher
+            .publish_position(&DronePosition {
+                drone_id: drone_id.clone(),
+                ..Default::default()
+            })
+            .unwrap();
+        publisher
+            .publish_battery(&BatteryState {
+                drone_id: drone_id.clone(),
+                charge_percent: 80.0,
+                ..Default::default()
+            })
+            .unwrap();
+        publisher
+            .publish_status(&DroneStatus {
+                drone_id,
+                at_target: true,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    /// Publish every track until `count` reports arrive on `telemetry`, then return them.
+    async fn receive(
+        publisher: &mut TelemetryPublisher,
+        telemetry: impl Stream<Item = Result<DroneTelemetry, RpcWireError>>,
+        count: usize,
+    ) -> Vec<DroneTelemetry> {
+        let mut state = (publisher, Box::pin(telemetry));
+        let mut received = Vec::new();
+        while received.len() < count {
+            let report = resend_until(
+                &mut state,
+                async |(publisher, _)| publish_all(publisher),
+                async |(_, telemetry)| telemetry.next().await,
+            )
+            .await;
+            received.push(report.unwrap().unwrap());
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_receives_every_track() {
+        let origin = Origin::produce();
+        let mut publisher = TelemetryPublisher::new(&origin.producer, "drone-1").unwrap();
+        let telemetry = subscribe_telemetry(&origin.consumer, "drone-1", &TelemetryTrack::ALL)
+            .await
+            .unwrap();
+
+        let received = receive(&mut publisher, telemetry, 30).await;
+        assert!(
+            received
+                .iter()
+                .any(|r| matches!(r, DroneTelemetry::Position(_)))
+        );
+        assert!(
+            received
+                .iter()
+                .any(|r| matches!(r, DroneTelemetry::Battery(_)))
+        );
+        assert!(
+            received
+                .iter()
+                .any(|r| matches!(r, DroneTelemetry::Status(_)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_position_only_subscriber_ignores_other_tracks() {
+        let origin = Origin::produce();
+        let mut publisher = TelemetryPublisher::new(&origin.producer, "drone-1").unwrap();
+        let telemetry =
+            subscribe_telemetry(&origin.consumer, "drone-1", &[TelemetryTrack::Position])
+                .await
+                .unwrap();
+
+        let received = receive(&mut publisher, telemetry, 10).await;
+        assert!(
+            received
+                .iter()
+                .all(|r| matches!(r, DroneTelemetry::Position(_)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_drone_waits_for_announcement() {
+        let origin = Origin::produce();
+
+        let mut subscriber = tokio::spawn({
+            let consumer = origin.consumer.clone();
+            async move {
+                let positions = subscribe_drone(&consumer, "drone-2").await.unwrap();
+                Box::pin(positions).next().await.unwrap()
+            }
+        });
+
+        // Another drone's reports must not reach the subscriber
+        let mut other = TelemetryPublisher::new(&origin.producer, "drone-1").unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let mut publisher = TelemetryPublisher::new(&origin.producer, "drone-2").unwrap();
+
+        let received = resend_until(
+            &mut subscriber,
+            async |_| {
+                publish_all(&mut other);
+                publisher
+                    .publish_position(&DronePosition {
+                        drone_id: "drone-2".to_string(),
+                        ..Default::default()
+                    })
+                    .unwrap();
+            },
+            async |subscriber| subscriber.await,
+        )
+        .await
+        .unwrap();
+        assert_eq!(received.drone_id, "drone-2");
+    }
+
+    #[test]
+    fn test_track_names_round_trip() {
+        for track in TelemetryTrack::ALL {
+            assert_eq!(TelemetryTrack::from_name(track.name()), Some(track));
+        }
+        assert_eq!(TelemetryTrack::from_name("acks"), None);
+    }
+}
@@ -1,31 +1,51 @@
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use futures::StreamExt;
+use tokio::sync::oneshot;
 use tonic::{Request, Response, Status, Streaming};
 use tracing::{debug, info, warn};
+use uuid::Uuid;
 
-use crate::drone::DroneSessionMap;
-use crate::drone_proto::DronePosition;
+use crate::clock::Clock;
+use crate::command::error::EnqueueError;
+use crate::command::{Command, CommandId, CommandReceipt, QueuedCommand};
+use crate::drone::{DroneSessionId, DroneSessionMap};
+use crate::drone_proto::drone_message::Payload;
+use crate::drone_proto::drone_service_server::{DroneService, DroneServiceServer};
 use crate::drone_proto::echo_service_server::{EchoService, EchoServiceServer};
+use crate::drone_proto::{
+    BroadcastAck, CommandAck, CommandAckQuery, CommandType, DroneCommand, DroneMessage,
+    DronePosition,
+};
 use crate::state_machine::echo::Position;
+use crate::telemetry::{TelemetryLimiter, TelemetryRateLimit};
 use crate::unit::UnitId;
 use crate::unit_context::UnitContext;
 use crate::unit_map::UnitMap;
+use crate::unit_map::unit_ref::UnitRef;
 
 pub async fn start_server(
     addr: SocketAddr,
     unit_map: Arc<UnitMap<UnitContext>>,
     session_map: Arc<DroneSessionMap>,
 ) -> anyhow::Result<()> {
-    let service = DroneServiceImpl::new(unit_map, session_map);
+    serve(addr, DroneServiceImpl::new(unit_map, session_map)).await
+}
+
+/// Serve the echo and drone services from `service` on `addr`.
+pub async fn serve(addr: SocketAddr, service: DroneServiceImpl) -> anyhow::Result<()> {
+    let service = Arc::new(service);
 
     info!(address = %addr, "gRPC server starting");
 
     tonic::transport::Server::builder()
-        .add_service(EchoServiceServer::new(service))
+        .add_service(EchoServiceServer::from_arc(Arc::clone(&service)))
+        .add_service(DroneServiceServer::from_arc(service))
         .serve(addr)
         .await?;
 
@@ -35,15 +55,84 @@ pub async fn start_server(
 pub struct DroneServiceImpl {
     unit_map: Arc<UnitMap<UnitContext>>,
     session_map: Arc<DroneSessionMap>,
+    telemetry_limiter: Arc<TelemetryLimiter>,
+    session_takeover: Option<Duration>,
+    clock: Arc<dyn Clock>,
 }
 
 impl DroneServiceImpl {
+    /// Create the service over `unit_map`, reading the time from the unit map's clock.
+    ///
+    /// Command expiry, the telemetry rate limit and the contexts of drones that connect are
+    /// all driven by it, so a [`UnitMap::with_clock`] clock applies to the whole service.
     pub fn new(unit_map: Arc<UnitMap<UnitContext>>, session_map: Arc<DroneSessionMap>) -> Self {
         Self {
+            clock: unit_map.clock(),
             unit_map,
             session_map,
+            telemetry_limiter: Arc::new(TelemetryLimiter::default()),
+            session_takeover: None,
         }
     }
+
+    /// Let a reconnecting drone take over its previous session instead of being rejected with
+    /// `already_exists`.
+    ///
+    /// The previous session gets `grace` to close by itself, after which it is evicted and its
+    /// command stream closed. The drone's [`UnitContext`] and queued commands carry over.
+    ///
+    /// Only a stale session is taken over: one still receiving messages from its drone during
+    /// the grace period is kept, and the newcomer rejected with `already_exists` as it would
+    /// be without takeover. This keeps a client claiming a connected drone's ID from
+    /// evicting it.
+    pub fn with_session_takeover(mut self, grace: Duration) -> Self {
+        self.session_takeover = Some(grace);
+        self
+    }
+
+    /// Drop position reports in a drone session that arrive faster than `limit`.
+    ///
+    /// Each drone is limited separately. Unlimited by default.
+    pub fn with_telemetry_rate_limit(mut self, limit: TelemetryRateLimit) -> Self {
+        self.telemetry_limiter = Arc::new(TelemetryLimiter::new(Some(limit)));
+        self
+    }
+
+    /// The number of position reports from `unit_id` dropped by the rate limit this session.
+    pub fn dropped_telemetry(&self, unit_id: &UnitId) -> u64 {
+        self.telemetry_limiter.dropped(unit_id)
+    }
+
+    /// The number of position reports dropped by the rate limit across all drones.
+    pub fn total_dropped_telemetry(&self) -> u64 {
+        self.telemetry_limiter.total_dropped()
+    }
+
+    /// Create a drone session for `unit_id`, taking over an existing one if configured to.
+    async fn create_drone_session(&self, unit_id: &UnitId) -> Result<DroneSessionId, Status> {
+        let error = match self.session_map.create_session(unit_id) {
+            Ok(session_id) => return Ok(session_id),
+            Err(e) => e,
+        };
+        let Some(grace) = self.session_takeover else {
+            return Err(Status::already_exists(error.to_string()));
+        };
+
+        if let Some(previous) = self.session_map.get_session_id(unit_id)
+            && !self
+                .session_map
+                .session_stale(unit_id, &previous, grace)
+                .await
+        {
+            warn!(drone_id = %unit_id, "Previous session still active, refusing takeover");
+            return Err(Status::already_exists(error.to_string()));
+        }
+        let (session_id, evicted) = self.session_map.create_session_takeover(unit_id);
+        if let Some(evicted) = evicted {
+            info!(drone_id = %unit_id, evicted = %evicted, "Took over stale session");
+        }
+        Ok(session_id)
+    }
 }
 
 #[tonic::async_trait]
@@ -57,25 +146,13 @@ impl EchoService for DroneServiceImpl {
         let mut inbound = request.into_inner();
 
         // I need the first message to come in in order to get the drone ID.
-        let first_msg = inbound
-            .next()
-            .await
-            .ok_or_else(|| Status::invalid_argument("Empty stream"))?
-            .map_err(|e| Status::internal(e.to_string()))?;
-
+        let first_msg = first_message(&mut inbound).await?;
         let drone_id = first_msg.drone_id.clone();
-
-        let unit_id = UnitId::from(drone_id.as_str());
+        let unit_id = parse_unit_id(&drone_id)?;
 
         info!(drone_id = %drone_id, "DroneSession started");
 
-        // Create or reuse unit context
-        if self.unit_map.get_unit(&unit_id).is_err() {
-            let context = UnitContext::new();
-            self.unit_map
-                .insert_unit(unit_id.clone(), context)
-                .map_err(|e| Status::internal(e.to_string()))?;
-        }
+        self.ensure_unit(&unit_id)?;
 
         match self.session_map.create_session(&unit_id) {
             Ok(session_id) => {
@@ -98,22 +175,15 @@ impl EchoService for DroneServiceImpl {
         tokio::spawn(async move {
             while let Some(msg_result) = inbound.next().await {
                 match msg_result {
+                    Ok(pos) if pos.drone_id.is_empty() => {
+                        debug!(drone_id = %drone_id_for_task, "Ignoring position without a drone ID");
+                    }
                     Ok(pos) => {
-                        let position = Position {
-                            drone_id: pos.drone_id.clone(),
-                            latitude: pos.latitude,
-                            longitude: pos.longitude,
-                            altitude_m: pos.altitude_m,
-                            heading_deg: pos.heading_deg,
-                            speed_mps: pos.speed_mps,
-                            timestamp: pos.timestamp,
-                        };
-
-                        if let Ok(unit_ref) =
-                            unit_map_for_telemetry.get_unit(&unit_id_for_telemetry)
-                        {
-                            let _ = unit_ref.view(|ctx| ctx.update_position(position));
-                        }
+                        update_telemetry(
+                            &unit_map_for_telemetry,
+                            &unit_id_for_telemetry,
+                            pos.into(),
+                        );
                     }
                     Err(e) => {
                         warn!(drone_id = %drone_id_for_task, error = %e, "Telemetry stream error");
@@ -125,6 +195,7 @@ impl EchoService for DroneServiceImpl {
             // Cleanup on disconnect
             info!(drone_id = %drone_id_for_task, "Telemetry stream closed");
             let _ = telemetry_session_map.remove_session(&unit_id_for_telemetry);
+            let _ = unit_map_for_telemetry.remove_unit(&unit_id_for_telemetry);
         });
 
         let unit_map_for_echo = Arc::clone(&self.unit_map);
@@ -146,19 +217,10 @@ impl EchoService for DroneServiceImpl {
                         unit_ref.view(|ctx| ctx.poll_position()).ok().flatten()
                     });
 
-                if let Some(pos_bytes) = maybe_pos {
-                    let pos = DronePosition {
-            drone_id: pos_bytes.drone_id,
-             latitude: pos_bytes.latitude,
-             longitude: pos_bytes.longitude,
-             altitude_m: pos_bytes.altitude_m,
-             heading_deg: pos_bytes.heading_deg,
-             speed_mps: pos_bytes.speed_mps,
-             timestamp: pos_bytes.timestamp,
-
-                    };
-                            debug!(drone_id = %drone_id_for_stream, position = ?pos, "Sending position");
-                            yield Ok(pos);
+                if let Some(pos) = maybe_pos {
+                    let pos = DronePosition::from(pos);
+                    debug!(drone_id = %drone_id_for_stream, position = ?pos, "Sending position");
+                    yield Ok(pos);
                 }
 
                 tokio::time::sleep(Duration::from_millis(50)).await;
@@ -169,20 +231,1305 @@ impl EchoService for DroneServiceImpl {
     }
 }
 
+#[tonic::async_trait]
+impl DroneService for DroneServiceImpl {
+    type DroneSessionStream =
+        Pin<Box<dyn futures::Stream<Item = Result<DroneCommand, Status>> + Send>>;
+
+    async fn drone_session(
+        &self,
+        request: Request<Streaming<DroneMessage>>,
+    ) -> Result<Response<Self::DroneSessionStream>, Status> {
+        let mut inbound = request.into_inner();
+
+        // The drone ID is taken from a hello, or from the first position report for drones
+        // that predate hellos.
+        let SessionIdentity {
+            drone_id,
+            capabilities,
+            first_pos,
+        } = SessionIdentity::from_first_message(first_message(&mut inbound).await?)?;
+        let unit_id = parse_unit_id(&drone_id)?;
+
+        info!(drone_id = %drone_id, capabilities = ?capabilities, "DroneSession started");
+
+        // Created before the unit so a stale session cleaning up during a takeover grace period
+        // cannot remove the unit from under this one
+        let session_id = self.create_drone_session(&unit_id).await?;
+        info!(drone_id = %drone_id, session_id = %session_id, "Session created");
+
+        // Only once the session is ours, so a rejected duplicate can't overwrite the
+        // capabilities of the drone already connected
+        self.ensure_unit(&unit_id)?;
+        if let Ok(unit_ref) = self.unit_map.get_unit(&unit_id) {
+            let _ = unit_ref.view(|ctx| ctx.set_capabilities(capabilities));
+        }
+
+        if let Some(first_pos) = first_pos {
+            self.process_position(&unit_id, first_pos);
+        }
+
+        let (drained, commands_drained) = oneshot::channel();
+        let connection_lost = Arc::new(AtomicBool::new(false));
+        let telemetry = TelemetrySession {
+            unit_map: Arc::clone(&self.unit_map),
+            session_map: Arc::clone(&self.session_map),
+            limiter: Arc::clone(&self.telemetry_limiter),
+            unit_id: unit_id.clone(),
+            session_id: session_id.clone(),
+            drone_id: drone_id.clone(),
+            commands_drained,
+            connection_lost: Arc::clone(&connection_lost),
+            clock: Arc::clone(&self.clock),
+        };
+        tokio::spawn(telemetry.run(inbound));
+
+        let outbound = command_stream(
+            Arc::clone(&self.unit_map),
+            Arc::clone(&self.session_map),
+            unit_id,
+            session_id,
+            drone_id,
+            drained,
+            connection_lost,
+        );
+
+        Ok(Response::new(Box::pin(outbound)))
+    }
+
+    async fn send_command(
+        &self,
+        request: Request<DroneCommand>,
+    ) -> Result<Response<CommandAck>, Status> {
+        let command = request.into_inner();
+        let unit_id = UnitId::from(command.drone_id.as_str());
+        let parsed = command_from_proto(&command)?;
+        let expires_at = expiry_from_proto(&command, self.clock.now());
+        let command_id = if command.command_id.is_empty() {
+            CommandId::generate()
+        } else {
+            command
+                .command_id
+                .parse::<Uuid>()
+                .map(CommandId::from)
+                .map_err(|_| Status::invalid_argument("command_id must be a UUID"))?
+        };
+
+        let unit_ref = self
+            .unit_map
+            .get_unit(&unit_id)
+            .map_err(|e| Status::not_found(e.to_string()))?;
+
+        // A command merged into a pending duplicate is acknowledged under that one's ID
+        let command_id = unit_ref
+            .view(|ctx| ctx.enqueue_command_until(command_id, parsed, expires_at))
+            // The drone disconnected after it was looked up, closing its queue
+            .map_err(|_| {
+                Status::unavailable(format!(
+                    "{unit_id} disconnected before the command was queued"
+                ))
+            })?
+            .map_err(|e| {
+                warn!(drone_id = %command.drone_id, error = %e, "Rejecting command");
+                enqueue_status(&unit_id, e)
+            })?;
+
+        debug!(drone_id = %command.drone_id, command_id = %command_id, "Command queued");
+
+        Ok(Response::new(CommandAck {
+            accepted: true,
+            message: String::new(),
+            command_id: command_id.to_string(),
+        }))
+    }
+
+    async fn broadcast_command(
+        &self,
+        request: Request<DroneCommand>,
+    ) -> Result<Response<BroadcastAck>, Status> {
+        let command = request.into_inner();
+        if !command.drone_id.is_empty() || !command.command_id.is_empty() {
+            return Err(Status::invalid_argument(
+                "drone_id and command_id are assigned per drone",
+            ));
+        }
+        let parsed = command_from_proto(&command)?;
+        let expires_at = expiry_from_proto(&command, self.clock.now());
+
+        let acks = broadcast_to(self.unit_map.iter(), &parsed, expires_at);
+        info!(command = ?parsed, drones = acks.len(), "Command broadcast");
+
+        Ok(Response::new(BroadcastAck { acks }))
+    }
+
+    async fn get_command_ack(
+        &self,
+        request: Request<CommandAckQuery>,
+    ) -> Result<Response<CommandAck>, Status> {
+        let query = request.into_inner();
+        let unit_id = UnitId::from(query.drone_id.as_str());
+        let command_id = query
+            .command_id
+            .parse::<Uuid>()
+            .map(CommandId::from)
+            .map_err(|_| Status::invalid_argument("command_id must be a UUID"))?;
+
+        let receipt = self
+            .unit_map
+            .get_unit(&unit_id)
+            .map_err(|e| Status::not_found(e.to_string()))?
+            .view(|ctx| ctx.take_ack(&command_id))
+            .map_err(|e| Status::not_found(e.to_string()))?
+            .ok_or_else(|| {
+                Status::not_found(format!("{unit_id} has not acknowledged {command_id}"))
+            })?;
+
+        Ok(Response::new(CommandAck {
+            accepted: receipt.accepted,
+            message: receipt.message,
+            command_id: command_id.to_string(),
+        }))
+    }
+}
+
+/// How long a drone session's cleanup waits for its command stream to flush the commands still
+/// queued for the drone before removing its unit.
+///
+/// The stream only hands the commands to the transport, so this is short: a stream that isn't
+/// being polled is not going to deliver them.
+const COMMAND_DRAIN_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// The telemetry half of a drone session, applying the drone's reports and cleaning the session
+/// up once they stop.
+struct TelemetrySession {
+    unit_map: Arc<UnitMap<UnitContext>>,
+    session_map: Arc<DroneSessionMap>,
+    limiter: Arc<TelemetryLimiter>,
+    unit_id: UnitId,
+    session_id: DroneSessionId,
+    drone_id: String,
+    /// Resolves once the session's command stream has ended.
+    commands_drained: oneshot::Receiver<()>,
+    /// Set when the inbound stream fails, telling the command stream not to flush.
+    connection_lost: Arc<AtomicBool>,
+    clock: Arc<dyn Clock>,
+}
+
+impl TelemetrySession {
+    async fn run(
+        self,
+        mut inbound: impl futures::Stream<Item = Result<DroneMessage, Status>> + Unpin,
+    ) {
+        let taken_over = self
+            .session_map
+            .session_closed(&self.unit_id, &self.session_id);
+        let mut mismatches = 0;
+        tokio::pin!(taken_over);
+        loop {
+            let msg_result = tokio::select! {
+                msg_result = inbound.next() => match msg_result {
+                    Some(msg_result) => msg_result,
+                    None => break,
+                },
+                _ = &mut taken_over => {
+                    info!(drone_id = %self.drone_id, "Session taken over, closing telemetry stream");
+                    break;
+                }
+            };
+            if msg_result.is_ok() {
+                self.session_map
+                    .session_active(&self.unit_id, &self.session_id);
+            }
+            match msg_result {
+                Ok(DroneMessage {
+                    payload: Some(Payload::Position(pos)),
+                }) => match session_position(&self.unit_id, pos) {
+                    Ok(position) => {
+                        update_telemetry_limited(
+                            &self.unit_map,
+                            &self.limiter,
+                            &self.unit_id,
+                            position,
+                            self.clock.now(),
+                        );
+                    }
+                    Err(RejectedPosition::MissingDroneId) => {
+                        debug!(drone_id = %self.drone_id, "Ignoring position without a drone ID");
+                    }
+                    Err(RejectedPosition::OtherDrone(claimed)) => {
+                        mismatches += 1;
+                        warn!(
+                            drone_id = %self.drone_id,
+                            claimed = %claimed,
+                            mismatches,
+                            "Dropping position reported for another drone"
+                        );
+                        if mismatches >= MAX_DRONE_ID_MISMATCHES {
+                            warn!(drone_id = %self.drone_id, "Ending session after repeated drone ID mismatches");
+                            break;
+                        }
+                    }
+                },
+                Ok(DroneMessage {
+                    payload: Some(Payload::Ack(ack)),
+                }) => {
+                    record_ack(&self.unit_map, &self.unit_id, ack);
+                }
+                Ok(DroneMessage {
+                    payload: Some(Payload::Hello(_)),
+                }) => {
+                    debug!(drone_id = %self.drone_id, "Ignoring hello after session start");
+                }
+                Ok(DroneMessage { payload: None }) => {
+                    debug!(drone_id = %self.drone_id, "Ignoring empty drone message");
+                }
+                Err(e) => {
+                    warn!(drone_id = %self.drone_id, error = %e, "Telemetry stream error");
+                    self.connection_lost.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+        }
+
+        info!(
+            drone_id = %self.drone_id,
+            dropped = self.limiter.dropped(&self.unit_id),
+            "Telemetry stream closed"
+        );
+        // A session that was taken over leaves the unit to its replacement
+        if self
+            .session_map
+            .remove_session_if_current(&self.unit_id, &self.session_id)
+            .is_some()
+        {
+            // Removing the session wakes the command stream to flush what is still queued,
+            // unless the connection was lost and there is nobody to flush to
+            if !self.connection_lost.load(Ordering::Relaxed)
+                && tokio::time::timeout(COMMAND_DRAIN_TIMEOUT, self.commands_drained)
+                    .await
+                    .is_err()
+            {
+                warn!(drone_id = %self.drone_id, "Command stream did not drain in time");
+            }
+            // Unless the drone has reconnected meanwhile, and the unit is the new session's
+            let removed = self
+                .session_map
+                .without_session(&self.unit_id, || self.unit_map.remove_unit(&self.unit_id));
+            if removed.is_some() {
+                self.limiter.remove(&self.unit_id);
+            }
+        }
+    }
+}
+
+/// The commands queued for `unit_id` in session `session_id`, emitted as soon as they are queued.
+///
+/// Parks between commands rather than polling the queue, waking only when a command is queued
+/// or to end the stream once the session is removed or taken over. Commands still queued when
+/// the session is removed are flushed first, unless `connection_lost` is set, as there is no
+/// one left to deliver them to. `drained` is dropped once the stream has ended.
+fn command_stream(
+    unit_map: Arc<UnitMap<UnitContext>>,
+    session_map: Arc<DroneSessionMap>,
+    unit_id: UnitId,
+    session_id: DroneSessionId,
+    drone_id: String,
+    drained: oneshot::Sender<()>,
+    connection_lost: Arc<AtomicBool>,
+) -> impl futures::Stream<Item = Result<DroneCommand, Status>> + Send + 'static {
+    let session_closed = session_map.session_closed(&unit_id, &session_id);
+    async_stream::stream! {
+        let _drained = drained;
+        let Some(command_queued) = unit_map
+            .get_unit(&unit_id)
+            .ok()
+            .and_then(|unit_ref| unit_ref.view(|ctx| ctx.command_queued()).ok())
+        else {
+            debug!(drone_id = %drone_id, "Unit not found, closing command stream");
+            return;
+        };
+
+        tokio::pin!(session_closed);
+        loop {
+            let current = session_map.get_session_id(&unit_id);
+            // A session that took over delivers the queue from here on
+            let taken_over = current.as_ref().is_some_and(|current| *current != session_id);
+            let lost = current.is_none() && connection_lost.load(Ordering::Relaxed);
+            while let Some(command) = unit_map
+                .get_unit(&unit_id)
+                .ok()
+                .filter(|_| !taken_over && !lost)
+                .and_then(|unit_ref| unit_ref.view(|ctx| ctx.poll_command()).ok().flatten())
+            {
+                let command = command_to_proto(&drone_id, command);
+                debug!(drone_id = %drone_id, command = ?command, "Sending command");
+                yield Ok(command);
+            }
+
+            if current.as_ref() != Some(&session_id) {
+                debug!(drone_id = %drone_id, "Session ended, closing command stream");
+                break;
+            }
+
+            tokio::select! {
+                _ = command_queued.notified() => {}
+                _ = &mut session_closed => {}
+            }
+        }
+    }
+}
+
+/// How many positions claiming another drone's ID a session may send before it is ended.
+const MAX_DRONE_ID_MISMATCHES: u32 = 10;
+
+/// Why a position report in a drone session was not applied.
+#[derive(Debug, PartialEq, Eq)]
+enum RejectedPosition {
+    MissingDroneId,
+    /// The report claims to be from the drone with this ID, not the session's.
+    OtherDrone(String),
+}
+
+/// Check a position report received in `unit_id`'s session really is from that drone.
+///
+/// A drone can only report its own position, so a report naming another drone is either a
+/// bug or an attempt to spoof it.
+fn session_position(unit_id: &UnitId, pos: DronePosition) -> Result<Position, RejectedPosition> {
+    if pos.drone_id.is_empty() {
+        return Err(RejectedPosition::MissingDroneId);
+    }
+    if pos.drone_id != unit_id.as_str() {
+        return Err(RejectedPosition::OtherDrone(pos.drone_id));
+    }
+    Ok(pos.into())
+}
+
+/// Who opened a drone session, learned from its first message.
+#[derive(Debug, PartialEq)]
+struct SessionIdentity {
+    drone_id: String,
+    capabilities: Vec<String>,
+    /// The position the drone identified itself with, to be processed as telemetry.
+    first_pos: Option<DronePosition>,
+}
+
+impl SessionIdentity {
+    /// Identify the drone from a hello, or from a position report for drones that predate
+    /// hellos. Any other first message is rejected.
+    fn from_first_message(message: DroneMessage) -> Result<Self, Status> {
+        match message.payload {
+            Some(Payload::Hello(hello)) => Ok(Self {
+                drone_id: hello.drone_id,
+                capabilities: hello.capabilities,
+                first_pos: None,
+            }),
+            Some(Payload::Position(pos)) => Ok(Self {
+                drone_id: pos.drone_id.clone(),
+                capabilities: Vec::new(),
+                first_pos: Some(pos),
+            }),
+            Some(Payload::Ack(_)) | None => Err(Status::invalid_argument(
+                "first message must be a hello or a position report",
+            )),
+        }
+    }
+}
+
+/// Queue a copy of `command` for each of `units`, each under its own command ID.
+///
+/// A unit that was removed since `units` was collected is skipped, it has no session left to
+/// deliver the command to.
+fn broadcast_to(
+    units: impl Iterator<Item = (UnitId, UnitRef<UnitContext>)>,
+    command: &Command,
+    expires_at: Option<Instant>,
+) -> HashMap<String, CommandAck> {
+    let mut acks = HashMap::new();
+    for (unit_id, unit_ref) in units {
+        let command_id = CommandId::generate();
+        let enqueued = match unit_ref
+            .view(|ctx| ctx.enqueue_command_until(command_id, command.clone(), expires_at))
+        {
+            Ok(enqueued) => enqueued,
+            Err(_) => {
+                debug!(drone_id = %unit_id, "Drone departed before broadcast reached it");
+                continue;
+            }
+        };
+
+        let (message, command_id) = match enqueued {
+            Ok(command_id) => (String::new(), command_id),
+            Err(e) => {
+                warn!(drone_id = %unit_id, error = %e, "Rejecting broadcast command");
+                (e.to_string(), command_id)
+            }
+        };
+        acks.insert(
+            unit_id.to_string(),
+            CommandAck {
+                accepted: message.is_empty(),
+                message,
+                command_id: command_id.to_string(),
+            },
+        );
+    }
+    acks
+}
+
 impl DroneServiceImpl {
     fn process_position(&self, unit_id: &UnitId, pos: crate::drone_proto::DronePosition) {
-        let position = Position {
-            drone_id: pos.drone_id,
-            latitude: pos.latitude,
-            longitude: pos.longitude,
-            altitude_m: pos.altitude_m,
-            heading_deg: pos.heading_deg,
-            speed_mps: pos.speed_mps,
-            timestamp: pos.timestamp,
+        update_telemetry(&self.unit_map, unit_id, pos.into());
+    }
+
+    /// Create the context of `unit_id` unless it is still known from an earlier session.
+    fn ensure_unit(&self, unit_id: &UnitId) -> Result<(), Status> {
+        if self.unit_map.get_unit(unit_id).is_err() {
+            let context = UnitContext::new().with_clock(Arc::clone(&self.clock));
+            self.unit_map
+                .insert_unit(unit_id.clone(), context)
+                .map_err(|e| Status::internal(e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Wait for the first message of a session, which identifies the drone.
+async fn first_message<M>(inbound: &mut Streaming<M>) -> Result<M, Status> {
+    inbound
+        .next()
+        .await
+        .ok_or_else(|| Status::invalid_argument("Empty stream"))?
+        .map_err(|e| Status::internal(e.to_string()))
+}
+
+fn parse_unit_id(drone_id: &str) -> Result<UnitId, Status> {
+    UnitId::try_new(drone_id).map_err(|e| Status::invalid_argument(e.to_string()))
+}
+
+/// Apply a position report to the unit and notify anyone watching the unit map.
+///
+/// Returns whether the report was applied, an implausible position is logged and skipped.
+fn update_telemetry(unit_map: &UnitMap<UnitContext>, unit_id: &UnitId, position: Position) -> bool {
+    let Ok(unit_ref) = unit_map.get_unit(unit_id) else {
+        return false;
+    };
+
+    match unit_ref.view(|ctx| ctx.update_telemetry(position)) {
+        Ok(Ok(())) => {
+            unit_map.telemetry_updated(unit_id);
+            true
+        }
+        Ok(Err(e)) => {
+            warn!(drone_id = %unit_id, error = %e, "Skipping invalid position report");
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+/// Apply a position report received at `now`, unless it exceeds the unit's rate limit.
+///
+/// Returns whether the report was applied.
+fn update_telemetry_limited(
+    unit_map: &UnitMap<UnitContext>,
+    limiter: &TelemetryLimiter,
+    unit_id: &UnitId,
+    position: Position,
+    now: Instant,
+) -> bool {
+    if !limiter.allow_at(unit_id, now) {
+        debug!(drone_id = %unit_id, "Dropping position report over the rate limit");
+        return false;
+    }
+
+    update_telemetry(unit_map, unit_id, position)
+}
+
+fn record_ack(unit_map: &UnitMap<UnitContext>, unit_id: &UnitId, ack: CommandAck) {
+    let Ok(command_id) = ack.command_id.parse::<Uuid>().map(CommandId::from) else {
+        warn!(drone_id = %unit_id, command_id = %ack.command_id, "Ignoring ack with invalid command_id");
+        return;
+    };
+
+    debug!(drone_id = %unit_id, command_id = %command_id, accepted = ack.accepted, "Command acknowledged");
+
+    let receipt = CommandReceipt {
+        accepted: ack.accepted,
+        message: ack.message,
+    };
+    if let Ok(unit_ref) = unit_map.get_unit(unit_id) {
+        let _ = unit_ref.view(|ctx| ctx.record_ack(command_id, receipt));
+    }
+}
+
+fn command_from_proto(command: &DroneCommand) -> Result<Command, Status> {
+    let command_type = command
+        .try_command_type()
+        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+    match command_type {
+        CommandType::Unspecified => Err(Status::invalid_argument("command type is required")),
+        CommandType::Arm => Ok(Command::Arm),
+        CommandType::Takeoff => Ok(Command::Takeoff {
+            altitude_m: command.altitude_m,
+        }),
+        CommandType::Goto => Ok(Command::Goto {
+            latitude: command.latitude,
+            longitude: command.longitude,
+            altitude_m: command.altitude_m,
+        }),
+        CommandType::ReturnHome => Ok(Command::ReturnHome),
+        CommandType::Land => Ok(Command::Land),
+    }
+}
+
+/// The status reporting why a command could not be queued for `unit_id`.
+///
+/// A full queue is expected to clear as the drone catches up, so is retryable. A command the
+/// drone's flight state rejects needs the state to change first.
+fn enqueue_status(unit_id: &UnitId, error: EnqueueError) -> Status {
+    match error {
+        EnqueueError::QueueFull(e) => Status::resource_exhausted(format!(
+            "{unit_id} is not draining commands fast enough, {e}"
+        )),
+        EnqueueError::Rejected(e) => Status::failed_precondition(format!("{unit_id} {e}")),
+    }
+}
+
+/// When a command received at `now` should be discarded, if its TTL is set.
+fn expiry_from_proto(command: &DroneCommand, now: Instant) -> Option<Instant> {
+    (command.ttl_ms > 0).then(|| now + Duration::from_millis(u64::from(command.ttl_ms)))
+}
+
+fn command_to_proto(drone_id: &str, queued: QueuedCommand) -> DroneCommand {
+    let mut proto = match queued.command {
+        Command::Arm => DroneCommand::arm(drone_id),
+        Command::Takeoff { altitude_m } => DroneCommand::takeoff(drone_id, altitude_m),
+        Command::Goto {
+            latitude,
+            longitude,
+            altitude_m,
+        } => DroneCommand::goto(drone_id, latitude, longitude, altitude_m),
+        Command::ReturnHome => DroneCommand::return_home(drone_id),
+        Command::Land => DroneCommand::land(drone_id),
+    };
+    proto.command_id = queued.id.to_string();
+    proto
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::drone_proto::DroneHello;
+
+    /// Get the drone airborne so it accepts any command.
+    fn airborne(context: UnitContext) -> UnitContext {
+        for command in [Command::Arm, Command::Takeoff { altitude_m: 10.0 }] {
+            context
+                .enqueue_command(CommandId::generate(), command)
+                .unwrap();
+            context.poll_command().unwrap();
+        }
+        context
+    }
+
+    fn service_with_units(unit_ids: &[&str]) -> DroneServiceImpl {
+        let unit_map = Arc::new(UnitMap::new());
+        for unit_id in unit_ids {
+            unit_map
+                .insert_unit(UnitId::from(*unit_id), airborne(UnitContext::new()))
+                .unwrap();
+        }
+        DroneServiceImpl::new(unit_map, Arc::new(DroneSessionMap::new()))
+    }
+
+    fn service_with_unit(unit_id: &UnitId, context: UnitContext) -> DroneServiceImpl {
+        let unit_map = Arc::new(UnitMap::new());
+        unit_map
+            .insert_unit(unit_id.clone(), airborne(context))
+            .unwrap();
+        DroneServiceImpl::new(unit_map, Arc::new(DroneSessionMap::new()))
+    }
+
+    #[tokio::test]
+    async fn test_send_command_full_queue_is_resource_exhausted() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new().with_command_capacity(1));
+
+        let ack = service
+            .send_command(Request::new(DroneCommand::land("drone-1")))
+            .await
+            .unwrap();
+        assert!(ack.into_inner().accepted);
+
+        let status = service
+            .send_command(Request::new(DroneCommand::land("drone-1")))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(status.message().contains("command queue is full"));
+    }
+
+    #[tokio::test]
+    async fn test_send_command_invalid_for_state_is_failed_precondition() {
+        let unit_id = UnitId::from("drone-1");
+        let unit_map = Arc::new(UnitMap::new());
+        unit_map
+            .insert_unit(unit_id.clone(), UnitContext::new())
+            .unwrap();
+        let service = DroneServiceImpl::new(unit_map, Arc::new(DroneSessionMap::new()));
+
+        // A drone on the ground can't land
+        let status = service
+            .send_command(Request::new(DroneCommand::land("drone-1")))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        assert_eq!(status.message(), "drone-1 cannot accept Land while Idle");
+    }
+
+    #[tokio::test]
+    async fn test_send_command_propagates_command_id() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new());
+
+        let command_id = Uuid::new_v4().to_string();
+        let mut command = DroneCommand::land("drone-1");
+        command.command_id = command_id.clone();
+
+        let ack = service
+            .send_command(Request::new(command))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(ack.command_id, command_id);
+
+        let queued = service
+            .unit_map
+            .get_unit(&unit_id)
+            .unwrap()
+            .view(|ctx| ctx.poll_command())
+            .unwrap()
+            .unwrap();
+        let emitted = command_to_proto("drone-1", queued);
+        assert_eq!(emitted.command_id, command_id);
+    }
+
+    #[tokio::test]
+    async fn test_send_command_acks_coalesced_command_under_pending_id() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new().with_command_dedup(true));
+
+        let mut acks = Vec::new();
+        for _ in 0..2 {
+            let ack = service
+                .send_command(Request::new(DroneCommand::goto(
+                    "drone-1", 37.0, -122.0, 50.0,
+                )))
+                .await
+                .unwrap()
+                .into_inner();
+            assert!(ack.accepted);
+            acks.push(ack.command_id);
+        }
+        assert_eq!(acks[0], acks[1]);
+
+        let queued = service
+            .unit_map
+            .get_unit(&unit_id)
+            .unwrap()
+            .view(|ctx| ctx.poll_command())
+            .unwrap()
+            .unwrap();
+        assert_eq!(queued.id.to_string(), acks[0]);
+    }
+
+    #[tokio::test]
+    async fn test_send_command_generates_command_id() {
+        let service = service_with_unit(&UnitId::from("drone-1"), UnitContext::new());
+
+        let ack = service
+            .send_command(Request::new(DroneCommand::land("drone-1")))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(ack.command_id.parse::<Uuid>().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_send_command_ttl_expires_queued_command() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new());
+
+        let mut command = DroneCommand::land("drone-1");
+        command.ttl_ms = 100;
+        let sent_at = Instant::now();
+        service.send_command(Request::new(command)).await.unwrap();
+
+        let unit_ref = service.unit_map.get_unit(&unit_id).unwrap();
+        let polled = unit_ref
+            .view(|ctx| ctx.poll_command_at(sent_at + Duration::from_secs(1)))
+            .unwrap();
+        assert!(polled.is_none());
+        assert_eq!(unit_ref.view(|ctx| ctx.expired_commands()).unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_command_ttl_follows_service_clock() {
+        let unit_id = UnitId::from("drone-1");
+        let clock = TestClock::new();
+        let unit_map = Arc::new(UnitMap::new().with_clock(Arc::new(clock.clone())));
+        let context = UnitContext::new().with_clock(Arc::new(clock.clone()));
+        unit_map
+            .insert_unit(unit_id.clone(), airborne(context))
+            .unwrap();
+        let service = DroneServiceImpl::new(unit_map, Arc::new(DroneSessionMap::new()));
+
+        let mut command = DroneCommand::land("drone-1");
+        command.ttl_ms = 100;
+        service.send_command(Request::new(command)).await.unwrap();
+
+        clock.advance(Duration::from_secs(1));
+        let unit_ref = service.unit_map.get_unit(&unit_id).unwrap();
+        assert!(unit_ref.view(|ctx| ctx.poll_command()).unwrap().is_none());
+        assert_eq!(unit_ref.view(|ctx| ctx.expired_commands()).unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_command_unknown_drone_is_not_found() {
+        let service = service_with_unit(&UnitId::from("drone-1"), UnitContext::new());
+
+        let status = service
+            .send_command(Request::new(DroneCommand::land("drone-2")))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_get_command_ack_hands_out_the_drone_ack_once() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new());
+        let command_id = CommandId::generate();
+        let query = || {
+            Request::new(CommandAckQuery {
+                drone_id: "drone-1".to_string(),
+                command_id: command_id.to_string(),
+            })
         };
 
-        if let Ok(unit_ref) = self.unit_map.get_unit(unit_id) {
-            let _ = unit_ref.view(|ctx| ctx.update_position(position));
+        let status = service.get_command_ack(query()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        record_ack(
+            &service.unit_map,
+            &unit_id,
+            CommandAck {
+                accepted: true,
+                message: String::new(),
+                command_id: command_id.to_string(),
+            },
+        );
+        let ack = service.get_command_ack(query()).await.unwrap().into_inner();
+        assert!(ack.accepted);
+        assert_eq!(ack.command_id, command_id.to_string());
+
+        let status = service.get_command_ack(query()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[test]
+    fn test_telemetry_rate_limit_drops_excess_updates() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new())
+            .with_telemetry_rate_limit(TelemetryRateLimit::new(10.0, 10));
+
+        let now = Instant::now();
+        let applied = (0..100)
+            .filter(|i| {
+                let position = Position {
+                    drone_id: "drone-1".to_string(),
+                    latitude: 37.7749,
+                    longitude: -122.4194,
+                    altitude_m: 100.0,
+                    heading_deg: 0.0,
+                    speed_mps: 0.0,
+                    timestamp: *i,
+                };
+                update_telemetry_limited(
+                    &service.unit_map,
+                    &service.telemetry_limiter,
+                    &unit_id,
+                    position,
+                    now,
+                )
+            })
+            .count();
+        assert_eq!(applied, 10);
+
+        let recorded = service
+            .unit_map
+            .get_unit(&unit_id)
+            .unwrap()
+            .view(|ctx| ctx.recent_positions())
+            .unwrap();
+        let timestamps: Vec<_> = recorded.iter().map(|pos| pos.timestamp).collect();
+        assert_eq!(timestamps, (0..10).collect::<Vec<_>>());
+        assert_eq!(service.dropped_telemetry(&unit_id), 90);
+        assert_eq!(service.total_dropped_telemetry(), 90);
+
+        // A second later the bucket has refilled
+        let position = recorded[0].clone();
+        assert!(update_telemetry_limited(
+            &service.unit_map,
+            &service.telemetry_limiter,
+            &unit_id,
+            position,
+            now + Duration::from_secs(1),
+        ));
+    }
+
+    #[test]
+    fn test_invalid_position_is_skipped() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new());
+
+        service.process_position(
+            &unit_id,
+            DronePosition {
+                drone_id: "drone-1".to_string(),
+                latitude: 999.0,
+                ..Default::default()
+            },
+        );
+
+        let (last, invalid) = service
+            .unit_map
+            .get_unit(&unit_id)
+            .unwrap()
+            .view(|ctx| (ctx.last_position(), ctx.invalid_telemetry()))
+            .unwrap();
+        assert_eq!(last, None);
+        assert_eq!(invalid, 1);
+    }
+
+    #[test]
+    fn test_session_identity_from_hello() {
+        let hello = DroneMessage {
+            payload: Some(Payload::Hello(DroneHello {
+                drone_id: "drone-1".to_string(),
+                capabilities: vec!["goto".to_string()],
+            })),
+        };
+
+        let identity = SessionIdentity::from_first_message(hello).unwrap();
+        assert_eq!(identity.drone_id, "drone-1");
+        assert_eq!(identity.capabilities, ["goto"]);
+        assert_eq!(identity.first_pos, None);
+    }
+
+    #[test]
+    fn test_session_identity_from_position() {
+        let position = DronePosition {
+            drone_id: "drone-1".to_string(),
+            ..Default::default()
+        };
+        let message = DroneMessage {
+            payload: Some(Payload::Position(position.clone())),
+        };
+
+        let identity = SessionIdentity::from_first_message(message).unwrap();
+        assert_eq!(identity.drone_id, "drone-1");
+        assert_eq!(identity.first_pos, Some(position));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_command_stream_delivers_without_polling_delay() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new());
+        let session_id = service.session_map.create_session(&unit_id).unwrap();
+
+        let mut commands = Box::pin(command_stream(
+            Arc::clone(&service.unit_map),
+            Arc::clone(&service.session_map),
+            unit_id.clone(),
+            session_id,
+            "drone-1".to_string(),
+            oneshot::channel().0,
+            Arc::default(),
+        ));
+
+        // Let the stream park waiting for a command before queueing one
+        let parked = tokio::time::timeout(Duration::from_millis(10), commands.next()).await;
+        assert!(parked.is_err());
+
+        let started = tokio::time::Instant::now();
+        service
+            .unit_map
+            .get_unit(&unit_id)
+            .unwrap()
+            .view(|ctx| ctx.enqueue_command(CommandId::generate(), Command::Land))
+            .unwrap()
+            .unwrap();
+
+        let command = commands.next().await.unwrap().unwrap();
+        assert_eq!(command.command_type(), CommandType::Land);
+        // Time only advances while the runtime is idle, so any sleep before delivery shows here
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_command_stream_ends_with_session() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new());
+        let session_id = service.session_map.create_session(&unit_id).unwrap();
+
+        let mut commands = Box::pin(command_stream(
+            Arc::clone(&service.unit_map),
+            Arc::clone(&service.session_map),
+            unit_id.clone(),
+            session_id,
+            "drone-1".to_string(),
+            oneshot::channel().0,
+            Arc::default(),
+        ));
+        let parked = tokio::time::timeout(Duration::from_millis(10), commands.next()).await;
+        assert!(parked.is_err());
+
+        service.session_map.remove_session(&unit_id).unwrap();
+        assert!(commands.next().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_session_takeover_keeps_queued_commands() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new())
+            .with_session_takeover(Duration::from_secs(2));
+        let stale = service.create_drone_session(&unit_id).await.unwrap();
+        let mut stale_commands = Box::pin(command_stream(
+            Arc::clone(&service.unit_map),
+            Arc::clone(&service.session_map),
+            unit_id.clone(),
+            stale,
+            "drone-1".to_string(),
+            oneshot::channel().0,
+            Arc::default(),
+        ));
+        let parked = tokio::time::timeout(Duration::from_millis(10), stale_commands.next()).await;
+        assert!(parked.is_err());
+
+        service
+            .unit_map
+            .get_unit(&unit_id)
+            .unwrap()
+            .view(|ctx| ctx.enqueue_command(CommandId::generate(), Command::Land))
+            .unwrap()
+            .unwrap();
+
+        let started = tokio::time::Instant::now();
+        let session_id = service.create_drone_session(&unit_id).await.unwrap();
+        // The stale session did not close by itself, so it was evicted after the grace period
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+        assert!(stale_commands.next().await.is_none());
+
+        let mut commands = Box::pin(command_stream(
+            Arc::clone(&service.unit_map),
+            Arc::clone(&service.session_map),
+            unit_id.clone(),
+            session_id,
+            "drone-1".to_string(),
+            oneshot::channel().0,
+            Arc::default(),
+        ));
+        let command = commands.next().await.unwrap().unwrap();
+        assert_eq!(command.command_type(), CommandType::Land);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_session_takeover_refused_while_previous_session_active() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new())
+            .with_session_takeover(Duration::from_secs(2));
+        let previous = service.create_drone_session(&unit_id).await.unwrap();
+
+        let session_map = Arc::clone(&service.session_map);
+        let (active_unit, active_session) = (unit_id.clone(), previous.clone());
+        let reporting = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            session_map.session_active(&active_unit, &active_session);
+        });
+
+        let status = service.create_drone_session(&unit_id).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::AlreadyExists);
+        assert_eq!(service.session_map.get_session_id(&unit_id), Some(previous));
+        reporting.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_telemetry_error_ends_command_stream_without_flushing() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new());
+        let session_id = service.create_drone_session(&unit_id).await.unwrap();
+
+        let (drained, commands_drained) = oneshot::channel();
+        let connection_lost = Arc::new(AtomicBool::new(false));
+        let mut commands = Box::pin(command_stream(
+            Arc::clone(&service.unit_map),
+            Arc::clone(&service.session_map),
+            unit_id.clone(),
+            session_id.clone(),
+            "drone-1".to_string(),
+            drained,
+            Arc::clone(&connection_lost),
+        ));
+        let parked = tokio::time::timeout(Duration::from_millis(10), commands.next()).await;
+        assert!(parked.is_err());
+
+        // Queued while the stream is not being polled, as if the connection dropped before it
+        // was sent
+        service
+            .unit_map
+            .get_unit(&unit_id)
+            .unwrap()
+            .view(|ctx| ctx.enqueue_command(CommandId::generate(), Command::Land))
+            .unwrap()
+            .unwrap();
+        let telemetry = TelemetrySession {
+            unit_map: Arc::clone(&service.unit_map),
+            session_map: Arc::clone(&service.session_map),
+            limiter: Arc::clone(&service.telemetry_limiter),
+            unit_id: unit_id.clone(),
+            session_id,
+            drone_id: "drone-1".to_string(),
+            commands_drained,
+            connection_lost,
+            clock: Arc::clone(&service.clock),
+        };
+        let inbound = futures::stream::iter([Err(Status::unavailable("connection reset"))]);
+        // Cleanup does not wait on the command stream once the connection is lost
+        tokio::time::timeout(Duration::from_millis(100), telemetry.run(inbound))
+            .await
+            .expect("cleanup should not wait for the command stream");
+        assert!(!service.session_map.has_active_session(&unit_id));
+        assert!(service.unit_map.get_unit(&unit_id).is_err());
+
+        let ended = tokio::time::timeout(Duration::from_millis(100), commands.next()).await;
+        assert!(ended.expect("command stream should end promptly").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_keeps_unit_of_reconnected_drone() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new());
+        let session_id = service.create_drone_session(&unit_id).await.unwrap();
+
+        let (drained, commands_drained) = oneshot::channel::<()>();
+        let telemetry = TelemetrySession {
+            unit_map: Arc::clone(&service.unit_map),
+            session_map: Arc::clone(&service.session_map),
+            limiter: Arc::clone(&service.telemetry_limiter),
+            unit_id: unit_id.clone(),
+            session_id,
+            drone_id: "drone-1".to_string(),
+            commands_drained,
+            connection_lost: Arc::default(),
+            clock: Arc::clone(&service.clock),
+        };
+        let telemetry = tokio::spawn(telemetry.run(futures::stream::empty()));
+
+        // The drone reconnects while the old session's cleanup waits for its command stream
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        service.create_drone_session(&unit_id).await.unwrap();
+        drop(drained);
+        telemetry.await.unwrap();
+
+        assert!(service.session_map.has_active_session(&unit_id));
+        assert!(service.unit_map.get_unit(&unit_id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_session_without_takeover_is_already_exists() {
+        let unit_id = UnitId::from("drone-1");
+        let service = service_with_unit(&unit_id, UnitContext::new());
+        service.create_drone_session(&unit_id).await.unwrap();
+
+        let status = service.create_drone_session(&unit_id).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::AlreadyExists);
+    }
+
+    #[tokio::test]
+    async fn test_session_position_rejects_other_drone_id() {
+        let service = service_with_units(&["drone-1", "drone-2"]);
+        let unit_id = UnitId::from("drone-1");
+        let session_id = service.create_drone_session(&unit_id).await.unwrap();
+        let spoofed = DronePosition {
+            drone_id: "drone-2".to_string(),
+            altitude_m: 20.0,
+            ..Default::default()
+        };
+        let own = DronePosition {
+            drone_id: "drone-1".to_string(),
+            altitude_m: 10.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            session_position(&unit_id, spoofed.clone()),
+            Err(RejectedPosition::OtherDrone("drone-2".to_string()))
+        );
+
+        let (_drained, commands_drained) = oneshot::channel();
+        let telemetry = TelemetrySession {
+            unit_map: Arc::clone(&service.unit_map),
+            session_map: Arc::clone(&service.session_map),
+            limiter: Arc::clone(&service.telemetry_limiter),
+            unit_id: unit_id.clone(),
+            session_id,
+            drone_id: "drone-1".to_string(),
+            commands_drained,
+            connection_lost: Arc::default(),
+            clock: Arc::clone(&service.clock),
+        };
+        let inbound = futures::stream::iter([spoofed, own].map(|pos| {
+            Ok(DroneMessage {
+                payload: Some(Payload::Position(pos)),
+            })
+        }))
+        .chain(futures::stream::pending());
+        let telemetry = tokio::spawn(telemetry.run(inbound));
+
+        let recent = |unit_id: &str| {
+            service
+                .unit_map
+                .get_unit(&UnitId::from(unit_id))
+                .unwrap()
+                .view(|ctx| ctx.recent_positions())
+                .unwrap()
+        };
+        // The session's own report follows the spoofed one, so once it lands both were handled
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while recent("drone-1").is_empty() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("the drone's own position was not applied");
+        telemetry.abort();
+
+        let own_positions = recent("drone-1");
+        assert_eq!(own_positions.len(), 1);
+        assert_eq!(own_positions[0].altitude_m, 10.0);
+        assert!(recent("drone-2").is_empty());
+    }
+
+    #[test]
+    fn test_session_position_accepts_own_drone_id() {
+        let own = DronePosition {
+            drone_id: "drone-1".to_string(),
+            altitude_m: 10.0,
+            ..Default::default()
+        };
+        let position = session_position(&UnitId::from("drone-1"), own).unwrap();
+        assert_eq!(position.altitude_m, 10.0);
+
+        let missing = DronePosition::default();
+        assert_eq!(
+            session_position(&UnitId::from("drone-1"), missing),
+            Err(RejectedPosition::MissingDroneId)
+        );
+    }
+
+    #[test]
+    fn test_session_identity_rejects_other_first_messages() {
+        for payload in [Some(Payload::Ack(CommandAck::default())), None] {
+            let status = SessionIdentity::from_first_message(DroneMessage { payload }).unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
         }
     }
+
+    #[tokio::test]
+    async fn test_broadcast_command_reaches_every_drone() {
+        let service = service_with_units(&["drone-1", "drone-2"]);
+
+        let acks = service
+            .broadcast_command(Request::new(DroneCommand::return_home("")))
+            .await
+            .unwrap()
+            .into_inner()
+            .acks;
+        assert_eq!(acks.len(), 2);
+        assert_ne!(acks["drone-1"].command_id, acks["drone-2"].command_id);
+
+        for (drone_id, ack) in &acks {
+            assert!(ack.accepted);
+            let queued = service
+                .unit_map
+                .get_unit(&UnitId::from(drone_id.as_str()))
+                .unwrap()
+                .view(|ctx| ctx.poll_command())
+                .unwrap()
+                .unwrap();
+            let emitted = command_to_proto(drone_id, queued);
+            assert_eq!(emitted.drone_id, *drone_id);
+            assert_eq!(emitted.command_id, ack.command_id);
+            assert_eq!(emitted.command_type(), CommandType::ReturnHome);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_command_skips_departed_drone() {
+        let service = service_with_units(&["drone-1", "drone-2"]);
+
+        let units = service.unit_map.iter();
+        service
+            .unit_map
+            .remove_unit(&UnitId::from("drone-2"))
+            .unwrap();
+
+        let acks = broadcast_to(units, &Command::ReturnHome, None);
+        assert_eq!(acks.keys().collect::<Vec<_>>(), ["drone-1"]);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_command_reports_rejections() {
+        let service = service_with_units(&["drone-1"]);
+        let grounded = UnitId::from("drone-2");
+        service
+            .unit_map
+            .insert_unit(grounded, UnitContext::new())
+            .unwrap();
+
+        let acks = service
+            .broadcast_command(Request::new(DroneCommand::land("")))
+            .await
+            .unwrap()
+            .into_inner()
+            .acks;
+        assert!(acks["drone-1"].accepted);
+        assert!(!acks["drone-2"].accepted);
+        assert!(!acks["drone-2"].message.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_command_rejects_command_id() {
+        let service = service_with_units(&["drone-1"]);
+        let mut command = DroneCommand::return_home("");
+        command.command_id = Uuid::new_v4().to_string();
+
+        let status = service
+            .broadcast_command(Request::new(command))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_unknown_command_type_is_invalid_argument() {
+        let command = DroneCommand {
+            command_type: 42,
+            ..DroneCommand::land("drone-1")
+        };
+        let status = command_from_proto(&command).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.message().contains("42"));
+    }
 }
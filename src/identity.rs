@@ -0,0 +1,45 @@
+//! Client ids that survive restarts.
+
+use uuid::Uuid;
+
+/// The namespace every [`stable_client_id`] is derived in. Changing it changes every id.
+const CLIENT_ID_NAMESPACE: Uuid = Uuid::from_u128(0x39f5f04d_570e_43b6_beca_45b34404c514);
+
+/// A client id derived from `seed`, such as a hostname or a configured name.
+///
+/// The id is a UUIDv5 of `seed`, in lowercase hyphenated form. The same seed gives the same id
+/// on every run, on every machine and across releases of this crate, so a restarted client
+/// keeps its identity and the server can tell it is reconnecting rather than a new client.
+/// Different seeds give different ids. Two clients sharing a seed share an id too, and will
+/// fight over their sessions.
+///
+/// The seed can be recovered from the id only by guessing it, but the id is not a secret and
+/// must not be used for authentication.
+pub fn stable_client_id(seed: &str) -> String {
+    Uuid::new_v5(&CLIENT_ID_NAMESPACE, seed.as_bytes()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_gives_same_id() {
+        assert_eq!(
+            stable_client_id("drone-host-1"),
+            stable_client_id("drone-host-1")
+        );
+        assert_ne!(
+            stable_client_id("drone-host-1"),
+            stable_client_id("drone-host-2")
+        );
+    }
+
+    #[test]
+    fn test_id_is_stable_across_releases() {
+        assert_eq!(
+            stable_client_id("drone-host-1"),
+            "a7eb8562-9527-5f55-952f-c741fbabd756"
+        );
+    }
+}
@@ -1,38 +1,253 @@
+pub mod clock;
+pub mod command;
+pub mod connect;
 pub mod drone;
 pub mod grpc;
+mod identity;
 pub mod state_machine;
+pub mod telemetry;
+#[cfg(test)]
+mod test_util;
+pub mod tls;
+mod track;
 pub mod unit;
 pub mod unit_context;
 pub mod unit_map;
 
 use anyhow::Result;
-use moq_lite::{Client, Origin, Session};
+use moq_lite::{Client, Session};
+use tracing::warn;
 use url::Url;
 use web_transport_quinn::ClientBuilder;
 
+use crate::connect::error::{AttemptError, ConnectError};
+use crate::connect::{ConnectOptions, RelayConnection, SessionOrigins, authorize_url};
+pub use crate::connect::{connection_stats, wait_ready};
+pub use crate::identity::stable_client_id;
+use crate::tls::TlsConfig;
+pub use crate::track::{
+    GroupGapTracker, TrackMessage, decoded_track_stream, decoded_track_stream_with_gaps,
+};
+
 pub mod drone_proto {
     include!(concat!(env!("OUT_DIR"), "/drone.rs"));
 }
 
 pub const PRIMARY_TRACK: &str = "primary";
 
+/// The track a drone publishes its command acknowledgements on.
+pub const ACK_TRACK: &str = "acks";
+
+/// The broadcast a drone publishes [`ACK_TRACK`] under.
+pub fn ack_broadcast_path(drone_id: &str) -> String {
+    format!("ack/{drone_id}")
+}
+
+/// The track a drone publishes its position reports on.
+pub const POSITION_TRACK: &str = "position";
+
+/// The track a drone publishes its battery state on.
+pub const BATTERY_TRACK: &str = "battery";
+
+/// The track a drone publishes its command progress on.
+pub const STATUS_TRACK: &str = "status";
+
+/// The broadcast a drone publishes [`POSITION_TRACK`], [`BATTERY_TRACK`] and [`STATUS_TRACK`]
+/// under.
+pub fn drone_broadcast_path(drone_id: &str) -> String {
+    format!("telemetry/{drone_id}")
+}
+
+/// Resolves once the process is asked to stop, by Ctrl-C or, on Unix, `SIGTERM`.
+///
+/// A signal that cannot be listened for is logged and never resolves, so the other can still
+/// stop the process.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!(error = %e, "Cannot listen for Ctrl-C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                warn!(error = %e, "Cannot listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+}
+
 /// Connect to the relay as a publisher + subscriber (bidirectional).
 /// Returns the session handle and the origin producer/consumer pair.
+///
+/// The relay's certificate is NOT verified, use [`connect_bidirectional_with_tls`] for anything
+/// other than local development.
 pub async fn connect_bidirectional(
     relay_url: &str,
 ) -> Result<(Session, moq_lite::OriginProducer, moq_lite::OriginConsumer)> {
-    let pub_origin = Origin::produce();
-    let sub_origin = Origin::produce();
+    warn!(relay = %relay_url, "Connecting without certificate verification, this is insecure");
 
     let wt_client = ClientBuilder::new()
         .dangerous()
         .with_no_certificate_verification()?;
-    let wt_session = wt_client.connect(relay_url.parse::<Url>()?).await?;
+
+    connect_with_client(&wt_client, relay_url).await
+}
+
+/// Connect to the relay as a publisher + subscriber (bidirectional), verifying the relay's
+/// certificate according to `tls`.
+/// Returns the session handle and the origin producer/consumer pair.
+pub async fn connect_bidirectional_with_tls(
+    relay_url: &str,
+    tls: TlsConfig,
+) -> Result<(Session, moq_lite::OriginProducer, moq_lite::OriginConsumer)> {
+    let wt_client = tls.build_client()?;
+    connect_with_client(&wt_client, relay_url).await
+}
+
+/// Connect to the relay as a publisher + subscriber (bidirectional), bounding each attempt by a
+/// timeout and retrying transient failures with backoff.
+/// Returns the session handle and the origin producer/consumer pair.
+pub async fn connect_bidirectional_opts(
+    relay_url: &str,
+    options: ConnectOptions,
+) -> Result<(Session, moq_lite::OriginProducer, moq_lite::OriginConsumer), ConnectError> {
+    let connection = connect_bidirectional_with_stats(relay_url, options).await?;
+    Ok((connection.session, connection.producer, connection.consumer))
+}
+
+/// Connect to the relay like [`connect_bidirectional_opts`], also returning the underlying
+/// WebTransport session so its metrics can be read with [`connection_stats`].
+/// Returns the session handle, the transport and the origin producer/consumer pair together.
+pub async fn connect_bidirectional_with_stats(
+    relay_url: &str,
+    options: ConnectOptions,
+) -> Result<RelayConnection, ConnectError> {
+    let mut url = relay_url.parse::<Url>()?;
+    if let Some(token) = &options.auth_token {
+        authorize_url(&mut url, token);
+    }
+
+    let wt_client = match &options.tls {
+        Some(tls) => tls.build_client()?,
+        None => {
+            warn!(relay = %relay_url, "Connecting without certificate verification, this is insecure");
+            ClientBuilder::new()
+                .dangerous()
+                .with_no_certificate_verification()
+                .map_err(ConnectError::Client)?
+        }
+    };
+
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+
+        let origins = SessionOrigins::new(&options)?;
+        let attempt = tokio::time::timeout(
+            options.connect_timeout,
+            establish(&wt_client, &url, origins),
+        );
+        let error = match attempt.await {
+            Ok(Ok(connection)) => return Ok(connection),
+            Ok(Err(e)) => e,
+            Err(_) => AttemptError::Timeout(options.connect_timeout),
+        };
+
+        if !error.is_retryable() {
+            return Err(ConnectError::Fatal {
+                attempts,
+                source: error,
+            });
+        }
+
+        let Some(backoff) = options.retry.backoff(attempts - 1) else {
+            return Err(ConnectError::RetriesExhausted {
+                attempts,
+                source: error,
+            });
+        };
+
+        warn!(
+            relay = %relay_url,
+            attempt = attempts,
+            error = %error,
+            backoff = ?backoff,
+            "Relay connection failed, retrying"
+        );
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+/// Connect to the relay as a publisher + subscriber (bidirectional), presenting `token` to a
+/// relay that gates publish/subscribe rights.
+/// Returns the session handle and the origin producer/consumer pair.
+pub async fn connect_bidirectional_authed(
+    relay_url: &str,
+    token: &str,
+) -> Result<(Session, moq_lite::OriginProducer, moq_lite::OriginConsumer), ConnectError> {
+    connect_bidirectional_opts(relay_url, ConnectOptions::default().with_auth_token(token)).await
+}
+
+/// Connect to the relay as a publisher + subscriber (bidirectional), publishing only under
+/// `publish_root` and consuming only under `consume_root`.
+/// Returns the session handle and the origin producer/consumer pair, already rooted, so
+/// broadcast paths are relative to the roots.
+///
+/// A root that cannot be scoped to is reported before connecting. Use
+/// [`ConnectOptions::with_publish_root`] and [`ConnectOptions::with_consume_root`] to scope a
+/// connection with other options.
+pub async fn connect_bidirectional_scoped(
+    relay_url: &str,
+    publish_root: &str,
+    consume_root: &str,
+) -> Result<(Session, moq_lite::OriginProducer, moq_lite::OriginConsumer), ConnectError> {
+    let options = ConnectOptions::default()
+        .with_publish_root(publish_root)
+        .with_consume_root(consume_root);
+    connect_bidirectional_opts(relay_url, options).await
+}
+
+async fn connect_with_client(
+    wt_client: &web_transport_quinn::Client,
+    relay_url: &str,
+) -> Result<(Session, moq_lite::OriginProducer, moq_lite::OriginConsumer)> {
+    let origins = SessionOrigins::new(&ConnectOptions::default())?;
+    let connection = establish(wt_client, &relay_url.parse::<Url>()?, origins).await?;
+    Ok((connection.session, connection.producer, connection.consumer))
+}
+
+async fn establish(
+    wt_client: &web_transport_quinn::Client,
+    url: &Url,
+    origins: SessionOrigins,
+) -> Result<RelayConnection, AttemptError> {
+    let wt_session = wt_client.connect(url.clone()).await?;
 
     let client = Client::new()
-        .with_publish(pub_origin.consumer)
-        .with_consume(sub_origin.producer);
-    let session = client.connect(wt_session).await?;
+        .with_publish(origins.publish)
+        .with_consume(origins.consume);
+    let session = client.connect(wt_session.clone()).await?;
 
-    Ok((session, pub_origin.producer, sub_origin.consumer))
+    Ok(RelayConnection {
+        session,
+        transport: wt_session,
+        producer: origins.producer,
+        consumer: origins.consumer,
+    })
 }
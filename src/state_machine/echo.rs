@@ -1,4 +1,6 @@
 use super::StateMachine;
+use super::error::InvalidPosition;
+use crate::drone_proto::DronePosition;
 
 #[derive(Debug)]
 pub struct EchoMachine {
@@ -17,6 +19,64 @@ pub struct Position {
     pub timestamp: u64,
 }
 
+impl Position {
+    /// Check the position could be real: every field is finite, the latitude is within ±90
+    /// degrees and the longitude within ±180.
+    pub fn validate(&self) -> Result<(), InvalidPosition> {
+        for (field, value) in [
+            ("latitude", self.latitude),
+            ("longitude", self.longitude),
+            ("altitude_m", self.altitude_m),
+            ("heading_deg", self.heading_deg),
+            ("speed_mps", self.speed_mps),
+        ] {
+            if !value.is_finite() {
+                return Err(InvalidPosition::NotFinite { field });
+            }
+        }
+
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            return Err(InvalidPosition::Latitude {
+                latitude: self.latitude,
+            });
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(InvalidPosition::Longitude {
+                longitude: self.longitude,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl From<DronePosition> for Position {
+    fn from(pos: DronePosition) -> Self {
+        Self {
+            drone_id: pos.drone_id,
+            latitude: pos.latitude,
+            longitude: pos.longitude,
+            altitude_m: pos.altitude_m,
+            heading_deg: pos.heading_deg,
+            speed_mps: pos.speed_mps,
+            timestamp: pos.timestamp,
+        }
+    }
+}
+
+impl From<Position> for DronePosition {
+    fn from(pos: Position) -> Self {
+        Self {
+            drone_id: pos.drone_id,
+            latitude: pos.latitude,
+            longitude: pos.longitude,
+            altitude_m: pos.altitude_m,
+            heading_deg: pos.heading_deg,
+            speed_mps: pos.speed_mps,
+            timestamp: pos.timestamp,
+        }
+    }
+}
+
 impl EchoMachine {
     pub fn new() -> Self {
         Self {
@@ -68,3 +128,87 @@ impl StateMachine for EchoMachine {
         self.poll_position().map(EchoOutput::Position)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::position_at;
+
+    #[test]
+    fn test_validate_accepts_boundary_coordinates() {
+        for (latitude, longitude) in [(90.0, 180.0), (-90.0, -180.0), (0.0, 0.0)] {
+            assert_eq!(position_at(latitude, longitude, 100.0).validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_out_of_range_coordinates() {
+        assert_eq!(
+            position_at(999.0, 0.0, 100.0).validate(),
+            Err(InvalidPosition::Latitude { latitude: 999.0 })
+        );
+        assert_eq!(
+            position_at(-90.001, 0.0, 100.0).validate(),
+            Err(InvalidPosition::Latitude { latitude: -90.001 })
+        );
+        assert_eq!(
+            position_at(0.0, 180.5, 100.0).validate(),
+            Err(InvalidPosition::Longitude { longitude: 180.5 })
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_non_finite_values() {
+        assert_eq!(
+            position_at(f64::NAN, 0.0, 100.0).validate(),
+            Err(InvalidPosition::NotFinite { field: "latitude" })
+        );
+        assert_eq!(
+            position_at(0.0, f64::NEG_INFINITY, 100.0).validate(),
+            Err(InvalidPosition::NotFinite { field: "longitude" })
+        );
+
+        let mut pos = position_at(0.0, 0.0, 100.0);
+        pos.altitude_m = f64::INFINITY;
+        assert_eq!(
+            pos.validate(),
+            Err(InvalidPosition::NotFinite {
+                field: "altitude_m"
+            })
+        );
+        pos.altitude_m = 0.0;
+        pos.speed_mps = f64::NAN;
+        assert_eq!(
+            pos.validate(),
+            Err(InvalidPosition::NotFinite { field: "speed_mps" })
+        );
+    }
+
+    #[test]
+    fn test_position_round_trips_through_proto() {
+        let position = Position {
+            drone_id: "drone-1".to_string(),
+            latitude: 37.7749,
+            longitude: -122.4194,
+            altitude_m: 100.5,
+            heading_deg: 270.0,
+            speed_mps: 12.25,
+            timestamp: 1_700_000_000,
+        };
+
+        let proto = DronePosition::from(position.clone());
+        assert_eq!(
+            proto,
+            DronePosition {
+                drone_id: "drone-1".to_string(),
+                latitude: 37.7749,
+                longitude: -122.4194,
+                altitude_m: 100.5,
+                heading_deg: 270.0,
+                speed_mps: 12.25,
+                timestamp: 1_700_000_000,
+            }
+        );
+        assert_eq!(Position::from(proto), position);
+    }
+}
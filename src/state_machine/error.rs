@@ -0,0 +1,14 @@
+//! Error types for the state machine inputs.
+
+/// Indicates that a reported position is not physically plausible.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum InvalidPosition {
+    #[error("{field} is not a finite number")]
+    NotFinite { field: &'static str },
+
+    #[error("latitude {latitude} is outside ±90 degrees")]
+    Latitude { latitude: f64 },
+
+    #[error("longitude {longitude} is outside ±180 degrees")]
+    Longitude { longitude: f64 },
+}
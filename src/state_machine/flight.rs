@@ -0,0 +1,252 @@
+use super::StateMachine;
+use super::echo::Position;
+use crate::command::Command;
+use crate::command::error::CommandRejected;
+
+/// Altitude below which a drone is considered on the ground.
+const GROUND_ALTITUDE_M: f64 = 0.5;
+
+/// Horizontal distance, in degrees, within which a drone is considered at home.
+const HOME_RADIUS_DEG: f64 = 0.0001;
+
+/// The lifecycle state of a drone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlightState {
+    #[default]
+    Idle,
+    Armed,
+    Flying,
+    Returning,
+    Landing,
+    Landed,
+}
+
+impl FlightState {
+    /// The state entered by accepting `command`, or `None` if it is not allowed from here.
+    pub fn on_command(self, command: &Command) -> Option<FlightState> {
+        use FlightState::*;
+
+        match (self, command) {
+            (Idle | Landed, Command::Arm) => Some(Armed),
+            (Armed, Command::Takeoff { .. }) => Some(Flying),
+            (Flying | Returning, Command::Goto { .. }) => Some(Flying),
+            (Flying | Returning | Landing, Command::ReturnHome) => Some(Returning),
+            (Flying | Returning | Landing, Command::Land) => Some(Landing),
+            _ => None,
+        }
+    }
+}
+
+/// Tracks a drone's [`FlightState`] from the commands sent to it and the telemetry it reports.
+///
+/// Commands are validated with [`check_command`](FlightMachine::check_command) before being
+/// processed, the machine itself ignores any command that is not valid from its current state.
+#[derive(Debug, Default)]
+pub struct FlightMachine {
+    state: FlightState,
+    home: Option<Position>,
+    last_position: Option<Position>,
+    pending_transition: bool,
+}
+
+impl FlightMachine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn state(&self) -> FlightState {
+        self.state
+    }
+
+    /// The state `command` would transition to, or why it is rejected.
+    pub fn check_command(&self, command: &Command) -> Result<FlightState, CommandRejected> {
+        self.state
+            .on_command(command)
+            .ok_or_else(|| CommandRejected {
+                state: self.state,
+                command: command.clone(),
+            })
+    }
+
+    /// The state `command` would transition to once every command in `ahead` has been
+    /// delivered, or why it is rejected.
+    ///
+    /// A command in `ahead` that is not valid when its turn comes is skipped, as it will be when
+    /// it is delivered.
+    pub fn check_queued_command<'a>(
+        &self,
+        ahead: impl IntoIterator<Item = &'a Command>,
+        command: &Command,
+    ) -> Result<FlightState, CommandRejected> {
+        let state = ahead.into_iter().fold(self.state, |state, ahead| {
+            state.on_command(ahead).unwrap_or(state)
+        });
+        state.on_command(command).ok_or_else(|| CommandRejected {
+            state,
+            command: command.clone(),
+        })
+    }
+
+    fn process_command(&mut self, command: Command) {
+        let Some(next) = self.state.on_command(&command) else {
+            return;
+        };
+
+        if command == Command::Arm {
+            self.home = self.last_position.clone();
+        }
+        self.transition(next);
+    }
+
+    fn process_telemetry(&mut self, pos: Position) {
+        let on_ground = pos.altitude_m < GROUND_ALTITUDE_M;
+        let at_home = self.home.as_ref().is_none_or(|home| {
+            (home.latitude - pos.latitude).abs() < HOME_RADIUS_DEG
+                && (home.longitude - pos.longitude).abs() < HOME_RADIUS_DEG
+        });
+
+        match self.state {
+            FlightState::Landing if on_ground => self.transition(FlightState::Landed),
+            FlightState::Returning if on_ground && at_home => self.transition(FlightState::Landed),
+            _ => {}
+        }
+
+        self.last_position = Some(pos);
+    }
+
+    fn transition(&mut self, next: FlightState) {
+        if self.state != next {
+            self.state = next;
+            self.pending_transition = true;
+        }
+    }
+
+    fn poll_transition(&mut self) -> Option<FlightState> {
+        if self.pending_transition {
+            self.pending_transition = false;
+            Some(self.state)
+        } else {
+            None
+        }
+    }
+}
+
+pub enum FlightInput {
+    Command(Command),
+    Telemetry(Position),
+}
+
+pub enum FlightOutput {
+    /// The state the drone has transitioned into.
+    Transition(FlightState),
+}
+
+impl StateMachine for FlightMachine {
+    type Input = FlightInput;
+    type Output = FlightOutput;
+
+    fn process_input(&mut self, input: Self::Input) {
+        match input {
+            FlightInput::Command(command) => self.process_command(command),
+            FlightInput::Telemetry(pos) => self.process_telemetry(pos),
+        }
+    }
+
+    fn poll_output(&mut self) -> Option<Self::Output> {
+        self.poll_transition().map(FlightOutput::Transition)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::position_at;
+
+    #[test]
+    fn test_transition_table() {
+        use FlightState::*;
+
+        let goto = Command::Goto {
+            latitude: 37.0,
+            longitude: -122.0,
+            altitude_m: 50.0,
+        };
+        let takeoff = Command::Takeoff { altitude_m: 10.0 };
+
+        #[rustfmt::skip]
+        let table = [
+            // state      Arm           Takeoff       Goto          ReturnHome       Land
+            (Idle,      [Some(Armed), None,         None,         None,            None]),
+            (Armed,     [None,        Some(Flying), None,         None,            None]),
+            (Flying,    [None,        None,         Some(Flying), Some(Returning), Some(Landing)]),
+            (Returning, [None,        None,         Some(Flying), Some(Returning), Some(Landing)]),
+            (Landing,   [None,        None,         None,         Some(Returning), Some(Landing)]),
+            (Landed,    [Some(Armed), None,         None,         None,            None]),
+        ];
+
+        let commands = [
+            Command::Arm,
+            takeoff,
+            goto,
+            Command::ReturnHome,
+            Command::Land,
+        ];
+        for (state, expected) in table {
+            for (command, next) in commands.iter().zip(expected) {
+                assert_eq!(
+                    state.on_command(command),
+                    next,
+                    "{command:?} while {state:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejected_command_reports_state() {
+        let machine = FlightMachine::new();
+        let goto = Command::Goto {
+            latitude: 37.0,
+            longitude: -122.0,
+            altitude_m: 50.0,
+        };
+
+        let rejected = machine.check_command(&goto).unwrap_err();
+        assert_eq!(rejected.state, FlightState::Idle);
+        assert_eq!(rejected.command, goto);
+    }
+
+    #[test]
+    fn test_landing_touchdown_is_landed() {
+        let mut machine = FlightMachine::new();
+        machine.process_input(FlightInput::Command(Command::Arm));
+        machine.process_input(FlightInput::Command(Command::Takeoff { altitude_m: 10.0 }));
+        machine.process_input(FlightInput::Command(Command::Land));
+        while machine.poll_output().is_some() {}
+
+        machine.process_input(FlightInput::Telemetry(position_at(37.0, -122.0, 5.0)));
+        assert_eq!(machine.state(), FlightState::Landing);
+
+        machine.process_input(FlightInput::Telemetry(position_at(37.0, -122.0, 0.1)));
+        assert!(matches!(
+            machine.poll_output(),
+            Some(FlightOutput::Transition(FlightState::Landed))
+        ));
+    }
+
+    #[test]
+    fn test_returning_lands_only_at_home() {
+        let mut machine = FlightMachine::new();
+        machine.process_input(FlightInput::Telemetry(position_at(37.0, -122.0, 0.0)));
+        machine.process_input(FlightInput::Command(Command::Arm));
+        machine.process_input(FlightInput::Command(Command::Takeoff { altitude_m: 10.0 }));
+        machine.process_input(FlightInput::Command(Command::ReturnHome));
+
+        // On the ground but away from home
+        machine.process_input(FlightInput::Telemetry(position_at(37.5, -122.0, 0.0)));
+        assert_eq!(machine.state(), FlightState::Returning);
+
+        machine.process_input(FlightInput::Telemetry(position_at(37.0, -122.0, 0.0)));
+        assert_eq!(machine.state(), FlightState::Landed);
+    }
+}
@@ -0,0 +1,199 @@
+use super::StateMachine;
+use super::echo::Position;
+
+/// Tolerance used when deciding whether a point lies on a fence edge.
+const EDGE_EPSILON: f64 = 1e-12;
+
+/// An authorized flight area described by a polygon of `(latitude, longitude)` vertices.
+///
+/// The polygon is implicitly closed, the last vertex connects back to the first. Points on an
+/// edge or vertex are considered inside.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Geofence {
+    vertices: Vec<(f64, f64)>,
+}
+
+impl Geofence {
+    pub fn new(vertices: Vec<(f64, f64)>) -> Self {
+        Self { vertices }
+    }
+
+    pub fn vertices(&self) -> &[(f64, f64)] {
+        &self.vertices
+    }
+
+    /// Whether the point at `latitude`/`longitude` lies inside or on the fence.
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        let point = (latitude, longitude);
+        let edges = self
+            .vertices
+            .iter()
+            .zip(self.vertices.iter().cycle().skip(1));
+
+        let mut inside = false;
+        for (&a, &b) in edges {
+            if on_segment(point, a, b) {
+                return true;
+            }
+
+            // Ray cast along increasing longitude
+            if (a.0 > point.0) != (b.0 > point.0) {
+                let crossing = a.1 + (point.0 - a.0) * (b.1 - a.1) / (b.0 - a.0);
+                if point.1 < crossing {
+                    inside = !inside;
+                }
+            }
+        }
+
+        inside
+    }
+}
+
+fn on_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> bool {
+    let cross = (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0);
+    if cross.abs() > EDGE_EPSILON {
+        return false;
+    }
+
+    p.0 >= a.0.min(b.0) && p.0 <= a.0.max(b.0) && p.1 >= a.1.min(b.1) && p.1 <= a.1.max(b.1)
+}
+
+/// The result of checking a unit's telemetry against its geofence.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GeofenceStatus {
+    /// Whether any reported position has fallen outside the fence.
+    pub violated: bool,
+    /// The first position reported outside the fence.
+    pub first_violation: Option<Position>,
+}
+
+/// Checks reported positions against a [`Geofence`], latching the first violation.
+#[derive(Debug, Default)]
+pub struct GeofenceMachine {
+    fence: Option<Geofence>,
+    status: GeofenceStatus,
+    pending_violation: bool,
+}
+
+impl GeofenceMachine {
+    pub fn new(fence: Option<Geofence>) -> Self {
+        Self {
+            fence,
+            status: GeofenceStatus::default(),
+            pending_violation: false,
+        }
+    }
+
+    pub fn status(&self) -> &GeofenceStatus {
+        &self.status
+    }
+
+    fn check_position(&mut self, pos: Position) {
+        let Some(fence) = &self.fence else {
+            return;
+        };
+
+        if self.status.violated || fence.contains(pos.latitude, pos.longitude) {
+            return;
+        }
+
+        self.status = GeofenceStatus {
+            violated: true,
+            first_violation: Some(pos),
+        };
+        self.pending_violation = true;
+    }
+
+    fn poll_violation(&mut self) -> Option<Position> {
+        if self.pending_violation {
+            self.pending_violation = false;
+            self.status.first_violation.clone()
+        } else {
+            None
+        }
+    }
+}
+
+pub enum GeofenceInput {
+    Position(Position),
+}
+
+pub enum GeofenceOutput {
+    /// Emitted once, when the fence is first violated.
+    Violated(Position),
+}
+
+impl StateMachine for GeofenceMachine {
+    type Input = GeofenceInput;
+    type Output = GeofenceOutput;
+
+    fn process_input(&mut self, input: Self::Input) {
+        match input {
+            GeofenceInput::Position(pos) => self.check_position(pos),
+        }
+    }
+
+    fn poll_output(&mut self) -> Option<Self::Output> {
+        self.poll_violation().map(GeofenceOutput::Violated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::position_at;
+
+    fn square() -> Geofence {
+        Geofence::new(vec![(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)])
+    }
+
+    #[test]
+    fn test_point_inside() {
+        assert!(square().contains(0.5, 0.5));
+    }
+
+    #[test]
+    fn test_point_outside() {
+        assert!(!square().contains(1.5, 0.5));
+        assert!(!square().contains(0.5, -0.1));
+    }
+
+    #[test]
+    fn test_point_on_edge_is_inside() {
+        assert!(square().contains(0.0, 0.5));
+        assert!(square().contains(0.5, 1.0));
+        assert!(square().contains(1.0, 1.0));
+    }
+
+    #[test]
+    fn test_first_violation_is_latched() {
+        let mut machine = GeofenceMachine::new(Some(square()));
+
+        machine.process_input(GeofenceInput::Position(position_at(0.5, 0.5, 100.0)));
+        assert!(machine.poll_output().is_none());
+        assert!(!machine.status().violated);
+
+        machine.process_input(GeofenceInput::Position(position_at(2.0, 2.0, 100.0)));
+        machine.process_input(GeofenceInput::Position(position_at(3.0, 3.0, 100.0)));
+
+        let Some(GeofenceOutput::Violated(pos)) = machine.poll_output() else {
+            panic!("expected a violation");
+        };
+        assert_eq!(pos, position_at(2.0, 2.0, 100.0));
+        assert!(machine.poll_output().is_none());
+
+        // Returning inside the fence does not clear the violation
+        machine.process_input(GeofenceInput::Position(position_at(0.5, 0.5, 100.0)));
+        assert_eq!(
+            machine.status().first_violation,
+            Some(position_at(2.0, 2.0, 100.0))
+        );
+    }
+
+    #[test]
+    fn test_no_fence_never_violates() {
+        let mut machine = GeofenceMachine::new(None);
+        machine.process_input(GeofenceInput::Position(position_at(90.0, 180.0, 100.0)));
+        assert!(machine.poll_output().is_none());
+    }
+}
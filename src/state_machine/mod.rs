@@ -1,4 +1,7 @@
 pub mod echo;
+pub mod error;
+pub mod flight;
+pub mod geofence;
 pub mod wrappers;
 
 /// The [`StateMachine`] trait provides calling semantics and indicates the upholding of invariants
@@ -0,0 +1,173 @@
+use crate::state_machine::echo::Position;
+
+/// How reported positions are smoothed before they are used.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum FilterKind {
+    /// Positions are used as reported.
+    #[default]
+    None,
+
+    /// An exponential moving average, weighting each new report by `alpha` in `(0, 1]`.
+    ///
+    /// Lower values smooth more but lag further behind real movement.
+    Ema { alpha: f64 },
+
+    /// A constant-position Kalman filter run independently on each axis.
+    ///
+    /// `process_noise` is how far the true position is expected to drift between reports and
+    /// `measurement_noise` how far a report is expected to stray from it, both as variances in
+    /// the axis' own units.
+    Kalman {
+        process_noise: f64,
+        measurement_noise: f64,
+    },
+}
+
+/// Smooths a unit's latitude, longitude and altitude according to a [`FilterKind`].
+///
+/// Heading, speed and the other fields of a report are passed through unchanged.
+#[derive(Debug, Clone)]
+pub struct PositionFilter {
+    kind: FilterKind,
+    axes: Option<[AxisEstimate; 3]>,
+}
+
+/// The running estimate for one axis, and its variance for the Kalman filter.
+#[derive(Debug, Clone, Copy)]
+struct AxisEstimate {
+    value: f64,
+    variance: f64,
+}
+
+impl PositionFilter {
+    pub fn new(kind: FilterKind) -> Self {
+        Self { kind, axes: None }
+    }
+
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    /// Fold `raw` into the estimate, returning the filtered position.
+    ///
+    /// The first report is returned as-is and seeds the estimate.
+    pub fn apply(&mut self, raw: &Position) -> Position {
+        let measured = [raw.latitude, raw.longitude, raw.altitude_m];
+
+        let axes = match (&mut self.axes, self.kind) {
+            (_, FilterKind::None) | (None, _) => {
+                let axes = self.axes.insert(measured.map(|value| AxisEstimate {
+                    value,
+                    variance: initial_variance(self.kind),
+                }));
+                return with_axes(raw, axes);
+            }
+            (Some(axes), _) => axes,
+        };
+
+        for (axis, measured) in axes.iter_mut().zip(measured) {
+            match self.kind {
+                FilterKind::None => unreachable!("handled above"),
+                FilterKind::Ema { alpha } => {
+                    axis.value += alpha.clamp(0.0, 1.0) * (measured - axis.value);
+                }
+                FilterKind::Kalman {
+                    process_noise,
+                    measurement_noise,
+                } => {
+                    let predicted = axis.variance + process_noise;
+                    let gain = predicted / (predicted + measurement_noise);
+                    axis.value += gain * (measured - axis.value);
+                    axis.variance = (1.0 - gain) * predicted;
+                }
+            }
+        }
+
+        with_axes(raw, axes)
+    }
+}
+
+/// The variance of an estimate seeded from a single report.
+fn initial_variance(kind: FilterKind) -> f64 {
+    match kind {
+        FilterKind::Kalman {
+            measurement_noise, ..
+        } => measurement_noise,
+        FilterKind::None | FilterKind::Ema { .. } => 0.0,
+    }
+}
+
+fn with_axes(raw: &Position, axes: &[AxisEstimate; 3]) -> Position {
+    let [latitude, longitude, altitude] = axes;
+    Position {
+        latitude: latitude.value,
+        longitude: longitude.value,
+        altitude_m: altitude.value,
+        ..raw.clone()
+    }
+}
+
+impl Default for PositionFilter {
+    fn default() -> Self {
+        Self::new(FilterKind::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::position_at;
+
+    #[test]
+    fn test_none_passes_positions_through() {
+        let mut filter = PositionFilter::default();
+        for altitude in [100.0, 150.0, 90.0] {
+            assert_eq!(
+                filter.apply(&position_at(0.0, 0.0, altitude)),
+                position_at(0.0, 0.0, altitude)
+            );
+        }
+    }
+
+    #[test]
+    fn test_first_report_seeds_estimate() {
+        for kind in [
+            FilterKind::Ema { alpha: 0.2 },
+            FilterKind::Kalman {
+                process_noise: 0.01,
+                measurement_noise: 4.0,
+            },
+        ] {
+            let mut filter = PositionFilter::new(kind);
+            assert_eq!(
+                filter.apply(&position_at(0.0, 0.0, 100.0)),
+                position_at(0.0, 0.0, 100.0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_ema_moves_fraction_towards_report() {
+        let mut filter = PositionFilter::new(FilterKind::Ema { alpha: 0.25 });
+        filter.apply(&position_at(0.0, 0.0, 100.0));
+        assert_eq!(
+            filter.apply(&position_at(0.0, 0.0, 200.0)).altitude_m,
+            125.0
+        );
+    }
+
+    #[test]
+    fn test_kalman_converges_on_steady_reports() {
+        let mut filter = PositionFilter::new(FilterKind::Kalman {
+            process_noise: 0.01,
+            measurement_noise: 4.0,
+        });
+        filter.apply(&position_at(0.0, 0.0, 0.0));
+
+        let mut filtered = 0.0;
+        for _ in 0..200 {
+            filtered = filter.apply(&position_at(0.0, 0.0, 50.0)).altitude_m;
+        }
+        assert!((filtered - 50.0).abs() < 0.01);
+    }
+}
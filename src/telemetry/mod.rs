@@ -0,0 +1,123 @@
+mod filter;
+mod rate_limit;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::state_machine::echo::Position;
+
+pub use self::filter::{FilterKind, PositionFilter};
+pub use self::rate_limit::{TelemetryLimiter, TelemetryRateLimit};
+
+/// The default number of positions retained per unit.
+pub const DEFAULT_HISTORY_CAPACITY: usize = 128;
+
+/// A unit context that tracks when it last received telemetry.
+pub trait TelemetryAge {
+    /// When telemetry was last received, or `None` if it never has been.
+    fn last_telemetry_at(&self) -> Option<Instant>;
+
+    /// Whether no telemetry has been received within `max_age` of `now`.
+    ///
+    /// A unit that has never reported is considered stale.
+    fn is_stale_at(&self, max_age: Duration, now: Instant) -> bool {
+        self.last_telemetry_at()
+            .is_none_or(|at| now.saturating_duration_since(at) > max_age)
+    }
+
+    /// Whether no telemetry has been received within the last `max_age`.
+    fn is_stale(&self, max_age: Duration) -> bool {
+        self.is_stale_at(max_age, Instant::now())
+    }
+}
+
+/// A fixed-capacity ring buffer of the most recent positions reported by a unit.
+///
+/// The buffer is allocated once at construction and evicts the oldest position when full, so
+/// recording telemetry never reallocates.
+#[derive(Debug)]
+pub struct PositionHistory {
+    positions: VecDeque<Position>,
+    capacity: usize,
+}
+
+impl PositionHistory {
+    /// Construct an empty history retaining at most `capacity` positions.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            positions: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Record `position` as the most recent, evicting the oldest if at capacity.
+    pub fn push(&mut self, position: Position) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.positions.len() == self.capacity {
+            self.positions.pop_front();
+        }
+        self.positions.push_back(position);
+    }
+
+    /// The most recently recorded position.
+    pub fn last(&self) -> Option<&Position> {
+        self.positions.back()
+    }
+
+    /// Iterate the retained positions from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &Position> {
+        self.positions.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl Default for PositionHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::position;
+
+    #[test]
+    fn test_evicts_oldest_when_full() {
+        let mut history = PositionHistory::new(3);
+        for timestamp in 0..5 {
+            history.push(position(timestamp));
+        }
+
+        let timestamps: Vec<_> = history.iter().map(|pos| pos.timestamp).collect();
+        assert_eq!(timestamps, vec![2, 3, 4]);
+        assert_eq!(history.last().map(|pos| pos.timestamp), Some(4));
+    }
+
+    #[test]
+    fn test_does_not_reallocate() {
+        let mut history = PositionHistory::new(4);
+        let allocated = history.positions.capacity();
+
+        for timestamp in 0..64 {
+            history.push(position(timestamp));
+        }
+
+        assert_eq!(history.positions.capacity(), allocated);
+        assert_eq!(history.len(), 4);
+    }
+}
@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use dashmap::DashMap;
+
+use crate::unit::UnitId;
+
+/// How many telemetry reports per second a single unit may have processed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TelemetryRateLimit {
+    /// Sustained reports per second.
+    pub per_second: f64,
+    /// Reports allowed in a burst above the sustained rate.
+    pub burst: u32,
+}
+
+impl TelemetryRateLimit {
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        Self { per_second, burst }
+    }
+}
+
+/// A token bucket refilled at the limit's rate, holding at most `burst` tokens.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+    dropped: u64,
+}
+
+impl TokenBucket {
+    fn full(limit: &TelemetryRateLimit, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(limit.burst),
+            refilled_at: now,
+            dropped: 0,
+        }
+    }
+
+    fn try_take_at(&mut self, limit: &TelemetryRateLimit, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * limit.per_second).min(f64::from(limit.burst));
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            self.dropped += 1;
+            false
+        }
+    }
+}
+
+/// Per-unit rate limiting of inbound telemetry.
+///
+/// Each unit gets its own bucket, so one unit flooding reports can't starve the others. Reports
+/// over the limit are dropped and counted rather than queued.
+#[derive(Debug, Default)]
+pub struct TelemetryLimiter {
+    limit: Option<TelemetryRateLimit>,
+    buckets: DashMap<UnitId, TokenBucket, ahash::RandomState>,
+    total_dropped: AtomicU64,
+}
+
+impl TelemetryLimiter {
+    /// A limiter enforcing `limit` per unit, or allowing everything if `None`.
+    pub fn new(limit: Option<TelemetryRateLimit>) -> Self {
+        Self {
+            limit,
+            buckets: DashMap::default(),
+            total_dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn limit(&self) -> Option<TelemetryRateLimit> {
+        self.limit
+    }
+
+    /// Whether a report from `unit_id` may be processed now.
+    pub fn allow(&self, unit_id: &UnitId) -> bool {
+        self.allow_at(unit_id, Instant::now())
+    }
+
+    /// Whether a report from `unit_id` received at `now` may be processed.
+    pub fn allow_at(&self, unit_id: &UnitId, now: Instant) -> bool {
+        let Some(limit) = &self.limit else {
+            return true;
+        };
+
+        let allowed = self
+            .buckets
+            .entry(unit_id.clone())
+            .or_insert_with(|| TokenBucket::full(limit, now))
+            .try_take_at(limit, now);
+        if !allowed {
+            self.total_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        allowed
+    }
+
+    /// The number of reports from `unit_id` dropped for exceeding the limit.
+    pub fn dropped(&self, unit_id: &UnitId) -> u64 {
+        self.buckets.get(unit_id).map_or(0, |bucket| bucket.dropped)
+    }
+
+    /// Forget `unit_id`'s bucket and dropped count, once its session has ended.
+    pub fn remove(&self, unit_id: &UnitId) {
+        self.buckets.remove(unit_id);
+    }
+
+    /// The number of reports dropped across all units, including those since removed.
+    pub fn total_dropped(&self) -> u64 {
+        self.total_dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_burst_then_sustained_rate() {
+        let limiter = TelemetryLimiter::new(Some(TelemetryRateLimit::new(10.0, 5)));
+        let unit_id = UnitId::from("drone-1");
+        let start = Instant::now();
+
+        let allowed = (0..20)
+            .filter(|_| limiter.allow_at(&unit_id, start))
+            .count();
+        assert_eq!(allowed, 5);
+        assert_eq!(limiter.dropped(&unit_id), 15);
+        assert_eq!(limiter.total_dropped(), 15);
+
+        // 10 per second refills one token every 100ms
+        assert!(!limiter.allow_at(&unit_id, start + Duration::from_millis(50)));
+        assert!(limiter.allow_at(&unit_id, start + Duration::from_millis(150)));
+    }
+
+    #[test]
+    fn test_units_are_limited_independently() {
+        let limiter = TelemetryLimiter::new(Some(TelemetryRateLimit::new(1.0, 1)));
+        let now = Instant::now();
+
+        assert!(limiter.allow_at(&UnitId::from("drone-1"), now));
+        assert!(!limiter.allow_at(&UnitId::from("drone-1"), now));
+        assert!(limiter.allow_at(&UnitId::from("drone-2"), now));
+    }
+
+    #[test]
+    fn test_unlimited_allows_everything() {
+        let limiter = TelemetryLimiter::default();
+        let unit_id = UnitId::from("drone-1");
+        let now = Instant::now();
+
+        assert!((0..1000).all(|_| limiter.allow_at(&unit_id, now)));
+        assert_eq!(limiter.dropped(&unit_id), 0);
+    }
+}
@@ -0,0 +1,26 @@
+//! Fixtures shared by the unit tests.
+
+use crate::state_machine::echo::Position;
+
+/// A report from `drone-1` over San Francisco at 100m, stamped `timestamp`.
+pub(crate) fn position(timestamp: u64) -> Position {
+    Position {
+        drone_id: "drone-1".to_string(),
+        latitude: 37.7749,
+        longitude: -122.4194,
+        altitude_m: 100.0,
+        heading_deg: 0.0,
+        speed_mps: 0.0,
+        timestamp,
+    }
+}
+
+/// A report from `drone-1` at the given coordinates, stamped 0.
+pub(crate) fn position_at(latitude: f64, longitude: f64, altitude_m: f64) -> Position {
+    Position {
+        latitude,
+        longitude,
+        altitude_m,
+        ..position(0)
+    }
+}
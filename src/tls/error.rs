@@ -0,0 +1,20 @@
+//! Error types for building a TLS client.
+
+/// Indicates that a [`TlsConfig`](super::TlsConfig) could not be turned into a client.
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("invalid PEM in {what}: {source}")]
+    InvalidPem {
+        what: &'static str,
+        source: rustls::pki_types::pem::Error,
+    },
+
+    #[error("no trusted root certificates were configured")]
+    NoRootCertificates,
+
+    #[error(transparent)]
+    Rustls(#[from] rustls::Error),
+
+    #[error("failed to create QUIC endpoint: {0}")]
+    Endpoint(#[from] std::io::Error),
+}
@@ -0,0 +1,200 @@
+//! Finding MoQ broadcasts and reading protobuf messages off their tracks.
+
+use std::ops::Range;
+
+use futures::{Stream, StreamExt};
+use moq_lite::{BroadcastConsumer, OriginConsumer, Path, TrackConsumer};
+use prost::Message;
+use rpcmoq_lite::{RpcInbound, RpcWireError};
+
+/// Decode every frame of `track` as an `M`, in the order they arrive.
+///
+/// Follows the track from group to group like an [`RpcInbound`], which also strips the codec
+/// tag and decompresses each frame. A frame that fails to decode yields
+/// [`RpcWireError::Decode`] and the stream carries on with the next one.
+pub fn decoded_track_stream<M: Message + Default>(
+    track: TrackConsumer,
+) -> impl Stream<Item = Result<M, RpcWireError>> + Send + use<M> {
+    RpcInbound::from_track(track).map(|frame| {
+        let payload = frame?;
+        M::decode(payload).map_err(|_| RpcWireError::Decode)
+    })
+}
+
+/// The broadcast at `path`, waiting for it to be announced if it has not been yet.
+///
+/// Returns `None` if `consumer` may not subscribe to it, or the origin closes first.
+pub(crate) async fn announced_broadcast(
+    consumer: &OriginConsumer,
+    path: &str,
+) -> Option<BroadcastConsumer> {
+    if let Some(broadcast) = consumer.consume_broadcast(path) {
+        return Some(broadcast);
+    }
+
+    let mut announcements = consumer.consume_only(&[Path::new(path)])?;
+    loop {
+        if let (announced, Some(broadcast)) = announcements.announced().await?
+            && announced.as_str() == path
+        {
+            return Some(broadcast);
+        }
+    }
+}
+
+/// Notices groups skipped between the successive group sequences of a track.
+///
+/// moq-lite hands a subscriber that falls behind the latest group, dropping the ones in
+/// between, so a jump in sequence means messages were lost.
+#[derive(Debug, Default)]
+pub struct GroupGapTracker {
+    last: Option<u64>,
+    missed: u64,
+}
+
+impl GroupGapTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the sequence of the group a frame arrived in, returning the sequences skipped
+    /// since the previous group, if any.
+    ///
+    /// Frames of the same group share a sequence and never report a gap, nor does the first
+    /// group seen, as a subscriber joins a track part way through.
+    pub fn observe(&mut self, sequence: u64) -> Option<Range<u64>> {
+        let last = self.last.replace(sequence.max(self.last.unwrap_or(0)))?;
+        let missing = last + 1..sequence;
+        if missing.is_empty() {
+            return None;
+        }
+        self.missed += missing.end - missing.start;
+        Some(missing)
+    }
+
+    /// The number of groups skipped so far.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+/// A message decoded off a track, with where it sat in the track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackMessage<M> {
+    pub message: M,
+    /// The sequence of the group the message arrived in.
+    pub sequence: u64,
+    /// The number of groups skipped between the previous message and this one.
+    pub gap_since_last: u64,
+}
+
+/// Decode every frame of `track` like [`decoded_track_stream`], also reporting the groups
+/// skipped before each message.
+///
+/// A frame that fails to decode yields [`RpcWireError::Decode`], and the gap before it is
+/// reported with the next message instead.
+pub fn decoded_track_stream_with_gaps<M: Message + Default>(
+    track: TrackConsumer,
+) -> impl Stream<Item = Result<TrackMessage<M>, RpcWireError>> + Send + use<M> {
+    let state = (RpcInbound::from_track(track), GroupGapTracker::new(), 0);
+    futures::stream::unfold(state, |(mut inbound, mut gaps, mut gap)| async move {
+        let next = match inbound.next_with_group().await? {
+            Ok((sequence, payload)) => {
+                gap += gaps
+                    .observe(sequence)
+                    .map_or(0, |missing| missing.end - missing.start);
+                match M::decode(payload) {
+                    Ok(message) => Ok(TrackMessage {
+                        message,
+                        sequence,
+                        gap_since_last: std::mem::take(&mut gap),
+                    }),
+                    Err(_) => Err(RpcWireError::Decode),
+                }
+            }
+            Err(err) => Err(RpcWireError::from(err)),
+        };
+        Some((next, (inbound, gaps, gap)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drone_proto::DronePosition;
+    use crate::test_util::position;
+    use moq_lite::Track;
+    use rpcmoq_lite::RpcOutbound;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_decodes_every_frame_of_a_track() {
+        let track = Track::new("position").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let positions = decoded_track_stream::<DronePosition>(track.consumer);
+
+        // One group, so the reader cannot skip ahead past any of the frames
+        let _group = outbound.begin_group();
+        outbound.send(&DronePosition::from(position(1))).unwrap();
+        // A length-delimited field with its length missing
+        outbound.send_raw(&b"\x0a"[..]);
+        outbound.send(&DronePosition::from(position(2))).unwrap();
+
+        let received: Vec<_> =
+            tokio::time::timeout(Duration::from_secs(1), positions.take(3).collect())
+                .await
+                .unwrap();
+        assert!(matches!(
+            &received[..],
+            [Ok(first), Err(RpcWireError::Decode), Ok(second)]
+                if *first == position(1).into() && *second == position(2).into()
+        ));
+    }
+
+    #[test]
+    fn test_gap_tracker_reports_skipped_groups() {
+        let mut gaps = GroupGapTracker::new();
+        assert_eq!(gaps.observe(0), None);
+        assert_eq!(gaps.observe(1), None);
+        assert_eq!(gaps.observe(1), None);
+        assert_eq!(gaps.observe(3), Some(2..3));
+        assert_eq!(gaps.missed(), 1);
+    }
+
+    #[test]
+    fn test_gap_tracker_ignores_late_start() {
+        let mut gaps = GroupGapTracker::new();
+        assert_eq!(gaps.observe(5), None);
+        assert_eq!(gaps.observe(9), Some(6..9));
+        assert_eq!(gaps.missed(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_decoded_messages_report_gaps() {
+        let track = Track::new("position").produce();
+        let mut outbound = RpcOutbound::new(track.producer);
+        let mut positions = Box::pin(decoded_track_stream_with_gaps::<DronePosition>(
+            track.consumer,
+        ));
+        let mut next = async || {
+            tokio::time::timeout(Duration::from_secs(1), positions.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap()
+        };
+
+        outbound.send(&DronePosition::from(position(0))).unwrap();
+        assert_eq!(next().await.gap_since_last, 0);
+        outbound.send(&DronePosition::from(position(1))).unwrap();
+        assert_eq!(next().await.gap_since_last, 0);
+
+        // Group 2 is superseded before the reader gets to it
+        outbound.send(&DronePosition::from(position(2))).unwrap();
+        outbound.send(&DronePosition::from(position(3))).unwrap();
+        let received = next().await;
+        assert_eq!(received.message, position(3).into());
+        assert_eq!(received.sequence, 3);
+        assert_eq!(received.gap_since_last, 1);
+    }
+}
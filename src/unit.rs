@@ -14,12 +14,40 @@ impl UnitId {
         Self(id.into())
     }
 
+    /// Create a new [`UnitId`] from an untrusted string, such as a client supplied drone ID.
+    ///
+    /// Unit IDs are used as broadcast path segments, so empty IDs and IDs containing `/` are
+    /// rejected.
+    pub fn try_new(id: impl AsRef<str>) -> Result<Self, UnitIdError> {
+        let id = id.as_ref();
+
+        if id.is_empty() {
+            return Err(UnitIdError::Empty);
+        }
+
+        if id.contains('/') {
+            return Err(UnitIdError::ContainsSlash(id.to_string()));
+        }
+
+        Ok(Self(id.into()))
+    }
+
     /// Returns the underlying string slice.
     pub fn as_str(&self) -> &str {
         &self.0
     }
 }
 
+/// Indicates that a string is not a valid [`UnitId`].
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum UnitIdError {
+    #[error("unit id must not be empty")]
+    Empty,
+
+    #[error("unit id ({0}) must not contain '/'")]
+    ContainsSlash(String),
+}
+
 impl Display for UnitId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
@@ -37,3 +65,27 @@ impl From<&str> for UnitId {
         Self(s.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_new_valid() {
+        let unit_id = UnitId::try_new("drone-1").unwrap();
+        assert_eq!(unit_id.as_str(), "drone-1");
+    }
+
+    #[test]
+    fn test_try_new_empty() {
+        assert_eq!(UnitId::try_new(""), Err(UnitIdError::Empty));
+    }
+
+    #[test]
+    fn test_try_new_slash() {
+        assert_eq!(
+            UnitId::try_new("drone/1"),
+            Err(UnitIdError::ContainsSlash("drone/1".to_string()))
+        );
+    }
+}
@@ -1,26 +1,253 @@
-use std::sync::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use tokio::sync::Notify;
+use tracing::{debug, warn};
+
+use crate::clock::{self, Clock};
+use crate::command::{
+    Command, CommandId, CommandQueue, CommandReceipt, DEFAULT_COMMAND_CAPACITY, QueuedCommand,
+    error::EnqueueError,
+};
 use crate::state_machine::{
     StateMachine,
     echo::{EchoInput, EchoMachine, EchoOutput, Position},
+    error::InvalidPosition,
+    flight::{FlightInput, FlightMachine, FlightOutput, FlightState},
+    geofence::{Geofence, GeofenceInput, GeofenceMachine, GeofenceOutput, GeofenceStatus},
+};
+use crate::telemetry::{
+    DEFAULT_HISTORY_CAPACITY, FilterKind, PositionFilter, PositionHistory, TelemetryAge,
 };
 
 #[derive(Debug)]
 pub struct UnitContext {
     echo: Mutex<EchoMachine>,
+    flight: Mutex<FlightMachine>,
+    history: Mutex<PositionHistory>,
+    filter: Mutex<PositionFilter>,
+    last_raw_position: Mutex<Option<Position>>,
+    last_telemetry_at: Mutex<Option<Instant>>,
+    invalid_telemetry: AtomicU64,
+    geofence: Mutex<GeofenceMachine>,
+    geofence_autoreturn: bool,
+    capabilities: Mutex<Vec<String>>,
+    commands: Mutex<CommandQueue>,
+    command_dedup: bool,
+    command_queued: Arc<Notify>,
+    // Bounded to the command capacity so unclaimed acks can't accumulate.
+    receipts: Mutex<VecDeque<(CommandId, CommandReceipt)>>,
+    clock: Arc<dyn Clock>,
 }
 
 impl UnitContext {
     pub fn new() -> Self {
         Self {
             echo: Mutex::new(EchoMachine::new()),
+            flight: Mutex::new(FlightMachine::new()),
+            history: Mutex::new(PositionHistory::new(DEFAULT_HISTORY_CAPACITY)),
+            filter: Mutex::new(PositionFilter::default()),
+            last_raw_position: Mutex::new(None),
+            last_telemetry_at: Mutex::new(None),
+            invalid_telemetry: AtomicU64::new(0),
+            geofence: Mutex::new(GeofenceMachine::new(None)),
+            geofence_autoreturn: false,
+            capabilities: Mutex::new(Vec::new()),
+            commands: Mutex::new(CommandQueue::new(DEFAULT_COMMAND_CAPACITY)),
+            command_dedup: false,
+            command_queued: Arc::new(Notify::new()),
+            receipts: Mutex::new(VecDeque::with_capacity(DEFAULT_COMMAND_CAPACITY)),
+            clock: clock::system(),
         }
     }
 
+    /// Read the time from `clock` instead of the system clock.
+    ///
+    /// Telemetry is timestamped, commands expire and staleness is judged by this clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Bound the command queue to at most `capacity` pending commands.
+    pub fn with_command_capacity(mut self, capacity: usize) -> Self {
+        self.commands = Mutex::new(CommandQueue::new(capacity));
+        self.receipts = Mutex::new(VecDeque::with_capacity(capacity));
+        self
+    }
+
+    /// Drop a queued command that repeats the most recently queued one still pending.
+    ///
+    /// See [`CommandQueue::coalesce`]. Safety commands are always queued.
+    pub fn with_command_dedup(mut self, enabled: bool) -> Self {
+        self.command_dedup = enabled;
+        self
+    }
+
+    /// Retain the last `capacity` reported positions.
+    pub fn with_history_capacity(mut self, capacity: usize) -> Self {
+        self.history = Mutex::new(PositionHistory::new(capacity));
+        self
+    }
+
+    /// Smooth reported positions with `kind` before they are recorded or checked.
+    ///
+    /// The history, geofence and flight state all see the filtered position. The position as
+    /// reported is still available from [`last_raw_position`](Self::last_raw_position).
+    pub fn with_position_filter(mut self, kind: FilterKind) -> Self {
+        self.filter = Mutex::new(PositionFilter::new(kind));
+        self
+    }
+
+    /// Check every reported position against `fence`.
+    pub fn with_geofence(mut self, fence: Geofence) -> Self {
+        self.geofence = Mutex::new(GeofenceMachine::new(Some(fence)));
+        self
+    }
+
+    /// Queue a [`Command::ReturnHome`] when the geofence is first violated.
+    pub fn with_geofence_autoreturn(mut self, enabled: bool) -> Self {
+        self.geofence_autoreturn = enabled;
+        self
+    }
+
     // TODO: Make a view type instead of passing through to the state machine here
-    pub fn update_position(&self, pos: Position) {
+    pub fn update_telemetry(&self, pos: Position) -> Result<(), InvalidPosition> {
+        self.update_telemetry_at(pos, self.clock.now())
+    }
+
+    /// Record telemetry as having been received at `now`.
+    ///
+    /// A position that fails [`Position::validate`] is counted and otherwise ignored, it
+    /// neither refreshes the telemetry age nor reaches the state machines.
+    pub fn update_telemetry_at(&self, pos: Position, now: Instant) -> Result<(), InvalidPosition> {
+        if let Err(e) = pos.validate() {
+            self.invalid_telemetry.fetch_add(1, Ordering::Relaxed);
+            return Err(e);
+        }
+
+        *self
+            .last_telemetry_at
+            .lock()
+            .expect("telemetry time lock poisoned") = Some(now);
+
+        let raw = pos;
+        let pos = self
+            .filter
+            .lock()
+            .expect("position filter lock poisoned")
+            .apply(&raw);
+        *self
+            .last_raw_position
+            .lock()
+            .expect("raw position lock poisoned") = Some(raw);
+
+        self.history
+            .lock()
+            .expect("position history lock poisoned")
+            .push(pos.clone());
+
+        self.check_geofence(pos.clone());
+        self.update_flight(FlightInput::Telemetry(pos.clone()));
+
         let mut machine = self.echo.lock().expect("telemetry machine lock poisoned");
         machine.process_input(EchoInput::Position(pos));
+        Ok(())
+    }
+
+    /// The number of position reports rejected as invalid.
+    pub fn invalid_telemetry(&self) -> u64 {
+        self.invalid_telemetry.load(Ordering::Relaxed)
+    }
+
+    fn update_flight(&self, input: FlightInput) {
+        let mut machine = self.flight.lock().expect("flight machine lock poisoned");
+        machine.process_input(input);
+
+        while let Some(FlightOutput::Transition(state)) = machine.poll_output() {
+            debug!(state = ?state, "Flight state changed");
+        }
+    }
+
+    /// The drone's current flight state.
+    pub fn current_state(&self) -> FlightState {
+        let machine = self.flight.lock().expect("flight machine lock poisoned");
+        machine.state()
+    }
+
+    fn check_geofence(&self, pos: Position) {
+        let violation = {
+            let mut machine = self
+                .geofence
+                .lock()
+                .expect("geofence machine lock poisoned");
+            machine.process_input(GeofenceInput::Position(pos));
+            machine.poll_output()
+        };
+
+        let Some(GeofenceOutput::Violated(pos)) = violation else {
+            return;
+        };
+
+        warn!(
+            drone_id = %pos.drone_id,
+            latitude = pos.latitude,
+            longitude = pos.longitude,
+            "Geofence violated"
+        );
+
+        if self.geofence_autoreturn
+            && let Err(e) = self.enqueue_command(CommandId::generate(), Command::ReturnHome)
+        {
+            warn!(drone_id = %pos.drone_id, error = %e, "Failed to queue geofence return");
+        }
+    }
+
+    /// Whether the unit has left its geofence, and where it first did.
+    pub fn geofence_status(&self) -> GeofenceStatus {
+        let machine = self
+            .geofence
+            .lock()
+            .expect("geofence machine lock poisoned");
+        machine.status().clone()
+    }
+
+    /// The retained positions after filtering, oldest first.
+    pub fn recent_positions(&self) -> Vec<Position> {
+        let history = self.history.lock().expect("position history lock poisoned");
+        history.iter().cloned().collect()
+    }
+
+    /// The most recently reported position, after filtering.
+    pub fn last_position(&self) -> Option<Position> {
+        let history = self.history.lock().expect("position history lock poisoned");
+        history.last().cloned()
+    }
+
+    /// The most recently reported position, exactly as reported.
+    pub fn last_raw_position(&self) -> Option<Position> {
+        self.last_raw_position
+            .lock()
+            .expect("raw position lock poisoned")
+            .clone()
+    }
+
+    /// Record the features the drone announced when it opened its session.
+    pub fn set_capabilities(&self, capabilities: Vec<String>) {
+        *self
+            .capabilities
+            .lock()
+            .expect("capabilities lock poisoned") = capabilities;
+    }
+
+    /// The features the drone announced, empty if it did not send a hello.
+    pub fn capabilities(&self) -> Vec<String> {
+        self.capabilities
+            .lock()
+            .expect("capabilities lock poisoned")
+            .clone()
     }
 
     pub fn poll_position(&self) -> Option<Position> {
@@ -29,6 +256,185 @@ impl UnitContext {
             EchoOutput::Position(pos) => pos,
         })
     }
+
+    /// Queue a command for delivery to the drone.
+    ///
+    /// Safety commands such as [`Command::Land`] are delivered ahead of anything already queued.
+    /// The command is checked against the [`FlightState`] the commands delivered ahead of it
+    /// will leave, and again when it is delivered: one that a safety command overtook and made
+    /// invalid, such as a [`Command::Goto`] queued before a [`Command::Land`], is never sent,
+    /// and a rejection is recorded as its acknowledgement instead.
+    ///
+    /// The `id` is sent to the drone with the command and returned in its acknowledgement.
+    ///
+    /// With [`with_command_dedup`](Self::with_command_dedup), a command identical to the most
+    /// recently queued one still pending is merged into it instead, and the drone acknowledges
+    /// it under the ID of the pending command.
+    ///
+    /// Returns the ID the drone will acknowledge the command under: `id`, or that of the
+    /// pending command it was merged into.
+    ///
+    /// Returns [`EnqueueError::Rejected`] if the command is not valid for the drone's
+    /// [`FlightState`] by the time it would be delivered, or [`EnqueueError::QueueFull`] if the
+    /// drone has not drained enough of its pending commands.
+    pub fn enqueue_command(
+        &self,
+        id: CommandId,
+        command: Command,
+    ) -> Result<CommandId, EnqueueError> {
+        self.enqueue_command_until(id, command, None)
+    }
+
+    /// Queue a command that is discarded instead of delivered if still queued at `expires_at`.
+    ///
+    /// See [`enqueue_command`](Self::enqueue_command).
+    pub fn enqueue_command_until(
+        &self,
+        id: CommandId,
+        command: Command,
+        expires_at: Option<Instant>,
+    ) -> Result<CommandId, EnqueueError> {
+        // Hold both locks across the push so the checked state can't change underneath us.
+        let flight = self.flight.lock().expect("flight machine lock poisoned");
+        let mut queue = self.commands.lock().expect("command queue lock poisoned");
+        let ahead = queue.ahead_of(command.priority());
+        flight.check_queued_command(ahead.map(|queued| &queued.command), &command)?;
+
+        let queued = QueuedCommand {
+            id,
+            command,
+            expires_at,
+        };
+        if self.command_dedup
+            && let Some(pending) = queue.coalesce(&queued)
+        {
+            debug!(
+                command_id = %id,
+                pending_id = %pending,
+                command = ?queued.command,
+                "Coalesced duplicate command"
+            );
+            return Ok(pending);
+        }
+        queue.push(queued)?;
+        self.command_queued.notify_one();
+        Ok(id)
+    }
+
+    /// Take the highest priority command to deliver to the drone, if any.
+    pub fn poll_command(&self) -> Option<QueuedCommand> {
+        self.poll_command_at(self.clock.now())
+    }
+
+    /// Take the highest priority command that has not expired at `now`, if any.
+    ///
+    /// The flight state follows the command from here, as it is on its way to the drone.
+    /// Commands that expired, or are no longer valid for the flight state, are discarded with a
+    /// rejection recorded as their acknowledgement.
+    pub fn poll_command_at(&self, now: Instant) -> Option<QueuedCommand> {
+        let mut flight = self.flight.lock().expect("flight machine lock poisoned");
+        let mut rejected = Vec::new();
+        let command = {
+            let mut queue = self.commands.lock().expect("command queue lock poisoned");
+            let expired = queue.remove_expired(now);
+            if !expired.is_empty() {
+                warn!(expired = expired.len(), "Discarded expired commands");
+            }
+            rejected.extend(
+                expired
+                    .into_iter()
+                    .map(|command| (command.id, "expired before delivery".to_string())),
+            );
+            loop {
+                let Some(command) = queue.pop_at(now) else {
+                    break None;
+                };
+                match flight.check_command(&command.command) {
+                    Ok(_) => break Some(command),
+                    Err(e) => {
+                        warn!(
+                            command_id = %command.id,
+                            error = %e,
+                            "Discarded command overtaken by a safety command"
+                        );
+                        rejected.push((command.id, e.to_string()));
+                    }
+                }
+            }
+        };
+
+        if let Some(command) = &command {
+            flight.process_input(FlightInput::Command(command.command.clone()));
+            while let Some(FlightOutput::Transition(state)) = flight.poll_output() {
+                debug!(state = ?state, "Flight state changed");
+            }
+        }
+        drop(flight);
+
+        for (id, message) in rejected {
+            let receipt = CommandReceipt {
+                accepted: false,
+                message,
+            };
+            self.record_ack(id, receipt);
+        }
+        command
+    }
+
+    /// Notified whenever a command is queued, so a delivery loop can wait for one instead of
+    /// polling.
+    ///
+    /// A command queued while nobody is waiting leaves a permit, so the next wait returns
+    /// immediately rather than missing it.
+    pub fn command_queued(&self) -> Arc<Notify> {
+        Arc::clone(&self.command_queued)
+    }
+
+    /// The number of commands discarded for expiring before they were delivered.
+    pub fn expired_commands(&self) -> u64 {
+        self.commands
+            .lock()
+            .expect("command queue lock poisoned")
+            .expired()
+    }
+
+    /// Record the drone's acknowledgement of the command `id`.
+    ///
+    /// Only the most recent acknowledgements are retained, the oldest unclaimed one is dropped
+    /// once the command capacity is reached.
+    pub fn record_ack(&self, id: CommandId, receipt: CommandReceipt) {
+        let capacity = self
+            .commands
+            .lock()
+            .expect("command queue lock poisoned")
+            .capacity();
+
+        let mut receipts = self.receipts.lock().expect("receipts lock poisoned");
+        if receipts.len() >= capacity {
+            receipts.pop_front();
+        }
+        receipts.push_back((id, receipt));
+    }
+
+    /// Take the drone's acknowledgement of the command `id`, if it has arrived.
+    pub fn take_ack(&self, id: &CommandId) -> Option<CommandReceipt> {
+        let mut receipts = self.receipts.lock().expect("receipts lock poisoned");
+        let index = receipts.iter().position(|(acked, _)| acked == id)?;
+        receipts.remove(index).map(|(_, receipt)| receipt)
+    }
+}
+
+impl TelemetryAge for UnitContext {
+    fn last_telemetry_at(&self) -> Option<Instant> {
+        *self
+            .last_telemetry_at
+            .lock()
+            .expect("telemetry time lock poisoned")
+    }
+
+    fn is_stale(&self, max_age: Duration) -> bool {
+        self.is_stale_at(max_age, self.clock.now())
+    }
 }
 
 impl Default for UnitContext {
@@ -36,3 +442,367 @@ impl Default for UnitContext {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::test_util::position;
+
+    /// A context whose drone has armed and taken off, with the queue drained.
+    fn flying(context: UnitContext) -> UnitContext {
+        context
+            .enqueue_command(CommandId::generate(), Command::Arm)
+            .unwrap();
+        context.poll_command().unwrap();
+        context
+            .enqueue_command(CommandId::generate(), Command::Takeoff { altitude_m: 10.0 })
+            .unwrap();
+        context.poll_command().unwrap();
+        context
+    }
+
+    #[test]
+    fn test_recent_positions() {
+        let context = UnitContext::new().with_history_capacity(2);
+        assert_eq!(context.last_position(), None);
+
+        for timestamp in 1..=3 {
+            context.update_telemetry(position(timestamp)).unwrap();
+        }
+
+        assert_eq!(context.recent_positions(), vec![position(2), position(3)]);
+        assert_eq!(context.last_position(), Some(position(3)));
+        // Echo output is unaffected by the history
+        assert_eq!(context.poll_position(), Some(position(3)));
+    }
+
+    fn variance(values: &[f64]) -> f64 {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+    }
+
+    #[test]
+    fn test_position_filter_reduces_jitter() {
+        for kind in [
+            FilterKind::Ema { alpha: 0.2 },
+            FilterKind::Kalman {
+                process_noise: 1e-12,
+                measurement_noise: 1e-8,
+            },
+        ] {
+            let context = UnitContext::new().with_position_filter(kind);
+
+            // Deterministic jitter of up to ~10m around a hovering drone
+            let mut seed: u32 = 7;
+            let mut raw = Vec::new();
+            let mut filtered = Vec::new();
+            for timestamp in 0..200 {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                let noise = (f64::from(seed >> 16) / f64::from(u16::MAX) - 0.5) * 1e-4;
+
+                let mut pos = position(timestamp);
+                pos.latitude += noise;
+                context.update_telemetry(pos).unwrap();
+
+                raw.push(context.last_raw_position().unwrap().latitude);
+                filtered.push(context.last_position().unwrap().latitude);
+            }
+
+            assert!(
+                variance(&filtered) < variance(&raw) / 4.0,
+                "{kind:?} did not smooth the track"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_position_is_counted_not_stored() {
+        let clock = TestClock::new();
+        let context = UnitContext::new().with_clock(Arc::new(clock.clone()));
+        context.update_telemetry(position(1)).unwrap();
+
+        clock.advance(Duration::from_secs(10));
+        let mut garbage = position(2);
+        garbage.latitude = 999.0;
+        assert_eq!(
+            context.update_telemetry(garbage),
+            Err(InvalidPosition::Latitude { latitude: 999.0 })
+        );
+        let mut garbage = position(3);
+        garbage.altitude_m = f64::NAN;
+        assert!(context.update_telemetry(garbage).is_err());
+
+        assert_eq!(context.invalid_telemetry(), 2);
+        assert_eq!(context.last_raw_position(), Some(position(1)));
+        assert_eq!(context.recent_positions(), vec![position(1)]);
+        // Only valid telemetry keeps the drone fresh
+        assert!(context.is_stale(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_unfiltered_positions_match_raw() {
+        let context = UnitContext::new();
+        context.update_telemetry(position(1)).unwrap();
+        assert_eq!(context.last_position(), context.last_raw_position());
+    }
+
+    #[test]
+    fn test_geofence_violation_queues_return_home() {
+        let fence = Geofence::new(vec![(37.0, -123.0), (37.0, -122.0), (38.0, -122.0)]);
+        let context = flying(
+            UnitContext::new()
+                .with_geofence(fence)
+                .with_geofence_autoreturn(true),
+        );
+
+        let mut outside = position(1);
+        outside.longitude = -121.0;
+        context.update_telemetry(outside.clone()).unwrap();
+        context.update_telemetry(position(2)).unwrap();
+
+        let status = context.geofence_status();
+        assert!(status.violated);
+        assert_eq!(status.first_violation, Some(outside));
+
+        assert_eq!(
+            context.poll_command().map(|queued| queued.command),
+            Some(Command::ReturnHome)
+        );
+        assert!(context.poll_command().is_none());
+    }
+
+    #[test]
+    fn test_staleness_follows_telemetry() {
+        let context = UnitContext::new();
+        let start = Instant::now();
+        let max_age = Duration::from_secs(5);
+
+        assert_eq!(context.last_telemetry_at(), None);
+        assert!(context.is_stale_at(max_age, start));
+
+        context.update_telemetry_at(position(1), start).unwrap();
+        assert_eq!(context.last_telemetry_at(), Some(start));
+        assert!(!context.is_stale_at(max_age, start + Duration::from_secs(5)));
+        assert!(context.is_stale_at(max_age, start + Duration::from_secs(6)));
+
+        context
+            .update_telemetry_at(position(2), start + Duration::from_secs(6))
+            .unwrap();
+        assert!(!context.is_stale_at(max_age, start + Duration::from_secs(6)));
+    }
+
+    #[test]
+    fn test_clock_drives_staleness_and_expiry() {
+        let clock = TestClock::new();
+        let context = flying(UnitContext::new().with_clock(Arc::new(clock.clone())));
+        let max_age = Duration::from_secs(5);
+
+        context.update_telemetry(position(1)).unwrap();
+        assert_eq!(context.last_telemetry_at(), Some(clock.now()));
+        clock.advance(max_age);
+        assert!(!context.is_stale(max_age));
+        clock.advance(Duration::from_secs(1));
+        assert!(context.is_stale(max_age));
+
+        context
+            .enqueue_command_until(
+                CommandId::generate(),
+                Command::Land,
+                Some(clock.now() + Duration::from_millis(100)),
+            )
+            .unwrap();
+        clock.advance(Duration::from_millis(150));
+        assert!(context.poll_command().is_none());
+        assert_eq!(context.expired_commands(), 1);
+    }
+
+    #[test]
+    fn test_invalid_command_is_rejected() {
+        let context = UnitContext::new();
+        let goto = Command::Goto {
+            latitude: 37.0,
+            longitude: -122.0,
+            altitude_m: 50.0,
+        };
+
+        let result = context.enqueue_command(CommandId::generate(), goto);
+        assert!(matches!(result, Err(EnqueueError::Rejected(_))));
+        assert!(context.poll_command().is_none());
+        assert_eq!(context.current_state(), FlightState::Idle);
+    }
+
+    #[test]
+    fn test_commands_and_telemetry_drive_flight_state() {
+        let context = flying(UnitContext::new());
+        assert_eq!(context.current_state(), FlightState::Flying);
+
+        context
+            .enqueue_command(CommandId::generate(), Command::Land)
+            .unwrap();
+        // The drone is still flying until the command is on its way
+        assert_eq!(context.current_state(), FlightState::Flying);
+        context.poll_command().unwrap();
+        assert_eq!(context.current_state(), FlightState::Landing);
+
+        let mut touchdown = position(1);
+        touchdown.altitude_m = 0.0;
+        context.update_telemetry(touchdown).unwrap();
+        assert_eq!(context.current_state(), FlightState::Landed);
+    }
+
+    #[test]
+    fn test_safety_command_discards_the_commands_it_overtakes() {
+        let context = flying(UnitContext::new());
+        let goto = Command::Goto {
+            latitude: 37.0,
+            longitude: -122.0,
+            altitude_m: 50.0,
+        };
+        let overtaken = CommandId::generate();
+        context.enqueue_command(overtaken, goto.clone()).unwrap();
+        context
+            .enqueue_command(CommandId::generate(), Command::Land)
+            .unwrap();
+
+        assert_eq!(
+            context.poll_command().map(|queued| queued.command),
+            Some(Command::Land)
+        );
+        assert!(context.poll_command().is_none());
+        assert_eq!(context.current_state(), FlightState::Landing);
+        assert!(!context.take_ack(&overtaken).unwrap().accepted);
+
+        // Nor is a command accepted that a pending safety command would leave invalid
+        let context = flying(UnitContext::new());
+        context
+            .enqueue_command(CommandId::generate(), Command::Land)
+            .unwrap();
+        let result = context.enqueue_command(CommandId::generate(), goto);
+        assert!(matches!(result, Err(EnqueueError::Rejected(_))));
+    }
+
+    #[test]
+    fn test_expired_command_is_not_delivered() {
+        let context = flying(UnitContext::new());
+        let now = Instant::now();
+        let goto = Command::Goto {
+            latitude: 37.0,
+            longitude: -122.0,
+            altitude_m: 50.0,
+        };
+        let expires_at = Some(now + Duration::from_millis(100));
+        let stale_goto = CommandId::generate();
+        context
+            .enqueue_command_until(stale_goto, goto, expires_at)
+            .unwrap();
+        let stale_land = CommandId::generate();
+        context
+            .enqueue_command_until(stale_land, Command::Land, expires_at)
+            .unwrap();
+
+        assert!(
+            context
+                .poll_command_at(now + Duration::from_millis(150))
+                .is_none()
+        );
+        assert_eq!(context.expired_commands(), 2);
+        // The drone never heard of the landing, so neither does the flight state
+        assert_eq!(context.current_state(), FlightState::Flying);
+        for id in [stale_goto, stale_land] {
+            let receipt = context.take_ack(&id).unwrap();
+            assert!(!receipt.accepted);
+            assert_eq!(receipt.message, "expired before delivery");
+        }
+    }
+
+    #[test]
+    fn test_command_dedup_keeps_one_of_repeated_commands() {
+        let context = flying(UnitContext::new().with_command_dedup(true));
+        let goto = Command::Goto {
+            latitude: 37.0,
+            longitude: -122.0,
+            altitude_m: 50.0,
+        };
+        let first = CommandId::generate();
+        assert_eq!(context.enqueue_command(first, goto.clone()).unwrap(), first);
+        for _ in 0..2 {
+            let coalesced = context
+                .enqueue_command(CommandId::generate(), goto.clone())
+                .unwrap();
+            assert_eq!(coalesced, first);
+        }
+
+        let delivered = context.poll_command().unwrap();
+        assert_eq!(delivered.id, first);
+        assert_eq!(delivered.command, goto);
+        assert!(context.poll_command().is_none());
+
+        // Once delivered, repeating it is a new instruction
+        context
+            .enqueue_command(CommandId::generate(), goto.clone())
+            .unwrap();
+        assert_eq!(
+            context.poll_command().map(|queued| queued.command),
+            Some(goto)
+        );
+    }
+
+    #[test]
+    fn test_command_dedup_never_drops_safety_commands() {
+        let context = flying(UnitContext::new().with_command_dedup(true));
+        for _ in 0..2 {
+            context
+                .enqueue_command(CommandId::generate(), Command::ReturnHome)
+                .unwrap();
+        }
+
+        assert_eq!(
+            context.poll_command().map(|queued| queued.command),
+            Some(Command::ReturnHome)
+        );
+        assert_eq!(
+            context.poll_command().map(|queued| queued.command),
+            Some(Command::ReturnHome)
+        );
+    }
+
+    #[test]
+    fn test_ack_correlates_by_command_id() {
+        let context = flying(UnitContext::new());
+        let land = CommandId::generate();
+        context.enqueue_command(land, Command::Land).unwrap();
+
+        let delivered = context.poll_command().unwrap();
+        assert_eq!(delivered.id, land);
+        assert_eq!(context.take_ack(&land), None);
+
+        let receipt = CommandReceipt {
+            accepted: true,
+            message: String::new(),
+        };
+        context.record_ack(CommandId::generate(), receipt.clone());
+        context.record_ack(land, receipt.clone());
+
+        assert_eq!(context.take_ack(&land), Some(receipt));
+        // Acks are handed out once
+        assert_eq!(context.take_ack(&land), None);
+    }
+
+    #[test]
+    fn test_unclaimed_acks_are_bounded() {
+        let context = UnitContext::new().with_command_capacity(1);
+        let first = CommandId::generate();
+        let second = CommandId::generate();
+        let receipt = CommandReceipt {
+            accepted: true,
+            message: String::new(),
+        };
+
+        context.record_ack(first, receipt.clone());
+        context.record_ack(second, receipt);
+
+        assert!(context.take_ack(&first).is_none());
+        assert!(context.take_ack(&second).is_some());
+    }
+}
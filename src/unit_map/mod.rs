@@ -1,7 +1,11 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use crate::clock::{self, Clock};
+use crate::telemetry::TelemetryAge;
 pub use crate::unit::UnitId;
 use dashmap::{DashMap, Entry};
+use tokio::sync::broadcast;
 
 use self::{
     error::{UnitAlreadyPresent, UnitNotFound},
@@ -11,6 +15,17 @@ use self::{
 pub mod error;
 pub mod unit_ref;
 
+/// The number of events buffered per [`watch`](UnitMap::watch) receiver before it lags.
+const EVENT_CAPACITY: usize = 256;
+
+/// A change to the units tracked by a [`UnitMap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnitEvent {
+    Added(UnitId),
+    Removed(UnitId),
+    TelemetryUpdated(UnitId),
+}
+
 /// A map of units identified by a [`UnitId`] and their associated context `T`.
 ///
 /// When a unit is added to the map it is turned into a shared resource for which only references
@@ -21,6 +36,8 @@ pub mod unit_ref;
 #[derive(Debug)]
 pub struct UnitMap<T> {
     entity_map: DashMap<UnitId, Arc<T>, ahash::RandomState>,
+    events: broadcast::Sender<UnitEvent>,
+    clock: Arc<dyn Clock>,
 }
 
 impl<T> UnitMap<T> {
@@ -29,6 +46,20 @@ impl<T> UnitMap<T> {
         Self::default()
     }
 
+    /// Judge staleness by `clock` instead of the system clock.
+    ///
+    /// Give the unit contexts the same clock, or they timestamp telemetry by another. A
+    /// `DroneServiceImpl` over this map reads the time from it too.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The clock staleness is judged by.
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        Arc::clone(&self.clock)
+    }
+
     /// Create a unit entity entry tracked by the `unit_id` and associated with the `unit_context`.
     pub fn insert_unit(&self, unit_id: UnitId, unit_context: T) -> Result<(), UnitAlreadyPresent> {
         match self.entity_map.entry(unit_id) {
@@ -37,7 +68,9 @@ impl<T> UnitMap<T> {
             }),
 
             Entry::Vacant(slot) => {
+                let unit_id = slot.key().clone();
                 slot.insert(Arc::new(unit_context));
+                self.emit(UnitEvent::Added(unit_id));
                 Ok(())
             }
         }
@@ -51,6 +84,7 @@ impl<T> UnitMap<T> {
                 unit_id: unit_id.clone(),
             })?;
 
+        self.emit(UnitEvent::Removed(unit_id.clone()));
         Ok(())
     }
 
@@ -66,12 +100,277 @@ impl<T> UnitMap<T> {
                 unit_id: unit_id.clone(),
             })
     }
+
+    /// Subscribe to units being added, removed, or reporting telemetry.
+    ///
+    /// Only events emitted after subscribing are received. A receiver that falls more than a
+    /// fixed number of events behind gets [`broadcast::error::RecvError::Lagged`] rather than
+    /// slowing down the map.
+    pub fn watch(&self) -> broadcast::Receiver<UnitEvent> {
+        self.events.subscribe()
+    }
+
+    /// Notify watchers that `unit_id` has received new telemetry.
+    pub fn telemetry_updated(&self, unit_id: &UnitId) {
+        self.emit(UnitEvent::TelemetryUpdated(unit_id.clone()));
+    }
+
+    fn emit(&self, event: UnitEvent) {
+        // Sending only fails when nobody is watching
+        let _ = self.events.send(event);
+    }
+
+    /// The IDs of all units currently in the map.
+    pub fn unit_ids(&self) -> Vec<UnitId> {
+        self.entity_map
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Iterate a snapshot of the units currently in the map.
+    ///
+    /// The snapshot is taken up front so no map locks are held during iteration, and concurrent
+    /// inserts or removals do not affect it. A unit removed after the snapshot yields a
+    /// [`UnitRef`] whose [`view`](UnitRef::view) fails.
+    pub fn iter(&self) -> impl Iterator<Item = (UnitId, UnitRef<T>)> + use<T> {
+        let snapshot: Vec<_> = self
+            .entity_map
+            .iter()
+            .map(|entry| {
+                let unit_id = entry.key().clone();
+                let unit_ref = UnitRef::new(unit_id.clone(), Arc::downgrade(entry.value()));
+                (unit_id, unit_ref)
+            })
+            .collect();
+
+        snapshot.into_iter()
+    }
+}
+
+impl<T: TelemetryAge> UnitMap<T> {
+    /// The units that have not reported telemetry within the last `max_age`.
+    pub fn stale_units(&self, max_age: Duration) -> Vec<UnitId> {
+        self.stale_units_at(max_age, self.clock.now())
+    }
+
+    /// The units that have not reported telemetry within `max_age` of `now`.
+    pub fn stale_units_at(&self, max_age: Duration, now: Instant) -> Vec<UnitId> {
+        self.entity_map
+            .iter()
+            .filter(|entry| entry.value().is_stale_at(max_age, now))
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Remove every unit that has not reported telemetry within the last `max_age`.
+    ///
+    /// Returns the IDs of the removed units.
+    pub fn expire_idle(&self, max_age: Duration) -> Vec<UnitId> {
+        self.expire_idle_at(max_age, self.clock.now())
+    }
+
+    /// Remove every unit that has not reported telemetry within `max_age` of `now`.
+    ///
+    /// Returns the IDs of the removed units.
+    pub fn expire_idle_at(&self, max_age: Duration, now: Instant) -> Vec<UnitId> {
+        let mut expired = Vec::new();
+        self.entity_map.retain(|unit_id, unit_context| {
+            let stale = unit_context.is_stale_at(max_age, now);
+            if stale {
+                expired.push(unit_id.clone());
+            }
+            !stale
+        });
+
+        for unit_id in &expired {
+            self.emit(UnitEvent::Removed(unit_id.clone()));
+        }
+        expired
+    }
 }
 
 impl<T> Default for UnitMap<T> {
     fn default() -> Self {
         Self {
             entity_map: DashMap::default(),
+            events: broadcast::Sender::new(EVENT_CAPACITY),
+            clock: clock::system(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::clock::TestClock;
+
+    /// A context whose telemetry time is set directly by the test.
+    #[derive(Default)]
+    struct MockTelemetry(Mutex<Option<Instant>>);
+
+    impl MockTelemetry {
+        fn report(&self, at: Instant) {
+            *self.0.lock().unwrap() = Some(at);
+        }
+    }
+
+    impl TelemetryAge for MockTelemetry {
+        fn last_telemetry_at(&self) -> Option<Instant> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_iterate_units() {
+        let map = UnitMap::new();
+        map.insert_unit(UnitId::from("drone-1"), 1).unwrap();
+        map.insert_unit(UnitId::from("drone-2"), 2).unwrap();
+
+        let mut unit_ids = map.unit_ids();
+        unit_ids.sort();
+        assert_eq!(
+            unit_ids,
+            vec![UnitId::from("drone-1"), UnitId::from("drone-2")]
+        );
+
+        let mut values: Vec<_> = map
+            .iter()
+            .map(|(unit_id, unit_ref)| (unit_id, unit_ref.view(|value| *value).unwrap()))
+            .collect();
+        values.sort();
+        assert_eq!(
+            values,
+            vec![(UnitId::from("drone-1"), 1), (UnitId::from("drone-2"), 2)]
+        );
+    }
+
+    #[test]
+    fn test_iteration_is_a_snapshot() {
+        let map = UnitMap::new();
+        map.insert_unit(UnitId::from("drone-1"), 1).unwrap();
+
+        let mut iter = map.iter();
+
+        // Mutating the map mid-iteration neither deadlocks nor changes the snapshot
+        map.insert_unit(UnitId::from("drone-2"), 2).unwrap();
+        map.remove_unit(&UnitId::from("drone-1")).unwrap();
+
+        let (unit_id, unit_ref) = iter.next().unwrap();
+        assert_eq!(unit_id, UnitId::from("drone-1"));
+        assert!(unit_ref.view(|_| ()).is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_watch_events() {
+        let map = UnitMap::new();
+        let mut events = map.watch();
+        let unit_id = UnitId::from("drone-1");
+
+        map.insert_unit(unit_id.clone(), 1).unwrap();
+        map.telemetry_updated(&unit_id);
+        map.remove_unit(&unit_id).unwrap();
+
+        assert_eq!(
+            events.try_recv().unwrap(),
+            UnitEvent::Added(unit_id.clone())
+        );
+        assert_eq!(
+            events.try_recv().unwrap(),
+            UnitEvent::TelemetryUpdated(unit_id.clone())
+        );
+        assert_eq!(events.try_recv().unwrap(), UnitEvent::Removed(unit_id));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_lagged_watcher_does_not_block() {
+        let map = UnitMap::new();
+        let mut events = map.watch();
+        let unit_id = UnitId::from("drone-1");
+        map.insert_unit(unit_id.clone(), 1).unwrap();
+
+        for _ in 0..EVENT_CAPACITY * 2 {
+            map.telemetry_updated(&unit_id);
+        }
+
+        assert!(matches!(
+            events.try_recv(),
+            Err(broadcast::error::TryRecvError::Lagged(_))
+        ));
+    }
+
+    #[test]
+    fn test_stale_units() {
+        let map = UnitMap::new();
+        let quiet = UnitId::from("drone-quiet");
+        let chatty = UnitId::from("drone-chatty");
+        map.insert_unit(quiet.clone(), MockTelemetry::default())
+            .unwrap();
+        map.insert_unit(chatty.clone(), MockTelemetry::default())
+            .unwrap();
+
+        let start = Instant::now();
+        let max_age = Duration::from_secs(10);
+        for unit_id in [&quiet, &chatty] {
+            map.get_unit(unit_id)
+                .unwrap()
+                .view(|ctx| ctx.report(start))
+                .unwrap();
+        }
+        assert!(map.stale_units_at(max_age, start).is_empty());
+
+        // Advance the clock, with only one unit reporting along the way
+        let later = start + Duration::from_secs(30);
+        map.get_unit(&chatty)
+            .unwrap()
+            .view(|ctx| ctx.report(later))
+            .unwrap();
+
+        assert_eq!(map.stale_units_at(max_age, later), vec![quiet]);
+    }
+
+    #[test]
+    fn test_expire_idle_follows_clock() {
+        let clock = TestClock::new();
+        let map = UnitMap::new().with_clock(Arc::new(clock.clone()));
+        let unit_id = UnitId::from("drone-1");
+        map.insert_unit(unit_id.clone(), MockTelemetry::default())
+            .unwrap();
+        map.get_unit(&unit_id)
+            .unwrap()
+            .view(|ctx| ctx.report(clock.now()))
+            .unwrap();
+
+        let max_age = Duration::from_secs(10);
+        clock.advance(max_age);
+        assert!(map.expire_idle(max_age).is_empty());
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(map.expire_idle(max_age), vec![unit_id]);
+    }
+
+    #[test]
+    fn test_expire_idle() {
+        let map = UnitMap::new();
+        let unit_id = UnitId::from("drone-1");
+        map.insert_unit(unit_id.clone(), MockTelemetry::default())
+            .unwrap();
+
+        let start = Instant::now();
+        let max_age = Duration::from_secs(10);
+        let unit_ref = map.get_unit(&unit_id).unwrap();
+        unit_ref.view(|ctx| ctx.report(start)).unwrap();
+
+        assert!(map.expire_idle_at(max_age, start).is_empty());
+        assert!(map.get_unit(&unit_id).is_ok());
+
+        let expired = map.expire_idle_at(max_age, start + Duration::from_secs(11));
+        assert_eq!(expired, vec![unit_id.clone()]);
+        assert!(map.get_unit(&unit_id).is_err());
+        assert!(unit_ref.view(|_| ()).is_err());
+    }
+}